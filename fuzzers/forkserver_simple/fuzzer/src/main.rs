@@ -12,7 +12,7 @@ use libafl::{
     },
     corpus::{Corpus, InMemoryCorpus, OnDiskCorpus},
     events::SimpleEventManager,
-    executors::multi_forkserver::{ForkserverExecutor, TimeoutForkserverExecutor},
+    executors::forkserver::{ForkserverExecutor, TimeoutForkserverExecutor},
     feedback_and_fast, feedback_or,
     feedbacks::{CrashFeedback, MaxMapFeedback, TimeFeedback},
     fuzzer::{Fuzzer, StdFuzzer},
@@ -203,10 +203,9 @@ pub fn main() {
         .debug_child(debug_child)
         .shmem_provider(&mut shmem_provider)
         .autotokens(&mut tokens)
-        // hardcoded? @TODO
-        .parse_aug_afl_cmdline(args)
+        .parse_afl_cmdline(args)
         .envs(envs)
-        .build(tuple_list!(time_observer, edges_observer, sched_edges_observer))
+        .build_multi(tuple_list!(time_observer, edges_observer, sched_edges_observer))
         .unwrap();
 
     let mut executor = TimeoutForkserverExecutor::with_signal(
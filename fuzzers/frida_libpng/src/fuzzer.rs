@@ -17,14 +17,16 @@ use libafl::{
         tuples::{tuple_list, Merge},
         AsSlice,
     },
-    corpus::{ondisk::OnDiskMetadataFormat, CachedOnDiskCorpus, Corpus, OnDiskCorpus},
-    events::{llmp::LlmpRestartingEventManager, EventConfig},
+    corpus::{
+        ondisk::OnDiskMetadataFormat, CachedOnDiskCorpus, Corpus, InMemoryCorpus, OnDiskCorpus,
+    },
+    events::{llmp::LlmpRestartingEventManager, EventConfig, SimpleRestartingEventManager},
     executors::{inprocess::InProcessExecutor, ExitKind, ShadowExecutor},
     feedback_and_fast, feedback_or, feedback_or_fast,
     feedbacks::{ConstFeedback, CrashFeedback, MaxMapFeedback, TimeFeedback, TimeoutFeedback},
-    fuzzer::{Fuzzer, StdFuzzer},
+    fuzzer::{Fuzzer, ReplayRunner, StdFuzzer},
     inputs::{BytesInput, HasTargetBytes},
-    monitors::MultiMonitor,
+    monitors::{MultiMonitor, SimpleMonitor},
     mutators::{
         scheduled::{havoc_mutations, tokens_mutations, StdScheduledMutator},
         token_mutations::I2SRandReplace,
@@ -67,6 +69,11 @@ pub fn main() {
 /// The actual fuzzer
 #[allow(clippy::too_many_lines, clippy::too_many_arguments)]
 unsafe fn fuzz(options: FuzzerOptions) -> Result<(), Error> {
+    // A replay runs in a single client, without a broker
+    if options.replay.is_some() {
+        return replay(&options);
+    }
+
     // 'While the stats are state, they are usually used in the broker - which is likely never restarted
     let monitor = MultiMonitor::new(|s| println!("{}", s));
 
@@ -482,3 +489,108 @@ unsafe fn fuzz(options: FuzzerOptions) -> Result<(), Error> {
         .build()
         .launch()
 }
+
+/// Replays the inputs given with `--replay` instead of fuzzing.
+/// If an input crashes the client, the next client resumes the replay after it.
+#[allow(clippy::too_many_lines)]
+unsafe fn replay(options: &FuzzerOptions) -> Result<(), Error> {
+    let monitor = SimpleMonitor::new(|s| println!("{}", s));
+
+    // The state, and with it the progress of the replay, survives crashes in this shared map
+    let mut shmem_provider = StdShMemProvider::new()?;
+    let (state, mut mgr) = match SimpleRestartingEventManager::launch(monitor, &mut shmem_provider)
+    {
+        Ok(res) => res,
+        Err(Error::ShuttingDown) => return Ok(()),
+        Err(err) => panic!("Failed to setup the restarter: {}", err),
+    };
+
+    let lib = libloading::Library::new(options.clone().harness.unwrap()).unwrap();
+    let target_func: libloading::Symbol<unsafe extern "C" fn(data: *const u8, size: usize) -> i32> =
+        lib.get(options.harness_function.as_bytes()).unwrap();
+
+    let mut frida_harness = |input: &BytesInput| {
+        let target = input.target_bytes();
+        let buf = target.as_slice();
+        (target_func)(buf.as_ptr(), buf.len());
+        ExitKind::Ok
+    };
+
+    let gum = Gum::obtain();
+
+    let coverage = CoverageRuntime::new();
+    #[cfg(unix)]
+    let asan = AsanRuntime::new(options.clone());
+
+    #[cfg(unix)]
+    let mut frida_helper =
+        FridaInstrumentationHelper::new(&gum, options, tuple_list!(coverage, asan));
+    #[cfg(windows)]
+    let mut frida_helper = FridaInstrumentationHelper::new(&gum, options, tuple_list!(coverage));
+
+    // Create an observation channel using the coverage map
+    let edges_observer = HitcountsMapObserver::new(StdMapObserver::new_from_ptr(
+        "edges",
+        frida_helper.map_ptr_mut().unwrap(),
+        MAP_SIZE,
+    ));
+
+    // Reports the exit kind and coverage of each input
+    let replay = ReplayRunner::new(&edges_observer);
+
+    let mut feedback = MaxMapFeedback::new(&edges_observer);
+
+    // Feedbacks to recognize an input as solution
+    #[cfg(unix)]
+    let mut objective = feedback_or_fast!(
+        CrashFeedback::new(),
+        TimeoutFeedback::new(),
+        feedback_and_fast!(ConstFeedback::from(true), AsanErrorsFeedback::new())
+    );
+    #[cfg(windows)]
+    let mut objective = feedback_or_fast!(CrashFeedback::new(), TimeoutFeedback::new());
+
+    // If not restarting, create a State from scratch
+    let mut state = state.unwrap_or_else(|| {
+        StdState::new(
+            StdRand::with_seed(current_nanos()),
+            InMemoryCorpus::new(),
+            // The crashing inputs are already on disk
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap()
+    });
+
+    let mut fuzzer = StdFuzzer::new(QueueScheduler::new(), feedback, objective);
+
+    #[cfg(unix)]
+    let observers = tuple_list!(edges_observer, AsanErrorsObserver::new(&ASAN_ERRORS));
+    #[cfg(windows)]
+    let observers = tuple_list!(edges_observer);
+
+    let mut executor = FridaInProcessExecutor::new(
+        &gum,
+        InProcessExecutor::new(
+            &mut frida_harness,
+            observers,
+            &mut fuzzer,
+            &mut state,
+            &mut mgr,
+        )?,
+        &mut frida_helper,
+    );
+
+    if let Some(report) =
+        replay.replay_options(&mut fuzzer, &mut executor, &mut state, &mut mgr, options)?
+    {
+        println!(
+            "Replayed {} inputs: {} crashed, {} timed out",
+            report.results.len(),
+            report.with_exit_kind(ExitKind::Crash).count(),
+            report.with_exit_kind(ExitKind::Timeout).count()
+        );
+    }
+    Ok(())
+}
@@ -4,7 +4,7 @@
 //! or use its mutations for another fuzzer.
 //! This is a less hacky alternative to the `KloRoutines` based fuzzer, that will also work on non-`Unix`.
 
-use core::cell::{Cell, RefCell};
+use core::cell::RefCell;
 use std::{path::PathBuf, rc::Rc};
 
 use libafl::{
@@ -88,7 +88,7 @@ pub fn main() {
     // Setup a mutational stage with a basic bytes mutator
     let mutator = StdScheduledMutator::new(havoc_mutations());
 
-    let exit_kind = Rc::new(Cell::new(None));
+    let exit_kind = Rc::new(RefCell::new(None));
 
     let stage_idx = 0;
 
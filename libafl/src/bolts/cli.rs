@@ -10,6 +10,7 @@
 //! use libafl::bolts::cli::{parse_args, FuzzerOptions};
 //!
 //! fn fuzz(options: FuzzerOptions) {}
+//! // see `libafl::fuzzer::ReplayRunner::replay_options`
 //! fn replay(options: FuzzerOptions) {}
 //!
 //! fn main() {
@@ -75,7 +76,7 @@ use serde::{Deserialize, Serialize};
 use std::error;
 use std::{net::SocketAddr, path::PathBuf, time::Duration};
 
-use crate::Error;
+use crate::{fuzzer::replay::ReplayReportFormat, Error};
 
 use super::core_affinity::Cores;
 
@@ -294,7 +295,7 @@ pub struct FuzzerOptions {
     #[clap(short = 'a', long, parse(try_from_str), name = "REMOTE")]
     pub remote_broker_addr: Option<SocketAddr>,
 
-    /// path to file that should be sent to the harness for crash reproduction
+    /// path to file, or directory of files, that should be sent to the harness for crash reproduction
     #[clap(short, long, parse(from_os_str), help_heading = "Replay Options")]
     pub replay: Option<PathBuf>,
 
@@ -308,6 +309,24 @@ pub struct FuzzerOptions {
         requires = "replay"
     )]
     pub repeat: Option<usize>,
+
+    /// file to write the report of the replay to, with the exit kind, execution time and coverage of each input
+    #[clap(
+        long,
+        parse(from_os_str),
+        help_heading = "Replay Options",
+        requires = "replay"
+    )]
+    pub replay_report: Option<PathBuf>,
+
+    /// format of the replay report
+    #[clap(
+        long,
+        arg_enum,
+        default_value = "json",
+        help_heading = "Replay Options"
+    )]
+    pub replay_format: ReplayReportFormat,
 }
 
 impl FuzzerOptions {
@@ -438,4 +457,22 @@ mod tests {
     fn parse_timeout_gives_correct_values() {
         assert_eq!(parse_timeout("1525").unwrap(), Duration::from_millis(1525));
     }
+
+    /// pass a replay directory and report options, expect them to be parsed
+    #[test]
+    #[cfg(feature = "cli")]
+    fn replay_report_options_parsed() {
+        let parsed = FuzzerOptions::parse_from([
+            "some-command",
+            "--replay",
+            "crashes/",
+            "--replay-report",
+            "report.csv",
+            "--replay-format",
+            "csv",
+        ]);
+        assert_eq!(parsed.replay, Some(PathBuf::from("crashes/")));
+        assert_eq!(parsed.replay_report, Some(PathBuf::from("report.csv")));
+        assert_eq!(parsed.replay_format, ReplayReportFormat::Csv);
+    }
 }
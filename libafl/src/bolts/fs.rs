@@ -2,7 +2,7 @@
 
 #[cfg(feature = "std")]
 use alloc::borrow::ToOwned;
-use alloc::rc::Rc;
+use alloc::{rc::Rc, vec::Vec};
 use core::cell::RefCell;
 #[cfg(unix)]
 use std::os::unix::prelude::{AsRawFd, RawFd};
@@ -47,6 +47,27 @@ where
     inner(path.as_ref(), bytes)
 }
 
+/// Returns the file at `path`, or all files in the directory at `path`, sorted, skipping hidden files,
+/// like the metadata of an [`crate::corpus::OnDiskCorpus`].
+pub fn input_files<P>(path: P) -> Result<Vec<PathBuf>, Error>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = vec![];
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() && !entry.file_name().to_string_lossy().starts_with('.') {
+            files.push(entry.path());
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
 /// An [`InputFile`] to write fuzzer input to.
 /// The target/forkserver will read from this file.
 #[cfg(feature = "std")]
@@ -33,13 +33,13 @@ use core::fmt::{self, Debug, Formatter};
 use core::marker::PhantomData;
 #[cfg(feature = "std")]
 use serde::de::DeserializeOwned;
-#[cfg(feature = "std")]
-use std::net::SocketAddr;
 #[cfg(all(feature = "std", any(windows, not(feature = "fork"))))]
 use std::process::Stdio;
 #[cfg(all(unix, feature = "std", feature = "fork"))]
 use std::{fs::File, os::unix::io::AsRawFd};
 #[cfg(feature = "std")]
+use std::{net::SocketAddr, path::PathBuf};
+#[cfg(feature = "std")]
 use typed_builder::TypedBuilder;
 
 /// The (internal) `env` that indicates we're running as client.
@@ -78,6 +78,9 @@ where
     /// clusters.
     #[builder(default = None)]
     remote_broker_addr: Option<SocketAddr>,
+    /// The path of the Unix socket the broker takes control commands on, see [`crate::events::control`]
+    #[builder(default = None)]
+    control_path: Option<PathBuf>,
     /// If this launcher should spawn a new `broker` on `[Self::broker_port]` (default).
     /// The reason you may not want this is, if you already have a [`Launcher`]
     /// with a different configuration (for the same target) running on this machine.
@@ -104,6 +107,7 @@ where
             .field("core", &self.cores)
             .field("spawn_broker", &self.spawn_broker)
             .field("remote_broker_addr", &self.remote_broker_addr)
+            .field("control_path", &self.control_path)
             .field("stdout_file", &self.stdout_file)
             .finish_non_exhaustive()
     }
@@ -184,9 +188,12 @@ where
                             .build()
                             .launch()?;
 
-                        (self.run_client.take().unwrap())(state, mgr, bind_to.id)
-                            .expect("Client closure failed");
-                        break;
+                        match (self.run_client.take().unwrap())(state, mgr, bind_to.id) {
+                            Ok(()) => break,
+                            // Stopped by the broker, let the respawner know we exited on purpose
+                            Err(Error::ShuttingDown) => std::process::exit(0),
+                            Err(err) => panic!("Client closure failed: {:?}", err),
+                        }
                     }
                 };
             }
@@ -203,6 +210,7 @@ where
                 .broker_port(self.broker_port)
                 .kind(ManagerKind::Broker)
                 .remote_broker_addr(self.remote_broker_addr)
+                .control_path(self.control_path.clone())
                 .configuration(self.configuration)
                 .build()
                 .launch()?;
@@ -254,10 +262,12 @@ where
                     .build()
                     .launch()?;
 
-                (self.run_client.take().unwrap())(state, mgr, core_id)
-                    .expect("Client closure failed");
-
-                unreachable!("Fuzzer client code should never get here!");
+                match (self.run_client.take().unwrap())(state, mgr, core_id) {
+                    // Stopped by the broker, let the respawner know we exited on purpose
+                    Err(Error::ShuttingDown) => std::process::exit(0),
+                    Err(err) => panic!("Client closure failed: {:?}", err),
+                    Ok(()) => unreachable!("Fuzzer client code should never get here!"),
+                }
             }
             Err(std::env::VarError::NotPresent) => {
                 // I am a broker
@@ -303,6 +313,7 @@ where
                 .broker_port(self.broker_port)
                 .kind(ManagerKind::Broker)
                 .remote_broker_addr(self.remote_broker_addr)
+                .control_path(self.control_path.clone())
                 .configuration(self.configuration)
                 .build()
                 .launch()?;
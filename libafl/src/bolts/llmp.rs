@@ -1972,6 +1972,21 @@ where
     pub fn loop_forever<F>(&mut self, on_new_msg: &mut F, sleep_time: Option<Duration>)
     where
         F: FnMut(ClientId, Tag, Flags, &[u8]) -> Result<LlmpMsgHookResult, Error>,
+    {
+        self.loop_forever_with(on_new_msg, &mut |_| Ok(true), sleep_time);
+    }
+
+    /// Loops, like [`LlmpBroker::loop_forever`], but calls `on_round` after each round.
+    /// `on_round` gets the broadcast map, so it can send own messages to all clients.
+    /// Stops looping once `on_round` returns `false`.
+    pub fn loop_forever_with<F, G>(
+        &mut self,
+        on_new_msg: &mut F,
+        on_round: &mut G,
+        sleep_time: Option<Duration>,
+    ) where
+        F: FnMut(ClientId, Tag, Flags, &[u8]) -> Result<LlmpMsgHookResult, Error>,
+        G: FnMut(&mut LlmpSender<SP>) -> Result<bool, Error>,
     {
         #[cfg(unix)]
         if let Err(_e) = unsafe { setup_signal_handler(&mut GLOBAL_SIGHANDLER_STATE) } {
@@ -1983,6 +1998,9 @@ where
         while !self.is_shutting_down() {
             self.once(on_new_msg)
                 .expect("An error occurred when brokering. Exiting.");
+            if !on_round(&mut self.llmp_out).expect("An error occurred when brokering. Exiting.") {
+                break;
+            }
 
             #[cfg(feature = "std")]
             if let Some(time) = sleep_time {
@@ -0,0 +1,957 @@
+//! Snapshots of the writable memory of the current process, restored page by page.
+//!
+//! A [`MemorySnapshot`] saves every private writable mapping once, together with the program break,
+//! the layout of the mappings and the open file descriptors. From then on, the kernel tracks which
+//! pages get written, either with the soft-dirty bits of `/proc/self/pagemap`, or by write-protecting
+//! the pages with an asynchronous `userfaultfd`. [`MemorySnapshot::restore`] copies back the dirty
+//! pages only, unmaps what was mapped since the snapshot, resets the program break and reopens or
+//! closes file descriptors.
+//!
+//! The snapshot covers the whole process, including the memory of the code that restores it.
+//! It is meant for a process that does nothing but run a target, like the child process of the
+//! [`crate::executors::snapshot::InProcessSnapshotExecutor`].
+//! The stack of the calling thread is left alone.
+
+use core::{
+    borrow::Borrow,
+    marker::PhantomData,
+    mem::size_of,
+    ptr, slice,
+    sync::atomic::{compiler_fence, Ordering},
+};
+use std::{ffi::CStr, io, os::unix::io::RawFd};
+
+use crate::Error;
+
+/// The maximum size of `/proc/self/maps` we can parse
+const MAPS_TEXT_SIZE: usize = 4 << 20;
+/// The maximum number of mappings of the process
+const MAX_MAPPINGS: usize = 1 << 16;
+/// The maximum number of open file descriptors at the time of the snapshot
+const MAX_FDS: usize = 1 << 12;
+/// The number of `pagemap` entries read at once
+const PAGEMAP_CHUNK: usize = 1 << 12;
+/// How often we try to snapshot, if the memory grows while we do
+const SNAPSHOT_ATTEMPTS: usize = 4;
+
+/// The soft-dirty bit of a `pagemap` entry
+const PM_SOFT_DIRTY: u64 = 1 << 55;
+/// The `userfaultfd` write-protect bit of a `pagemap` entry
+const PM_UFFD_WP: u64 = 1 << 57;
+
+/// `_IOWR(0xAA, 0x3F, struct uffdio_api)`
+const UFFDIO_API: libc::c_ulong = 0xc018_aa3f;
+/// `_IOWR(0xAA, 0x00, struct uffdio_register)`
+const UFFDIO_REGISTER: libc::c_ulong = 0xc020_aa00;
+/// `_IOWR(0xAA, 0x06, struct uffdio_writeprotect)`
+const UFFDIO_WRITEPROTECT: libc::c_ulong = 0xc018_aa06;
+const UFFD_API: u64 = 0xaa;
+const UFFD_FEATURE_WP_UNPOPULATED: u64 = 1 << 13;
+const UFFD_FEATURE_WP_ASYNC: u64 = 1 << 15;
+const UFFDIO_REGISTER_MODE_WP: u64 = 1 << 1;
+const UFFDIO_WRITEPROTECT_MODE_WP: u64 = 1 << 0;
+
+#[repr(C)]
+struct UffdioApi {
+    api: u64,
+    features: u64,
+    ioctls: u64,
+}
+
+#[repr(C)]
+struct UffdioRange {
+    start: u64,
+    len: u64,
+}
+
+#[repr(C)]
+struct UffdioRegister {
+    range: UffdioRange,
+    mode: u64,
+    ioctls: u64,
+}
+
+#[repr(C)]
+struct UffdioWriteprotect {
+    range: UffdioRange,
+    mode: u64,
+}
+
+/// How a [`MemorySnapshot`] finds the pages written since the last restore
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirtyTracking {
+    /// The soft-dirty bits of `/proc/self/pagemap`, reset through `/proc/self/clear_refs`.
+    /// Needs a kernel built with `CONFIG_MEM_SOFT_DIRTY`.
+    SoftDirty,
+    /// An asynchronous `userfaultfd` write-protects the pages, a write removes the protection,
+    /// visible in `/proc/self/pagemap`. Needs Linux 6.7 and the permission to use `userfaultfd`.
+    Userfaultfd,
+}
+
+impl DirtyTracking {
+    /// The tracking the running kernel supports, preferring [`DirtyTracking::Userfaultfd`]
+    #[must_use]
+    pub fn detect() -> Option<Self> {
+        unsafe {
+            if let Ok(uffd) = userfaultfd() {
+                libc::close(uffd);
+                return Some(Self::Userfaultfd);
+            }
+            let probe = RawMap::new(page_size()).ok()?;
+            let supported = soft_dirty_supported(&probe).unwrap_or(false);
+            supported.then(|| Self::SoftDirty)
+        }
+    }
+}
+
+/// A mapping of the process, as listed in `/proc/self/maps`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Mapping {
+    start: usize,
+    end: usize,
+    prot: i32,
+}
+
+impl Mapping {
+    fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    fn overlaps(&self, start: usize, end: usize) -> bool {
+        self.start < end && start < self.end
+    }
+}
+
+/// A saved mapping, with the offset of its content in the backing store
+#[derive(Debug, Clone, Copy)]
+struct Region {
+    mapping: Mapping,
+    offset: usize,
+}
+
+/// A file descriptor open at the time of the snapshot, with a duplicate to reopen it
+#[derive(Debug, Clone, Copy)]
+struct SavedFd {
+    fd: RawFd,
+    backup: RawFd,
+    dev: libc::dev_t,
+    ino: libc::ino_t,
+    offset: libc::off_t,
+}
+
+/// An anonymous mapping between two guard pages, which is never part of a snapshot.
+#[derive(Debug)]
+struct RawMap {
+    ptr: *mut u8,
+    len: usize,
+}
+
+impl RawMap {
+    fn new(len: usize) -> Result<Self, Error> {
+        let page_size = page_size();
+        let len = round_up(len.max(1), page_size);
+        unsafe {
+            // The guards keep the kernel from merging this mapping with its neighbours
+            let guarded = libc::mmap(
+                ptr::null_mut(),
+                len + 2 * page_size,
+                libc::PROT_NONE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_NORESERVE,
+                -1,
+                0,
+            );
+            if guarded == libc::MAP_FAILED {
+                return Err(last_os_error("mmap"));
+            }
+            let ptr = (guarded as *mut u8).add(page_size);
+            if libc::mprotect(
+                ptr as *mut libc::c_void,
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+            ) != 0
+            {
+                let err = last_os_error("mprotect");
+                libc::munmap(guarded, len + 2 * page_size);
+                return Err(err);
+            }
+            Ok(Self { ptr, len })
+        }
+    }
+
+    fn contains(&self, mapping: &Mapping) -> bool {
+        let start = self.ptr as usize - page_size();
+        mapping.start >= start && mapping.end <= self.ptr as usize + self.len + page_size()
+    }
+
+    /// The content, which may be written through a shared reference, as it is outside of the snapshot.
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn bytes(&self) -> &mut [u8] {
+        slice::from_raw_parts_mut(self.ptr, self.len)
+    }
+}
+
+impl Drop for RawMap {
+    fn drop(&mut self) {
+        let page_size = page_size();
+        unsafe {
+            libc::munmap(
+                self.ptr.sub(page_size) as *mut libc::c_void,
+                self.len + 2 * page_size,
+            );
+        }
+    }
+}
+
+/// A fixed-capacity array in a [`RawMap`]. It never allocates, and its length is kept
+/// by the caller, so that filling it does not write to the snapshotted memory.
+#[derive(Debug)]
+struct Table<T: Copy> {
+    map: RawMap,
+    capacity: usize,
+    phantom: PhantomData<T>,
+}
+
+impl<T: Copy> Table<T> {
+    fn new(capacity: usize) -> Result<Self, Error> {
+        Ok(Self {
+            map: RawMap::new(capacity * size_of::<T>())?,
+            capacity,
+            phantom: PhantomData,
+        })
+    }
+
+    fn set(&self, idx: usize, value: T) -> Result<(), Error> {
+        if idx >= self.capacity {
+            return Err(Error::illegal_state(
+                "Too many entries for a memory snapshot",
+            ));
+        }
+        unsafe { (self.map.ptr as *mut T).add(idx).write(value) };
+        Ok(())
+    }
+
+    fn as_mut_ptr(&self) -> *mut T {
+        self.map.ptr as *mut T
+    }
+
+    unsafe fn at(&self, idx: usize) -> T {
+        (self.map.ptr as *const T).add(idx).read()
+    }
+
+    unsafe fn get(&self, len: usize) -> &[T] {
+        slice::from_raw_parts(self.map.ptr as *const T, len)
+    }
+}
+
+/// How the kernel tracks the dirty pages
+#[derive(Debug)]
+enum Tracker {
+    SoftDirty { clear_refs: RawFd },
+    Userfaultfd { uffd: RawFd },
+}
+
+/// A snapshot of the writable memory, the memory layout and the file descriptors of the current process.
+/// Restoring it neither allocates nor writes to the snapshotted memory, apart from the restored pages.
+#[derive(Debug)]
+pub struct MemorySnapshot {
+    page_size: usize,
+    /// The saved content of the regions
+    backing: RawMap,
+    regions: Table<Region>,
+    regions_len: usize,
+    /// All mappings, also the ones we don't save, sorted
+    layout: Table<Mapping>,
+    layout_len: usize,
+    fds: Table<SavedFd>,
+    fds_len: usize,
+    brk: usize,
+    /// Scratch space for `/proc/self/maps`, the current mappings, `pagemap` entries and regions to copy entirely
+    text: RawMap,
+    current: Table<Mapping>,
+    pagemap_entries: Table<u64>,
+    full_copy: Table<bool>,
+    pagemap: RawFd,
+    tracker: Tracker,
+}
+
+impl MemorySnapshot {
+    /// Snapshots the current process and starts tracking the dirty pages with `tracking`
+    pub fn take(tracking: DirtyTracking) -> Result<Self, Error> {
+        let text = RawMap::new(MAPS_TEXT_SIZE)?;
+        let current = Table::new(MAX_MAPPINGS)?;
+        let regions = Table::new(MAX_MAPPINGS)?;
+        let layout = Table::new(MAX_MAPPINGS)?;
+        let fds = Table::new(MAX_FDS)?;
+        let pagemap_entries = Table::new(PAGEMAP_CHUNK)?;
+        let full_copy = Table::new(MAX_MAPPINGS)?;
+
+        unsafe {
+            let pagemap = open_proc(b"/proc/self/pagemap\0", libc::O_RDONLY)?;
+            let tracker = match tracking {
+                DirtyTracking::SoftDirty => {
+                    if !soft_dirty_supported(&text)? {
+                        libc::close(pagemap);
+                        return Err(Error::unsupported(
+                            "The kernel does not track soft-dirty pages",
+                        ));
+                    }
+                    Tracker::SoftDirty {
+                        clear_refs: open_proc(b"/proc/self/clear_refs\0", libc::O_WRONLY)?,
+                    }
+                }
+                DirtyTracking::Userfaultfd => Tracker::Userfaultfd {
+                    uffd: userfaultfd()?,
+                },
+            };
+
+            let mut snapshot = Self {
+                page_size: page_size(),
+                backing: RawMap::new(1)?,
+                regions,
+                regions_len: 0,
+                layout,
+                layout_len: 0,
+                fds,
+                fds_len: 0,
+                brk: libc::sbrk(0) as usize,
+                text,
+                current,
+                pagemap_entries,
+                full_copy,
+                pagemap,
+                tracker,
+            };
+            snapshot.save_fds()?;
+
+            let mut attempts = 0;
+            loop {
+                let size = snapshot.find_regions()?;
+                if size <= snapshot.backing.len {
+                    break;
+                }
+                attempts += 1;
+                if attempts == SNAPSHOT_ATTEMPTS {
+                    return Err(Error::illegal_state(
+                        "The memory keeps growing while taking a snapshot",
+                    ));
+                }
+                // Leave some room, mapping the backing store may grow the memory
+                snapshot.backing = RawMap::new(size + size / 8 + 16 * snapshot.page_size)?;
+            }
+
+            if let Tracker::Userfaultfd { uffd } = snapshot.tracker {
+                snapshot.register(uffd)?;
+            }
+            snapshot.layout_len = snapshot.read_maps(&snapshot.layout)?;
+            snapshot.brk = libc::sbrk(0) as usize;
+
+            for region in snapshot.regions.get(snapshot.regions_len) {
+                ptr::copy_nonoverlapping(
+                    region.mapping.start as *const u8,
+                    snapshot.backing.ptr.add(region.offset),
+                    region.mapping.len(),
+                );
+            }
+            snapshot.reset_tracking()?;
+            compiler_fence(Ordering::SeqCst);
+            Ok(snapshot)
+        }
+    }
+
+    /// The number of bytes in the snapshot
+    #[must_use]
+    pub fn size(&self) -> usize {
+        unsafe {
+            self.regions
+                .get(self.regions_len)
+                .iter()
+                .map(|region| region.mapping.len())
+                .sum()
+        }
+    }
+
+    /// Restores the process as it was at the time of the snapshot.
+    /// Returns the number of restored pages.
+    ///
+    /// # Safety
+    /// Every object allocated since the snapshot is gone after this call, references to them must
+    /// not be used anymore. The same holds for other threads, they should not run meanwhile.
+    pub unsafe fn restore(&self) -> Result<usize, Error> {
+        self.restore_fds()?;
+        self.restore_brk()?;
+        let layout_changed = self.restore_layout()?;
+        let restored = self.restore_pages()?;
+        if layout_changed {
+            if let Tracker::Userfaultfd { uffd } = self.tracker {
+                self.register(uffd)?;
+            }
+        }
+        self.reset_tracking()?;
+        compiler_fence(Ordering::SeqCst);
+        Ok(restored)
+    }
+
+    /// Lists the mappings to save into `regions`, returns their size
+    unsafe fn find_regions(&mut self) -> Result<usize, Error> {
+        let stack = &self.page_size as *const usize as usize;
+        let current_len = self.read_maps(&self.current)?;
+        let mut size = 0;
+        let mut regions_len = 0;
+        for mapping in self.current.get(current_len) {
+            let saved = mapping.prot & (libc::PROT_READ | libc::PROT_WRITE)
+                == libc::PROT_READ | libc::PROT_WRITE
+                && !mapping.overlaps(stack, stack + 1)
+                && !self.owns(mapping);
+            if saved {
+                self.regions.set(
+                    regions_len,
+                    Region {
+                        mapping: *mapping,
+                        offset: size,
+                    },
+                )?;
+                regions_len += 1;
+                size += mapping.len();
+            }
+        }
+        self.regions_len = regions_len;
+        Ok(size)
+    }
+
+    /// If the mapping is part of the snapshot machinery
+    fn owns(&self, mapping: &Mapping) -> bool {
+        [
+            &self.backing,
+            &self.text,
+            &self.current.map,
+            &self.regions.map,
+            &self.layout.map,
+            &self.fds.map,
+            &self.pagemap_entries.map,
+            &self.full_copy.map,
+        ]
+        .iter()
+        .any(|map| map.contains(mapping))
+    }
+
+    /// Reads the private mappings of `/proc/self/maps` into `table`, skipping the kernel's own ones.
+    /// Shared mappings, like the coverage maps, are never saved, nor touched.
+    unsafe fn read_maps(&self, table: &Table<Mapping>) -> Result<usize, Error> {
+        let text = read_proc(b"/proc/self/maps\0", self.text.bytes())?;
+        let mut len = 0;
+        for line in text.split(|c| *c == b'\n').filter(|line| !line.is_empty()) {
+            let mut fields = line.split(|c| *c == b' ').filter(|field| !field.is_empty());
+            let (range, perms) = match (fields.next(), fields.next()) {
+                (Some(range), Some(perms)) if perms.len() == 4 => (range, perms),
+                _ => return Err(Error::illegal_state("Malformed /proc/self/maps")),
+            };
+            let name = fields.nth(3).unwrap_or_default();
+            if perms[3] != b'p' || name.starts_with(b"[v") {
+                // shared, or [vdso], [vvar], [vsyscall]
+                continue;
+            }
+            let mut bounds = range.split(|c| *c == b'-').map(parse_hex);
+            let (start, end) = match (bounds.next(), bounds.next()) {
+                (Some(Some(start)), Some(Some(end))) => (start, end),
+                _ => return Err(Error::illegal_state("Malformed /proc/self/maps")),
+            };
+            let mut prot = libc::PROT_NONE;
+            for (perm, flag) in
+                perms[..3]
+                    .iter()
+                    .zip([libc::PROT_READ, libc::PROT_WRITE, libc::PROT_EXEC])
+            {
+                if *perm != b'-' {
+                    prot |= flag;
+                }
+            }
+            table.set(len, Mapping { start, end, prot })?;
+            len += 1;
+        }
+        Ok(len)
+    }
+
+    /// Saves the open file descriptors, each with a duplicate to reopen it later
+    unsafe fn save_fds(&mut self) -> Result<(), Error> {
+        let mut fds_len = 0;
+        let own = [self.pagemap, self.tracker_fd()];
+        let mut result = Ok(());
+        for_each_fd(self.text.bytes(), |fd| {
+            if own.contains(&fd) || result.is_err() {
+                return;
+            }
+            let mut stat: libc::stat = core::mem::zeroed();
+            if libc::fstat(fd, &mut stat) != 0 {
+                return;
+            }
+            let backup = libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 0);
+            if backup < 0 {
+                result = Err(last_os_error("fcntl"));
+                return;
+            }
+            let saved = SavedFd {
+                fd,
+                backup,
+                dev: stat.st_dev,
+                ino: stat.st_ino,
+                offset: libc::lseek(fd, 0, libc::SEEK_CUR),
+            };
+            result = self.fds.set(fds_len, saved);
+            fds_len += 1;
+        })?;
+        self.fds_len = fds_len;
+        result
+    }
+
+    fn tracker_fd(&self) -> RawFd {
+        match self.tracker {
+            Tracker::SoftDirty { clear_refs } => clear_refs,
+            Tracker::Userfaultfd { uffd } => uffd,
+        }
+    }
+
+    /// Closes the files opened since the snapshot, reopens the closed ones and rewinds the others
+    unsafe fn restore_fds(&self) -> Result<(), Error> {
+        let saved = self.fds.get(self.fds_len);
+        let own = [self.pagemap, self.tracker_fd()];
+        for_each_fd(self.text.bytes(), |fd| {
+            let known = own.contains(&fd)
+                || saved
+                    .iter()
+                    .any(|saved| saved.fd == fd || saved.backup == fd);
+            if !known {
+                libc::close(fd);
+            }
+        })?;
+        for saved in saved {
+            let mut stat: libc::stat = core::mem::zeroed();
+            let same = libc::fstat(saved.fd, &mut stat) == 0
+                && stat.st_dev == saved.dev
+                && stat.st_ino == saved.ino;
+            if !same && libc::dup2(saved.backup, saved.fd) < 0 {
+                return Err(last_os_error("dup2"));
+            }
+            if saved.offset >= 0 {
+                libc::lseek(saved.fd, saved.offset, libc::SEEK_SET);
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves the program break back to where it was
+    unsafe fn restore_brk(&self) -> Result<(), Error> {
+        if libc::sbrk(0) as usize != self.brk && libc::brk(self.brk as *mut libc::c_void) != 0 {
+            return Err(last_os_error("brk"));
+        }
+        Ok(())
+    }
+
+    /// Unmaps the new mappings and maps the missing ones again.
+    /// Returns if the layout changed.
+    unsafe fn restore_layout(&self) -> Result<bool, Error> {
+        let layout = self.layout.get(self.layout_len);
+        let mut current_len = self.read_maps(&self.current)?;
+
+        let mut unmapped = false;
+        for mapping in self.current.get(current_len) {
+            if layout
+                .binary_search_by_key(&mapping.start, |m| m.start)
+                .ok()
+                .map_or(false, |idx| layout[idx] == *mapping)
+            {
+                continue;
+            }
+            let mut pos = mapping.start;
+            for known in layout
+                .iter()
+                .filter(|m| m.overlaps(mapping.start, mapping.end))
+            {
+                if known.start > pos {
+                    libc::munmap(pos as *mut libc::c_void, known.start - pos);
+                    unmapped = true;
+                }
+                pos = pos.max(known.end);
+            }
+            if pos < mapping.end {
+                libc::munmap(pos as *mut libc::c_void, mapping.end - pos);
+                unmapped = true;
+            }
+        }
+        if unmapped {
+            current_len = self.read_maps(&self.current)?;
+        }
+
+        let current = self.current.get(current_len);
+        let regions = self.regions.get(self.regions_len);
+        let mut fixed = false;
+        for known in layout {
+            if covers(current, known, true) {
+                continue;
+            }
+            fixed = true;
+            if covers(current, known, false) {
+                if libc::mprotect(known.start as *mut libc::c_void, known.len(), known.prot) != 0 {
+                    return Err(last_os_error("mprotect"));
+                }
+            } else if covers(regions.iter().map(|region| region.mapping), known, false) {
+                let remapped = libc::mmap(
+                    known.start as *mut libc::c_void,
+                    known.len(),
+                    known.prot,
+                    libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_FIXED,
+                    -1,
+                    0,
+                );
+                if remapped == libc::MAP_FAILED {
+                    return Err(last_os_error("mmap"));
+                }
+                for (idx, region) in regions.iter().enumerate() {
+                    if region.mapping.overlaps(known.start, known.end) {
+                        self.full_copy.set(idx, true)?;
+                    }
+                }
+            } else {
+                return Err(Error::illegal_state(
+                    "A mapping which is not part of the snapshot is gone",
+                ));
+            }
+        }
+        Ok(unmapped || fixed)
+    }
+
+    /// Copies back the dirty pages, returns how many
+    #[allow(clippy::cast_possible_wrap)]
+    unsafe fn restore_pages(&self) -> Result<usize, Error> {
+        let page_size = self.page_size;
+        let entries = self.pagemap_entries.as_mut_ptr();
+        let mut restored = 0;
+        for (idx, region) in self.regions.get(self.regions_len).iter().enumerate() {
+            let start = region.mapping.start;
+            let saved = self.backing.ptr.add(region.offset);
+            let pages = region.mapping.len() / page_size;
+            if self.full_copy.at(idx) {
+                ptr::copy_nonoverlapping(saved, start as *mut u8, region.mapping.len());
+                self.full_copy.set(idx, false)?;
+                restored += pages;
+                continue;
+            }
+            let mut page = 0;
+            while page < pages {
+                let chunk = (pages - page).min(PAGEMAP_CHUNK);
+                let len = chunk * size_of::<u64>();
+                let offset = (start / page_size + page) * size_of::<u64>();
+                let read = libc::pread(
+                    self.pagemap,
+                    entries as *mut libc::c_void,
+                    len,
+                    offset as libc::off_t,
+                );
+                if read != len as isize {
+                    return Err(last_os_error("pread of /proc/self/pagemap"));
+                }
+                for (i, entry) in slice::from_raw_parts(entries, chunk).iter().enumerate() {
+                    if self.is_dirty(*entry) {
+                        let offset = (page + i) * page_size;
+                        ptr::copy_nonoverlapping(
+                            saved.add(offset),
+                            (start + offset) as *mut u8,
+                            page_size,
+                        );
+                        restored += 1;
+                    }
+                }
+                page += chunk;
+            }
+        }
+        Ok(restored)
+    }
+
+    fn is_dirty(&self, entry: u64) -> bool {
+        match self.tracker {
+            Tracker::SoftDirty { .. } => entry & PM_SOFT_DIRTY != 0,
+            Tracker::Userfaultfd { .. } => entry & PM_UFFD_WP == 0,
+        }
+    }
+
+    /// Registers the regions for write-protection
+    unsafe fn register(&self, uffd: RawFd) -> Result<(), Error> {
+        for region in self.regions.get(self.regions_len) {
+            let mut register = UffdioRegister {
+                range: UffdioRange {
+                    start: region.mapping.start as u64,
+                    len: region.mapping.len() as u64,
+                },
+                mode: UFFDIO_REGISTER_MODE_WP,
+                ioctls: 0,
+            };
+            if libc::ioctl(uffd, UFFDIO_REGISTER, &mut register) != 0 {
+                return Err(last_os_error("UFFDIO_REGISTER"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Marks all pages as clean
+    unsafe fn reset_tracking(&self) -> Result<(), Error> {
+        match self.tracker {
+            Tracker::SoftDirty { clear_refs } => {
+                if libc::write(clear_refs, b"4".as_ptr() as *const libc::c_void, 1) != 1 {
+                    return Err(last_os_error("write to /proc/self/clear_refs"));
+                }
+            }
+            Tracker::Userfaultfd { uffd } => {
+                for region in self.regions.get(self.regions_len) {
+                    let mut protect = UffdioWriteprotect {
+                        range: UffdioRange {
+                            start: region.mapping.start as u64,
+                            len: region.mapping.len() as u64,
+                        },
+                        mode: UFFDIO_WRITEPROTECT_MODE_WP,
+                    };
+                    if libc::ioctl(uffd, UFFDIO_WRITEPROTECT, &mut protect) != 0 {
+                        return Err(last_os_error("UFFDIO_WRITEPROTECT"));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for MemorySnapshot {
+    fn drop(&mut self) {
+        unsafe {
+            for saved in self.fds.get(self.fds_len) {
+                libc::close(saved.backup);
+            }
+            libc::close(self.pagemap);
+            // Closing the userfaultfd also unregisters the regions
+            libc::close(self.tracker_fd());
+        }
+    }
+}
+
+/// If `mappings` cover all of `mapping`, optionally with the same protection
+fn covers<M>(mappings: M, mapping: &Mapping, same_prot: bool) -> bool
+where
+    M: IntoIterator,
+    M::Item: Borrow<Mapping>,
+{
+    let mut pos = mapping.start;
+    for other in mappings {
+        let other = other.borrow();
+        if !other.overlaps(mapping.start, mapping.end) {
+            continue;
+        }
+        if other.start > pos || (same_prot && other.prot != mapping.prot) {
+            return false;
+        }
+        pos = pos.max(other.end);
+    }
+    pos >= mapping.end
+}
+
+#[allow(clippy::cast_sign_loss)]
+fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+fn round_up(len: usize, align: usize) -> usize {
+    (len + align - 1) / align * align
+}
+
+fn parse_hex(digits: &[u8]) -> Option<usize> {
+    let mut value: usize = 0;
+    for digit in digits {
+        let digit = (*digit as char).to_digit(16)?;
+        value = value.checked_mul(16)? + digit as usize;
+    }
+    Some(value)
+}
+
+fn last_os_error(what: &str) -> Error {
+    let err = io::Error::last_os_error();
+    Error::unknown(format!("{} failed: {}", what, err))
+}
+
+unsafe fn open_proc(path: &[u8], flags: i32) -> Result<RawFd, Error> {
+    let path = CStr::from_bytes_with_nul_unchecked(path);
+    let fd = libc::open(path.as_ptr(), flags | libc::O_CLOEXEC);
+    if fd < 0 {
+        return Err(last_os_error("open"));
+    }
+    Ok(fd)
+}
+
+/// Reads a whole `/proc` file into `buf`, without allocating
+#[allow(clippy::cast_sign_loss)]
+unsafe fn read_proc<'a>(path: &[u8], buf: &'a mut [u8]) -> Result<&'a [u8], Error> {
+    let fd = open_proc(path, libc::O_RDONLY)?;
+    let mut len = 0;
+    loop {
+        if len == buf.len() {
+            libc::close(fd);
+            return Err(Error::illegal_state("A /proc file is too large to read"));
+        }
+        let read = libc::read(
+            fd,
+            buf.as_mut_ptr().add(len) as *mut libc::c_void,
+            buf.len() - len,
+        );
+        if read < 0 {
+            let err = last_os_error("read");
+            libc::close(fd);
+            return Err(err);
+        }
+        if read == 0 {
+            break;
+        }
+        len += read as usize;
+    }
+    libc::close(fd);
+    Ok(&buf[..len])
+}
+
+/// Calls `f` for each open file descriptor, reading `/proc/self/fd` without allocating.
+/// All file descriptors are collected first, `f` may close them.
+#[allow(clippy::cast_sign_loss)]
+unsafe fn for_each_fd<F>(buf: &mut [u8], mut f: F) -> Result<(), Error>
+where
+    F: FnMut(RawFd),
+{
+    let dir = open_proc(b"/proc/self/fd\0", libc::O_RDONLY | libc::O_DIRECTORY)?;
+    let mut len = 0;
+    loop {
+        let read = libc::syscall(
+            libc::SYS_getdents64,
+            dir,
+            buf.as_mut_ptr().add(len),
+            buf.len() - len,
+        );
+        if read < 0 {
+            let err = last_os_error("getdents64");
+            libc::close(dir);
+            return Err(err);
+        }
+        if read == 0 {
+            break;
+        }
+        len += read as usize;
+    }
+    libc::close(dir);
+
+    // struct linux_dirent64 { u64 d_ino; i64 d_off; u16 d_reclen; u8 d_type; char d_name[]; }
+    let mut pos = 0;
+    while pos < len {
+        let reclen = u16::from_ne_bytes([buf[pos + 16], buf[pos + 17]]) as usize;
+        let name = CStr::from_ptr(buf.as_ptr().add(pos + 19) as *const libc::c_char);
+        if let Some(fd) = core::str::from_utf8(name.to_bytes())
+            .ok()
+            .and_then(|name| name.parse::<RawFd>().ok())
+        {
+            if fd != dir {
+                f(fd);
+            }
+        }
+        pos += reclen;
+    }
+    Ok(())
+}
+
+/// Creates an asynchronous, write-protecting `userfaultfd`
+unsafe fn userfaultfd() -> Result<RawFd, Error> {
+    let uffd = libc::syscall(libc::SYS_userfaultfd, libc::O_CLOEXEC | libc::O_NONBLOCK) as RawFd;
+    if uffd < 0 {
+        return Err(last_os_error("userfaultfd"));
+    }
+    let mut api = UffdioApi {
+        api: UFFD_API,
+        features: UFFD_FEATURE_WP_ASYNC | UFFD_FEATURE_WP_UNPOPULATED,
+        ioctls: 0,
+    };
+    if libc::ioctl(uffd, UFFDIO_API, &mut api) != 0 {
+        let err = last_os_error("UFFDIO_API");
+        libc::close(uffd);
+        return Err(err);
+    }
+    Ok(uffd)
+}
+
+/// Checks if writing to a page of `probe` sets its soft-dirty bit
+#[allow(clippy::cast_possible_wrap)]
+unsafe fn soft_dirty_supported(probe: &RawMap) -> Result<bool, Error> {
+    let clear_refs = open_proc(b"/proc/self/clear_refs\0", libc::O_WRONLY)?;
+    let cleared = libc::write(clear_refs, b"4".as_ptr() as *const libc::c_void, 1) == 1;
+    libc::close(clear_refs);
+    if !cleared {
+        return Ok(false);
+    }
+    ptr::write_volatile(probe.ptr, 1);
+
+    let pagemap = open_proc(b"/proc/self/pagemap\0", libc::O_RDONLY)?;
+    let mut entry: u64 = 0;
+    let read = libc::pread(
+        pagemap,
+        &mut entry as *mut u64 as *mut libc::c_void,
+        size_of::<u64>(),
+        (probe.ptr as usize / page_size() * size_of::<u64>()) as libc::off_t,
+    );
+    libc::close(pagemap);
+    Ok(read == size_of::<u64>() as isize && entry & PM_SOFT_DIRTY != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{boxed::Box, vec};
+    use serial_test::serial;
+    use std::{fs::File, os::unix::io::IntoRawFd, panic};
+
+    use super::{DirtyTracking, MemorySnapshot};
+
+    static mut GLOBAL: [u8; 3 * 4096] = [0; 3 * 4096];
+
+    /// Changes the memory, the layout and the files, then restores them
+    unsafe fn change_and_restore(tracking: DirtyTracking) -> bool {
+        let heap = Box::into_raw(Box::new([1_u8; 64]));
+        let snapshot = MemorySnapshot::take(tracking).unwrap();
+        assert!(snapshot.size() > 0);
+
+        GLOBAL[4096] = 1;
+        (*heap)[0] = 2;
+        let mut leaked = vec![3_u8; 1 << 20];
+        let leaked_ptr = leaked.as_mut_ptr() as *mut libc::c_void;
+        core::mem::forget(leaked);
+        let fd = File::open("/proc/self/maps").unwrap().into_raw_fd();
+
+        let restored = snapshot.restore().unwrap();
+        let unmapped = libc::msync(leaked_ptr, 4096, libc::MS_ASYNC) != 0;
+        let closed = libc::fcntl(fd, libc::F_GETFD) == -1;
+        let memory = GLOBAL[4096] == 0 && (*heap)[0] == 1;
+
+        GLOBAL[0] = 1;
+        let restored_again = snapshot.restore().unwrap();
+        restored > 0 && unmapped && closed && memory && GLOBAL[0] == 0 && restored_again < restored
+    }
+
+    #[test]
+    #[serial]
+    fn test_memory_snapshot() {
+        let tracking = match DirtyTracking::detect() {
+            Some(tracking) => tracking,
+            None => return,
+        };
+        // The snapshot covers the whole process, so we take it in a child
+        unsafe {
+            let pid = libc::fork();
+            assert!(pid >= 0);
+            if pid == 0 {
+                let ok = panic::catch_unwind(|| change_and_restore(tracking)).unwrap_or(false);
+                libc::_exit(if ok { 0 } else { 1 });
+            }
+            let mut status = 0;
+            libc::waitpid(pid, &mut status, 0);
+            assert!(libc::WIFEXITED(status));
+            assert_eq!(libc::WEXITSTATUS(status), 0);
+        }
+    }
+}
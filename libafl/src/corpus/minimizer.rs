@@ -0,0 +1,498 @@
+//! Offline corpus minimization, in the spirit of `afl-cmin`.
+//!
+//! The [`MapCorpusMinimizer`] replays every input of a corpus through an [`Executor`], records which entries
+//! of a [`MapObserver`] each input sets, and how often, and keeps a minimal subset of the inputs that still covers every
+//! entry seen with every hitcount bucket seen, like the tuples of `afl-cmin`.
+//! Like `afl-cmin`, only inputs that run without crashing or timing out are kept, unless [`MapCorpusMinimizer::keep_failing`] is set.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::marker::PhantomData;
+use std::{fs, path::Path};
+
+use crate::{
+    corpus::{Corpus, Testcase},
+    executors::{Executor, ExitKind, HasObservers},
+    inputs::Input,
+    observers::{MapObserver, ObserversTuple},
+    state::HasExecutions,
+    Error,
+};
+
+/// How to solve the set cover problem when minimizing a corpus
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetCoverStrategy {
+    /// Repeatedly pick the input covering the most entries not yet covered, like `afl-cmin`. Fast, but may keep a few inputs too many.
+    Greedy,
+    /// Search for a smallest set of inputs, using branch and bound. Exponential in the worst case.
+    Optimal,
+}
+
+/// Returns the indices of a smallest (for [`SetCoverStrategy::Optimal`]) or small (for [`SetCoverStrategy::Greedy`])
+/// subset of `coverage` that covers every entry any of them covers.
+/// Ties are broken in favour of lower indices, so callers can order the inputs by preference, e.g. by size.
+#[must_use]
+pub fn set_cover(coverage: &[Vec<usize>], strategy: SetCoverStrategy) -> Vec<usize> {
+    // Map the covered entries to dense ids
+    let mut ids = hashbrown::HashMap::new();
+    let sets: Vec<Vec<usize>> = coverage
+        .iter()
+        .map(|entries| {
+            let mut set: Vec<usize> = entries
+                .iter()
+                .map(|entry| {
+                    let next = ids.len();
+                    *ids.entry(*entry).or_insert(next)
+                })
+                .collect();
+            set.sort_unstable();
+            set.dedup();
+            set
+        })
+        .collect();
+
+    let greedy = greedy_cover(&sets, ids.len());
+    match strategy {
+        SetCoverStrategy::Greedy => greedy,
+        SetCoverStrategy::Optimal => {
+            let mut search = CoverSearch::new(&sets, ids.len(), greedy);
+            search.run();
+            let mut best = search.best;
+            best.sort_unstable();
+            best
+        }
+    }
+}
+
+fn greedy_cover(sets: &[Vec<usize>], universe: usize) -> Vec<usize> {
+    let mut covered = vec![false; universe];
+    let mut remaining = universe;
+    let mut chosen = vec![];
+    while remaining > 0 {
+        let (best, gain) = sets
+            .iter()
+            .enumerate()
+            .map(|(idx, set)| (idx, set.iter().filter(|e| !covered[**e]).count()))
+            .fold((0, 0), |best, cur| if cur.1 > best.1 { cur } else { best });
+        if gain == 0 {
+            break;
+        }
+        for entry in &sets[best] {
+            covered[*entry] = true;
+        }
+        remaining -= gain;
+        chosen.push(best);
+    }
+    chosen.sort_unstable();
+    chosen
+}
+
+/// Branch and bound search for an optimal set cover
+struct CoverSearch<'a> {
+    sets: &'a [Vec<usize>],
+    /// For each entry, the sets covering it
+    covering: Vec<Vec<usize>>,
+    /// For each entry, how many chosen sets cover it
+    covered: Vec<usize>,
+    remaining: usize,
+    max_set_len: usize,
+    chosen: Vec<usize>,
+    best: Vec<usize>,
+}
+
+impl<'a> CoverSearch<'a> {
+    fn new(sets: &'a [Vec<usize>], universe: usize, initial: Vec<usize>) -> Self {
+        let mut covering = vec![vec![]; universe];
+        for (idx, set) in sets.iter().enumerate() {
+            for entry in set {
+                covering[*entry].push(idx);
+            }
+        }
+        Self {
+            sets,
+            covering,
+            covered: vec![0; universe],
+            remaining: universe,
+            max_set_len: sets.iter().map(Vec::len).max().unwrap_or(0),
+            chosen: vec![],
+            best: initial,
+        }
+    }
+
+    fn run(&mut self) {
+        if self.remaining == 0 {
+            if self.chosen.len() < self.best.len() {
+                self.best = self.chosen.clone();
+            }
+            return;
+        }
+        // Even if every further set covered `max_set_len` new entries, we could not beat the best cover
+        let lower_bound =
+            self.chosen.len() + (self.remaining + self.max_set_len - 1) / self.max_set_len;
+        if lower_bound >= self.best.len() {
+            return;
+        }
+
+        // Branch on the uncovered entry with the fewest options
+        let entry = (0..self.covered.len())
+            .filter(|e| self.covered[*e] == 0)
+            .min_by_key(|e| self.covering[*e].len())
+            .unwrap();
+        for set in self.covering[entry].clone() {
+            self.choose(set);
+            self.run();
+            self.unchoose(set);
+        }
+    }
+
+    fn choose(&mut self, set: usize) {
+        for entry in &self.sets[set] {
+            if self.covered[*entry] == 0 {
+                self.remaining -= 1;
+            }
+            self.covered[*entry] += 1;
+        }
+        self.chosen.push(set);
+    }
+
+    fn unchoose(&mut self, set: usize) {
+        self.chosen.pop();
+        for entry in &self.sets[set] {
+            self.covered[*entry] -= 1;
+            if self.covered[*entry] == 0 {
+                self.remaining += 1;
+            }
+        }
+    }
+}
+
+/// The number of AFL hitcount buckets of a map entry
+const HITCOUNT_BUCKETS: usize = 8;
+
+/// The AFL hitcount bucket of a raw hitcount: 1, 2, 3, 4-7, 8-15, 16-31, 32-127, 128+
+fn hitcount_bucket(count: u64) -> usize {
+    match count {
+        0 | 1 => 0,
+        2 => 1,
+        3 => 2,
+        4..=7 => 3,
+        8..=15 => 4,
+        16..=31 => 5,
+        32..=127 => 6,
+        _ => 7,
+    }
+}
+
+/// Minimizes a corpus to a subset of inputs that covers every entry of a [`MapObserver`] that any input covers.
+/// The entries are compared by their AFL hitcount bucket, so the [`MapObserver`] needs to hold the raw hitcounts,
+/// and should not be wrapped in a [`crate::observers::HitcountsMapObserver`], which buckets them already.
+#[derive(Debug, Clone)]
+pub struct MapCorpusMinimizer<O> {
+    observer_name: String,
+    strategy: SetCoverStrategy,
+    keep_failing: bool,
+    phantom: PhantomData<O>,
+}
+
+impl<O> MapCorpusMinimizer<O>
+where
+    O: MapObserver,
+    O::Entry: Into<u64>,
+{
+    /// Creates a new [`MapCorpusMinimizer`], using the coverage of the given [`MapObserver`].
+    #[must_use]
+    pub fn new(observer: &O, strategy: SetCoverStrategy) -> Self {
+        Self {
+            observer_name: observer.name().to_string(),
+            strategy,
+            keep_failing: false,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Also keep inputs whose run did not end with [`ExitKind::Ok`], such as crashes and timeouts.
+    /// By default, their coverage is ignored, like `afl-cmin` does.
+    #[must_use]
+    pub fn keep_failing(mut self, keep_failing: bool) -> Self {
+        self.keep_failing = keep_failing;
+        self
+    }
+
+    /// Runs the `input` and returns the map entries it sets, as `index * 8 + bucket`, with the AFL hitcount bucket of the entry.
+    /// Returns no entries if the run failed, unless [`MapCorpusMinimizer::keep_failing`] is set, so the input is never kept.
+    pub fn coverage<E, EM, I, OT, S, Z>(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        mgr: &mut EM,
+        input: &I,
+    ) -> Result<Vec<usize>, Error>
+    where
+        E: Executor<EM, I, S, Z> + HasObservers<I, OT, S>,
+        I: Input,
+        OT: ObserversTuple<I, S>,
+        S: HasExecutions,
+    {
+        executor.observers_mut().pre_exec_all(state, input)?;
+        let exit_kind = executor.run_target(fuzzer, state, mgr, input)?;
+        *state.executions_mut() += 1;
+        executor
+            .observers_mut()
+            .post_exec_all(state, input, &exit_kind)?;
+        if exit_kind != ExitKind::Ok && !self.keep_failing {
+            return Ok(vec![]);
+        }
+
+        let observer = executor
+            .observers()
+            .match_name::<O>(&self.observer_name)
+            .ok_or_else(|| {
+                Error::key_not_found(format!(
+                    "MapCorpusMinimizer: observer {} not found",
+                    self.observer_name
+                ))
+            })?;
+        let initial = observer.initial();
+        Ok((0..observer.usable_count())
+            .filter(|idx| *observer.get(*idx) != initial)
+            .map(|idx| idx * HITCOUNT_BUCKETS + hitcount_bucket((*observer.get(idx)).into()))
+            .collect())
+    }
+
+    /// Runs all `inputs` and returns the indices of the inputs to keep.
+    pub fn minimize<E, EM, I, OT, S, Z>(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        mgr: &mut EM,
+        inputs: &[I],
+    ) -> Result<Vec<usize>, Error>
+    where
+        E: Executor<EM, I, S, Z> + HasObservers<I, OT, S>,
+        I: Input,
+        OT: ObserversTuple<I, S>,
+        S: HasExecutions,
+    {
+        let coverage = inputs
+            .iter()
+            .map(|input| self.coverage(fuzzer, executor, state, mgr, input))
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(set_cover(&coverage, self.strategy))
+    }
+
+    /// Runs all testcases of the `corpus`, loading them from disk if needed, and returns the indices of the testcases to keep.
+    pub fn minimize_corpus<C, E, EM, I, OT, S, Z>(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        mgr: &mut EM,
+        corpus: &C,
+    ) -> Result<Vec<usize>, Error>
+    where
+        C: Corpus<I>,
+        E: Executor<EM, I, S, Z> + HasObservers<I, OT, S>,
+        I: Input,
+        OT: ObserversTuple<I, S>,
+        S: HasExecutions,
+    {
+        let mut coverage = Vec::with_capacity(corpus.count());
+        for idx in 0..corpus.count() {
+            let input = corpus.get(idx)?.borrow_mut().load_input()?.clone();
+            coverage.push(self.coverage(fuzzer, executor, state, mgr, &input)?);
+        }
+        Ok(set_cover(&coverage, self.strategy))
+    }
+
+    /// Minimizes the inputs in the directory `in_dir`, adding the inputs to keep to the corpus `out`,
+    /// for example an [`crate::corpus::OnDiskCorpus`] in the output directory.
+    /// Smaller inputs are preferred, like `afl-cmin` does. Returns the number of inputs kept.
+    pub fn minimize_dir<C, E, EM, I, OT, P, S, Z>(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        mgr: &mut EM,
+        in_dir: P,
+        out: &mut C,
+    ) -> Result<usize, Error>
+    where
+        C: Corpus<I>,
+        E: Executor<EM, I, S, Z> + HasObservers<I, OT, S>,
+        I: Input,
+        OT: ObserversTuple<I, S>,
+        P: AsRef<Path>,
+        S: HasExecutions,
+    {
+        let mut files = vec![];
+        for entry in fs::read_dir(in_dir)? {
+            let entry = entry?;
+            let attr = entry.metadata()?;
+            if attr.is_file() && attr.len() > 0 {
+                files.push((attr.len(), entry.path()));
+            }
+        }
+        files.sort();
+
+        let inputs = files
+            .iter()
+            .map(|(_, path)| I::from_file(path))
+            .collect::<Result<Vec<I>, Error>>()?;
+        let keep = self.minimize(fuzzer, executor, state, mgr, &inputs)?;
+        for idx in &keep {
+            out.add(Testcase::new(inputs[*idx].clone()))?;
+        }
+        Ok(keep.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, vec::Vec};
+
+    use crate::{
+        bolts::{
+            rands::StdRand,
+            tuples::{tuple_list, tuple_list_type},
+            AsMutSlice,
+        },
+        corpus::{
+            minimizer::{set_cover, MapCorpusMinimizer, SetCoverStrategy},
+            InMemoryCorpus,
+        },
+        executors::{Executor, ExitKind, HasObservers},
+        inputs::{BytesInput, HasBytesVec},
+        observers::StdMapObserver,
+        state::StdState,
+        Error,
+    };
+
+    type TestState =
+        StdState<InMemoryCorpus<BytesInput>, BytesInput, StdRand, InMemoryCorpus<BytesInput>>;
+
+    /// Counts each input byte in its map entry, and crashes on a `0xff`
+    #[derive(Debug)]
+    struct BytesExecutor {
+        observers: tuple_list_type!(StdMapObserver<'static, u8>),
+    }
+
+    impl<EM, S, Z> Executor<EM, BytesInput, S, Z> for BytesExecutor {
+        fn run_target(
+            &mut self,
+            _fuzzer: &mut Z,
+            _state: &mut S,
+            _mgr: &mut EM,
+            input: &BytesInput,
+        ) -> Result<ExitKind, Error> {
+            let map = self.observers.0.as_mut_slice();
+            for byte in input.bytes() {
+                map[*byte as usize] += 1;
+            }
+            if input.bytes().contains(&0xff) {
+                Ok(ExitKind::Crash)
+            } else {
+                Ok(ExitKind::Ok)
+            }
+        }
+    }
+
+    impl HasObservers<BytesInput, tuple_list_type!(StdMapObserver<'static, u8>), TestState>
+        for BytesExecutor
+    {
+        fn observers(&self) -> &tuple_list_type!(StdMapObserver<'static, u8>) {
+            &self.observers
+        }
+
+        fn observers_mut(&mut self) -> &mut tuple_list_type!(StdMapObserver<'static, u8>) {
+            &mut self.observers
+        }
+    }
+
+    #[test]
+    fn test_minimize_skips_failing_inputs() {
+        let observer = StdMapObserver::new_owned("map", vec![0_u8; 256]);
+        let minimizer = MapCorpusMinimizer::new(&observer, SetCoverStrategy::Greedy);
+        let mut executor = BytesExecutor {
+            observers: tuple_list!(observer),
+        };
+        let mut state: TestState = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+        let inputs: Vec<BytesInput> = [&[1_u8, 2][..], &[3, 0xff], &[2]]
+            .iter()
+            .map(|bytes| BytesInput::new(bytes.to_vec()))
+            .collect();
+
+        // The crashing input covers new entries, but is dropped
+        let keep = minimizer
+            .minimize(&mut (), &mut executor, &mut state, &mut (), &inputs)
+            .unwrap();
+        assert_eq!(keep, vec![0]);
+
+        let keep = minimizer
+            .keep_failing(true)
+            .minimize(&mut (), &mut executor, &mut state, &mut (), &inputs)
+            .unwrap();
+        assert_eq!(keep, vec![0, 1]);
+    }
+
+    #[test]
+    fn test_minimize_buckets_hitcounts() {
+        let observer = StdMapObserver::new_owned("map", vec![0_u8; 256]);
+        let minimizer = MapCorpusMinimizer::new(&observer, SetCoverStrategy::Greedy);
+        let mut executor = BytesExecutor {
+            observers: tuple_list!(observer),
+        };
+        let mut state: TestState = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+        // The same entry, hit once, 4 and 6 times: the last two share the 4-7 bucket
+        let inputs: Vec<BytesInput> = [1_usize, 4, 6]
+            .iter()
+            .map(|count| BytesInput::new(vec![2; *count]))
+            .collect();
+
+        let keep = minimizer
+            .minimize(&mut (), &mut executor, &mut state, &mut (), &inputs)
+            .unwrap();
+        assert_eq!(keep, vec![0, 1]);
+    }
+
+    #[test]
+    fn test_set_cover() {
+        // Greedy picks the largest set first, and then needs two more
+        let coverage = vec![
+            vec![10, 11, 12],
+            vec![13, 14, 15],
+            vec![10, 11, 13, 14],
+            vec![12],
+            vec![15],
+            vec![],
+        ];
+        assert_eq!(
+            set_cover(&coverage, SetCoverStrategy::Greedy),
+            vec![0, 1, 2]
+        );
+        assert_eq!(set_cover(&coverage, SetCoverStrategy::Optimal), vec![0, 1]);
+
+        // On ties, the first input wins
+        let coverage = vec![vec![1, 2], vec![1, 2], vec![1]];
+        assert_eq!(set_cover(&coverage, SetCoverStrategy::Greedy), vec![0]);
+        assert_eq!(set_cover(&coverage, SetCoverStrategy::Optimal), vec![0]);
+        assert!(set_cover(&[], SetCoverStrategy::Optimal).is_empty());
+    }
+}
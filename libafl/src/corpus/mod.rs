@@ -16,6 +16,11 @@ pub mod cached;
 #[cfg(feature = "std")]
 pub use cached::CachedOnDiskCorpus;
 
+#[cfg(feature = "std")]
+pub mod minimizer;
+#[cfg(feature = "std")]
+pub use minimizer::{MapCorpusMinimizer, SetCoverStrategy};
+
 use core::cell::RefCell;
 
 use crate::{inputs::Input, Error};
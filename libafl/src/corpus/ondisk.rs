@@ -64,13 +64,61 @@ where
     /// Add an entry to the corpus and return its index
     #[inline]
     fn add(&mut self, mut testcase: Testcase<I>) -> Result<usize, Error> {
+        self.save_testcase(self.entries.len(), &mut testcase)?;
+        self.entries.push(RefCell::new(testcase));
+        Ok(self.entries.len() - 1)
+    }
+
+    /// Replaces the testcase at the given idx, also on disk
+    #[inline]
+    fn replace(&mut self, idx: usize, mut testcase: Testcase<I>) -> Result<(), Error> {
+        if idx >= self.entries.len() {
+            return Err(Error::key_not_found(format!("Index {} out of bounds", idx)));
+        }
+        self.save_testcase(idx, &mut testcase)?;
+        self.entries[idx] = RefCell::new(testcase);
+        Ok(())
+    }
+
+    /// Removes an entry from the corpus, returning it if it was present.
+    #[inline]
+    fn remove(&mut self, idx: usize) -> Result<Option<Testcase<I>>, Error> {
+        if idx >= self.entries.len() {
+            Ok(None)
+        } else {
+            Ok(Some(self.entries.remove(idx).into_inner()))
+        }
+    }
+
+    /// Get by id
+    #[inline]
+    fn get(&self, idx: usize) -> Result<&RefCell<Testcase<I>>, Error> {
+        Ok(&self.entries[idx])
+    }
+
+    /// Current testcase scheduled
+    #[inline]
+    fn current(&self) -> &Option<usize> {
+        &self.current
+    }
+
+    /// Current testcase scheduled (mutable)
+    #[inline]
+    fn current_mut(&mut self) -> &mut Option<usize> {
+        &mut self.current
+    }
+}
+
+impl<I> OnDiskCorpus<I>
+where
+    I: Input,
+{
+    /// Writes the input of the `testcase` to disk, and its metadata, if enabled.
+    /// A file name is picked if it has none yet, for the testcase at `idx`.
+    fn save_testcase(&self, idx: usize, testcase: &mut Testcase<I>) -> Result<(), Error> {
         if testcase.filename().is_none() {
             // TODO walk entry metadata to ask for pieces of filename (e.g. :havoc in AFL)
-            let file_orig = testcase
-                .input()
-                .as_ref()
-                .unwrap()
-                .generate_name(self.entries.len());
+            let file_orig = testcase.input().as_ref().unwrap().generate_name(idx);
             let mut file = file_orig.clone();
 
             let mut ctr = 2;
@@ -125,53 +173,9 @@ where
         testcase
             .store_input()
             .expect("Could not save testcase to disk");
-        self.entries.push(RefCell::new(testcase));
-        Ok(self.entries.len() - 1)
-    }
-
-    /// Replaces the testcase at the given idx
-    #[inline]
-    fn replace(&mut self, idx: usize, testcase: Testcase<I>) -> Result<(), Error> {
-        if idx >= self.entries.len() {
-            return Err(Error::key_not_found(format!("Index {} out of bounds", idx)));
-        }
-        self.entries[idx] = RefCell::new(testcase);
         Ok(())
     }
 
-    /// Removes an entry from the corpus, returning it if it was present.
-    #[inline]
-    fn remove(&mut self, idx: usize) -> Result<Option<Testcase<I>>, Error> {
-        if idx >= self.entries.len() {
-            Ok(None)
-        } else {
-            Ok(Some(self.entries.remove(idx).into_inner()))
-        }
-    }
-
-    /// Get by id
-    #[inline]
-    fn get(&self, idx: usize) -> Result<&RefCell<Testcase<I>>, Error> {
-        Ok(&self.entries[idx])
-    }
-
-    /// Current testcase scheduled
-    #[inline]
-    fn current(&self) -> &Option<usize> {
-        &self.current
-    }
-
-    /// Current testcase scheduled (mutable)
-    #[inline]
-    fn current_mut(&mut self) -> &mut Option<usize> {
-        &mut self.current
-    }
-}
-
-impl<I> OnDiskCorpus<I>
-where
-    I: Input,
-{
     /// Creates the [`OnDiskCorpus`].
     /// Will error, if [`std::fs::create_dir_all()`] failed for `dir_path`.
     pub fn new<P>(dir_path: P) -> Result<Self, Error>
@@ -0,0 +1,670 @@
+//! Control a running campaign without restarting it.
+//!
+//! The broker takes [`ControlRequest`]s on a [`ControlSocket`], and relays the commands to its clients,
+//! which apply them if their manager is wrapped in a [`ControlledEventManager`].
+//!
+//! The socket takes one command per line, and answers each with `ok`, or `error: <reason>`:
+//! - `add <path>`: load the input file, or all input files in the directory, and send them to all clients.
+//!   Like testcases from other clients, they are evaluated, and added to the corpus if interesting,
+//! - `dump <dir>`: every client writes a checkpoint of its state to `<dir>/client_<id>/`,
+//!   to restore with a [`crate::state::checkpoint::Checkpointer`] for this directory,
+//! - `log <debug|info|warn|error>`: only show and send logs of at least this severity,
+//! - `tokens <path>`: every client replaces its token dictionary with the one in the file,
+//! - `pause [id]`, `resume [id]` and `stop [id]`: pause, resume or stop one client, or all of them,
+//! - `shutdown`: stop all clients, then the broker.
+//!
+//! The socket is not authenticated, so it is a Unix socket only its owner may connect to.
+
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::time::Duration;
+use serde::Serialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    thread,
+};
+#[cfg(unix)]
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    sync::{Arc, Mutex},
+};
+
+#[cfg(unix)]
+use nix::sys::stat::{umask, Mode};
+
+use crate::{
+    bolts::fs::input_files,
+    events::{
+        ControlCommand, CustomBufHandlerFn, Event, EventConfig, EventFirer, EventManager,
+        EventManagerId, EventProcessor, EventRestarter, HasControlCommands, HasCustomBufHandlers,
+        HasEventManagerId, LogSeverity, ProgressReporter,
+    },
+    inputs::Input,
+    mutators::Tokens,
+    observers::ObserversTuple,
+    state::{checkpoint::Checkpointer, HasExecutions, HasMetadata},
+    Error,
+};
+
+/// The first sleep of a paused client, before it checks for new commands again
+const PAUSE_MIN_SLEEP: Duration = Duration::from_millis(10);
+/// The longest sleep of a paused client, and so the longest it takes to resume
+const PAUSE_MAX_SLEEP: Duration = Duration::from_secs(1);
+
+/// A request received on the [`ControlSocket`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ControlRequest {
+    /// Send a command to one client, or to all clients
+    Command {
+        /// The id of the client, or `None` for all clients
+        client_id: Option<u32>,
+        /// The command
+        command: ControlCommand,
+    },
+    /// Load the inputs at this path, and send them to all clients
+    AddInputs {
+        /// An input file, or a directory of input files
+        path: PathBuf,
+    },
+    /// Stop all clients, then the broker
+    Shutdown,
+}
+
+impl ControlRequest {
+    /// Parses a line of the control protocol
+    pub fn parse(line: &str) -> Result<Self, Error> {
+        let line = line.trim();
+        let (verb, arg) = match line.split_once(char::is_whitespace) {
+            Some((verb, arg)) => (verb, arg.trim()),
+            None => (line, ""),
+        };
+        let path = || {
+            if arg.is_empty() {
+                Err(Error::illegal_argument(format!("{} needs a path", verb)))
+            } else {
+                Ok(arg.to_string())
+            }
+        };
+        let client_id = || {
+            if arg.is_empty() {
+                Ok(None)
+            } else {
+                arg.parse()
+                    .map(Some)
+                    .map_err(|_| Error::illegal_argument(format!("Invalid client id {}", arg)))
+            }
+        };
+        let for_clients = |client_id, command| ControlRequest::Command { client_id, command };
+
+        Ok(match verb {
+            "add" => ControlRequest::AddInputs {
+                path: path()?.into(),
+            },
+            "dump" => for_clients(None, ControlCommand::DumpState { dir: path()? }),
+            "log" => for_clients(
+                None,
+                ControlCommand::SetLogLevel {
+                    level: parse_severity(arg)?,
+                },
+            ),
+            "tokens" => for_clients(None, ControlCommand::ReloadTokens { path: path()? }),
+            "pause" => for_clients(client_id()?, ControlCommand::Pause),
+            "resume" => for_clients(client_id()?, ControlCommand::Resume),
+            "stop" => for_clients(client_id()?, ControlCommand::Stop),
+            "shutdown" => ControlRequest::Shutdown,
+            _ => return Err(Error::illegal_argument(format!("Unknown command {}", verb))),
+        })
+    }
+
+    /// Makes all paths absolute, so the clients find them independently of their working directory.
+    /// Fails if an input or a dictionary does not exist, and creates the checkpoint directory.
+    #[cfg(unix)]
+    fn resolve_paths(self) -> Result<Self, Error> {
+        let absolute = |path: &str| -> Result<String, Error> {
+            Ok(fs::canonicalize(path)?.to_string_lossy().into_owned())
+        };
+        Ok(match self {
+            ControlRequest::AddInputs { path } => ControlRequest::AddInputs {
+                path: fs::canonicalize(path)?,
+            },
+            ControlRequest::Command {
+                client_id,
+                command: ControlCommand::DumpState { dir },
+            } => {
+                fs::create_dir_all(&dir)?;
+                ControlRequest::Command {
+                    client_id,
+                    command: ControlCommand::DumpState {
+                        dir: absolute(&dir)?,
+                    },
+                }
+            }
+            ControlRequest::Command {
+                client_id,
+                command: ControlCommand::ReloadTokens { path },
+            } => ControlRequest::Command {
+                client_id,
+                command: ControlCommand::ReloadTokens {
+                    path: absolute(&path)?,
+                },
+            },
+            request => request,
+        })
+    }
+}
+
+/// Parses a [`LogSeverity`], ignoring the case
+fn parse_severity(name: &str) -> Result<LogSeverity, Error> {
+    match name.to_ascii_lowercase().as_str() {
+        "debug" => Ok(LogSeverity::Debug),
+        "info" => Ok(LogSeverity::Info),
+        "warn" => Ok(LogSeverity::Warn),
+        "error" => Ok(LogSeverity::Error),
+        _ => Err(Error::illegal_argument(format!(
+            "Invalid log level {}",
+            name
+        ))),
+    }
+}
+
+/// A Unix socket taking [`ControlRequest`]s, one per line. A background thread serves all connections,
+/// the broker takes the requests with [`ControlSocket::take_requests`].
+///
+/// The socket is created with `0600` permissions, so only the user running the broker can connect to it.
+/// It is removed when the [`ControlSocket`] is dropped.
+#[cfg(unix)]
+#[derive(Debug)]
+pub struct ControlSocket {
+    path: PathBuf,
+    requests: Arc<Mutex<Vec<ControlRequest>>>,
+}
+
+#[cfg(unix)]
+impl ControlSocket {
+    /// Listens for control connections on a new Unix socket at `path`.
+    /// Fails if a file already exists at `path`.
+    pub fn bind<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref().to_path_buf();
+        // Set the permissions while creating the socket, as anyone connecting to it controls the campaign
+        let old_mask = umask(Mode::from_bits_truncate(0o177));
+        let listener = UnixListener::bind(&path);
+        umask(old_mask);
+        let listener = listener?;
+
+        let requests = Arc::new(Mutex::new(vec![]));
+        let queue = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let queue = queue.clone();
+                // A connection stays open for many commands, so it should not block others
+                thread::spawn(move || drop(serve_control(stream, &queue)));
+            }
+        });
+        Ok(Self { path, requests })
+    }
+
+    /// The path of the socket
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The requests received since the last call
+    #[must_use]
+    pub fn take_requests(&self) -> Vec<ControlRequest> {
+        core::mem::take(&mut *self.requests.lock().unwrap())
+    }
+}
+
+#[cfg(unix)]
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        drop(fs::remove_file(&self.path));
+    }
+}
+
+/// Reads requests from a control connection, until it is closed
+#[cfg(unix)]
+fn serve_control(stream: UnixStream, requests: &Mutex<Vec<ControlRequest>>) -> Result<(), Error> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match ControlRequest::parse(&line).and_then(ControlRequest::resolve_paths) {
+            Ok(request) => {
+                requests.lock().unwrap().push(request);
+                writer.write_all(b"ok\n")?;
+            }
+            Err(err) => writeln!(writer, "error: {}", err)?,
+        }
+    }
+    Ok(())
+}
+
+/// Loads the input file at `path`, or all input files in the directory at `path`, skipping hidden files
+pub fn load_inputs<I, P>(path: P) -> Result<Vec<I>, Error>
+where
+    I: Input,
+    P: AsRef<Path>,
+{
+    input_files(path)?.iter().map(I::from_file).collect()
+}
+
+/// Wraps an [`EventManager`], and applies the [`ControlCommand`]s it receives from the broker.
+///
+/// While paused, the client keeps processing events, and sleeps in between, backing off to [`PAUSE_MAX_SLEEP`],
+/// as the broker has no way to wake it up.
+#[derive(Debug)]
+pub struct ControlledEventManager<EM> {
+    inner: EM,
+    /// If the broker sent a [`ControlCommand::Pause`]
+    paused: bool,
+    /// Logs below this severity are not sent
+    log_level: LogSeverity,
+}
+
+impl<EM> ControlledEventManager<EM> {
+    /// Wraps the `inner` manager
+    pub fn new(inner: EM) -> Self {
+        Self {
+            inner,
+            paused: false,
+            log_level: LogSeverity::Debug,
+        }
+    }
+
+    /// The wrapped manager
+    pub fn inner(&self) -> &EM {
+        &self.inner
+    }
+
+    /// The wrapped manager, mutable
+    pub fn inner_mut(&mut self) -> &mut EM {
+        &mut self.inner
+    }
+
+    /// Applies the [`ControlCommand`]s received by the wrapped manager.
+    /// Failing to write a checkpoint or to load a dictionary is logged, but does not stop the client.
+    fn apply_control_commands<I, S>(&mut self, state: &mut S) -> Result<(), Error>
+    where
+        EM: EventFirer<I> + HasControlCommands + HasEventManagerId,
+        I: Input,
+        S: HasExecutions + HasMetadata + Serialize,
+    {
+        for command in self.inner.take_control_commands() {
+            let (severity_level, message) = match command {
+                ControlCommand::Pause => {
+                    self.paused = true;
+                    (LogSeverity::Info, "Paused".to_string())
+                }
+                ControlCommand::Resume => {
+                    self.paused = false;
+                    (LogSeverity::Info, "Resumed".to_string())
+                }
+                ControlCommand::Stop => return Err(Error::ShuttingDown),
+                ControlCommand::SetLogLevel { level } => {
+                    self.log_level = level;
+                    continue;
+                }
+                ControlCommand::DumpState { dir } => {
+                    let dir = Path::new(&dir).join(format!("client_{}", self.inner.mgr_id().id));
+                    match Checkpointer::new(&dir).and_then(|checkpointer| checkpointer.save(state))
+                    {
+                        Ok(()) => (
+                            LogSeverity::Info,
+                            format!("Wrote a checkpoint of the state to {:?}", dir),
+                        ),
+                        Err(err) => (
+                            LogSeverity::Warn,
+                            format!("Could not write a checkpoint to {:?}: {}", dir, err),
+                        ),
+                    }
+                }
+                ControlCommand::ReloadTokens { path } => match Tokens::from_file(&path) {
+                    Ok(tokens) => {
+                        let message =
+                            format!("Loaded {} tokens from {}", tokens.tokens().len(), path);
+                        state.add_metadata(tokens);
+                        (LogSeverity::Info, message)
+                    }
+                    Err(err) => (
+                        LogSeverity::Warn,
+                        format!("Could not load tokens from {}: {}", path, err),
+                    ),
+                },
+            };
+            EventFirer::<I>::log(self, state, severity_level, message)?;
+        }
+        Ok(())
+    }
+}
+
+impl<EM, I> EventFirer<I> for ControlledEventManager<EM>
+where
+    EM: EventFirer<I>,
+    I: Input,
+{
+    fn fire<S>(&mut self, state: &mut S, event: Event<I>) -> Result<(), Error> {
+        self.inner.fire(state, event)
+    }
+
+    /// Send off an [`Event::Log`] event to the broker, unless it is below the log level set by the broker
+    fn log<S>(
+        &mut self,
+        state: &mut S,
+        severity_level: LogSeverity,
+        message: String,
+    ) -> Result<(), Error> {
+        if severity_level < self.log_level {
+            return Ok(());
+        }
+        self.inner.log(state, severity_level, message)
+    }
+
+    fn serialize_observers<OT, S>(&mut self, observers: &OT) -> Result<Vec<u8>, Error>
+    where
+        OT: ObserversTuple<I, S> + Serialize,
+    {
+        self.inner.serialize_observers(observers)
+    }
+
+    fn configuration(&self) -> EventConfig {
+        self.inner.configuration()
+    }
+}
+
+impl<EM, S> EventRestarter<S> for ControlledEventManager<EM>
+where
+    EM: EventRestarter<S>,
+{
+    fn on_restart(&mut self, state: &mut S) -> Result<(), Error> {
+        self.inner.on_restart(state)
+    }
+
+    fn await_restart_safe(&mut self) {
+        self.inner.await_restart_safe();
+    }
+}
+
+impl<E, EM, I, S, Z> EventProcessor<E, I, S, Z> for ControlledEventManager<EM>
+where
+    EM: EventProcessor<E, I, S, Z> + EventFirer<I> + HasControlCommands + HasEventManagerId,
+    I: Input,
+    S: HasExecutions + HasMetadata + Serialize,
+{
+    fn process(&mut self, fuzzer: &mut Z, state: &mut S, executor: &mut E) -> Result<usize, Error> {
+        let mut count = self.inner.process(fuzzer, state, executor)?;
+        self.apply_control_commands(state)?;
+        let mut sleep = PAUSE_MIN_SLEEP;
+        while self.paused {
+            thread::sleep(sleep);
+            sleep = (sleep * 2).min(PAUSE_MAX_SLEEP);
+            count += self.inner.process(fuzzer, state, executor)?;
+            self.apply_control_commands(state)?;
+        }
+        Ok(count)
+    }
+
+    fn deserialize_observers<OT>(&mut self, observers_buf: &[u8]) -> Result<OT, Error>
+    where
+        OT: ObserversTuple<I, S> + serde::de::DeserializeOwned,
+    {
+        self.inner.deserialize_observers(observers_buf)
+    }
+}
+
+impl<E, EM, I, S, Z> EventManager<E, I, S, Z> for ControlledEventManager<EM>
+where
+    EM: EventManager<E, I, S, Z> + HasControlCommands,
+    I: Input,
+    S: HasExecutions + HasMetadata + Serialize,
+{
+}
+
+impl<EM, I> ProgressReporter<I> for ControlledEventManager<EM>
+where
+    EM: ProgressReporter<I>,
+    I: Input,
+{
+}
+
+impl<EM> HasEventManagerId for ControlledEventManager<EM>
+where
+    EM: HasEventManagerId,
+{
+    fn mgr_id(&self) -> EventManagerId {
+        self.inner.mgr_id()
+    }
+}
+
+impl<EM, S> HasCustomBufHandlers<S> for ControlledEventManager<EM>
+where
+    EM: HasCustomBufHandlers<S>,
+{
+    fn add_custom_buf_handler(&mut self, handler: Box<CustomBufHandlerFn<S>>) {
+        self.inner.add_custom_buf_handler(handler);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec, vec::Vec};
+    #[cfg(unix)]
+    use std::os::unix::{fs::PermissionsExt, net::UnixStream};
+    use std::{
+        fs,
+        io::{BufRead, BufReader, Write},
+        thread,
+        time::Duration,
+    };
+
+    #[cfg(unix)]
+    use crate::events::control::ControlSocket;
+    use crate::{
+        bolts::rands::StdRand,
+        corpus::InMemoryCorpus,
+        events::{
+            control::{ControlRequest, ControlledEventManager},
+            ControlCommand, Event, EventFirer, EventManagerId, EventProcessor, HasControlCommands,
+            HasEventManagerId, LogSeverity,
+        },
+        inputs::BytesInput,
+        mutators::Tokens,
+        state::{HasMetadata, StdState},
+        Error,
+    };
+
+    type TestState =
+        StdState<InMemoryCorpus<BytesInput>, BytesInput, StdRand, InMemoryCorpus<BytesInput>>;
+
+    /// A manager receiving one batch of commands per call to `process`, and recording the logs it sends
+    #[derive(Default)]
+    struct CommandsManager {
+        batches: Vec<Vec<ControlCommand>>,
+        received: Vec<ControlCommand>,
+        logs: Vec<(LogSeverity, String)>,
+    }
+
+    impl EventFirer<BytesInput> for CommandsManager {
+        fn fire<S>(&mut self, _state: &mut S, event: Event<BytesInput>) -> Result<(), Error> {
+            if let Event::Log {
+                severity_level,
+                message,
+                ..
+            } = event
+            {
+                self.logs.push((severity_level, message));
+            }
+            Ok(())
+        }
+    }
+
+    impl EventProcessor<(), BytesInput, TestState, ()> for CommandsManager {
+        fn process(
+            &mut self,
+            _fuzzer: &mut (),
+            _state: &mut TestState,
+            _executor: &mut (),
+        ) -> Result<usize, Error> {
+            if self.batches.is_empty() {
+                return Ok(0);
+            }
+            self.received = self.batches.remove(0);
+            Ok(self.received.len())
+        }
+    }
+
+    impl HasControlCommands for CommandsManager {
+        fn take_control_commands(&mut self) -> Vec<ControlCommand> {
+            core::mem::take(&mut self.received)
+        }
+    }
+
+    impl HasEventManagerId for CommandsManager {
+        fn mgr_id(&self) -> EventManagerId {
+            EventManagerId { id: 1 }
+        }
+    }
+
+    fn test_state() -> TestState {
+        StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_control_request() {
+        assert_eq!(
+            ControlRequest::parse("pause 2").unwrap(),
+            ControlRequest::Command {
+                client_id: Some(2),
+                command: ControlCommand::Pause
+            }
+        );
+        assert_eq!(
+            ControlRequest::parse(" stop\n").unwrap(),
+            ControlRequest::Command {
+                client_id: None,
+                command: ControlCommand::Stop
+            }
+        );
+        assert_eq!(
+            ControlRequest::parse("log WARN").unwrap(),
+            ControlRequest::Command {
+                client_id: None,
+                command: ControlCommand::SetLogLevel {
+                    level: LogSeverity::Warn
+                }
+            }
+        );
+        assert_eq!(
+            ControlRequest::parse("shutdown").unwrap(),
+            ControlRequest::Shutdown
+        );
+        assert!(ControlRequest::parse("add").is_err());
+        assert!(ControlRequest::parse("pause one").is_err());
+        assert!(ControlRequest::parse("explode").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_control_socket() {
+        let path = std::env::temp_dir().join(format!("libafl_test_control_{}", std::process::id()));
+        drop(fs::remove_file(&path));
+        let socket = ControlSocket::bind(&path).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(ControlSocket::bind(&path).is_err());
+
+        let stream = UnixStream::connect(socket.path()).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut writer = stream;
+
+        let mut answer = String::new();
+        writer.write_all(b"resume 1\n").unwrap();
+        reader.read_line(&mut answer).unwrap();
+        assert_eq!(answer, "ok\n");
+
+        answer.clear();
+        writer.write_all(b"tokens /does/not/exist\n").unwrap();
+        reader.read_line(&mut answer).unwrap();
+        assert!(answer.starts_with("error: "));
+
+        // The answer is sent after queuing the request, but give the lock some slack
+        thread::sleep(Duration::from_millis(10));
+        assert_eq!(
+            socket.take_requests(),
+            [ControlRequest::Command {
+                client_id: Some(1),
+                command: ControlCommand::Resume
+            }]
+        );
+        assert!(socket.take_requests().is_empty());
+
+        drop(socket);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_controlled_event_manager() {
+        let dir =
+            std::env::temp_dir().join(format!("libafl_test_controlled_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let tokens_path = dir.join("tokens.dict");
+        fs::write(&tokens_path, "a=\"abc\"\nb=\"def\"\n").unwrap();
+
+        let mut state = test_state();
+        let mut mgr = ControlledEventManager::new(CommandsManager {
+            batches: vec![
+                vec![
+                    ControlCommand::SetLogLevel {
+                        level: LogSeverity::Warn,
+                    },
+                    ControlCommand::Pause,
+                ],
+                vec![],
+                vec![
+                    ControlCommand::ReloadTokens {
+                        path: tokens_path.to_string_lossy().into_owned(),
+                    },
+                    ControlCommand::ReloadTokens {
+                        path: "/does/not/exist".into(),
+                    },
+                    ControlCommand::Resume,
+                ],
+            ],
+            ..CommandsManager::default()
+        });
+
+        // Stays paused until the third batch resumes
+        assert_eq!(mgr.process(&mut (), &mut state, &mut ()).unwrap(), 5);
+        assert!(mgr.inner().batches.is_empty());
+        assert_eq!(state.metadata().get::<Tokens>().unwrap().tokens().len(), 2);
+        // Only the failure to load the dictionary is at least a warning
+        assert_eq!(mgr.inner().logs.len(), 1);
+        assert_eq!(mgr.inner().logs[0].0, LogSeverity::Warn);
+
+        mgr.inner_mut().batches.push(vec![ControlCommand::Stop]);
+        assert!(matches!(
+            mgr.process(&mut (), &mut state, &mut ()),
+            Err(Error::ShuttingDown)
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
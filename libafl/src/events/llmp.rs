@@ -13,14 +13,21 @@ use crate::bolts::{
 };
 #[cfg(feature = "std")]
 use crate::bolts::{llmp::LlmpConnection, shmem::StdShMemProvider, staterestore::StateRestorer};
+#[cfg(all(feature = "std", unix))]
+use crate::{
+    bolts::current_time,
+    events::{load_inputs, ControlRequest, ControlSocket},
+    executors::ExitKind,
+};
 use crate::{
     bolts::{
-        llmp::{self, Flags, LlmpClient, LlmpClientDescription, Tag},
+        llmp::{self, Flags, LlmpClient, LlmpClientDescription, LlmpSender, Tag},
         shmem::ShMemProvider,
     },
     events::{
-        BrokerEventResult, Event, EventConfig, EventFirer, EventManager, EventManagerId,
-        EventProcessor, EventRestarter, HasCustomBufHandlers, HasEventManagerId, ProgressReporter,
+        BrokerEventResult, ControlCommand, Event, EventConfig, EventFirer, EventManager,
+        EventManagerId, EventProcessor, EventRestarter, HasControlCommands, HasCustomBufHandlers,
+        HasEventManagerId, LogSeverity, ProgressReporter,
     },
     executors::{Executor, HasObservers},
     fuzzer::{EvaluatorObservers, ExecutionProcessor},
@@ -36,12 +43,22 @@ use alloc::{
 };
 #[cfg(feature = "std")]
 use core::sync::atomic::{compiler_fence, Ordering};
-use core::{marker::PhantomData, time::Duration};
+use core::{
+    cell::{Cell, RefCell},
+    marker::PhantomData,
+    mem,
+    time::Duration,
+};
 use serde::de::DeserializeOwned;
 #[cfg(feature = "std")]
 use serde::Serialize;
+#[cfg(all(feature = "std", unix))]
+use std::path::Path;
 #[cfg(feature = "std")]
-use std::net::{SocketAddr, ToSocketAddrs};
+use std::{
+    net::{SocketAddr, ToSocketAddrs},
+    path::PathBuf,
+};
 #[cfg(feature = "std")]
 use typed_builder::TypedBuilder;
 
@@ -74,6 +91,10 @@ where
     llmp: llmp::LlmpBroker<SP>,
     #[cfg(feature = "llmp_compression")]
     compressor: GzipCompressor,
+    /// Logs below this severity are not shown, changed by [`ControlCommand::SetLogLevel`] during the [`Self::broker_loop`]
+    log_level: Cell<LogSeverity>,
+    #[cfg(all(feature = "std", unix))]
+    control_socket: Option<ControlSocket>,
     phantom: PhantomData<I>,
 }
 
@@ -90,6 +111,9 @@ where
             llmp,
             #[cfg(feature = "llmp_compression")]
             compressor: GzipCompressor::new(COMPRESS_THRESHOLD),
+            log_level: Cell::new(LogSeverity::Debug),
+            #[cfg(all(feature = "std", unix))]
+            control_socket: None,
             phantom: PhantomData,
         })
     }
@@ -103,6 +127,9 @@ where
             llmp: llmp::LlmpBroker::create_attach_to_tcp(shmem_provider, port)?,
             #[cfg(feature = "llmp_compression")]
             compressor: GzipCompressor::new(COMPRESS_THRESHOLD),
+            log_level: Cell::new(LogSeverity::Debug),
+            #[cfg(all(feature = "std", unix))]
+            control_socket: None,
             phantom: PhantomData,
         })
     }
@@ -116,12 +143,27 @@ where
         self.llmp.connect_b2b(addr)
     }
 
-    /// Run forever in the broker
+    /// Listen for commands on a [`ControlSocket`] at the given path, see [`crate::events::control`].
+    #[cfg(all(feature = "std", unix))]
+    pub fn listen_control<P>(&mut self, path: P) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        self.control_socket = Some(ControlSocket::bind(path)?);
+        Ok(())
+    }
+
+    /// Run in the broker, until it is shut down by a signal or through the control socket.
+    /// After each round, the [`ControlCommand`]s requested through the monitor or the control socket are sent to the clients.
     pub fn broker_loop(&mut self) -> Result<(), Error> {
-        let monitor = &mut self.monitor;
+        // Both the message handler and the control command relay need the monitor
+        let monitor = RefCell::new(&mut self.monitor);
+        let log_level = &self.log_level;
+        #[cfg(all(feature = "std", unix))]
+        let control_socket = &self.control_socket;
         #[cfg(feature = "llmp_compression")]
         let compressor = &self.compressor;
-        self.llmp.loop_forever(
+        self.llmp.loop_forever_with(
             &mut |client_id: u32, tag: Tag, _flags: Flags, msg: &[u8]| {
                 if tag == LLMP_TAG_EVENT_TO_BOTH {
                     #[cfg(not(feature = "llmp_compression"))]
@@ -136,7 +178,12 @@ where
                         msg
                     };
                     let event: Event<I> = postcard::from_bytes(event_bytes)?;
-                    match Self::handle_in_broker(monitor, client_id, &event)? {
+                    match Self::handle_in_broker(
+                        *monitor.borrow_mut(),
+                        log_level.get(),
+                        client_id,
+                        &event,
+                    )? {
                         BrokerEventResult::Forward => Ok(llmp::LlmpMsgHookResult::ForwardToClients),
                         BrokerEventResult::Handled => Ok(llmp::LlmpMsgHookResult::Handled),
                     }
@@ -144,16 +191,88 @@ where
                     Ok(llmp::LlmpMsgHookResult::ForwardToClients)
                 }
             },
+            &mut |llmp_out: &mut LlmpSender<SP>| {
+                let mut commands = monitor.borrow_mut().take_control_commands();
+                let mut keep_running = true;
+                #[cfg(all(feature = "std", unix))]
+                if let Some(control_socket) = control_socket {
+                    for request in control_socket.take_requests() {
+                        match request {
+                            ControlRequest::Command { client_id, command } => {
+                                commands.push((client_id, command));
+                            }
+                            ControlRequest::AddInputs { path } => {
+                                Self::send_inputs(llmp_out, log_level.get(), &path)?;
+                            }
+                            ControlRequest::Shutdown => {
+                                commands.push((None, ControlCommand::Stop));
+                                keep_running = false;
+                            }
+                        }
+                    }
+                }
+                for (client_id, command) in commands {
+                    if let ControlCommand::SetLogLevel { level } = command {
+                        log_level.set(level);
+                    }
+                    let event: Event<I> = Event::Control { client_id, command };
+                    llmp_out.send_buf(LLMP_TAG_EVENT_TO_BOTH, &postcard::to_allocvec(&event)?)?;
+                }
+                Ok(keep_running)
+            },
             Some(Duration::from_millis(5)),
         );
 
         Ok(())
     }
 
+    /// Send the inputs at `path` to all clients, as new testcases from the broker
+    #[cfg(all(feature = "std", unix))]
+    fn send_inputs(
+        llmp_out: &mut LlmpSender<SP>,
+        log_level: LogSeverity,
+        path: &Path,
+    ) -> Result<(), Error> {
+        let inputs = match load_inputs::<I, _>(path) {
+            Ok(inputs) => inputs,
+            Err(err) => {
+                Self::print_log(
+                    log_level,
+                    LogSeverity::Warn,
+                    &format!("Could not load inputs from {:?}: {}", path, err),
+                );
+                return Ok(());
+            }
+        };
+        Self::print_log(
+            log_level,
+            LogSeverity::Info,
+            &format!(
+                "Sending {} inputs from {:?} to the clients",
+                inputs.len(),
+                path
+            ),
+        );
+        for input in inputs {
+            let event = Event::NewTestcase {
+                input,
+                observers_buf: None,
+                exit_kind: ExitKind::Ok,
+                corpus_size: 0,
+                client_config: EventConfig::AlwaysUnique,
+                time: current_time(),
+                executions: 0,
+            };
+            llmp_out.send_buf(LLMP_TAG_EVENT_TO_BOTH, &postcard::to_allocvec(&event)?)?;
+        }
+        Ok(())
+    }
+
     /// Handle arriving events in the broker
     #[allow(clippy::unnecessary_wraps)]
     fn handle_in_broker(
         monitor: &mut MT,
+        log_level: LogSeverity,
         client_id: u32,
         event: &Event<I>,
     ) -> Result<BrokerEventResult, Error> {
@@ -229,16 +348,24 @@ where
                 message,
                 phantom: _,
             } => {
-                let (_, _) = (severity_level, message);
-                // TODO rely on Monitor
+                let (_, _, _) = (severity_level, message, log_level);
                 #[cfg(feature = "std")]
-                println!("[LOG {}]: {}", severity_level, message);
+                Self::print_log(log_level, *severity_level, message);
                 Ok(BrokerEventResult::Handled)
             }
-            Event::CustomBuf { .. } => Ok(BrokerEventResult::Forward),
+            Event::CustomBuf { .. } | Event::Control { .. } => Ok(BrokerEventResult::Forward),
             //_ => Ok(BrokerEventResult::Forward),
         }
     }
+
+    /// Show a log of a client, or of the broker itself, unless it is below the log level
+    #[cfg(feature = "std")]
+    fn print_log(log_level: LogSeverity, severity_level: LogSeverity, message: &str) {
+        // TODO rely on Monitor
+        if severity_level >= log_level {
+            println!("[LOG {}]: {}", severity_level, message);
+        }
+    }
 }
 
 /// An [`EventManager`] that forwards all events to other attached fuzzers on shared maps or via tcp,
@@ -256,6 +383,8 @@ where
     #[cfg(feature = "llmp_compression")]
     compressor: GzipCompressor,
     configuration: EventConfig,
+    /// The [`ControlCommand`]s for this client, until they are taken with [`HasControlCommands::take_control_commands`]
+    control_commands: Vec<ControlCommand>,
     phantom: PhantomData<(I, OT, S)>,
 }
 
@@ -273,6 +402,7 @@ where
         let debug = debug.field("compressor", &self.compressor);
         debug
             .field("configuration", &self.configuration)
+            .field("control_commands", &self.control_commands)
             .field("phantom", &self.phantom)
             .finish_non_exhaustive()
     }
@@ -303,6 +433,7 @@ where
             #[cfg(feature = "llmp_compression")]
             compressor: GzipCompressor::new(COMPRESS_THRESHOLD),
             configuration,
+            control_commands: vec![],
             phantom: PhantomData,
             custom_buf_handlers: vec![],
         })
@@ -322,6 +453,7 @@ where
             #[cfg(feature = "llmp_compression")]
             compressor: GzipCompressor::new(COMPRESS_THRESHOLD),
             configuration,
+            control_commands: vec![],
             phantom: PhantomData,
             custom_buf_handlers: vec![],
         })
@@ -339,6 +471,7 @@ where
             #[cfg(feature = "llmp_compression")]
             compressor: GzipCompressor::new(COMPRESS_THRESHOLD),
             configuration,
+            control_commands: vec![],
             phantom: PhantomData,
             custom_buf_handlers: vec![],
         })
@@ -360,6 +493,7 @@ where
             #[cfg(feature = "llmp_compression")]
             compressor: GzipCompressor::new(COMPRESS_THRESHOLD),
             configuration,
+            control_commands: vec![],
             phantom: PhantomData,
             custom_buf_handlers: vec![],
         })
@@ -424,6 +558,15 @@ where
                 }
                 Ok(())
             }
+            Event::Control {
+                client_id: target_id,
+                command,
+            } => {
+                if target_id.map_or(true, |id| id == self.llmp.sender.id) {
+                    self.control_commands.push(command);
+                }
+                Ok(())
+            }
             _ => Err(Error::unknown(format!(
                 "Received illegal message that message should not have arrived: {:?}.",
                 event.name()
@@ -536,6 +679,17 @@ where
 {
 }
 
+impl<I, OT, S, SP> HasControlCommands for LlmpEventManager<I, OT, S, SP>
+where
+    I: Input,
+    OT: ObserversTuple<I, S>,
+    SP: ShMemProvider,
+{
+    fn take_control_commands(&mut self) -> Vec<ControlCommand> {
+        mem::take(&mut self.control_commands)
+    }
+}
+
 impl<I, OT, S, SP> HasCustomBufHandlers<S> for LlmpEventManager<I, OT, S, SP>
 where
     I: Input,
@@ -682,6 +836,18 @@ where
     }
 }
 
+#[cfg(feature = "std")]
+impl<I, OT, S, SP> HasControlCommands for LlmpRestartingEventManager<I, OT, S, SP>
+where
+    I: Input,
+    OT: ObserversTuple<I, S>,
+    SP: ShMemProvider + 'static,
+{
+    fn take_control_commands(&mut self) -> Vec<ControlCommand> {
+        self.llmp_mgr.take_control_commands()
+    }
+}
+
 /// The llmp connection from the actual fuzzer to the process supervising it
 const _ENV_FUZZER_SENDER: &str = "_AFL_ENV_FUZZER_SENDER";
 const _ENV_FUZZER_RECEIVER: &str = "_AFL_ENV_FUZZER_RECEIVER";
@@ -790,6 +956,9 @@ where
     /// The address to connect to
     #[builder(default = None)]
     remote_broker_addr: Option<SocketAddr>,
+    /// The path of the Unix socket the broker takes control commands on, see [`crate::events::control`]
+    #[builder(default = None)]
+    control_path: Option<PathBuf>,
     /// The type of manager to build
     #[builder(default = ManagerKind::Any)]
     kind: ManagerKind,
@@ -815,11 +984,25 @@ where
         let (staterestorer, new_shmem_provider, core_id) = if std::env::var(_ENV_FUZZER_SENDER)
             .is_err()
         {
-            let broker_things = |mut broker: LlmpEventBroker<I, MT, SP>, remote_broker_addr| {
+            let broker_things = |mut broker: LlmpEventBroker<I, MT, SP>,
+                                 remote_broker_addr,
+                                 control_path: Option<PathBuf>| {
                 if let Some(remote_broker_addr) = remote_broker_addr {
                     println!("B2b: Connecting to {:?}", &remote_broker_addr);
                     broker.connect_b2b(remote_broker_addr)?;
                 };
+                if let Some(control_path) = control_path {
+                    #[cfg(unix)]
+                    {
+                        broker.listen_control(&control_path)?;
+                        println!("Listening for control commands on {:?}", control_path);
+                    }
+                    #[cfg(not(unix))]
+                    return Err(Error::illegal_argument(format!(
+                        "The control socket {:?} needs Unix sockets",
+                        control_path
+                    )));
+                }
 
                 broker.broker_loop()
             };
@@ -841,7 +1024,11 @@ where
                                 "Doing broker things. Run this tool again to start fuzzing in a client."
                             );
 
-                            broker_things(event_broker, self.remote_broker_addr)?;
+                            broker_things(
+                                event_broker,
+                                self.remote_broker_addr,
+                                self.control_path.clone(),
+                            )?;
 
                             return Err(Error::shutting_down());
                         }
@@ -859,7 +1046,11 @@ where
                         self.broker_port,
                     )?;
 
-                    broker_things(event_broker, self.remote_broker_addr)?;
+                    broker_things(
+                        event_broker,
+                        self.remote_broker_addr,
+                        self.control_path.clone(),
+                    )?;
 
                     return Err(Error::shutting_down());
                 }
@@ -921,6 +1112,14 @@ where
 
                 #[allow(clippy::manual_assert)]
                 if !staterestorer.has_content() {
+                    if child_status == 0 {
+                        // The client returned from fuzzing without a restart, e.g. after a `ControlCommand::Stop`.
+                        println!(
+                            "Fuzzer-respawner: The client stopped, not spawning the next one."
+                        );
+                        return Err(Error::ShuttingDown);
+                    }
+
                     #[cfg(unix)]
                     if child_status == 137 {
                         // Out of Memory, see https://tldp.org/LDP/abs/html/exitcodes.html
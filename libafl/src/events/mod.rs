@@ -4,6 +4,10 @@ pub mod simple;
 pub use simple::*;
 pub mod llmp;
 pub use llmp::*;
+#[cfg(feature = "std")]
+pub mod control;
+#[cfg(feature = "std")]
+pub use control::*;
 
 use ahash::AHasher;
 use alloc::{
@@ -21,7 +25,7 @@ use crate::{
     bolts::current_time,
     executors::ExitKind,
     inputs::Input,
-    monitors::UserStats,
+    monitors::{AggregatorOps, UserStats, UserStatsValue},
     observers::ObserversTuple,
     state::{HasClientPerfMonitor, HasExecutions},
     Error,
@@ -38,8 +42,8 @@ pub struct EventManagerId {
 #[cfg(feature = "introspection")]
 use crate::monitors::ClientPerfMonitor;
 
-/// The log event severity
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+/// The log event severity, from the least to the most severe
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum LogSeverity {
     /// Debug severity
     Debug,
@@ -71,6 +75,47 @@ pub enum CustomBufEventResult {
     Next,
 }
 
+/// A command the broker sends to its clients, for example when requested from an interactive [`crate::monitors::Monitor`],
+/// or through the control socket of the broker.
+/// Clients apply the commands if their [`EventManager`] is wrapped in a [`ControlledEventManager`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum ControlCommand {
+    /// Stop fuzzing until a [`ControlCommand::Resume`] arrives
+    Pause,
+    /// Continue fuzzing after a [`ControlCommand::Pause`]
+    Resume,
+    /// Stop fuzzing, the client returns [`Error::ShuttingDown`] from the fuzz loop
+    Stop,
+    /// Write a checkpoint of the state to the [`crate::state::checkpoint::Checkpointer`] directory `client_<id>` in this directory
+    DumpState {
+        /// The directory for the checkpoints
+        dir: String,
+    },
+    /// Only send logs of at least this severity
+    SetLogLevel {
+        /// The least severity to send
+        level: LogSeverity,
+    },
+    /// Replace the [`crate::mutators::Tokens`] in the state with the ones in this dictionary file
+    ReloadTokens {
+        /// The path of the dictionary
+        path: String,
+    },
+}
+
+impl fmt::Display for ControlCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ControlCommand::Pause => write!(f, "Pause"),
+            ControlCommand::Resume => write!(f, "Resume"),
+            ControlCommand::Stop => write!(f, "Stop"),
+            ControlCommand::DumpState { dir } => write!(f, "DumpState {}", dir),
+            ControlCommand::SetLogLevel { level } => write!(f, "SetLogLevel {}", level),
+            ControlCommand::ReloadTokens { path } => write!(f, "ReloadTokens {}", path),
+        }
+    }
+}
+
 /// Indicate if an event worked or not
 #[derive(Serialize, Deserialize, Debug, Copy, Clone)]
 pub enum BrokerEventResult {
@@ -246,6 +291,13 @@ where
         /// Tag of this buffer
         tag: String,
     },
+    /// A command from the broker to one or all clients
+    Control {
+        /// The id of the client this command is for, or `None` for all clients
+        client_id: Option<u32>,
+        /// The command
+        command: ControlCommand,
+    },
     /*/// A custom type
     Custom {
         // TODO: Allow custom events
@@ -292,6 +344,7 @@ where
                 phantom: _,
             } => "Log",
             Event::CustomBuf { .. } => "CustomBuf",
+            Event::Control { .. } => "Control",
             /*Event::Custom {
                 sender_id: _, /*custom_event} => custom_event.name()*/
             } => "todo",*/
@@ -384,7 +437,7 @@ where
                     state,
                     Event::UpdateUserStats {
                         name: "stability".to_string(),
-                        value: UserStats::Float(stability),
+                        value: UserStats::new(UserStatsValue::Float(stability), AggregatorOps::Avg),
                         phantom: PhantomData,
                     },
                 )?;
@@ -467,6 +520,12 @@ where
 {
 }
 
+/// Receives the [`ControlCommand`]s the broker sends to this client, to apply them in a [`ControlledEventManager`]
+pub trait HasControlCommands {
+    /// The commands for this client, received since the last call
+    fn take_control_commands(&mut self) -> Vec<ControlCommand>;
+}
+
 /// The handler function for custom buffers exchanged via [`EventManager`]
 type CustomBufHandlerFn<S> =
     dyn FnMut(&mut S, &String, &[u8]) -> Result<CustomBufEventResult, Error>;
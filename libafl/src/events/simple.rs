@@ -2,8 +2,8 @@
 
 use crate::{
     events::{
-        BrokerEventResult, Event, EventFirer, EventManager, EventManagerId, EventProcessor,
-        EventRestarter, HasEventManagerId,
+        BrokerEventResult, ControlCommand, Event, EventFirer, EventManager, EventManagerId,
+        EventProcessor, EventRestarter, HasEventManagerId,
     },
     inputs::Input,
     monitors::Monitor,
@@ -52,6 +52,8 @@ where
     events: Vec<Event<I>>,
     /// The custom buf handler
     custom_buf_handlers: Vec<Box<CustomBufHandlerFn<S>>>,
+    /// If the monitor requested to pause fuzzing
+    paused: bool,
     phantom: PhantomData<S>,
 }
 
@@ -106,6 +108,7 @@ where
             let event = self.events.pop().unwrap();
             self.handle_in_client(state, event)?;
         }
+        self.handle_control_commands()?;
         Ok(count)
     }
 }
@@ -159,10 +162,34 @@ where
             monitor,
             events: vec![],
             custom_buf_handlers: vec![],
+            paused: false,
             phantom: PhantomData,
         }
     }
 
+    /// Applies the [`ControlCommand`]s requested through the monitor.
+    /// While paused, this blocks until the monitor requests to resume.
+    fn handle_control_commands(&mut self) -> Result<(), Error> {
+        loop {
+            for (_, command) in self.monitor.take_control_commands() {
+                match command {
+                    ControlCommand::Pause => self.paused = true,
+                    ControlCommand::Resume => self.paused = false,
+                    ControlCommand::Stop => return Err(Error::ShuttingDown),
+                    // Monitors only request to pause, resume or stop
+                    ControlCommand::DumpState { .. }
+                    | ControlCommand::SetLogLevel { .. }
+                    | ControlCommand::ReloadTokens { .. } => (),
+                }
+            }
+            if !self.paused {
+                return Ok(());
+            }
+            #[cfg(feature = "std")]
+            std::thread::sleep(core::time::Duration::from_millis(100));
+        }
+    }
+
     /// Handle arriving events in the broker
     #[allow(clippy::unnecessary_wraps)]
     fn handle_in_broker(monitor: &mut MT, event: &Event<I>) -> Result<BrokerEventResult, Error> {
@@ -241,6 +268,7 @@ where
                 Ok(BrokerEventResult::Handled)
             }
             Event::CustomBuf { .. } => Ok(BrokerEventResult::Forward),
+            Event::Control { .. } => Ok(BrokerEventResult::Handled),
             //_ => Ok(BrokerEventResult::Forward),
         }
     }
@@ -432,6 +460,14 @@ where
 
                 #[allow(clippy::manual_assert)]
                 if !staterestorer.has_content() {
+                    if child_status == 0 {
+                        // The client returned from fuzzing without a restart, e.g. after a `ControlCommand::Stop`.
+                        println!(
+                            "Fuzzer-respawner: The client stopped, not spawning the next one."
+                        );
+                        return Err(Error::ShuttingDown);
+                    }
+
                     #[cfg(unix)]
                     if child_status == 137 {
                         // Out of Memory, see https://tldp.org/LDP/abs/html/exitcodes.html
@@ -1,57 +1,78 @@
 //! Executor for differential fuzzing.
 //! It wraps two exeutors that will be run after each other with the same input.
 //! In comparison to the [`crate::executors::CombinedExecutor`] it also runs the secondary executor in `run_target`.
+//! To compare more than two implementations, the [`TupleDiffExecutor`] runs all executors of an [`ExecutorsTuple`].
 //!
 use crate::{
+    bolts::tuples::MatchName,
     executors::{Executor, ExitKind, HasObservers},
     inputs::Input,
-    observers::ObserversTuple,
+    observers::{ExitKindsObserver, ObserversTuple},
     Error,
 };
-use core::fmt::Debug;
+use alloc::{string::ToString, vec::Vec};
+use core::{fmt::Debug, marker::PhantomData};
 
-/// A [`DiffExecutor`] wraps a primary executor, forwarding its methods, and a secondary one
+/// A [`DiffExecutor`] wraps a primary executor, forwarding its methods, and a secondary one.
+/// Its observers are the [`DiffObservers`] of both executors.
+/// The observers of each executor are prepared right before, and finished right after, its own run.
 #[derive(Debug)]
-pub struct DiffExecutor<A, B>
+pub struct DiffExecutor<A, B, OTA, OTB>
 where
     A: Debug,
     B: Debug,
+    OTA: Debug,
+    OTB: Debug,
 {
-    primary: A,
-    secondary: B,
+    observers: DiffObservers<A, B, OTA, OTB>,
 }
 
-impl<A, B> DiffExecutor<A, B>
+impl<A, B, OTA, OTB> DiffExecutor<A, B, OTA, OTB>
 where
     A: Debug,
     B: Debug,
+    OTA: Debug,
+    OTB: Debug,
 {
     /// Create a new `DiffExecutor`, wrapping the given `executor`s.
     pub fn new<EM, I, S, Z>(primary: A, secondary: B) -> Self
     where
-        A: Executor<EM, I, S, Z>,
-        B: Executor<EM, I, S, Z>,
+        A: Executor<EM, I, S, Z> + HasObservers<I, OTA, S>,
+        B: Executor<EM, I, S, Z> + HasObservers<I, OTB, S>,
         I: Input,
+        OTA: ObserversTuple<I, S>,
+        OTB: ObserversTuple<I, S>,
     {
-        Self { primary, secondary }
+        Self {
+            observers: DiffObservers {
+                primary,
+                secondary,
+                primary_observers: A::observers,
+                primary_observers_mut: A::observers_mut,
+                secondary_observers: B::observers,
+                secondary_observers_mut: B::observers_mut,
+            },
+        }
     }
 
     /// Retrieve the primary `Executor` that is wrapped by this `DiffExecutor`.
     pub fn primary(&mut self) -> &mut A {
-        &mut self.primary
+        &mut self.observers.primary
     }
 
     /// Retrieve the secondary `Executor` that is wrapped by this `DiffExecutor`.
     pub fn secondary(&mut self) -> &mut B {
-        &mut self.secondary
+        &mut self.observers.secondary
     }
 }
 
-impl<A, B, EM, I, S, Z> Executor<EM, I, S, Z> for DiffExecutor<A, B>
+impl<A, B, EM, I, OTA, OTB, S, Z> Executor<EM, I, S, Z> for DiffExecutor<A, B, OTA, OTB>
 where
     A: Executor<EM, I, S, Z>,
     B: Executor<EM, I, S, Z>,
     I: Input,
+    OTA: ObserversTuple<I, S>,
+    OTB: ObserversTuple<I, S>,
 {
     fn run_target(
         &mut self,
@@ -60,10 +81,20 @@ where
         mgr: &mut EM,
         input: &I,
     ) -> Result<ExitKind, Error> {
-        let ret1 = self.primary.run_target(fuzzer, state, mgr, input)?;
-        self.primary.post_run_reset();
-        let ret2 = self.secondary.run_target(fuzzer, state, mgr, input)?;
-        self.secondary.post_run_reset();
+        let observers = &mut self.observers;
+
+        observers.primary_mut().pre_exec_all(state, input)?;
+        let ret1 = observers.primary.run_target(fuzzer, state, mgr, input)?;
+        observers.primary_mut().post_exec_all(state, input, &ret1)?;
+        observers.primary.post_run_reset();
+
+        observers.secondary_mut().pre_exec_all(state, input)?;
+        let ret2 = observers.secondary.run_target(fuzzer, state, mgr, input)?;
+        observers
+            .secondary_mut()
+            .post_exec_all(state, input, &ret2)?;
+        observers.secondary.post_run_reset();
+
         if ret1 == ret2 {
             Ok(ret1)
         } else {
@@ -76,19 +107,447 @@ where
     }
 }
 
-impl<A, B, I, OT, S> HasObservers<I, OT, S> for DiffExecutor<A, B>
+/// The observers of both executors of a [`DiffExecutor`], borrowed from the executors on every access.
+/// Observers are matched by name in the primary observers first, then in the secondary ones,
+/// so feedbacks, such as the [`crate::feedbacks::DiffFeedback`], can compare observers of both sides.
+///
+/// The [`DiffExecutor`] runs the observers of each side around that side's run,
+/// so the hooks of this [`ObserversTuple`] itself do nothing.
+#[derive(Debug)]
+pub struct DiffObservers<A, B, OTA, OTB> {
+    primary: A,
+    secondary: B,
+    primary_observers: fn(&A) -> &OTA,
+    primary_observers_mut: fn(&mut A) -> &mut OTA,
+    secondary_observers: fn(&B) -> &OTB,
+    secondary_observers_mut: fn(&mut B) -> &mut OTB,
+}
+
+impl<A, B, OTA, OTB> DiffObservers<A, B, OTA, OTB> {
+    /// The observers of the primary executor
+    #[must_use]
+    pub fn primary(&self) -> &OTA {
+        (self.primary_observers)(&self.primary)
+    }
+
+    /// The observers of the primary executor (mutable)
+    pub fn primary_mut(&mut self) -> &mut OTA {
+        (self.primary_observers_mut)(&mut self.primary)
+    }
+
+    /// The observers of the secondary executor
+    #[must_use]
+    pub fn secondary(&self) -> &OTB {
+        (self.secondary_observers)(&self.secondary)
+    }
+
+    /// The observers of the secondary executor (mutable)
+    pub fn secondary_mut(&mut self) -> &mut OTB {
+        (self.secondary_observers_mut)(&mut self.secondary)
+    }
+}
+
+impl<A, B, OTA, OTB> MatchName for DiffObservers<A, B, OTA, OTB>
 where
-    A: HasObservers<I, OT, S>,
+    OTA: MatchName,
+    OTB: MatchName,
+{
+    fn match_name<T>(&self, name: &str) -> Option<&T> {
+        self.primary()
+            .match_name(name)
+            .or_else(|| self.secondary().match_name(name))
+    }
+
+    fn match_name_mut<T>(&mut self, name: &str) -> Option<&mut T> {
+        if self.primary().match_name::<T>(name).is_some() {
+            self.primary_mut().match_name_mut(name)
+        } else {
+            self.secondary_mut().match_name_mut(name)
+        }
+    }
+}
+
+impl<A, B, I, OTA, OTB, S> ObserversTuple<I, S> for DiffObservers<A, B, OTA, OTB>
+where
+    A: Debug,
     B: Debug,
+    OTA: ObserversTuple<I, S>,
+    OTB: ObserversTuple<I, S>,
+{
+    fn pre_exec_all(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn post_exec_all(
+        &mut self,
+        _state: &mut S,
+        _input: &I,
+        _exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn pre_exec_child_all(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn post_exec_child_all(
+        &mut self,
+        _state: &mut S,
+        _input: &I,
+        _exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<A, B, I, OTA, OTB, S> HasObservers<I, DiffObservers<A, B, OTA, OTB>, S>
+    for DiffExecutor<A, B, OTA, OTB>
+where
+    A: Debug,
+    B: Debug,
+    OTA: ObserversTuple<I, S>,
+    OTB: ObserversTuple<I, S>,
+{
+    #[inline]
+    fn observers(&self) -> &DiffObservers<A, B, OTA, OTB> {
+        &self.observers
+    }
+
+    #[inline]
+    fn observers_mut(&mut self) -> &mut DiffObservers<A, B, OTA, OTB> {
+        &mut self.observers
+    }
+}
+
+/// A tuple of [`Executor`]s, all run with the same input.
+/// `OTS` is the tuple of the [`ObserversTuple`]s of the executors, in the same order.
+pub trait ExecutorsTuple<EM, I, OTS, S, Z>: Debug
+where
+    I: Input,
+{
+    /// Runs all executors, one after each other, appending their [`ExitKind`]s to `exit_kinds`.
+    /// The observers of each executor are prepared right before, and finished right after, its own run.
+    fn run_targets(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut S,
+        mgr: &mut EM,
+        input: &I,
+        exit_kinds: &mut Vec<ExitKind>,
+    ) -> Result<(), Error>;
+}
+
+impl<EM, I, S, Z> ExecutorsTuple<EM, I, (), S, Z> for ()
+where
+    I: Input,
+{
+    fn run_targets(
+        &mut self,
+        _fuzzer: &mut Z,
+        _state: &mut S,
+        _mgr: &mut EM,
+        _input: &I,
+        _exit_kinds: &mut Vec<ExitKind>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<EM, Head, HOT, I, S, Tail, TOTS, Z> ExecutorsTuple<EM, I, (HOT, TOTS), S, Z> for (Head, Tail)
+where
+    Head: Executor<EM, I, S, Z> + HasObservers<I, HOT, S>,
+    HOT: ObserversTuple<I, S>,
+    Tail: ExecutorsTuple<EM, I, TOTS, S, Z>,
+    I: Input,
+{
+    fn run_targets(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut S,
+        mgr: &mut EM,
+        input: &I,
+        exit_kinds: &mut Vec<ExitKind>,
+    ) -> Result<(), Error> {
+        self.0.observers_mut().pre_exec_all(state, input)?;
+        let exit_kind = self.0.run_target(fuzzer, state, mgr, input)?;
+        self.0
+            .observers_mut()
+            .post_exec_all(state, input, &exit_kind)?;
+        self.0.post_run_reset();
+        exit_kinds.push(exit_kind);
+        self.1.run_targets(fuzzer, state, mgr, input, exit_kinds)
+    }
+}
+
+/// A [`TupleDiffExecutor`] runs all executors of an [`ExecutorsTuple`] with the same input, to compare any number of implementations.
+/// Each executor keeps its own observers, run around its own execution.
+/// The observers to compare need to be part of the given [`ObserversTuple`] as well, with distinct names.
+/// If the observers contain an [`ExitKindsObserver`], it is filled with the [`ExitKind`] of each executor.
+#[derive(Debug)]
+pub struct TupleDiffExecutor<ET, OT, OTS>
+where
+    ET: Debug,
+    OT: Debug,
+{
+    executors: ET,
+    observers: OT,
+    exit_kinds: Vec<ExitKind>,
+    phantom: PhantomData<OTS>,
+}
+
+impl<ET, OT, OTS> TupleDiffExecutor<ET, OT, OTS>
+where
+    ET: Debug,
+    OT: Debug,
+{
+    /// Create a new `TupleDiffExecutor`, wrapping the given `executors` and the `observers` of all of them.
+    pub fn new<EM, I, S, Z>(executors: ET, observers: OT) -> Self
+    where
+        ET: ExecutorsTuple<EM, I, OTS, S, Z>,
+        I: Input,
+    {
+        Self {
+            executors,
+            observers,
+            exit_kinds: vec![],
+            phantom: PhantomData,
+        }
+    }
+
+    /// Retrieve the executors wrapped by this `TupleDiffExecutor`.
+    pub fn executors(&mut self) -> &mut ET {
+        &mut self.executors
+    }
+
+    /// The [`ExitKind`]s of the last run, in the order of the executors.
+    pub fn exit_kinds(&self) -> &[ExitKind] {
+        &self.exit_kinds
+    }
+}
+
+impl<ET, EM, I, OT, OTS, S, Z> Executor<EM, I, S, Z> for TupleDiffExecutor<ET, OT, OTS>
+where
+    ET: ExecutorsTuple<EM, I, OTS, S, Z>,
+    OTS: Debug,
+    I: Input,
+    OT: ObserversTuple<I, S>,
+{
+    fn run_target(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut S,
+        mgr: &mut EM,
+        input: &I,
+    ) -> Result<ExitKind, Error> {
+        self.exit_kinds.clear();
+        self.executors
+            .run_targets(fuzzer, state, mgr, input, &mut self.exit_kinds)?;
+
+        if let Some(observer) = self
+            .observers
+            .match_name_mut::<ExitKindsObserver>(ExitKindsObserver::NAME)
+        {
+            observer.set_exit_kinds(&self.exit_kinds);
+        }
+
+        let first = match self.exit_kinds.first() {
+            Some(first) => first.clone(),
+            None => {
+                return Err(Error::illegal_state(
+                    "TupleDiffExecutor: no executors to run".to_string(),
+                ))
+            }
+        };
+        match self.exit_kinds.iter().find(|kind| **kind != first) {
+            // We found a diff in the exit codes!
+            Some(other) => Ok(ExitKind::Diff {
+                primary: (&first).into(),
+                secondary: other.into(),
+            }),
+            None => Ok(first),
+        }
+    }
+}
+
+impl<ET, I, OT, OTS, S> HasObservers<I, OT, S> for TupleDiffExecutor<ET, OT, OTS>
+where
+    ET: Debug,
+    OTS: Debug,
     OT: ObserversTuple<I, S>,
 {
     #[inline]
     fn observers(&self) -> &OT {
-        self.primary.observers()
+        &self.observers
     }
 
     #[inline]
     fn observers_mut(&mut self) -> &mut OT {
-        self.primary.observers_mut()
+        &mut self.observers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::{String, ToString};
+
+    use crate::{
+        bolts::tuples::{tuple_list, tuple_list_type, MatchName, Named},
+        executors::{
+            differential::TupleDiffExecutor, DiffExecutor, DiffExitKind, Executor, ExitKind,
+            HasObservers,
+        },
+        inputs::BytesInput,
+        observers::{ExitKindsObserver, Observer},
+        Error,
+    };
+
+    /// Counts the runs it observed, and whether one is in progress
+    #[derive(Debug)]
+    struct RunObserver {
+        name: String,
+        running: bool,
+        runs: usize,
+    }
+
+    impl<I, S> Observer<I, S> for RunObserver {
+        fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+            self.running = true;
+            Ok(())
+        }
+
+        fn post_exec(
+            &mut self,
+            _state: &mut S,
+            _input: &I,
+            _exit_kind: &ExitKind,
+        ) -> Result<(), Error> {
+            self.running = false;
+            self.runs += 1;
+            Ok(())
+        }
+    }
+
+    impl Named for RunObserver {
+        fn name(&self) -> &str {
+            &self.name
+        }
+    }
+
+    #[derive(Debug)]
+    struct ConstExecutor {
+        exit_kind: ExitKind,
+        observers: tuple_list_type!(RunObserver),
+    }
+
+    impl ConstExecutor {
+        fn new(name: &str, exit_kind: ExitKind) -> Self {
+            Self {
+                exit_kind,
+                observers: tuple_list!(RunObserver {
+                    name: name.to_string(),
+                    running: false,
+                    runs: 0
+                }),
+            }
+        }
+    }
+
+    impl<EM, S, Z> Executor<EM, BytesInput, S, Z> for ConstExecutor {
+        fn run_target(
+            &mut self,
+            _fuzzer: &mut Z,
+            _state: &mut S,
+            _mgr: &mut EM,
+            _input: &BytesInput,
+        ) -> Result<ExitKind, Error> {
+            assert!(self.observers.0.running);
+            Ok(self.exit_kind.clone())
+        }
+    }
+
+    impl<S> HasObservers<BytesInput, tuple_list_type!(RunObserver), S> for ConstExecutor {
+        fn observers(&self) -> &tuple_list_type!(RunObserver) {
+            &self.observers
+        }
+
+        fn observers_mut(&mut self) -> &mut tuple_list_type!(RunObserver) {
+            &mut self.observers
+        }
+    }
+
+    #[test]
+    fn test_tuple_diff_executor() {
+        let input = BytesInput::new(vec![1]);
+        let mut executor = TupleDiffExecutor::new::<(), _, (), ()>(
+            tuple_list!(
+                ConstExecutor::new("a", ExitKind::Ok),
+                ConstExecutor::new("b", ExitKind::Crash),
+                ConstExecutor::new("c", ExitKind::Ok)
+            ),
+            tuple_list!(ExitKindsObserver::new()),
+        );
+        assert_eq!(
+            executor
+                .run_target(&mut (), &mut (), &mut (), &input)
+                .unwrap(),
+            ExitKind::Diff {
+                primary: DiffExitKind::Ok,
+                secondary: DiffExitKind::Crash
+            }
+        );
+        assert_eq!(
+            executor.observers.0.exit_kinds(),
+            &[DiffExitKind::Ok, DiffExitKind::Crash, DiffExitKind::Ok]
+        );
+
+        // A second run in a row, after the implementations agree again
+        (executor.executors().1).0.exit_kind = ExitKind::Ok;
+        assert_eq!(
+            executor
+                .run_target(&mut (), &mut (), &mut (), &input)
+                .unwrap(),
+            ExitKind::Ok
+        );
+        assert_eq!(
+            executor.observers.0.exit_kinds(),
+            &[DiffExitKind::Ok, DiffExitKind::Ok, DiffExitKind::Ok]
+        );
+        let executors = executor.executors();
+        for observer in [
+            &executors.0.observers.0,
+            &(executors.1).0.observers.0,
+            &((executors.1).1).0.observers.0,
+        ] {
+            assert!(!observer.running);
+            assert_eq!(observer.runs, 2);
+        }
+    }
+
+    #[test]
+    fn test_diff_executor() {
+        let input = BytesInput::new(vec![1]);
+        let mut executor = DiffExecutor::new::<(), _, (), ()>(
+            ConstExecutor::new("a", ExitKind::Ok),
+            ConstExecutor::new("b", ExitKind::Crash),
+        );
+        assert_eq!(
+            executor
+                .run_target(&mut (), &mut (), &mut (), &input)
+                .unwrap(),
+            ExitKind::Diff {
+                primary: DiffExitKind::Ok,
+                secondary: DiffExitKind::Crash
+            }
+        );
+
+        // The observers of both sides are found by name
+        let observers = &executor.observers;
+        for name in ["a", "b"] {
+            let observer = observers.match_name::<RunObserver>(name).unwrap();
+            assert!(!observer.running);
+            assert_eq!(observer.runs, 1);
+        }
+        assert_eq!(observers.secondary().0.name, "b");
     }
 }
@@ -1,12 +1,37 @@
 //! Expose an `Executor` based on a `Forkserver` in order to execute AFL/AFL++ binaries
+//!
+//! The [`ForkserverExecutor`] is generic over an [`InputDelivery`], that decides how the input reaches the target:
+//! [`BytesDelivery`] passes it on `stdin` or in one file, [`MultiFileDelivery`] passes each field of a multi-field
+//! input in its own file, and [`ArgvDelivery`] passes the fields as command line arguments.
+//!
+//! If a shared memory provider is set, and the target agrees, the input is passed over shared memory instead.
+//! For multi-field inputs, the target also has to advertise `FS_OPT_SHDMEM_MULTI`, and the testcase map then has the layout
+//! (all values are native-endian `u32`s):
+//!
+//! ```text
+//! | total_len | field_count | field_len[0] .. field_len[field_count - 1] | field data, back to back |
+//! ```
+//!
+//! where `total_len` counts all bytes following it. The `libafl_targets` forkserver runtime decodes this layout,
+//! see `libafl_targets::forkserver::fuzz_field`.
+//!
+//! In persistent mode, the child runs many inputs in a loop, stopping itself with `SIGSTOP` after each,
+//! and the forkserver resumes it for the next input instead of forking again.
+//! With a deferred forkserver, the target starts the forkserver only after its initialization.
+//! Both are detected from the signatures `__AFL_LOOP` and `__AFL_INIT` leave in the binary,
+//! for targets built with `afl-clang-fast`, or with `libafl_cc` and `ClangWrapper::afl_manual_control`,
+//! or enabled in the [`ForkserverExecutorBuilder`].
 
 use core::{
     fmt::{self, Debug, Formatter},
     marker::PhantomData,
+    ops::Range,
     time::Duration,
 };
 use std::{
+    env,
     ffi::{OsStr, OsString},
+    fs,
     io::{self, prelude::*, ErrorKind},
     os::unix::{io::RawFd, process::CommandExt},
     path::Path,
@@ -17,11 +42,12 @@ use crate::{
     bolts::{
         fs::{InputFile, INPUTFILE_STD},
         os::{dup2, pipes::Pipe},
+        ownedref::OwnedSlice,
         shmem::{ShMem, ShMemProvider, StdShMemProvider},
         AsMutSlice, AsSlice,
     },
     executors::{Executor, ExitKind, HasObservers},
-    inputs::{HasTargetBytes, Input},
+    inputs::{AsMultiBytes, HasTargetBytes, Input},
     mutators::Tokens,
     observers::{get_asan_runtime_flags_with_log_path, ASANBacktraceObserver, ObserversTuple},
     Error,
@@ -36,6 +62,7 @@ use nix::{
     },
     unistd::Pid,
 };
+use uuid::Uuid;
 
 const FORKSRV_FD: i32 = 198;
 #[allow(clippy::cast_possible_wrap)]
@@ -44,8 +71,14 @@ const FS_OPT_ENABLED: i32 = 0x80000001_u32 as i32;
 const FS_OPT_SHDMEM_FUZZ: i32 = 0x01000000_u32 as i32;
 #[allow(clippy::cast_possible_wrap)]
 const FS_OPT_AUTODICT: i32 = 0x10000000_u32 as i32;
+#[allow(clippy::cast_possible_wrap)]
+const FS_OPT_SHDMEM_MULTI: i32 = 0x04000000_u32 as i32;
 const SHMEM_FUZZ_HDR_SIZE: usize = 4;
 const MAX_FILE: usize = 1024 * 1024;
+const PERSIST_SIG: &[u8] = b"##SIG_AFL_PERSISTENT##";
+const DEFER_SIG: &[u8] = b"##SIG_AFL_DEFER_FORKSRV##";
+const PERSIST_ENV_VAR: &str = "__AFL_PERSISTENT";
+const DEFER_ENV_VAR: &str = "__AFL_DEFER_FORKSRV";
 
 /// Configure the target, `limit`, `setsid`, `pipe_stdin`, the code was borrowed from the [`Angora`](https://github.com/AngoraFuzzer/Angora) fuzzer
 pub trait ConfigTarget {
@@ -325,6 +358,316 @@ impl Forkserver {
     }
 }
 
+/// Writes `bytes` to the shared memory testcase `map`, prefixed by their length.
+/// Returns the number of bytes written.
+pub fn write_shmem_testcase(map: &mut [u8], bytes: &[u8]) -> Result<usize, Error> {
+    let size = bytes.len();
+    if SHMEM_FUZZ_HDR_SIZE + size > map.len() {
+        return Err(Error::illegal_argument(format!(
+            "Input of {} bytes does not fit into the shared memory testcase map",
+            size
+        )));
+    }
+    // The first four bytes tells the size of the shmem.
+    map[..4].copy_from_slice(&(size as u32).to_ne_bytes());
+    map[SHMEM_FUZZ_HDR_SIZE..(SHMEM_FUZZ_HDR_SIZE + size)].copy_from_slice(bytes);
+    Ok(SHMEM_FUZZ_HDR_SIZE + size)
+}
+
+/// Writes all `fields` to the shared memory testcase `map`, using the multi-field layout described in the module docs.
+/// Returns the number of bytes written.
+pub fn write_multi_shmem_testcase(
+    map: &mut [u8],
+    fields: &[OwnedSlice<u8>],
+) -> Result<usize, Error> {
+    let table_size = 4 + 4 * fields.len();
+    let data_size: usize = fields.iter().map(|f| f.as_slice().len()).sum();
+    let total = table_size + data_size;
+    if SHMEM_FUZZ_HDR_SIZE + total > map.len() {
+        return Err(Error::illegal_argument(format!(
+            "Input with {} fields and {} bytes does not fit into the shared memory testcase map",
+            fields.len(),
+            data_size
+        )));
+    }
+
+    map[..4].copy_from_slice(&(total as u32).to_ne_bytes());
+    map[4..8].copy_from_slice(&(fields.len() as u32).to_ne_bytes());
+    let mut table_pos = 8;
+    let mut data_pos = SHMEM_FUZZ_HDR_SIZE + table_size;
+    for field in fields {
+        let bytes = field.as_slice();
+        map[table_pos..table_pos + 4].copy_from_slice(&(bytes.len() as u32).to_ne_bytes());
+        map[data_pos..data_pos + bytes.len()].copy_from_slice(bytes);
+        table_pos += 4;
+        data_pos += bytes.len();
+    }
+    Ok(SHMEM_FUZZ_HDR_SIZE + total)
+}
+
+/// Finds the input placeholders in `arg`: every `@@`, as in `AFL`.
+/// If `indexed` is set, the digits right after it are the field index, as in `@@12`, otherwise the index is `0`.
+/// Returns the byte range of each placeholder with its index.
+fn placeholders(arg: &str, indexed: bool) -> Vec<(Range<usize>, usize)> {
+    let mut found = vec![];
+    let mut pos = 0;
+    while let Some(offset) = arg[pos..].find("@@") {
+        let start = pos + offset;
+        let digits = if indexed {
+            arg[start + 2..]
+                .bytes()
+                .take_while(u8::is_ascii_digit)
+                .count()
+        } else {
+            0
+        };
+        let end = start + 2 + digits;
+        let idx = if digits == 0 {
+            Some(0)
+        } else {
+            arg[start + 2..end].parse().ok()
+        };
+        // An index too large to parse is left alone
+        if let Some(idx) = idx {
+            found.push((start..end, idx));
+        }
+        pos = end;
+    }
+    found
+}
+
+/// Replaces the input placeholders in `arg`, see [`placeholders`].
+/// `replacement` returns the path for a field index, or `None` to keep the placeholder.
+fn replace_placeholders<F>(arg: &OsStr, indexed: bool, mut replacement: F) -> OsString
+where
+    F: FnMut(usize) -> Option<OsString>,
+{
+    let lossy = arg.to_string_lossy();
+    let found = placeholders(&lossy, indexed);
+    if found.is_empty() {
+        return arg.to_owned();
+    }
+    let mut resolved = OsString::new();
+    let mut pos = 0;
+    for (range, idx) in found {
+        resolved.push(&lossy[pos..range.start]);
+        match replacement(idx) {
+            Some(path) => resolved.push(path),
+            None => resolved.push(&lossy[range.clone()]),
+        }
+        pos = range.end;
+    }
+    resolved.push(&lossy[pos..]);
+    resolved
+}
+
+/// How a [`ForkserverExecutor`] passes each input to the target.
+///
+/// Independent of the delivery, the input is written to shared memory instead,
+/// if a shared memory provider was set on the builder and the target agreed to the shared memory testcase feature.
+pub trait InputDelivery<I>: Debug {
+    /// The file descriptor the target should read its `stdin` from, if any.
+    fn stdin_fd(&self) -> Option<RawFd> {
+        None
+    }
+
+    /// Replaces the input placeholders (such as `@@`) in a target argument or environment value.
+    fn resolve_arg(&self, arg: &OsStr) -> OsString {
+        arg.to_owned()
+    }
+
+    /// If the input is written to shared memory as multiple fields, see [`write_multi_shmem_testcase`].
+    /// The target then has to agree to `FS_OPT_SHDMEM_MULTI`, too.
+    fn uses_shmem_fields(&self) -> bool {
+        false
+    }
+
+    /// Writes the input for the next run.
+    /// `shmem` is the shared memory testcase map, if the target agreed to use it.
+    fn deliver(&mut self, input: &I, shmem: Option<&mut [u8]>) -> Result<(), Error>;
+}
+
+/// Passes the input bytes in a single file, either as `stdin` of the target, or at each `@@` in the arguments.
+#[derive(Debug, Clone)]
+pub struct BytesDelivery {
+    input_file: InputFile,
+    use_stdin: bool,
+}
+
+impl BytesDelivery {
+    /// Passes the input to `stdin` of the target, using the default input file.
+    pub fn stdin() -> Result<Self, Error> {
+        Self::with_file(INPUTFILE_STD, true)
+    }
+
+    /// Passes the input in the file at `path`.
+    pub fn file<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::with_file(path, false)
+    }
+
+    /// Passes the input in the file at `path`, that will also be `stdin` of the target if `use_stdin` is set.
+    pub fn with_file<P: AsRef<Path>>(path: P, use_stdin: bool) -> Result<Self, Error> {
+        Ok(Self {
+            input_file: InputFile::create(path)?,
+            use_stdin,
+        })
+    }
+
+    /// The [`InputFile`] the input is written to.
+    #[must_use]
+    pub fn input_file(&self) -> &InputFile {
+        &self.input_file
+    }
+}
+
+impl<I> InputDelivery<I> for BytesDelivery
+where
+    I: Input + HasTargetBytes,
+{
+    fn stdin_fd(&self) -> Option<RawFd> {
+        self.use_stdin.then(|| self.input_file.as_raw_fd())
+    }
+
+    fn resolve_arg(&self, arg: &OsStr) -> OsString {
+        replace_placeholders(arg, false, |_| {
+            Some(self.input_file.path.as_os_str().to_owned())
+        })
+    }
+
+    fn deliver(&mut self, input: &I, shmem: Option<&mut [u8]>) -> Result<(), Error> {
+        let target_bytes = input.target_bytes();
+        match shmem {
+            Some(map) => {
+                write_shmem_testcase(map, target_bytes.as_slice())?;
+            }
+            None => self.input_file.write_buf(target_bytes.as_slice())?,
+        }
+        Ok(())
+    }
+}
+
+/// Passes each field of a multi-field input in its own file.
+/// The file of field `N` replaces the `@@N` placeholder in the target arguments, a plain `@@` is field `0`.
+/// If `use_stdin` is set, the first field is passed on `stdin` instead, and `@@N` refers to field `N + 1`.
+#[derive(Debug, Clone)]
+pub struct MultiFileDelivery {
+    stdin_file: Option<InputFile>,
+    input_files: Vec<InputFile>,
+}
+
+impl MultiFileDelivery {
+    /// Passes the fields in the given files, the first field on `stdin` if `use_stdin` is set.
+    pub fn new<P: AsRef<Path>>(paths: &[P], use_stdin: bool) -> Result<Self, Error> {
+        let stdin_file = if use_stdin {
+            Some(InputFile::create(format!(".{}.std_input", Uuid::new_v4()))?)
+        } else {
+            None
+        };
+        let input_files = paths
+            .iter()
+            .map(InputFile::create)
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(Self {
+            stdin_file,
+            input_files,
+        })
+    }
+
+    /// The [`InputFile`]s the fields are written to, not including the `stdin` file.
+    #[must_use]
+    pub fn input_files(&self) -> &[InputFile] {
+        &self.input_files
+    }
+}
+
+impl<I> InputDelivery<I> for MultiFileDelivery
+where
+    I: Input + AsMultiBytes,
+{
+    fn stdin_fd(&self) -> Option<RawFd> {
+        self.stdin_file.as_ref().map(InputFile::as_raw_fd)
+    }
+
+    fn resolve_arg(&self, arg: &OsStr) -> OsString {
+        replace_placeholders(arg, true, |idx| {
+            self.input_files
+                .get(idx)
+                .map(|file| file.path.as_os_str().to_owned())
+        })
+    }
+
+    fn uses_shmem_fields(&self) -> bool {
+        true
+    }
+
+    fn deliver(&mut self, input: &I, shmem: Option<&mut [u8]>) -> Result<(), Error> {
+        let fields = input.as_multi_ownd_bytes();
+        if let Some(map) = shmem {
+            write_multi_shmem_testcase(map, &fields)?;
+            return Ok(());
+        }
+
+        let mut fields = fields.iter();
+        if let Some(stdin_file) = &mut self.stdin_file {
+            if let Some(field) = fields.next() {
+                stdin_file.write_buf(field.as_slice())?;
+            }
+        }
+        for (field, input_file) in fields.zip(self.input_files.iter_mut()) {
+            input_file.write_buf(field.as_slice())?;
+        }
+        Ok(())
+    }
+}
+
+/// Passes the fields of a multi-field input as the command line arguments of the target.
+/// The fields are written to `stdin`, separated by `\0` and terminated by `\0\0`,
+/// which is the format AFL++'s `argv-fuzz-inl.h` expects.
+#[derive(Debug, Clone)]
+pub struct ArgvDelivery {
+    input_file: InputFile,
+    buf: Vec<u8>,
+}
+
+impl ArgvDelivery {
+    /// Creates a new [`ArgvDelivery`], using the default input file.
+    pub fn new() -> Result<Self, Error> {
+        Ok(Self {
+            input_file: InputFile::create(INPUTFILE_STD)?,
+            buf: vec![],
+        })
+    }
+}
+
+impl<I> InputDelivery<I> for ArgvDelivery
+where
+    I: Input + AsMultiBytes,
+{
+    fn stdin_fd(&self) -> Option<RawFd> {
+        Some(self.input_file.as_raw_fd())
+    }
+
+    fn deliver(&mut self, input: &I, shmem: Option<&mut [u8]>) -> Result<(), Error> {
+        self.buf.clear();
+        for field in input.as_multi_ownd_bytes() {
+            // An argument cannot contain a `\0`, it ends at the first one.
+            let arg = field.as_slice();
+            let len = arg.iter().position(|b| *b == 0).unwrap_or(arg.len());
+            self.buf.extend_from_slice(&arg[..len]);
+            self.buf.push(0);
+        }
+        self.buf.push(0);
+
+        match shmem {
+            Some(map) => {
+                write_shmem_testcase(map, &self.buf)?;
+            }
+            None => self.input_file.write_buf(&self.buf)?,
+        }
+        Ok(())
+    }
+}
+
 /// A struct that has a forkserver
 pub trait HasForkserver {
     /// The [`ShMemProvider`] used for this forkserver's map
@@ -336,12 +679,6 @@ pub trait HasForkserver {
     /// The forkserver, mutable
     fn forkserver_mut(&mut self) -> &mut Forkserver;
 
-    /// The file the forkserver is reading from
-    fn input_file(&self) -> &InputFile;
-
-    /// The file the forkserver is reading from, mutable
-    fn input_file_mut(&mut self) -> &mut InputFile;
-
     /// The map of the fuzzer
     fn shmem(&self) -> &Option<<<Self as HasForkserver>::SP as ShMemProvider>::ShMem>;
 
@@ -349,6 +686,21 @@ pub trait HasForkserver {
     fn shmem_mut(&mut self) -> &mut Option<<<Self as HasForkserver>::SP as ShMemProvider>::ShMem>;
 }
 
+/// A forkserver that knows how to pass inputs of type `I` to its target
+pub trait HasInputDelivery<I>: HasForkserver {
+    /// The [`InputDelivery`] used for the inputs
+    type Delivery: InputDelivery<I>;
+
+    /// The input delivery
+    fn delivery(&self) -> &Self::Delivery;
+
+    /// The input delivery, mutable
+    fn delivery_mut(&mut self) -> &mut Self::Delivery;
+
+    /// Writes the input for the next run of the target
+    fn write_input(&mut self, input: &I) -> Result<(), Error>;
+}
+
 /// The timeout forkserver executor that wraps around the standard forkserver executor and sets a timeout before each run.
 #[derive(Debug)]
 pub struct TimeoutForkserverExecutor<E: Debug> {
@@ -378,8 +730,8 @@ impl<E: Debug> TimeoutForkserverExecutor<E> {
 
 impl<E: Debug, EM, I, S, Z> Executor<EM, I, S, Z> for TimeoutForkserverExecutor<E>
 where
-    I: Input + HasTargetBytes,
-    E: Executor<EM, I, S, Z> + HasForkserver,
+    I: Input,
+    E: Executor<EM, I, S, Z> + HasInputDelivery<I>,
 {
     #[inline]
     fn run_target(
@@ -393,22 +745,7 @@ where
 
         let last_run_timed_out = self.executor.forkserver().last_run_timed_out();
 
-        match &mut self.executor.shmem_mut() {
-            Some(shmem) => {
-                let target_bytes = input.target_bytes();
-                let size = target_bytes.as_slice().len();
-                let size_in_bytes = size.to_ne_bytes();
-                // The first four bytes tells the size of the shmem.
-                shmem.as_mut_slice()[..4].copy_from_slice(&size_in_bytes[..4]);
-                shmem.as_mut_slice()[SHMEM_FUZZ_HDR_SIZE..(SHMEM_FUZZ_HDR_SIZE + size)]
-                    .copy_from_slice(target_bytes.as_slice());
-            }
-            None => {
-                self.executor
-                    .input_file_mut()
-                    .write_buf(input.target_bytes().as_slice())?;
-            }
-        }
+        self.executor.write_input(input)?;
 
         let send_len = self
             .executor
@@ -469,38 +806,64 @@ where
     }
 }
 
+/// The signatures of persistent mode and of a deferred forkserver found in the binary of the `program`,
+/// looked up in the `PATH` if it has no directory. Unreadable binaries have none.
+fn forkserver_signatures(program: &OsStr) -> (bool, bool) {
+    let path = Path::new(program);
+    let path = if path.components().count() > 1 {
+        Some(path.to_path_buf())
+    } else {
+        env::var_os("PATH").and_then(|paths| {
+            env::split_paths(&paths)
+                .map(|dir| dir.join(path))
+                .find(|candidate| candidate.is_file())
+        })
+    };
+    let binary = match path.map(fs::read) {
+        Some(Ok(binary)) => binary,
+        _ => return (false, false),
+    };
+    let contains = |sig: &[u8]| binary.windows(sig.len()).any(|window| window == sig);
+    (contains(PERSIST_SIG), contains(DEFER_SIG))
+}
+
 /// This [`Executor`] can run binaries compiled for AFL/AFL++ that make use of a forkserver.
+/// How the input reaches the target is decided by the [`InputDelivery`] `D`.
 /// Shared memory feature is also available, but you have to set things up in your code.
 /// Please refer to AFL++'s docs. <https://github.com/AFLplusplus/AFLplusplus/blob/stable/instrumentation/README.persistent_mode.md>
-pub struct ForkserverExecutor<I, OT, S, SP>
+pub struct ForkserverExecutor<I, OT, S, SP, D = BytesDelivery>
 where
     OT: Debug,
     SP: ShMemProvider,
 {
     target: OsString,
     args: Vec<OsString>,
-    input_file: InputFile,
+    delivery: D,
     forkserver: Forkserver,
     observers: OT,
     map: Option<SP::ShMem>,
     phantom: PhantomData<(I, S)>,
     /// Cache that indicates if we have a `ASan` observer registered.
     has_asan_observer: Option<bool>,
+    /// If the target runs in persistent mode
+    persistent: bool,
 }
 
-impl<I, OT, S, SP> Debug for ForkserverExecutor<I, OT, S, SP>
+impl<I, OT, S, SP, D> Debug for ForkserverExecutor<I, OT, S, SP, D>
 where
     OT: Debug,
     SP: ShMemProvider,
+    D: Debug,
 {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         f.debug_struct("ForkserverExecutor")
             .field("target", &self.target)
             .field("args", &self.args)
-            .field("input_file", &self.input_file)
+            .field("delivery", &self.delivery)
             .field("forkserver", &self.forkserver)
             .field("observers", &self.observers)
             .field("map", &self.map)
+            .field("persistent", &self.persistent)
             .finish()
     }
 }
@@ -513,11 +876,12 @@ impl ForkserverExecutor<(), (), (), StdShMemProvider> {
     }
 }
 
-impl<I, OT, S, SP> ForkserverExecutor<I, OT, S, SP>
+impl<I, OT, S, SP, D> ForkserverExecutor<I, OT, S, SP, D>
 where
-    I: Input + HasTargetBytes,
+    I: Input,
     OT: ObserversTuple<I, S>,
     SP: ShMemProvider,
+    D: InputDelivery<I>,
 {
     /// The `target` binary that's going to run.
     pub fn target(&self) -> &OsString {
@@ -534,14 +898,44 @@ where
         &self.forkserver
     }
 
-    /// The [`InputFile`] used by this [`Executor`].
-    pub fn input_file(&self) -> &InputFile {
-        &self.input_file
+    /// The [`InputDelivery`] used by this [`Executor`].
+    pub fn delivery(&self) -> &D {
+        &self.delivery
+    }
+
+    /// If the target runs in persistent mode, resuming the same child for many inputs
+    pub fn is_persistent(&self) -> bool {
+        self.persistent
+    }
+
+    /// Replaces the observers with `observers`, returning this executor with them, and the previous observers.
+    pub fn replace_observers<OT2>(
+        self,
+        observers: OT2,
+    ) -> (ForkserverExecutor<I, OT2, S, SP, D>, OT)
+    where
+        OT2: ObserversTuple<I, S>,
+    {
+        (
+            ForkserverExecutor {
+                target: self.target,
+                args: self.args,
+                delivery: self.delivery,
+                forkserver: self.forkserver,
+                observers,
+                map: self.map,
+                phantom: PhantomData,
+                has_asan_observer: None,
+                persistent: self.persistent,
+            },
+            self.observers,
+        )
     }
 }
 
 /// The builder for `ForkserverExecutor`
 #[derive(Debug)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct ForkserverExecutorBuilder<'a, SP> {
     program: Option<OsString>,
     arguments: Vec<OsString>,
@@ -550,12 +944,14 @@ pub struct ForkserverExecutorBuilder<'a, SP> {
     use_stdin: bool,
     autotokens: Option<&'a mut Tokens>,
     input_filename: Option<OsString>,
+    input_filenames: Vec<(OsString, usize)>,
     shmem_provider: Option<&'a mut SP>,
+    is_persistent: bool,
+    is_deferred_frksrv: bool,
 }
 
 impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
-    /// Builds `ForkserverExecutor`.
-    #[allow(clippy::pedantic)]
+    /// Builds `ForkserverExecutor`, passing the input bytes on `stdin` or in a single file.
     pub fn build<I, OT, S>(
         &mut self,
         observers: OT,
@@ -565,14 +961,92 @@ impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
         OT: ObserversTuple<I, S>,
         SP: ShMemProvider,
     {
-        let input_filename = match &self.input_filename {
-            Some(name) => name.clone(),
-            None => OsString::from(".cur_input"),
+        let delivery = match &self.input_filename {
+            Some(name) => BytesDelivery::with_file(name, self.use_stdin)?,
+            None => BytesDelivery::with_file(INPUTFILE_STD, self.use_stdin)?,
         };
+        self.build_with_delivery(observers, delivery)
+    }
+
+    /// Builds `ForkserverExecutor` for multi-field inputs, passing each field in its own file.
+    /// The files replace the `@@N` placeholders of the arguments and environment values.
+    pub fn build_multi<I, OT, S>(
+        &mut self,
+        observers: OT,
+    ) -> Result<ForkserverExecutor<I, OT, S, SP, MultiFileDelivery>, Error>
+    where
+        I: Input + AsMultiBytes,
+        OT: ObserversTuple<I, S>,
+        SP: ShMemProvider,
+    {
+        let delivery = MultiFileDelivery::new(&self.multi_input_paths(), self.use_stdin)?;
+        self.build_with_delivery(observers, delivery)
+    }
+
+    /// The files of the fields of a multi-field input, up to the highest field referenced
+    /// by a placeholder, the `input_filename` or the `input_filenames`.
+    fn multi_input_paths(&self) -> Vec<OsString> {
+        let referenced = self
+            .arguments
+            .iter()
+            .chain(self.envs.iter().map(|(_, val)| val))
+            .flat_map(|arg| placeholders(&arg.to_string_lossy(), true))
+            .map(|(_, idx)| idx)
+            .chain(self.input_filename.iter().map(|_| 0))
+            .chain(self.input_filenames.iter().map(|(_, idx)| *idx))
+            .max();
+
+        let mut paths: Vec<OsString> = (0..referenced.map_or(0, |max| max + 1))
+            .map(|_| OsString::from(format!(".{}.cur_input", Uuid::new_v4())))
+            .collect();
+        if let Some(name) = &self.input_filename {
+            paths[0].clone_from(name);
+        }
+        for (name, idx) in &self.input_filenames {
+            paths[*idx].clone_from(name);
+        }
+        paths
+    }
 
-        let input_file = InputFile::create(&input_filename)?;
+    /// Builds `ForkserverExecutor`, using the given [`InputDelivery`].
+    #[allow(clippy::pedantic)]
+    pub fn build_with_delivery<I, OT, S, D>(
+        &mut self,
+        observers: OT,
+        delivery: D,
+    ) -> Result<ForkserverExecutor<I, OT, S, SP, D>, Error>
+    where
+        I: Input,
+        OT: ObserversTuple<I, S>,
+        SP: ShMemProvider,
+        D: InputDelivery<I>,
+    {
+        let arguments: Vec<OsString> = self
+            .arguments
+            .iter()
+            .map(|arg| delivery.resolve_arg(arg))
+            .collect();
+        let mut envs: Vec<(OsString, OsString)> = self
+            .envs
+            .iter()
+            .map(|(key, val)| (key.clone(), delivery.resolve_arg(val)))
+            .collect();
+
+        let (persistent_sig, deferred_sig) = self
+            .program
+            .as_ref()
+            .map_or((false, false), |program| forkserver_signatures(program));
+        let persistent = self.is_persistent || persistent_sig;
+        if persistent {
+            println!("Persistent mode enabled.");
+            envs.push((PERSIST_ENV_VAR.into(), "1".into()));
+        }
+        if self.is_deferred_frksrv || deferred_sig {
+            println!("Deferred forkserver enabled.");
+            envs.push((DEFER_ENV_VAR.into(), "1".into()));
+        }
 
-        let map = match &mut self.shmem_provider {
+        let mut map = match &mut self.shmem_provider {
             None => None,
             Some(provider) => {
                 // setup shared memory
@@ -589,10 +1063,10 @@ impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
             Some(t) => {
                 let forkserver = Forkserver::new(
                     t.clone(),
-                    self.arguments.clone(),
-                    self.envs.clone(),
-                    input_file.as_raw_fd(),
-                    self.use_stdin,
+                    arguments.clone(),
+                    envs,
+                    delivery.stdin_fd().unwrap_or(-1),
+                    delivery.stdin_fd().is_some(),
                     0,
                     self.debug_child,
                 )?;
@@ -613,12 +1087,20 @@ impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
         }
         println!("All right - fork server is up.");
         // If forkserver is responding, we then check if there's any option enabled.
+        let mut shmem_accepted = false;
         if status & FS_OPT_ENABLED == FS_OPT_ENABLED {
             let mut send_status = FS_OPT_ENABLED;
 
             if (status & FS_OPT_SHDMEM_FUZZ == FS_OPT_SHDMEM_FUZZ) && map.is_some() {
-                println!("Using SHARED MEMORY FUZZING feature.");
-                send_status |= FS_OPT_SHDMEM_FUZZ;
+                if !delivery.uses_shmem_fields() {
+                    println!("Using SHARED MEMORY FUZZING feature.");
+                    send_status |= FS_OPT_SHDMEM_FUZZ;
+                    shmem_accepted = true;
+                } else if status & FS_OPT_SHDMEM_MULTI == FS_OPT_SHDMEM_MULTI {
+                    println!("Using SHARED MEMORY FUZZING feature for all input fields.");
+                    send_status |= FS_OPT_SHDMEM_FUZZ | FS_OPT_SHDMEM_MULTI;
+                    shmem_accepted = true;
+                }
             }
 
             if (status & FS_OPT_AUTODICT == FS_OPT_AUTODICT) && self.autotokens.is_some() {
@@ -661,22 +1143,26 @@ impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
             println!("Forkserver Options are not available.");
         }
 
+        if !shmem_accepted {
+            // The target will not read from the map, fall back to the input delivery.
+            map = None;
+        }
+
         println!(
-            "ForkserverExecutor: program: {:?}, arguments: {:?}, use_stdin: {:?}",
-            target,
-            self.arguments.clone(),
-            self.use_stdin
+            "ForkserverExecutor: program: {:?}, arguments: {:?}, delivery: {:?}",
+            target, arguments, delivery
         );
 
         Ok(ForkserverExecutor {
             target,
-            args: self.arguments.clone(),
-            input_file,
+            args: arguments,
+            delivery,
             forkserver,
             observers,
             map,
             phantom: PhantomData,
             has_asan_observer: None, // initialized on first use
+            persistent,
         })
     }
 
@@ -688,7 +1174,9 @@ impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
     }
 
     #[must_use]
-    /// Parse afl style command line
+    /// Parse afl style command line.
+    /// `@@` is replaced by the input file, `@@0` to `@@N` by the files of the fields of a multi-field input.
+    /// If there is no placeholder, the input is passed on `stdin`.
     pub fn parse_afl_cmdline<IT, O>(mut self, args: IT) -> Self
     where
         IT: IntoIterator<Item = O>,
@@ -698,19 +1186,15 @@ impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
         let mut use_stdin = true;
 
         for item in args {
-            if item.as_ref() == "@@" && use_stdin {
+            let item = item.as_ref();
+            if !placeholders(&item.to_string_lossy(), false).is_empty() {
                 use_stdin = false;
-                res.push(OsString::from(".cur_input"));
             } else if let Some(name) = &self.input_filename {
-                if name == item.as_ref() && use_stdin {
+                if name == item {
                     use_stdin = false;
-                    res.push(name.clone());
-                } else {
-                    res.push(item.as_ref().to_os_string());
                 }
-            } else {
-                res.push(item.as_ref().to_os_string());
             }
+            res.push(item.to_os_string());
         }
 
         self.arguments = res;
@@ -735,7 +1219,10 @@ impl<'a> ForkserverExecutorBuilder<'a, StdShMemProvider> {
             use_stdin: true,
             autotokens: None,
             input_filename: None,
+            input_filenames: vec![],
             shmem_provider: None,
+            is_persistent: false,
+            is_deferred_frksrv: false,
         }
     }
 
@@ -804,6 +1291,7 @@ impl<'a> ForkserverExecutorBuilder<'a, StdShMemProvider> {
 
     #[must_use]
     /// Place the input at this position and set the filename for the input.
+    /// For multi-field inputs, this is the file of the first field.
     pub fn arg_input_file<P: AsRef<Path>>(self, path: P) -> Self {
         let mut moved = self.arg(path.as_ref());
         moved.input_filename = Some(path.as_ref().as_os_str().to_os_string());
@@ -816,6 +1304,32 @@ impl<'a> ForkserverExecutorBuilder<'a, StdShMemProvider> {
         self.arg_input_file(INPUTFILE_STD)
     }
 
+    #[must_use]
+    /// Set the filenames of the fields of a multi-field input, given as `(path, field index)`.
+    pub fn input_files<P: AsRef<Path>>(mut self, paths: Vec<(P, usize)>) -> Self {
+        for (path, idx) in paths {
+            self.input_filenames
+                .push((path.as_ref().as_os_str().to_os_string(), idx));
+        }
+        self
+    }
+
+    #[must_use]
+    /// Run the target in persistent mode, see `libafl_targets::persistent_loop`.
+    /// Enabled anyway if the binary uses `__AFL_LOOP`.
+    pub fn is_persistent(mut self, is_persistent: bool) -> Self {
+        self.is_persistent = is_persistent;
+        self
+    }
+
+    #[must_use]
+    /// The target starts the forkserver itself, after its initialization.
+    /// Only needed for `afl-clang-fast` targets which do not use `__AFL_INIT`, the `libafl_targets` forkserver never starts on its own.
+    pub fn is_deferred_frksrv(mut self, is_deferred_frksrv: bool) -> Self {
+        self.is_deferred_frksrv = is_deferred_frksrv;
+        self
+    }
+
     #[must_use]
     /// If `debug_child` is set, the child will print to `stdout`/`stderr`.
     pub fn debug_child(mut self, debug_child: bool) -> Self {
@@ -836,7 +1350,10 @@ impl<'a> ForkserverExecutorBuilder<'a, StdShMemProvider> {
             use_stdin: self.use_stdin,
             autotokens: self.autotokens,
             input_filename: self.input_filename,
+            input_filenames: self.input_filenames,
             shmem_provider: Some(shmem_provider),
+            is_persistent: self.is_persistent,
+            is_deferred_frksrv: self.is_deferred_frksrv,
         }
     }
 }
@@ -847,11 +1364,12 @@ impl<'a> Default for ForkserverExecutorBuilder<'a, StdShMemProvider> {
     }
 }
 
-impl<EM, I, OT, S, SP, Z> Executor<EM, I, S, Z> for ForkserverExecutor<I, OT, S, SP>
+impl<EM, I, OT, S, SP, D, Z> Executor<EM, I, S, Z> for ForkserverExecutor<I, OT, S, SP, D>
 where
-    I: Input + HasTargetBytes,
+    I: Input,
     OT: ObserversTuple<I, S>,
     SP: ShMemProvider,
+    D: InputDelivery<I>,
 {
     #[inline]
     fn run_target(
@@ -864,20 +1382,7 @@ where
         let mut exit_kind = ExitKind::Ok;
 
         // Write to testcase
-        match &mut self.map {
-            Some(map) => {
-                let target_bytes = input.target_bytes();
-                let size = target_bytes.as_slice().len();
-                let size_in_bytes = size.to_ne_bytes();
-                // The first four bytes tells the size of the shmem.
-                map.as_mut_slice()[..4].copy_from_slice(&size_in_bytes[..4]);
-                map.as_mut_slice()[SHMEM_FUZZ_HDR_SIZE..(SHMEM_FUZZ_HDR_SIZE + size)]
-                    .copy_from_slice(target_bytes.as_slice());
-            }
-            None => {
-                self.input_file.write_buf(input.target_bytes().as_slice())?;
-            }
-        }
+        self.write_input(input)?;
 
         let send_len = self
             .forkserver
@@ -935,11 +1440,12 @@ where
     }
 }
 
-impl<I, OT, S, SP> HasObservers<I, OT, S> for ForkserverExecutor<I, OT, S, SP>
+impl<I, OT, S, SP, D> HasObservers<I, OT, S> for ForkserverExecutor<I, OT, S, SP, D>
 where
-    I: Input + HasTargetBytes,
+    I: Input,
     OT: ObserversTuple<I, S>,
     SP: ShMemProvider,
+    D: InputDelivery<I>,
 {
     #[inline]
     fn observers(&self) -> &OT {
@@ -952,11 +1458,12 @@ where
     }
 }
 
-impl<I, OT, S, SP> HasForkserver for ForkserverExecutor<I, OT, S, SP>
+impl<I, OT, S, SP, D> HasForkserver for ForkserverExecutor<I, OT, S, SP, D>
 where
-    I: Input + HasTargetBytes,
+    I: Input,
     OT: ObserversTuple<I, S>,
     SP: ShMemProvider,
+    D: InputDelivery<I>,
 {
     type SP = SP;
 
@@ -971,23 +1478,39 @@ where
     }
 
     #[inline]
-    fn input_file(&self) -> &InputFile {
-        &self.input_file
+    fn shmem(&self) -> &Option<SP::ShMem> {
+        &self.map
     }
 
     #[inline]
-    fn input_file_mut(&mut self) -> &mut InputFile {
-        &mut self.input_file
+    fn shmem_mut(&mut self) -> &mut Option<SP::ShMem> {
+        &mut self.map
     }
+}
+
+impl<I, OT, S, SP, D> HasInputDelivery<I> for ForkserverExecutor<I, OT, S, SP, D>
+where
+    I: Input,
+    OT: ObserversTuple<I, S>,
+    SP: ShMemProvider,
+    D: InputDelivery<I>,
+{
+    type Delivery = D;
 
     #[inline]
-    fn shmem(&self) -> &Option<SP::ShMem> {
-        &self.map
+    fn delivery(&self) -> &D {
+        &self.delivery
     }
 
     #[inline]
-    fn shmem_mut(&mut self) -> &mut Option<SP::ShMem> {
-        &mut self.map
+    fn delivery_mut(&mut self) -> &mut D {
+        &mut self.delivery
+    }
+
+    #[inline]
+    fn write_input(&mut self, input: &I) -> Result<(), Error> {
+        let shmem = self.map.as_mut().map(AsMutSlice::as_mut_slice);
+        self.delivery.deliver(input, shmem)
     }
 }
 
@@ -1009,19 +1532,27 @@ where
 
 #[cfg(test)]
 mod tests {
+    use alloc::{string::String, vec::Vec};
+
     use crate::{
         bolts::{
+            ownedref::OwnedSlice,
             shmem::{ShMem, ShMemProvider, StdShMemProvider},
             tuples::tuple_list,
             AsMutSlice,
         },
-        executors::forkserver::ForkserverExecutorBuilder,
-        inputs::NopInput,
+        executors::forkserver::{
+            forkserver_signatures, placeholders, write_multi_shmem_testcase, ArgvDelivery,
+            BytesDelivery, ForkserverExecutorBuilder, InputDelivery, MultiFileDelivery, DEFER_SIG,
+            PERSIST_SIG,
+        },
+        inputs::{BytesInput, MultiInput, NopInput},
         observers::{ConstMapObserver, HitcountsMapObserver},
         Error,
     };
     use serial_test::serial;
-    use std::ffi::OsString;
+    use std::ffi::{OsStr, OsString};
+
     #[test]
     #[serial]
     fn test_forkserver() {
@@ -1057,4 +1588,118 @@ mod tests {
         };
         assert!(result);
     }
+
+    #[test]
+    fn test_multi_shmem_layout() {
+        let fields = vec![
+            OwnedSlice::from(vec![1_u8, 2, 3]),
+            OwnedSlice::from(vec![]),
+            OwnedSlice::from(vec![4_u8]),
+        ];
+        let mut map = vec![0_u8; 64];
+        let written = write_multi_shmem_testcase(&mut map, &fields).unwrap();
+        assert_eq!(written, 4 + 4 + 3 * 4 + 4);
+
+        let read_u32 = |pos: usize| u32::from_ne_bytes(map[pos..pos + 4].try_into().unwrap());
+        assert_eq!(read_u32(0) as usize, written - 4);
+        assert_eq!(read_u32(4), 3);
+        assert_eq!([read_u32(8), read_u32(12), read_u32(16)], [3, 0, 1]);
+        assert_eq!(&map[20..24], &[1, 2, 3, 4]);
+
+        let mut small_map = vec![0_u8; 16];
+        assert!(write_multi_shmem_testcase(&mut small_map, &fields).is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn test_deliveries() {
+        let input = MultiInput::new(vec![
+            BytesInput::new(b"-v".to_vec()),
+            BytesInput::new(b"fi\0le".to_vec()),
+        ]);
+
+        let mut multi = MultiFileDelivery::new(&[".test_field_0", ".test_field_1"], false).unwrap();
+        assert_eq!(
+            InputDelivery::<MultiInput>::resolve_arg(&multi, OsStr::new("-i@@1")),
+            OsString::from("-i.test_field_1")
+        );
+        multi.deliver(&input, None).unwrap();
+        assert_eq!(std::fs::read(".test_field_1").unwrap(), b"fi\0le");
+
+        let mut map = vec![0_u8; 32];
+        let mut argv = ArgvDelivery::new().unwrap();
+        argv.deliver(&input, Some(&mut map)).unwrap();
+        assert_eq!(&map[..4], &7_u32.to_ne_bytes());
+        assert_eq!(&map[4..11], b"-v\0fi\0\0");
+    }
+
+    #[test]
+    #[serial]
+    fn test_placeholders() {
+        let indices = |arg: &str| -> Vec<usize> {
+            placeholders(arg, true)
+                .into_iter()
+                .map(|(_, idx)| idx)
+                .collect()
+        };
+        assert_eq!(indices("-a@@10,@@2"), [10, 2]);
+        assert_eq!(indices("--file=@@"), [0]);
+        assert_eq!(indices("@"), [0_usize; 0]);
+
+        // Field indices of more than one digit
+        let paths: Vec<String> = (0..11).map(|n| format!(".test_field_{}", n)).collect();
+        let multi = MultiFileDelivery::new(&paths, false).unwrap();
+        assert_eq!(
+            InputDelivery::<MultiInput>::resolve_arg(&multi, OsStr::new("@@10:@@1:@@12")),
+            OsString::from(".test_field_10:.test_field_1:@@12")
+        );
+        for path in &paths {
+            std::fs::remove_file(path).unwrap();
+        }
+
+        // A placeholder within an argument disables stdin, and gets replaced
+        let builder = ForkserverExecutorBuilder::new().parse_afl_cmdline(["--file=@@"]);
+        assert!(!builder.use_stdin);
+        let bytes = BytesDelivery::file(".test_bytes_input").unwrap();
+        assert_eq!(
+            InputDelivery::<BytesInput>::resolve_arg(&bytes, OsStr::new("--file=@@")),
+            OsString::from("--file=.test_bytes_input")
+        );
+        std::fs::remove_file(".test_bytes_input").unwrap();
+
+        // The input file is the first field, even without any `@@N`
+        let builder = ForkserverExecutorBuilder::new().arg_input_file("in.txt");
+        assert_eq!(builder.multi_input_paths(), [OsString::from("in.txt")]);
+        let builder = ForkserverExecutorBuilder::new()
+            .arg_input_file("in.txt")
+            .arg("@@2");
+        let paths = builder.multi_input_paths();
+        assert_eq!(paths.len(), 3);
+        assert_eq!(paths[0], "in.txt");
+    }
+
+    #[test]
+    #[serial]
+    fn test_forkserver_signatures() {
+        let mut binary = b"\x7fELF".to_vec();
+        binary.extend_from_slice(PERSIST_SIG);
+        std::fs::write(".test_signatures", &binary).unwrap();
+        assert_eq!(
+            forkserver_signatures(OsStr::new("./.test_signatures")),
+            (true, false)
+        );
+
+        binary.extend_from_slice(DEFER_SIG);
+        std::fs::write(".test_signatures", &binary).unwrap();
+        assert_eq!(
+            forkserver_signatures(OsStr::new("./.test_signatures")),
+            (true, true)
+        );
+        std::fs::remove_file(".test_signatures").unwrap();
+
+        assert_eq!(
+            forkserver_signatures(OsStr::new("./.test_no_such_binary")),
+            (false, false)
+        );
+    }
 }
@@ -6,7 +6,7 @@ pub use inprocess::InProcessExecutor;
 pub use inprocess::InProcessForkExecutor;
 
 pub mod differential;
-pub use differential::DiffExecutor;
+pub use differential::{DiffExecutor, DiffObservers, ExecutorsTuple, TupleDiffExecutor};
 
 /// Timeout executor.
 /// Not possible on `no-std` Windows or `no-std`, but works for unix
@@ -20,11 +20,10 @@ pub mod forkserver;
 #[cfg(all(feature = "std", feature = "fork", unix))]
 pub use forkserver::{Forkserver, ForkserverExecutor, TimeoutForkserverExecutor};
 
-#[cfg(all(feature = "std", feature = "fork", unix))]
-pub mod multi_forkserver;
-#[cfg(all(feature = "std", feature = "fork", unix))]
-pub use multi_forkserver::*;
-
+#[cfg(all(feature = "std", feature = "fork", target_os = "linux"))]
+pub mod snapshot;
+#[cfg(all(feature = "std", feature = "fork", target_os = "linux"))]
+pub use snapshot::InProcessSnapshotExecutor;
 
 pub mod combined;
 pub use combined::CombinedExecutor;
@@ -40,18 +39,27 @@ pub mod command;
 #[cfg(all(feature = "std", unix))]
 pub use command::CommandExecutor;
 
+#[cfg(all(feature = "std", unix))]
+pub mod network;
+#[cfg(all(feature = "std", unix))]
+pub use network::NetworkExecutor;
+
 use crate::{
-    bolts::AsSlice,
+    bolts::{
+        serdeany::{SerdeAny, Wrap},
+        AsSlice,
+    },
     inputs::{HasTargetBytes, Input},
     observers::ObserversTuple,
     Error,
 };
 
-use core::fmt::Debug;
-use serde::{Deserialize, Serialize};
+use alloc::{boxed::Box, rc::Rc};
+use core::{any::Any, fmt::Debug};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 /// How an execution finished.
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum ExitKind {
     /// The run exited normally.
     Ok,
@@ -68,10 +76,124 @@ pub enum ExitKind {
         /// The exitkind of the secondary executor
         secondary: DiffExitKind,
     },
-    // The run resulted in a custom `ExitKind`.
-    // Custom(Box<dyn SerdeAny>),
+    /// The run resulted in a custom `ExitKind`, with a payload from the harness.
+    Custom(CustomExitKind),
+}
+
+impl ExitKind {
+    /// A custom [`ExitKind`] with the given payload, see [`CustomExitKind`]
+    #[must_use]
+    pub fn custom<T>(payload: T) -> Self
+    where
+        T: SerdeAny,
+    {
+        ExitKind::Custom(CustomExitKind::new(payload))
+    }
+
+    /// The payload of a custom [`ExitKind`], if this is one with a payload of type `T`
+    #[must_use]
+    pub fn custom_payload<T>(&self) -> Option<&T>
+    where
+        T: SerdeAny,
+    {
+        match self {
+            ExitKind::Custom(custom) => custom.downcast_ref(),
+            _ => None,
+        }
+    }
+}
+
+/// The payload of an [`ExitKind::Custom`], telling how the run ended beyond crashing,
+/// like a failed assertion, a leak, or the parser accepting an invalid input.
+/// Any [`SerdeAny`] type works as payload, e.g. using [`crate::impl_serdeany`].
+/// Only executors running the harness in the fuzzer process pass it on, executors running the target
+/// in a child process, like the [`InProcessForkExecutor`], report how the child exited instead.
+/// The payload is shared, so cloning the [`ExitKind`] is cheap.
+#[derive(Debug, Clone)]
+pub struct CustomExitKind {
+    payload: Rc<dyn SerdeAny>,
+}
+
+impl CustomExitKind {
+    /// Creates a new [`CustomExitKind`] with the given payload
+    #[must_use]
+    pub fn new<T>(payload: T) -> Self
+    where
+        T: SerdeAny,
+    {
+        Self {
+            payload: Rc::new(payload),
+        }
+    }
+
+    /// The payload
+    #[must_use]
+    pub fn payload(&self) -> &dyn SerdeAny {
+        self.payload.as_ref()
+    }
+
+    /// The payload, if it is of type `T`
+    #[must_use]
+    pub fn downcast_ref<T>(&self) -> Option<&T>
+    where
+        T: SerdeAny,
+    {
+        self.payload.as_any().downcast_ref()
+    }
+
+    /// If the payload is of type `T`
+    #[must_use]
+    pub fn is<T>(&self) -> bool
+    where
+        T: SerdeAny,
+    {
+        self.payload.as_any().is::<T>()
+    }
+}
+
+impl Serialize for CustomExitKind {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.payload.as_ref().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for CustomExitKind {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let payload: Box<dyn SerdeAny> = Deserialize::deserialize(deserializer)?;
+        Ok(Self {
+            payload: Rc::from(payload),
+        })
+    }
+}
+
+/// Payloads are equal if they have the same type and serialize to the same bytes.
+/// Payloads failing to serialize only equal themselves.
+impl PartialEq for CustomExitKind {
+    fn eq(&self, other: &Self) -> bool {
+        if Rc::ptr_eq(&self.payload, &other.payload) {
+            return true;
+        }
+        if Any::type_id(self.payload.as_any()) != Any::type_id(other.payload.as_any()) {
+            return false;
+        }
+        match (
+            postcard::to_allocvec(&Wrap(self.payload.as_ref())),
+            postcard::to_allocvec(&Wrap(other.payload.as_ref())),
+        ) {
+            (Ok(bytes), Ok(other_bytes)) => bytes == other_bytes,
+            _ => false,
+        }
+    }
 }
 
+impl Eq for CustomExitKind {}
+
 /// How one of the diffing executions finished.
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 pub enum DiffExitKind {
@@ -85,20 +207,27 @@ pub enum DiffExitKind {
     Timeout,
     /// One of the executors itelf repots a differential, we can't go into further details.
     Diff,
-    // The run resulted in a custom `ExitKind`.
-    // Custom(Box<dyn SerdeAny>),
+    /// The run resulted in a custom `ExitKind`, without its payload.
+    Custom,
 }
 
 crate::impl_serdeany!(ExitKind);
 
 impl From<ExitKind> for DiffExitKind {
     fn from(exitkind: ExitKind) -> Self {
+        DiffExitKind::from(&exitkind)
+    }
+}
+
+impl From<&ExitKind> for DiffExitKind {
+    fn from(exitkind: &ExitKind) -> Self {
         match exitkind {
             ExitKind::Ok => DiffExitKind::Ok,
             ExitKind::Crash => DiffExitKind::Crash,
             ExitKind::Oom => DiffExitKind::Oom,
             ExitKind::Timeout => DiffExitKind::Timeout,
             ExitKind::Diff { .. } => DiffExitKind::Diff,
+            ExitKind::Custom(_) => DiffExitKind::Custom,
         }
     }
 }
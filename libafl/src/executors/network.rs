@@ -0,0 +1,1212 @@
+//! The [`NetworkExecutor`] fuzzes network services.
+//!
+//! For each run, it makes sure the server runs and listens, sends the input over a TCP, UDP or Unix socket,
+//! either as one message, or the fields of a [`crate::inputs::MultiInput`] as successive messages,
+//! and collects the responses to each message into a [`ResponsesObserver`], or their codes into a [`ResponseCodeObserver`].
+//! The session ends once all messages got their responses, or as soon as the server closes the connection.
+//! A server that dies by a signal is a crash, a session that takes longer than the timeout is a hang.
+//! The server is killed after each session, unless it should be kept running.
+//!
+//! A [`ServerLauncher`] starts the server: the [`ServerCommand`] spawns a new process each time,
+//! the [`ForkserverLauncher`] lets the forkserver of an `AFL`-instrumented server fork it,
+//! such that the coverage map of a [`crate::executors::ForkserverExecutor`] works as usual:
+//! [`NetworkExecutorBuilder::build_with_forkserver`] moves its observers into the [`NetworkExecutor`].
+
+use core::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    time::Duration,
+};
+use std::{
+    ffi::{OsStr, OsString},
+    fs,
+    io::{self, ErrorKind, Read, Write},
+    net::{Shutdown, SocketAddr, TcpStream, UdpSocket},
+    os::unix::{net::UnixStream, process::ExitStatusExt},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    thread,
+    time::Instant,
+};
+
+use alloc::{
+    borrow::ToOwned,
+    string::{String, ToString},
+    vec::Vec,
+};
+use wait_timeout::ChildExt;
+
+#[cfg(feature = "fork")]
+use nix::{
+    sys::{
+        signal::{kill, Signal},
+        time::{TimeSpec, TimeValLike},
+    },
+    unistd::Pid,
+};
+
+use crate::{
+    bolts::{ownedref::OwnedSlice, AsSlice},
+    executors::{Executor, ExitKind, HasObservers},
+    inputs::{AsMultiBytes, HasTargetBytes, Input},
+    observers::{ObserversTuple, ResponseCodeObserver, ResponsesObserver},
+    Error,
+};
+#[cfg(feature = "fork")]
+use crate::{
+    bolts::{shmem::ShMemProvider, tuples::Merge},
+    executors::{
+        forkserver::{HasForkserver, InputDelivery},
+        ForkserverExecutor,
+    },
+};
+
+/// How long to wait between two attempts to reach a starting server
+const STARTUP_POLL_INTERVAL: Duration = Duration::from_millis(1);
+/// The maximum size of a single response read
+const RECV_BUF_SIZE: usize = 64 * 1024;
+
+/// Where the server listens
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetworkEndpoint {
+    /// A TCP port
+    Tcp(SocketAddr),
+    /// A UDP port, each message is a datagram
+    Udp(SocketAddr),
+    /// A Unix stream socket
+    Unix(PathBuf),
+}
+
+/// How the server exited
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerExit {
+    /// The server exited with this code
+    Exited(i32),
+    /// A signal killed the server
+    Signaled(i32),
+}
+
+impl ServerExit {
+    /// Converts a wait status, as returned by `waitpid`
+    #[must_use]
+    pub fn from_wait_status(status: i32) -> Self {
+        if libc::WIFSIGNALED(status) {
+            Self::Signaled(libc::WTERMSIG(status))
+        } else {
+            Self::Exited(libc::WEXITSTATUS(status))
+        }
+    }
+
+    /// The [`ExitKind`] of a run that ended the server like this
+    #[must_use]
+    pub fn exit_kind(&self) -> ExitKind {
+        match self {
+            // Signal exit codes
+            Self::Signaled(_) => ExitKind::Crash,
+            Self::Exited(code) if *code > 128 && *code < 160 => ExitKind::Crash,
+            Self::Exited(_) => ExitKind::Ok,
+        }
+    }
+}
+
+/// Starts and stops the server of a [`NetworkExecutor`]
+pub trait ServerLauncher: Debug {
+    /// Starts the server, if it is not running yet.
+    /// Returns if it was started.
+    fn launch(&mut self) -> Result<bool, Error>;
+
+    /// Waits up to `timeout` for the server to exit.
+    /// Returns how it exited, or `None` if it still runs, or was not running at all.
+    fn wait_exit(&mut self, timeout: Duration) -> Result<Option<ServerExit>, Error>;
+
+    /// Kills the server, if it runs
+    fn kill(&mut self) -> Result<(), Error>;
+}
+
+/// Spawns the server as a new process
+#[derive(Debug)]
+pub struct ServerCommand {
+    command: Command,
+    child: Option<Child>,
+}
+
+impl ServerCommand {
+    /// Creates a new [`ServerCommand`], spawning `command`
+    #[must_use]
+    pub fn new(command: Command) -> Self {
+        Self {
+            command,
+            child: None,
+        }
+    }
+
+    /// The id of the server process, if it runs
+    #[must_use]
+    pub fn pid(&self) -> Option<u32> {
+        self.child.as_ref().map(Child::id)
+    }
+}
+
+impl ServerLauncher for ServerCommand {
+    fn launch(&mut self) -> Result<bool, Error> {
+        if self.child.is_some() {
+            return Ok(false);
+        }
+        self.child = Some(self.command.spawn()?);
+        Ok(true)
+    }
+
+    fn wait_exit(&mut self, timeout: Duration) -> Result<Option<ServerExit>, Error> {
+        let status = match self.child.as_mut() {
+            Some(child) => child.wait_timeout(timeout)?,
+            None => return Ok(None),
+        };
+        Ok(status.map(|status| {
+            self.child = None;
+            match status.signal() {
+                Some(signal) => ServerExit::Signaled(signal),
+                None => ServerExit::Exited(status.code().unwrap_or_default()),
+            }
+        }))
+    }
+
+    fn kill(&mut self) -> Result<(), Error> {
+        if let Some(mut child) = self.child.take() {
+            // The server may have exited meanwhile
+            drop(child.kill());
+            child.wait()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ServerCommand {
+    fn drop(&mut self) {
+        drop(self.kill());
+    }
+}
+
+/// Lets the forkserver of an executor fork the server, see [`crate::executors::ForkserverExecutor`].
+/// The input delivery and the observers of the executor stay unused,
+/// [`NetworkExecutorBuilder::build_with_forkserver`] moves the observers into the [`NetworkExecutor`].
+#[cfg(feature = "fork")]
+#[derive(Debug)]
+pub struct ForkserverLauncher<E> {
+    executor: E,
+    running: bool,
+}
+
+#[cfg(feature = "fork")]
+impl<E> ForkserverLauncher<E>
+where
+    E: HasForkserver + Debug,
+{
+    /// Creates a new [`ForkserverLauncher`] for the forkserver of `executor`
+    #[must_use]
+    pub fn new(executor: E) -> Self {
+        Self {
+            executor,
+            running: false,
+        }
+    }
+
+    /// The executor with the forkserver
+    #[must_use]
+    pub fn executor(&self) -> &E {
+        &self.executor
+    }
+}
+
+#[cfg(feature = "fork")]
+impl<E> ServerLauncher for ForkserverLauncher<E>
+where
+    E: HasForkserver + Debug,
+{
+    fn launch(&mut self) -> Result<bool, Error> {
+        if self.running {
+            return Ok(false);
+        }
+        let forkserver = self.executor.forkserver_mut();
+        let send_len = forkserver.write_ctl(forkserver.last_run_timed_out())?;
+        forkserver.set_last_run_timed_out(0);
+        if send_len != 4 {
+            return Err(Error::illegal_state(
+                "Unable to request new process from fork server (OOM?)",
+            ));
+        }
+        let (recv_pid_len, pid) = forkserver.read_st()?;
+        if recv_pid_len != 4 || pid <= 0 {
+            return Err(Error::illegal_state("Fork server is misbehaving (OOM?)"));
+        }
+        forkserver.set_child_pid(Pid::from_raw(pid));
+        self.running = true;
+        Ok(true)
+    }
+
+    fn wait_exit(&mut self, timeout: Duration) -> Result<Option<ServerExit>, Error> {
+        if !self.running {
+            return Ok(None);
+        }
+        let timeout = TimeSpec::milliseconds(timeout.as_millis() as i64);
+        let forkserver = self.executor.forkserver_mut();
+        Ok(forkserver.read_st_timed(&timeout)?.map(|status| {
+            forkserver.set_status(status);
+            forkserver.set_child_pid(Pid::from_raw(0));
+            self.running = false;
+            ServerExit::from_wait_status(status)
+        }))
+    }
+
+    fn kill(&mut self) -> Result<(), Error> {
+        if !self.running {
+            return Ok(());
+        }
+        let forkserver = self.executor.forkserver_mut();
+        let _ = kill(forkserver.child_pid(), Signal::SIGKILL);
+        let (recv_status_len, status) = forkserver.read_st()?;
+        if recv_status_len != 4 {
+            return Err(Error::illegal_state("Could not kill the server"));
+        }
+        forkserver.set_status(status);
+        forkserver.set_child_pid(Pid::from_raw(0));
+        self.running = false;
+        Ok(())
+    }
+}
+
+/// Turns an input into the messages of a session
+pub trait Packetizer<I>: Debug {
+    /// The messages to send, in order
+    fn packets<'a>(&self, input: &'a I) -> Vec<OwnedSlice<'a, u8>>;
+}
+
+/// Sends the whole input as a single message
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SinglePacket;
+
+impl<I> Packetizer<I> for SinglePacket
+where
+    I: HasTargetBytes,
+{
+    fn packets<'a>(&self, input: &'a I) -> Vec<OwnedSlice<'a, u8>> {
+        vec![input.target_bytes()]
+    }
+}
+
+/// Sends each field of a multi-field input as a message of its own
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MultiPacket;
+
+impl<I> Packetizer<I> for MultiPacket
+where
+    I: AsMultiBytes,
+{
+    fn packets<'a>(&self, input: &'a I) -> Vec<OwnedSlice<'a, u8>> {
+        input.as_multi_ownd_bytes()
+    }
+}
+
+/// A connection to the server
+#[derive(Debug)]
+enum Connection {
+    Tcp(TcpStream),
+    Udp(UdpSocket),
+    Unix(UnixStream),
+}
+
+impl Connection {
+    /// Connects to a listening server, or returns `None` if nothing listens yet
+    fn connect(endpoint: &NetworkEndpoint, timeout: Duration) -> Result<Option<Self>, Error> {
+        let connection = match endpoint {
+            NetworkEndpoint::Tcp(addr) => TcpStream::connect_timeout(addr, timeout).map(|stream| {
+                drop(stream.set_nodelay(true));
+                Self::Tcp(stream)
+            }),
+            NetworkEndpoint::Udp(addr) => {
+                let local: SocketAddr = if addr.is_ipv4() {
+                    ([0, 0, 0, 0], 0).into()
+                } else {
+                    ([0_u16; 8], 0).into()
+                };
+                UdpSocket::bind(local)
+                    .and_then(|socket| socket.connect(addr).map(|()| Self::Udp(socket)))
+            }
+            NetworkEndpoint::Unix(path) => UnixStream::connect(path).map(Self::Unix),
+        };
+        match connection {
+            Ok(connection) => Ok(Some(connection)),
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    ErrorKind::ConnectionRefused | ErrorKind::NotFound | ErrorKind::TimedOut
+                ) =>
+            {
+                Ok(None)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn send(&mut self, message: &[u8]) -> io::Result<()> {
+        match self {
+            Self::Tcp(stream) => stream.write_all(message),
+            Self::Udp(socket) => socket.send(message).map(drop),
+            Self::Unix(stream) => stream.write_all(message),
+        }
+    }
+
+    /// Receives up to `buf.len()` bytes, or `None` if nothing arrives within `timeout`
+    fn recv(&mut self, buf: &mut [u8], timeout: Duration) -> io::Result<Option<usize>> {
+        let received = match self {
+            Self::Tcp(stream) => stream
+                .set_read_timeout(Some(timeout))
+                .and_then(|()| stream.read(buf)),
+            Self::Udp(socket) => socket
+                .set_read_timeout(Some(timeout))
+                .and_then(|()| socket.recv(buf)),
+            Self::Unix(stream) => stream
+                .set_read_timeout(Some(timeout))
+                .and_then(|()| stream.read(buf)),
+        };
+        match received {
+            Ok(len) => Ok(Some(len)),
+            Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// If an empty read means the server closed the connection
+    fn is_stream(&self) -> bool {
+        !matches!(self, Self::Udp(_))
+    }
+
+    fn close(self) {
+        match self {
+            Self::Tcp(stream) => drop(stream.shutdown(Shutdown::Both)),
+            Self::Unix(stream) => drop(stream.shutdown(Shutdown::Both)),
+            Self::Udp(_) => (),
+        }
+    }
+}
+
+/// How a session went
+#[derive(Debug, Default)]
+struct Session {
+    responses: Vec<Vec<u8>>,
+    closed: bool,
+    timed_out: bool,
+}
+
+/// The [`NetworkExecutor`] sends the inputs to a server over a socket, see the [module documentation](self).
+pub struct NetworkExecutor<L, I, OT, S, P> {
+    launcher: L,
+    endpoint: NetworkEndpoint,
+    packetizer: P,
+    observers: OT,
+    responses_observer_name: Option<String>,
+    response_codes_observer_name: Option<String>,
+    startup_timeout: Duration,
+    response_timeout: Duration,
+    timeout: Duration,
+    keep_server: bool,
+    require_response: bool,
+    phantom: PhantomData<(I, S)>,
+}
+
+impl<L, I, OT, S, P> Debug for NetworkExecutor<L, I, OT, S, P>
+where
+    L: Debug,
+    OT: Debug,
+    P: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NetworkExecutor")
+            .field("launcher", &self.launcher)
+            .field("endpoint", &self.endpoint)
+            .field("packetizer", &self.packetizer)
+            .field("observers", &self.observers)
+            .field("responses_observer_name", &self.responses_observer_name)
+            .field(
+                "response_codes_observer_name",
+                &self.response_codes_observer_name,
+            )
+            .field("startup_timeout", &self.startup_timeout)
+            .field("response_timeout", &self.response_timeout)
+            .field("timeout", &self.timeout)
+            .field("keep_server", &self.keep_server)
+            .field("require_response", &self.require_response)
+            .finish()
+    }
+}
+
+impl NetworkExecutor<ServerCommand, (), (), (), SinglePacket> {
+    /// Creates a builder for a new [`NetworkExecutor`]
+    #[must_use]
+    pub fn builder() -> NetworkExecutorBuilder {
+        NetworkExecutorBuilder::new()
+    }
+}
+
+impl<L, I, OT, S, P> NetworkExecutor<L, I, OT, S, P>
+where
+    L: ServerLauncher,
+    P: Packetizer<I>,
+{
+    /// The launcher of the server
+    #[must_use]
+    pub fn launcher(&self) -> &L {
+        &self.launcher
+    }
+
+    /// The launcher of the server, mutable
+    pub fn launcher_mut(&mut self) -> &mut L {
+        &mut self.launcher
+    }
+
+    /// Where the server listens
+    #[must_use]
+    pub fn endpoint(&self) -> &NetworkEndpoint {
+        &self.endpoint
+    }
+
+    /// Starts the server if needed, and connects to it once it listens
+    fn connect(&mut self) -> Result<Connection, Error> {
+        let started = self.launcher.launch()?;
+        let deadline = Instant::now() + self.startup_timeout;
+        loop {
+            let ready = match &self.endpoint {
+                // Nothing answers a UDP connect, look for the bound port instead
+                NetworkEndpoint::Udp(addr) if started => udp_port_bound(addr.port()),
+                _ => true,
+            };
+            if ready {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if let Some(connection) =
+                    Connection::connect(&self.endpoint, remaining.max(STARTUP_POLL_INTERVAL))?
+                {
+                    return Ok(connection);
+                }
+            }
+            if let Some(exit) = self.launcher.wait_exit(Duration::ZERO)? {
+                return Err(Error::illegal_state(format!(
+                    "The server exited before listening on {:?}: {:?}",
+                    self.endpoint, exit
+                )));
+            }
+            if Instant::now() >= deadline {
+                self.launcher.kill()?;
+                return Err(Error::illegal_state(format!(
+                    "The server does not listen on {:?}",
+                    self.endpoint
+                )));
+            }
+            thread::sleep(STARTUP_POLL_INTERVAL);
+        }
+    }
+
+    /// Sends the messages, collecting the responses, until the server closes the connection
+    fn session(&self, connection: &mut Connection, packets: &[OwnedSlice<u8>]) -> Session {
+        let deadline = Instant::now() + self.timeout;
+        let mut session = Session::default();
+        let mut buf = vec![0_u8; RECV_BUF_SIZE];
+        for packet in packets {
+            if Instant::now() >= deadline {
+                session.timed_out = true;
+                break;
+            }
+            if connection.send(packet.as_slice()).is_err() {
+                session.closed = true;
+                break;
+            }
+            let mut response = vec![];
+            loop {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    session.timed_out = true;
+                    break;
+                }
+                // A required response may take until the end of the session, later data only until idle
+                let idle = if response.is_empty() && self.require_response {
+                    remaining
+                } else {
+                    self.response_timeout.min(remaining)
+                };
+                match connection.recv(&mut buf, idle) {
+                    Ok(Some(0)) if connection.is_stream() => {
+                        session.closed = true;
+                        break;
+                    }
+                    Ok(Some(len)) => response.extend_from_slice(&buf[..len]),
+                    Ok(None) if response.is_empty() && self.require_response => (),
+                    Ok(None) => break,
+                    Err(_) => {
+                        session.closed = true;
+                        break;
+                    }
+                }
+            }
+            session.responses.push(response);
+            if session.closed || session.timed_out {
+                break;
+            }
+        }
+        session
+    }
+}
+
+impl<EM, L, I, OT, S, P, Z> Executor<EM, I, S, Z> for NetworkExecutor<L, I, OT, S, P>
+where
+    L: ServerLauncher,
+    I: Input,
+    OT: ObserversTuple<I, S>,
+    P: Packetizer<I>,
+{
+    fn run_target(
+        &mut self,
+        _fuzzer: &mut Z,
+        _state: &mut S,
+        _mgr: &mut EM,
+        input: &I,
+    ) -> Result<ExitKind, Error> {
+        let mut connection = self.connect()?;
+        let packets = self.packetizer.packets(input);
+        let session = self.session(&mut connection, &packets);
+        connection.close();
+
+        // A server that crashed on the last message may take a moment to go down
+        let grace = if session.closed {
+            self.response_timeout
+        } else {
+            Duration::ZERO
+        };
+        let exit_kind = match self.launcher.wait_exit(grace)? {
+            Some(exit) => exit.exit_kind(),
+            None if session.timed_out => {
+                self.launcher.kill()?;
+                ExitKind::Timeout
+            }
+            None => {
+                if !self.keep_server {
+                    self.launcher.kill()?;
+                }
+                ExitKind::Ok
+            }
+        };
+
+        if let Some(name) = &self.response_codes_observer_name {
+            self.observers
+                .match_name_mut::<ResponseCodeObserver>(name)
+                .unwrap()
+                .observe(&session.responses);
+        }
+        if let Some(name) = &self.responses_observer_name {
+            let observer = self
+                .observers
+                .match_name_mut::<ResponsesObserver>(name)
+                .unwrap();
+            observer.responses = session.responses;
+            observer.closed = session.closed;
+        }
+        Ok(exit_kind)
+    }
+}
+
+impl<L, I, OT, S, P> HasObservers<I, OT, S> for NetworkExecutor<L, I, OT, S, P>
+where
+    L: ServerLauncher,
+    I: Input,
+    OT: ObserversTuple<I, S>,
+    P: Packetizer<I>,
+{
+    #[inline]
+    fn observers(&self) -> &OT {
+        &self.observers
+    }
+
+    #[inline]
+    fn observers_mut(&mut self) -> &mut OT {
+        &mut self.observers
+    }
+}
+
+/// The builder for a [`NetworkExecutor`]
+#[derive(Debug, Clone)]
+pub struct NetworkExecutorBuilder {
+    program: Option<OsString>,
+    arguments: Vec<OsString>,
+    envs: Vec<(OsString, OsString)>,
+    debug_child: bool,
+    endpoint: Option<NetworkEndpoint>,
+    responses_observer_name: Option<String>,
+    response_codes_observer_name: Option<String>,
+    startup_timeout: Duration,
+    response_timeout: Duration,
+    timeout: Duration,
+    keep_server: bool,
+    require_response: bool,
+}
+
+impl Default for NetworkExecutorBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NetworkExecutorBuilder {
+    /// Creates a new [`NetworkExecutorBuilder`].
+    /// By default, the server gets 5 seconds to start listening, and a session 1 second,
+    /// a response is complete after 10 milliseconds without new data.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            program: None,
+            arguments: vec![],
+            envs: vec![],
+            debug_child: false,
+            endpoint: None,
+            responses_observer_name: None,
+            response_codes_observer_name: None,
+            startup_timeout: Duration::from_secs(5),
+            response_timeout: Duration::from_millis(10),
+            timeout: Duration::from_secs(1),
+            keep_server: false,
+            require_response: false,
+        }
+    }
+
+    /// The server binary, unless it is launched by [`NetworkExecutorBuilder::build_with_launcher`]
+    #[must_use]
+    pub fn program<O>(mut self, program: O) -> Self
+    where
+        O: AsRef<OsStr>,
+    {
+        self.program = Some(program.as_ref().to_owned());
+        self
+    }
+
+    /// Adds an argument of the server
+    #[must_use]
+    pub fn arg<O>(mut self, arg: O) -> Self
+    where
+        O: AsRef<OsStr>,
+    {
+        self.arguments.push(arg.as_ref().to_owned());
+        self
+    }
+
+    /// Adds arguments of the server
+    #[must_use]
+    pub fn args<IT, O>(mut self, args: IT) -> Self
+    where
+        IT: IntoIterator<Item = O>,
+        O: AsRef<OsStr>,
+    {
+        for arg in args {
+            self = self.arg(arg);
+        }
+        self
+    }
+
+    /// Adds an environment variable of the server
+    #[must_use]
+    pub fn env<K, V>(mut self, key: K, val: V) -> Self
+    where
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        self.envs
+            .push((key.as_ref().to_owned(), val.as_ref().to_owned()));
+        self
+    }
+
+    /// If set, the server prints to `stdout`/`stderr`
+    #[must_use]
+    pub fn debug_child(mut self, debug_child: bool) -> Self {
+        self.debug_child = debug_child;
+        self
+    }
+
+    /// Where the server listens. This option is required.
+    #[must_use]
+    pub fn endpoint(mut self, endpoint: NetworkEndpoint) -> Self {
+        self.endpoint = Some(endpoint);
+        self
+    }
+
+    /// The server listens on this TCP address
+    #[must_use]
+    pub fn tcp(self, addr: SocketAddr) -> Self {
+        self.endpoint(NetworkEndpoint::Tcp(addr))
+    }
+
+    /// The server listens on this UDP address
+    #[must_use]
+    pub fn udp(self, addr: SocketAddr) -> Self {
+        self.endpoint(NetworkEndpoint::Udp(addr))
+    }
+
+    /// The server listens on this Unix socket
+    #[must_use]
+    pub fn unix<P: AsRef<Path>>(self, path: P) -> Self {
+        self.endpoint(NetworkEndpoint::Unix(path.as_ref().to_path_buf()))
+    }
+
+    /// Collects the responses into the [`ResponsesObserver`] with this name
+    #[must_use]
+    pub fn responses_observer(mut self, name: &str) -> Self {
+        self.responses_observer_name = Some(name.to_string());
+        self
+    }
+
+    /// Extracts the codes of the responses into the [`ResponseCodeObserver`] with this name
+    #[must_use]
+    pub fn response_codes_observer(mut self, name: &str) -> Self {
+        self.response_codes_observer_name = Some(name.to_string());
+        self
+    }
+
+    /// How long the server may take to listen
+    #[must_use]
+    pub fn startup_timeout(mut self, timeout: Duration) -> Self {
+        self.startup_timeout = timeout;
+        self
+    }
+
+    /// After how long without new data a response is complete
+    #[must_use]
+    pub fn response_timeout(mut self, timeout: Duration) -> Self {
+        self.response_timeout = timeout;
+        self
+    }
+
+    /// How long a session may take before it is a hang
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Keeps the server running across sessions, until it exits or hangs
+    #[must_use]
+    pub fn keep_server(mut self, keep_server: bool) -> Self {
+        self.keep_server = keep_server;
+        self
+    }
+
+    /// Each message must get a response: a server that stays silent until the session timeout hangs.
+    /// Otherwise, the response to a message may be empty.
+    #[must_use]
+    pub fn require_response(mut self, require_response: bool) -> Self {
+        self.require_response = require_response;
+        self
+    }
+
+    /// Builds the [`NetworkExecutor`], spawning the `program` and sending the input as a single message
+    pub fn build<I, OT, S>(
+        &self,
+        observers: OT,
+    ) -> Result<NetworkExecutor<ServerCommand, I, OT, S, SinglePacket>, Error>
+    where
+        I: Input + HasTargetBytes,
+        OT: ObserversTuple<I, S>,
+    {
+        self.build_with_launcher(self.server_command()?, SinglePacket, observers)
+    }
+
+    /// Builds the [`NetworkExecutor`], spawning the `program` and sending each field of the input as a message
+    pub fn build_multi<I, OT, S>(
+        &self,
+        observers: OT,
+    ) -> Result<NetworkExecutor<ServerCommand, I, OT, S, MultiPacket>, Error>
+    where
+        I: Input + AsMultiBytes,
+        OT: ObserversTuple<I, S>,
+    {
+        self.build_with_launcher(self.server_command()?, MultiPacket, observers)
+    }
+
+    /// Builds the [`NetworkExecutor`] with any [`ServerLauncher`] and [`Packetizer`]
+    pub fn build_with_launcher<L, I, OT, S, P>(
+        &self,
+        launcher: L,
+        packetizer: P,
+        observers: OT,
+    ) -> Result<NetworkExecutor<L, I, OT, S, P>, Error>
+    where
+        L: ServerLauncher,
+        I: Input,
+        OT: ObserversTuple<I, S>,
+        P: Packetizer<I>,
+    {
+        let endpoint = self.endpoint.clone().ok_or_else(|| {
+            Error::illegal_argument("NetworkExecutorBuilder::build: no endpoint set")
+        })?;
+        if let Some(name) = &self.responses_observer_name {
+            if observers.match_name::<ResponsesObserver>(name).is_none() {
+                return Err(Error::key_not_found(format!(
+                    "No ResponsesObserver named {}",
+                    name
+                )));
+            }
+        }
+        if let Some(name) = &self.response_codes_observer_name {
+            if observers.match_name::<ResponseCodeObserver>(name).is_none() {
+                return Err(Error::key_not_found(format!(
+                    "No ResponseCodeObserver named {}",
+                    name
+                )));
+            }
+        }
+        Ok(NetworkExecutor {
+            launcher,
+            endpoint,
+            packetizer,
+            observers,
+            responses_observer_name: self.responses_observer_name.clone(),
+            response_codes_observer_name: self.response_codes_observer_name.clone(),
+            startup_timeout: self.startup_timeout,
+            response_timeout: self.response_timeout,
+            timeout: self.timeout,
+            keep_server: self.keep_server,
+            require_response: self.require_response,
+            phantom: PhantomData,
+        })
+    }
+
+    /// Builds the [`NetworkExecutor`], letting the forkserver of `executor` fork the server, see [`ForkserverLauncher`].
+    /// The observers of `executor`, like its coverage map observer, come first in the observers of the [`NetworkExecutor`].
+    #[cfg(feature = "fork")]
+    #[allow(clippy::type_complexity)]
+    pub fn build_with_forkserver<I, FOT, OT, S, SP, D, P>(
+        &self,
+        executor: ForkserverExecutor<I, FOT, S, SP, D>,
+        packetizer: P,
+        observers: OT,
+    ) -> Result<
+        NetworkExecutor<
+            ForkserverLauncher<ForkserverExecutor<I, (), S, SP, D>>,
+            I,
+            FOT::MergeResult,
+            S,
+            P,
+        >,
+        Error,
+    >
+    where
+        I: Input,
+        FOT: ObserversTuple<I, S> + Merge<OT>,
+        FOT::MergeResult: ObserversTuple<I, S>,
+        OT: ObserversTuple<I, S>,
+        SP: ShMemProvider,
+        D: InputDelivery<I>,
+        P: Packetizer<I>,
+    {
+        let (executor, executor_observers) = executor.replace_observers(());
+        self.build_with_launcher(
+            ForkserverLauncher::new(executor),
+            packetizer,
+            executor_observers.merge(observers),
+        )
+    }
+
+    fn server_command(&self) -> Result<ServerCommand, Error> {
+        let program = self.program.as_ref().ok_or_else(|| {
+            Error::illegal_argument("NetworkExecutorBuilder::build: no program set")
+        })?;
+        let mut command = Command::new(program);
+        command
+            .args(&self.arguments)
+            .envs(
+                self.envs
+                    .iter()
+                    .map(|(k, v)| (k.as_os_str(), v.as_os_str())),
+            )
+            .stdin(Stdio::null());
+        if !self.debug_child {
+            command.stdout(Stdio::null()).stderr(Stdio::null());
+        }
+        Ok(ServerCommand::new(command))
+    }
+}
+
+/// If a UDP socket is bound to `port`, according to `/proc/net/udp`.
+/// Elsewhere, we can only assume it is.
+fn udp_port_bound(port: u16) -> bool {
+    if !Path::new("/proc/net/udp").exists() {
+        return true;
+    }
+    let port = format!(":{:04X}", port);
+    ["/proc/net/udp", "/proc/net/udp6"].iter().any(|table| {
+        fs::read_to_string(table).map_or(false, |table| {
+            table.lines().skip(1).any(|line| {
+                line.split_whitespace()
+                    .nth(1)
+                    .map_or(false, |local| local.ends_with(&port))
+            })
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use core::time::Duration;
+    use serial_test::serial;
+    use std::{
+        io::{Read, Write},
+        net::{SocketAddr, TcpListener, UdpSocket},
+        os::unix::net::UnixListener,
+    };
+
+    use crate::{
+        bolts::tuples::tuple_list,
+        executors::{
+            network::{MultiPacket, NetworkEndpoint, ServerExit, ServerLauncher},
+            Executor, ExitKind, HasObservers, NetworkExecutor,
+        },
+        inputs::{BytesInput, MultiInput},
+        observers::ResponsesObserver,
+        Error,
+    };
+
+    /// Answers a message, `None` closes the session
+    fn answer(message: &[u8]) -> Option<Vec<u8>> {
+        match message {
+            b"crash" => unsafe {
+                libc::signal(libc::SIGABRT, libc::SIG_DFL);
+                libc::abort()
+            },
+            b"hang" => loop {
+                std::thread::sleep(Duration::from_secs(1));
+            },
+            b"bye" => None,
+            _ => Some([b"echo:", message].concat()),
+        }
+    }
+
+    /// A toy server in a forked process
+    #[derive(Debug)]
+    struct ToyServer {
+        endpoint: NetworkEndpoint,
+        pid: Option<libc::pid_t>,
+    }
+
+    impl ToyServer {
+        fn serve(&self) {
+            let mut buf = [0_u8; 64];
+            match &self.endpoint {
+                NetworkEndpoint::Tcp(addr) => {
+                    let listener = TcpListener::bind(addr).unwrap();
+                    for stream in listener.incoming() {
+                        let mut stream = stream.unwrap();
+                        while let Ok(len) = stream.read(&mut buf) {
+                            match answer(&buf[..len]) {
+                                Some(response) if len > 0 => stream.write_all(&response).unwrap(),
+                                _ => break,
+                            }
+                        }
+                    }
+                }
+                NetworkEndpoint::Unix(path) => {
+                    let listener = UnixListener::bind(path).unwrap();
+                    for stream in listener.incoming() {
+                        let mut stream = stream.unwrap();
+                        while let Ok(len) = stream.read(&mut buf) {
+                            match answer(&buf[..len]) {
+                                Some(response) if len > 0 => stream.write_all(&response).unwrap(),
+                                _ => break,
+                            }
+                        }
+                    }
+                }
+                NetworkEndpoint::Udp(addr) => {
+                    let socket = UdpSocket::bind(addr).unwrap();
+                    while let Ok((len, peer)) = socket.recv_from(&mut buf) {
+                        if let Some(response) = answer(&buf[..len]) {
+                            socket.send_to(&response, peer).unwrap();
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    impl ServerLauncher for ToyServer {
+        fn launch(&mut self) -> Result<bool, Error> {
+            if self.pid.is_some() {
+                return Ok(false);
+            }
+            if let NetworkEndpoint::Unix(path) = &self.endpoint {
+                drop(std::fs::remove_file(path));
+            }
+            match unsafe { libc::fork() } {
+                0 => {
+                    self.serve();
+                    unsafe { libc::_exit(0) };
+                }
+                pid => self.pid = Some(pid),
+            }
+            Ok(true)
+        }
+
+        fn wait_exit(&mut self, timeout: Duration) -> Result<Option<ServerExit>, Error> {
+            let pid = match self.pid {
+                Some(pid) => pid,
+                None => return Ok(None),
+            };
+            let start = std::time::Instant::now();
+            loop {
+                let mut status = 0;
+                if unsafe { libc::waitpid(pid, &mut status, libc::WNOHANG) } == pid {
+                    self.pid = None;
+                    return Ok(Some(ServerExit::from_wait_status(status)));
+                }
+                if start.elapsed() >= timeout {
+                    return Ok(None);
+                }
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        }
+
+        fn kill(&mut self) -> Result<(), Error> {
+            if let Some(pid) = self.pid.take() {
+                unsafe {
+                    libc::kill(pid, libc::SIGKILL);
+                    libc::waitpid(pid, core::ptr::null_mut(), 0);
+                }
+            }
+            Ok(())
+        }
+    }
+
+    fn free_port() -> u16 {
+        // The port is free again once the socket drops, another process taking it meanwhile is unlikely
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.local_addr().unwrap().port()
+    }
+
+    fn run_sessions(endpoint: NetworkEndpoint) {
+        let launcher = ToyServer {
+            endpoint: endpoint.clone(),
+            pid: None,
+        };
+        let mut executor = NetworkExecutor::builder()
+            .endpoint(endpoint)
+            .responses_observer("responses")
+            .require_response(true)
+            .timeout(Duration::from_millis(300))
+            .build_with_launcher::<_, MultiInput, _, (), _>(
+                launcher,
+                MultiPacket,
+                tuple_list!(ResponsesObserver::new("responses")),
+            )
+            .unwrap();
+
+        let mut run = |fields: &[&[u8]]| {
+            let input = MultiInput::new(
+                fields
+                    .iter()
+                    .map(|field| BytesInput::new(field.to_vec()))
+                    .collect(),
+            );
+            let exit_kind = executor
+                .run_target(&mut (), &mut (), &mut (), &input)
+                .unwrap();
+            let observer = &executor.observers().0;
+            (exit_kind, observer.responses.clone(), observer.closed)
+        };
+
+        let (exit_kind, responses, closed) = run(&[b"hello", b"world"]);
+        assert_eq!(exit_kind, ExitKind::Ok);
+        assert_eq!(
+            responses,
+            vec![b"echo:hello".to_vec(), b"echo:world".to_vec()]
+        );
+        assert!(!closed);
+
+        let (exit_kind, _, _) = run(&[b"hello", b"crash", b"world"]);
+        assert_eq!(exit_kind, ExitKind::Crash);
+
+        let (exit_kind, _, _) = run(&[b"hang"]);
+        assert_eq!(exit_kind, ExitKind::Timeout);
+
+        let (exit_kind, responses, _) = run(&[b"again"]);
+        assert_eq!(exit_kind, ExitKind::Ok);
+        assert_eq!(responses, vec![b"echo:again".to_vec()]);
+    }
+
+    #[test]
+    #[serial]
+    fn test_network_tcp() {
+        let addr: SocketAddr = ([127, 0, 0, 1], free_port()).into();
+        run_sessions(NetworkEndpoint::Tcp(addr));
+    }
+
+    #[test]
+    #[serial]
+    fn test_network_udp() {
+        let addr: SocketAddr = ([127, 0, 0, 1], free_port()).into();
+        run_sessions(NetworkEndpoint::Udp(addr));
+    }
+
+    #[test]
+    #[serial]
+    fn test_network_unix() {
+        let path = std::env::temp_dir().join(format!("libafl_toy_{}.sock", std::process::id()));
+        run_sessions(NetworkEndpoint::Unix(path.clone()));
+        drop(std::fs::remove_file(path));
+    }
+
+    #[test]
+    #[serial]
+    fn test_network_session_end() {
+        let addr: SocketAddr = ([127, 0, 0, 1], free_port()).into();
+        let launcher = ToyServer {
+            endpoint: NetworkEndpoint::Tcp(addr),
+            pid: None,
+        };
+        let mut executor = NetworkExecutor::builder()
+            .tcp(addr)
+            .responses_observer("responses")
+            .keep_server(true)
+            .build_with_launcher::<_, MultiInput, _, (), _>(
+                launcher,
+                MultiPacket,
+                tuple_list!(ResponsesObserver::new("responses")),
+            )
+            .unwrap();
+        let input = MultiInput::new(vec![
+            BytesInput::new(b"hi".to_vec()),
+            BytesInput::new(b"bye".to_vec()),
+            BytesInput::new(b"unsent".to_vec()),
+        ]);
+        for _ in 0..2 {
+            let exit_kind = executor
+                .run_target(&mut (), &mut (), &mut (), &input)
+                .unwrap();
+            assert_eq!(exit_kind, ExitKind::Ok);
+            let observer = &executor.observers().0;
+            assert!(observer.closed);
+            assert_eq!(observer.responses, vec![b"echo:hi".to_vec(), vec![]]);
+        }
+        // The server kept running across the sessions
+        assert!(executor.launcher().pid.is_some());
+        executor.launcher_mut().kill().unwrap();
+    }
+
+    #[test]
+    fn test_network_builder() {
+        assert!(NetworkExecutor::builder()
+            .program("server")
+            .build::<BytesInput, _, ()>(tuple_list!())
+            .is_err());
+        assert!(NetworkExecutor::builder()
+            .program("server")
+            .tcp(([127, 0, 0, 1], 1).into())
+            .responses_observer("responses")
+            .build::<BytesInput, _, ()>(tuple_list!())
+            .is_err());
+    }
+}
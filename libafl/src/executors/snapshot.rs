@@ -0,0 +1,439 @@
+//! The [`InProcessSnapshotExecutor`] runs the harness in a child process, which restores its memory
+//! from a snapshot after each run, instead of forking again.
+//!
+//! The child is forked once, snapshots its writable memory, program break, mappings and file descriptors
+//! with a [`MemorySnapshot`], and then runs one input after the other. After each run, it copies back
+//! only the pages the run wrote to, found with soft-dirty bits or `userfaultfd` write-protection.
+//! This gives the isolation of the [`crate::executors::InProcessForkExecutor`] at close to in-process speed.
+//! As in the fork executor, the observers need their maps in shared memory, and the child is
+//! forked again after a crash or a timeout.
+
+use core::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    time::Duration,
+};
+use std::io::{Read, Write};
+
+use alloc::vec;
+use nix::{
+    sys::{
+        select::{pselect, FdSet},
+        signal::{kill, SigSet, Signal},
+        time::{TimeSpec, TimeValLike},
+        wait::{waitpid, WaitPidFlag, WaitStatus},
+    },
+    unistd::{fork, ForkResult, Pid},
+};
+
+use crate::{
+    bolts::{
+        os::{
+            pipes::Pipe,
+            snapshot::{DirtyTracking, MemorySnapshot},
+        },
+        shmem::ShMemProvider,
+    },
+    events::{EventFirer, EventRestarter},
+    executors::{inprocess::InChildProcessHandlers, Executor, ExitKind, HasObservers},
+    feedbacks::Feedback,
+    fuzzer::HasObjective,
+    inputs::Input,
+    observers::ObserversTuple,
+    state::{HasClientPerfMonitor, HasSolutions},
+    Error,
+};
+
+/// The maximum size of a serialized [`ExitKind`] the child sends back
+const EXIT_KIND_BUF_SIZE: usize = 4096;
+
+/// The child process, and the pipes to talk to it
+#[derive(Debug)]
+struct SnapshotChild {
+    pid: Pid,
+    /// Sends the inputs
+    ctl_pipe: Pipe,
+    /// Receives the exit kinds
+    st_pipe: Pipe,
+}
+
+/// [`InProcessSnapshotExecutor`] runs the harness in a child process that restores its dirty pages after each execution.
+pub struct InProcessSnapshotExecutor<'a, H, I, OT, S, SP>
+where
+    H: FnMut(&I) -> ExitKind + ?Sized,
+    I: Input,
+    OT: ObserversTuple<I, S>,
+    SP: ShMemProvider,
+{
+    harness_fn: &'a mut H,
+    shmem_provider: SP,
+    observers: OT,
+    handlers: InChildProcessHandlers,
+    tracking: DirtyTracking,
+    timeout: Option<TimeSpec>,
+    child: Option<SnapshotChild>,
+    phantom: PhantomData<(I, S)>,
+}
+
+impl<'a, H, I, OT, S, SP> Debug for InProcessSnapshotExecutor<'a, H, I, OT, S, SP>
+where
+    H: FnMut(&I) -> ExitKind + ?Sized,
+    I: Input,
+    OT: ObserversTuple<I, S>,
+    SP: ShMemProvider,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InProcessSnapshotExecutor")
+            .field("observers", &self.observers)
+            .field("shmem_provider", &self.shmem_provider)
+            .field("tracking", &self.tracking)
+            .field("timeout", &self.timeout)
+            .field("child", &self.child)
+            .finish()
+    }
+}
+
+impl<'a, EM, H, I, OT, S, SP, Z> Executor<EM, I, S, Z>
+    for InProcessSnapshotExecutor<'a, H, I, OT, S, SP>
+where
+    H: FnMut(&I) -> ExitKind + ?Sized,
+    I: Input,
+    OT: ObserversTuple<I, S>,
+    SP: ShMemProvider,
+{
+    fn run_target(
+        &mut self,
+        _fuzzer: &mut Z,
+        state: &mut S,
+        _mgr: &mut EM,
+        input: &I,
+    ) -> Result<ExitKind, Error> {
+        let bytes = postcard::to_allocvec(input)?;
+        let mut len = (bytes.len() as u32).to_ne_bytes();
+
+        // A child that died after its last run is replaced
+        if let Some(child) = &self.child {
+            if waitpid(child.pid, Some(WaitPidFlag::WNOHANG))? != WaitStatus::StillAlive {
+                self.child = None;
+            }
+        }
+        if self.child.is_none() {
+            self.spawn(state)?;
+        }
+        let child = self.child.as_mut().unwrap();
+
+        let sent = child
+            .ctl_pipe
+            .write_all(&len)
+            .and_then(|()| child.ctl_pipe.write_all(&bytes));
+        if sent.is_err() {
+            return self.reap();
+        }
+
+        if let Some(timeout) = &self.timeout {
+            let st_read = child.st_pipe.read_end().unwrap();
+            let mut readfds = FdSet::new();
+            readfds.insert(st_read);
+            let ready = pselect(
+                Some(st_read + 1),
+                &mut readfds,
+                None,
+                None,
+                Some(timeout),
+                Some(&SigSet::empty()),
+            )?;
+            if ready == 0 {
+                let _ = kill(child.pid, Signal::SIGKILL);
+                waitpid(child.pid, None)?;
+                self.child = None;
+                return Ok(ExitKind::Timeout);
+            }
+        }
+
+        if child.st_pipe.read_exact(&mut len).is_err() {
+            return self.reap();
+        }
+        let mut exit_kind = vec![0_u8; u32::from_ne_bytes(len) as usize];
+        if child.st_pipe.read_exact(&mut exit_kind).is_err() {
+            return self.reap();
+        }
+        Ok(postcard::from_bytes(&exit_kind)?)
+    }
+}
+
+impl<'a, H, I, OT, S, SP> InProcessSnapshotExecutor<'a, H, I, OT, S, SP>
+where
+    H: FnMut(&I) -> ExitKind + ?Sized,
+    I: Input,
+    OT: ObserversTuple<I, S>,
+    SP: ShMemProvider,
+{
+    /// Creates a new [`InProcessSnapshotExecutor`], tracking the dirty pages with `tracking`,
+    /// see [`DirtyTracking::detect`].
+    #[allow(clippy::too_many_arguments)]
+    pub fn new<EM, OF, Z>(
+        harness_fn: &'a mut H,
+        observers: OT,
+        _fuzzer: &mut Z,
+        _state: &mut S,
+        _event_mgr: &mut EM,
+        shmem_provider: SP,
+        tracking: DirtyTracking,
+    ) -> Result<Self, Error>
+    where
+        EM: EventFirer<I> + EventRestarter<S>,
+        OF: Feedback<I, S>,
+        S: HasSolutions<I> + HasClientPerfMonitor,
+        Z: HasObjective<I, OF, S>,
+    {
+        let handlers = InChildProcessHandlers::new::<Self, I, OT, S>()?;
+        Ok(Self {
+            harness_fn,
+            shmem_provider,
+            observers,
+            handlers,
+            tracking,
+            timeout: None,
+            child: None,
+            phantom: PhantomData,
+        })
+    }
+
+    /// Kills runs that take longer than `timeout`, reporting [`ExitKind::Timeout`]
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(TimeSpec::milliseconds(timeout.as_millis() as i64));
+        self
+    }
+
+    /// How the dirty pages are tracked
+    #[must_use]
+    pub fn tracking(&self) -> DirtyTracking {
+        self.tracking
+    }
+
+    /// Retrieve the harness function.
+    #[inline]
+    pub fn harness(&self) -> &H {
+        self.harness_fn
+    }
+
+    /// Retrieve the harness function for a mutable reference.
+    #[inline]
+    pub fn harness_mut(&mut self) -> &mut H {
+        self.harness_fn
+    }
+
+    /// Forks the child and waits until it took its snapshot
+    fn spawn(&mut self, state: &mut S) -> Result<(), Error> {
+        let mut ctl_pipe = Pipe::new()?;
+        let mut st_pipe = Pipe::new()?;
+        self.shmem_provider.pre_fork()?;
+        match unsafe { fork() }? {
+            ForkResult::Child => {
+                self.shmem_provider.post_fork(true)?;
+                ctl_pipe.close_write_end();
+                st_pipe.close_read_end();
+                self.run_child(state, &mut ctl_pipe, &mut st_pipe)
+            }
+            ForkResult::Parent { child } => {
+                self.shmem_provider.post_fork(false)?;
+                ctl_pipe.close_read_end();
+                st_pipe.close_write_end();
+
+                let mut hello = [0_u8; 4];
+                let ready = st_pipe.read_exact(&mut hello).is_ok();
+                if !ready || u32::from_ne_bytes(hello) != 0 {
+                    let _ = kill(child, Signal::SIGKILL);
+                    waitpid(child, None)?;
+                    return Err(Error::unsupported(format!(
+                        "The child could not snapshot its memory with {:?}",
+                        self.tracking
+                    )));
+                }
+                self.child = Some(SnapshotChild {
+                    pid: child,
+                    ctl_pipe,
+                    st_pipe,
+                });
+                Ok(())
+            }
+        }
+    }
+
+    /// Snapshots the child, then runs inputs and restores the snapshot after each, until the pipe closes
+    fn run_child(&mut self, state: &mut S, ctl_pipe: &mut Pipe, st_pipe: &mut Pipe) -> ! {
+        let snapshot = match MemorySnapshot::take(self.tracking) {
+            Ok(snapshot) => snapshot,
+            Err(err) => {
+                eprintln!("Could not snapshot the child: {:?}", err);
+                let _ = st_pipe.write_all(&1_u32.to_ne_bytes());
+                unsafe { libc::_exit(1) };
+            }
+        };
+        if st_pipe.write_all(&0_u32.to_ne_bytes()).is_err() {
+            unsafe { libc::_exit(1) };
+        }
+
+        // The result lives on the stack, which the snapshot leaves alone
+        let mut exit_kind_buf = [0_u8; EXIT_KIND_BUF_SIZE];
+        loop {
+            let mut len = [0_u8; 4];
+            if ctl_pipe.read_exact(&mut len).is_err() {
+                unsafe { libc::_exit(0) };
+            }
+            // Everything allocated during the run must be dropped before the snapshot is restored
+            let exit_kind_len = {
+                let mut bytes = vec![0_u8; u32::from_ne_bytes(len) as usize];
+                if ctl_pipe.read_exact(&mut bytes).is_err() {
+                    unsafe { libc::_exit(0) };
+                }
+                let input: I = postcard::from_bytes(&bytes).expect("Could not receive the input");
+
+                self.handlers.pre_run_target(self, state, &input);
+                self.observers
+                    .pre_exec_child_all(state, &input)
+                    .expect("Failed to run pre_exec on observers");
+
+                let exit_kind = (self.harness_fn)(&input);
+
+                self.observers
+                    .post_exec_child_all(state, &input, &exit_kind)
+                    .expect("Failed to run post_exec on observers");
+                postcard::to_slice(&exit_kind, &mut exit_kind_buf)
+                    .expect("The exit kind is too large")
+                    .len()
+            };
+
+            let restored = unsafe { snapshot.restore() };
+            let sent = st_pipe
+                .write_all(&(exit_kind_len as u32).to_ne_bytes())
+                .and_then(|()| st_pipe.write_all(&exit_kind_buf[..exit_kind_len]));
+            if let Err(err) = restored {
+                eprintln!("Could not restore the snapshot: {:?}", err);
+                unsafe { libc::_exit(1) };
+            }
+            if sent.is_err() {
+                unsafe { libc::_exit(0) };
+            }
+        }
+    }
+
+    /// The child is gone during a run, find out why
+    fn reap(&mut self) -> Result<ExitKind, Error> {
+        let child = self.child.take().unwrap();
+        match waitpid(child.pid, None)? {
+            WaitStatus::Signaled(_, _, _) => Ok(ExitKind::Crash),
+            // Signal exit codes
+            WaitStatus::Exited(_, code) if code > 128 && code < 160 => Ok(ExitKind::Crash),
+            _ => Ok(ExitKind::Ok),
+        }
+    }
+}
+
+impl<'a, H, I, OT, S, SP> Drop for InProcessSnapshotExecutor<'a, H, I, OT, S, SP>
+where
+    H: FnMut(&I) -> ExitKind + ?Sized,
+    I: Input,
+    OT: ObserversTuple<I, S>,
+    SP: ShMemProvider,
+{
+    fn drop(&mut self) {
+        if let Some(child) = self.child.take() {
+            let _ = kill(child.pid, Signal::SIGKILL);
+            let _ = waitpid(child.pid, None);
+        }
+    }
+}
+
+impl<'a, H, I, OT, S, SP> HasObservers<I, OT, S> for InProcessSnapshotExecutor<'a, H, I, OT, S, SP>
+where
+    H: FnMut(&I) -> ExitKind + ?Sized,
+    I: Input,
+    OT: ObserversTuple<I, S>,
+    SP: ShMemProvider,
+{
+    #[inline]
+    fn observers(&self) -> &OT {
+        &self.observers
+    }
+
+    #[inline]
+    fn observers_mut(&mut self) -> &mut OT {
+        &mut self.observers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{marker::PhantomData, time::Duration};
+    use serial_test::serial;
+
+    use crate::{
+        bolts::AsSlice,
+        bolts::{
+            os::snapshot::DirtyTracking,
+            shmem::{ShMemProvider, StdShMemProvider},
+            tuples::tuple_list,
+        },
+        executors::{
+            inprocess::InChildProcessHandlers, snapshot::InProcessSnapshotExecutor, Executor,
+            ExitKind,
+        },
+        inputs::{BytesInput, HasTargetBytes},
+    };
+
+    static mut RUNS: usize = 0;
+
+    #[test]
+    #[serial]
+    fn test_snapshot_exec() {
+        let tracking = match DirtyTracking::detect() {
+            Some(tracking) => tracking,
+            None => return,
+        };
+
+        // Without the snapshot, the second run would see the first one
+        let mut harness = |input: &BytesInput| {
+            let runs = unsafe {
+                RUNS += 1;
+                RUNS
+            };
+            match input.target_bytes().as_slice() {
+                b"crash" => std::process::abort(),
+                b"hang" => std::thread::sleep(Duration::from_secs(10)),
+                _ => (),
+            }
+            if runs == 1 {
+                ExitKind::Ok
+            } else {
+                ExitKind::Oom
+            }
+        };
+        let mut executor = InProcessSnapshotExecutor::<_, BytesInput, (), (), _> {
+            harness_fn: &mut harness,
+            shmem_provider: StdShMemProvider::new().unwrap(),
+            observers: tuple_list!(),
+            handlers: InChildProcessHandlers::nop(),
+            tracking,
+            timeout: None,
+            child: None,
+            phantom: PhantomData,
+        }
+        .with_timeout(Duration::from_millis(500));
+
+        let mut run = |input: &[u8]| {
+            executor
+                .run_target(&mut (), &mut (), &mut (), &BytesInput::new(input.to_vec()))
+                .unwrap()
+        };
+        for _ in 0..4 {
+            assert_eq!(run(b"a"), ExitKind::Ok);
+        }
+        assert_eq!(run(b"crash"), ExitKind::Crash);
+        assert_eq!(run(b"a"), ExitKind::Ok);
+        assert_eq!(run(b"hang"), ExitKind::Timeout);
+        assert_eq!(run(b"a"), ExitKind::Ok);
+        assert_eq!(unsafe { RUNS }, 0);
+    }
+}
@@ -0,0 +1,414 @@
+//! The [`CrashBucketFeedback`] deduplicates crashes into buckets of the same stack hash and crash type.
+//!
+//! For each bucket, it keeps the smallest reproducer and counts the hits. The buckets are exported to a
+//! machine-readable JSON index next to the solutions directory, by default `<solutions dir>.buckets.json`.
+//! The index is rewritten when a bucket is created or gets a smaller reproducer, so the hits it lists may lag behind.
+//! Each reproducer has its own file, named after its crash type, stack hash and length, so earlier solutions are never overwritten.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt::Debug, marker::PhantomData};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    bolts::{tuples::Named, HasLen},
+    corpus::Testcase,
+    events::EventFirer,
+    executors::ExitKind,
+    feedbacks::{Feedback, HasObserverName},
+    inputs::Input,
+    observers::{ObserverWithHashField, ObserversTuple},
+    state::{HasClientPerfMonitor, HasNamedMetadata},
+    Error,
+};
+
+/// The prefix of the metadata names
+pub const CRASHBUCKETFEEDBACK_PREFIX: &str = "crashbucketfeedback_metadata_";
+
+/// A bucket of crashes with the same stack hash and crash type
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CrashBucket {
+    /// The id of the bucket, in order of discovery
+    pub id: usize,
+    /// The hash of the crashing stack
+    pub stack_hash: u64,
+    /// The crash type, e.g. the ASAN error type, if known
+    pub crash_type: Option<String>,
+    /// How many crashing inputs fell into this bucket
+    pub hits: usize,
+    /// The length of the smallest reproducer
+    pub smallest_len: usize,
+    /// The file name of the smallest reproducer, in the solutions directory.
+    /// A smaller reproducer is written to a new file.
+    pub reproducer: Option<String>,
+}
+
+/// The crash buckets found so far, also written as JSON index by the [`CrashBucketFeedback`]
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+pub struct CrashBucketsMetadata {
+    /// The buckets, indexed by their id
+    pub buckets: Vec<CrashBucket>,
+}
+
+crate::impl_serdeany!(CrashBucketsMetadata);
+
+impl CrashBucketsMetadata {
+    /// Returns the bucket for the given stack hash and crash type, if any
+    #[must_use]
+    pub fn bucket(&self, stack_hash: u64, crash_type: Option<&str>) -> Option<&CrashBucket> {
+        self.buckets
+            .iter()
+            .find(|b| b.stack_hash == stack_hash && b.crash_type.as_deref() == crash_type)
+    }
+
+    fn bucket_mut(
+        &mut self,
+        stack_hash: u64,
+        crash_type: Option<&str>,
+    ) -> Option<&mut CrashBucket> {
+        self.buckets
+            .iter_mut()
+            .find(|b| b.stack_hash == stack_hash && b.crash_type.as_deref() == crash_type)
+    }
+}
+
+/// A crash about to be added to the solutions
+#[derive(Clone, Debug)]
+struct PendingCrash {
+    stack_hash: u64,
+    crash_type: Option<String>,
+    len: usize,
+}
+
+/// A [`CrashBucketFeedback`] groups crashes by the hash and the crash type of an [`ObserverWithHashField`],
+/// like the [`crate::observers::ASANBacktraceObserver`].
+/// A crash is interesting if it opens a new bucket, or if it is smaller than the reproducer of its bucket.
+/// Use it as objective, together with the solutions directory of an [`crate::corpus::OnDiskCorpus`].
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CrashBucketFeedback<O> {
+    name: String,
+    observer_name: String,
+    solutions_dir: PathBuf,
+    index_path: PathBuf,
+    #[serde(skip)]
+    pending: Option<PendingCrash>,
+    o_type: PhantomData<O>,
+}
+
+impl<I, S, O> Feedback<I, S> for CrashBucketFeedback<O>
+where
+    I: Input + HasLen,
+    S: HasClientPerfMonitor + HasNamedMetadata,
+    O: ObserverWithHashField + Named + Debug,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        state.add_named_metadata(CrashBucketsMetadata::default(), &self.name);
+        Ok(())
+    }
+
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting<EM, OT>(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        input: &I,
+        observers: &OT,
+        exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<I>,
+        OT: ObserversTuple<I, S>,
+    {
+        self.pending = None;
+        if *exit_kind == ExitKind::Ok {
+            // The observer may still hold the hash of an earlier crash
+            return Ok(false);
+        }
+        let observer = observers
+            .match_name::<O>(&self.observer_name)
+            .ok_or_else(|| {
+                Error::key_not_found(format!(
+                    "CrashBucketFeedback: observer {} not found",
+                    self.observer_name
+                ))
+            })?;
+        let stack_hash = match observer.hash() {
+            Some(hash) => *hash,
+            // No crash
+            None => return Ok(false),
+        };
+        let crash_type = observer.crash_type();
+
+        let buckets = state
+            .named_metadata_mut()
+            .get_mut::<CrashBucketsMetadata>(&self.name)
+            .unwrap();
+        let interesting = match buckets.bucket_mut(stack_hash, crash_type) {
+            Some(bucket) => {
+                bucket.hits += 1;
+                input.len() < bucket.smallest_len
+            }
+            None => true,
+        };
+        if interesting {
+            self.pending = Some(PendingCrash {
+                stack_hash,
+                crash_type: crash_type.map(ToString::to_string),
+                len: input.len(),
+            });
+        }
+        Ok(interesting)
+    }
+
+    fn append_metadata(&mut self, state: &mut S, testcase: &mut Testcase<I>) -> Result<(), Error> {
+        let pending = match self.pending.take() {
+            Some(pending) => pending,
+            None => return Ok(()),
+        };
+        let buckets = state
+            .named_metadata_mut()
+            .get_mut::<CrashBucketsMetadata>(&self.name)
+            .unwrap();
+
+        if buckets
+            .bucket(pending.stack_hash, pending.crash_type.as_deref())
+            .is_none()
+        {
+            buckets.buckets.push(CrashBucket {
+                id: buckets.buckets.len(),
+                stack_hash: pending.stack_hash,
+                crash_type: pending.crash_type.clone(),
+                hits: 1,
+                smallest_len: pending.len,
+                reproducer: None,
+            });
+        }
+        let bucket = buckets
+            .bucket_mut(pending.stack_hash, pending.crash_type.as_deref())
+            .unwrap();
+        // The reproducers of a bucket get smaller, so the length makes their file names unique
+        let file_name = format!(
+            "{}-{:016x}-{}",
+            bucket.crash_type.as_deref().unwrap_or("crash"),
+            bucket.stack_hash,
+            pending.len
+        );
+        bucket.smallest_len = pending.len;
+        bucket.reproducer = Some(file_name.clone());
+        testcase.set_filename(
+            self.solutions_dir
+                .join(file_name)
+                .to_str()
+                .expect("Invalid Path")
+                .into(),
+        );
+
+        let buckets = buckets.clone();
+        self.write_index(&buckets)
+    }
+
+    fn discard_metadata(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.pending = None;
+        Ok(())
+    }
+}
+
+impl<O> Named for CrashBucketFeedback<O> {
+    #[inline]
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl<O> HasObserverName for CrashBucketFeedback<O> {
+    #[inline]
+    fn observer_name(&self) -> &str {
+        &self.observer_name
+    }
+}
+
+impl<O> CrashBucketFeedback<O>
+where
+    O: ObserverWithHashField + Named + Debug,
+{
+    /// Returns a new [`CrashBucketFeedback`], naming the reproducers in the given solutions directory.
+    #[must_use]
+    pub fn new<P>(observer: &O, solutions_dir: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        let solutions_dir = solutions_dir.as_ref().to_path_buf();
+        let mut index_name = solutions_dir
+            .file_name()
+            .map_or_else(|| "solutions".into(), std::ffi::OsStr::to_os_string);
+        index_name.push(".buckets.json");
+        Self {
+            name: CRASHBUCKETFEEDBACK_PREFIX.to_string() + observer.name(),
+            observer_name: observer.name().to_string(),
+            index_path: solutions_dir.with_file_name(index_name),
+            solutions_dir,
+            pending: None,
+            o_type: PhantomData,
+        }
+    }
+
+    /// Writes the JSON bucket index to the given path, instead of next to the solutions directory.
+    #[must_use]
+    pub fn with_index_path<P>(mut self, index_path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        self.index_path = index_path.as_ref().to_path_buf();
+        self
+    }
+
+    /// The path of the JSON bucket index
+    #[must_use]
+    pub fn index_path(&self) -> &Path {
+        &self.index_path
+    }
+
+    fn write_index(&self, buckets: &CrashBucketsMetadata) -> Result<(), Error> {
+        let mut tmp_name = self.index_path.file_name().unwrap().to_os_string();
+        tmp_name.push(".tmp");
+        let tmp_path = self.index_path.with_file_name(tmp_name);
+        fs::write(&tmp_path, serde_json::to_vec_pretty(buckets)?)?;
+        fs::rename(&tmp_path, &self.index_path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::{
+        bolts::{rands::StdRand, tuples::Named},
+        corpus::{InMemoryCorpus, Testcase},
+        events::NopEventManager,
+        executors::ExitKind,
+        feedbacks::{
+            crash_buckets::{CrashBucketFeedback, CrashBucketsMetadata},
+            Feedback,
+        },
+        inputs::BytesInput,
+        observers::{ASANBacktraceObserver, ObserverWithHashField},
+        state::{HasNamedMetadata, StdState},
+    };
+
+    const UAF_REPORT: &str =
+        "==1==ERROR: AddressSanitizer: heap-use-after-free on address 0x602000000010
+    #0 0x4f1234 in crash /src/target.c:10
+    #1 0x4f2345 in parse /src/target.c:20
+    #2 0x4f3456 in main /src/target.c:30
+freed by thread T0 here:
+    #0 0x4a0000 in free
+    #1 0x4f4567 in cleanup /src/target.c:40
+";
+
+    #[test]
+    fn test_crash_buckets() {
+        let dir =
+            std::env::temp_dir().join(format!("libafl_test_crash_buckets_{}", std::process::id()));
+        let solutions_dir = dir.join("crashes");
+        fs::create_dir_all(&solutions_dir).unwrap();
+
+        let mut observer = ASANBacktraceObserver::default().with_top_frames(2);
+        let mut feedback = CrashBucketFeedback::new(&observer, &solutions_dir);
+        let index_path = feedback.index_path().to_path_buf();
+        assert_eq!(index_path, dir.join("crashes.buckets.json"));
+
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+        Feedback::<BytesInput, _>::init_state(&mut feedback, &mut state).unwrap();
+        let mut mgr = NopEventManager {};
+
+        let mut run = |observer: &ASANBacktraceObserver, input: &[u8]| {
+            let input = BytesInput::new(input.to_vec());
+            let observers = (observer.clone(), ());
+            let interesting = feedback
+                .is_interesting(&mut state, &mut mgr, &input, &observers, &ExitKind::Crash)
+                .unwrap();
+            let mut testcase = Testcase::<BytesInput>::new(input);
+            if interesting {
+                feedback.append_metadata(&mut state, &mut testcase).unwrap();
+                // What the solutions corpus does
+                testcase.store_input().unwrap();
+            }
+            (interesting, testcase.filename().clone())
+        };
+
+        // No crash, no bucket
+        assert!(!run(&observer, b"AAAA").0);
+
+        // A new bucket
+        observer.parse_asan_output(UAF_REPORT);
+        assert_eq!(observer.error_type(), Some("heap-use-after-free"));
+        let (interesting, filename) = run(&observer, b"AAAA");
+        assert!(interesting);
+        assert!(filename
+            .as_ref()
+            .unwrap()
+            .starts_with(solutions_dir.to_str().unwrap()));
+
+        // Same top frames, different caller: the same bucket, and not smaller, so the index is left alone
+        observer.parse_asan_output(&UAF_REPORT.replace("0x4f3456", "0x4f9999"));
+        assert!(!run(&observer, b"BBBB").0);
+        let index: CrashBucketsMetadata =
+            serde_json::from_slice(&fs::read(&index_path).unwrap()).unwrap();
+        assert_eq!(index.buckets[0].hits, 1);
+        // A smaller reproducer replaces the old one in the bucket, in a file of its own
+        let (interesting, smaller_filename) = run(&observer, b"CC");
+        assert!(interesting);
+        assert_ne!(smaller_filename, filename);
+        assert_eq!(fs::read_dir(&solutions_dir).unwrap().count(), 2);
+        assert_eq!(fs::read(filename.as_ref().unwrap()).unwrap(), b"AAAA");
+        assert_eq!(fs::read(smaller_filename.as_ref().unwrap()).unwrap(), b"CC");
+
+        // Another crash type is another bucket
+        observer.parse_asan_output(&UAF_REPORT.replace("heap-use-after-free", "double-free"));
+        assert!(run(&observer, b"DDDDDD").0);
+
+        // Runs without a crash are never bucketed, even if the observer kept a hash
+        let input = BytesInput::new(b"C".to_vec());
+        assert!(!feedback
+            .is_interesting(
+                &mut state,
+                &mut mgr,
+                &input,
+                &(observer.clone(), ()),
+                &ExitKind::Ok
+            )
+            .unwrap());
+
+        observer.clear_hash();
+        let index: CrashBucketsMetadata =
+            serde_json::from_slice(&fs::read(feedback.index_path()).unwrap()).unwrap();
+        assert_eq!(index.buckets.len(), 2);
+        assert_eq!(index.buckets[0].hits, 3);
+        assert_eq!(index.buckets[0].smallest_len, 2);
+        assert_eq!(index.buckets[1].crash_type.as_deref(), Some("double-free"));
+        assert_eq!(
+            state
+                .named_metadata()
+                .get::<CrashBucketsMetadata>(feedback.name())
+                .unwrap()
+                .buckets,
+            index.buckets
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,227 @@
+//! The [`CustomExitFeedback`] matches the payload of an [`ExitKind::Custom`].
+//!
+//! A harness telling apart failures beyond crashes, like failed assertions or leaks, returns them
+//! as [`ExitKind::custom`] with a payload type of its own. Used as objective, the feedback turns them into solutions,
+//! and stores the payload in the metadata of each solution, so it is saved with it.
+
+use alloc::string::{String, ToString};
+use core::{any::type_name, fmt::Debug};
+
+use crate::{
+    bolts::{serdeany::SerdeAny, tuples::Named},
+    corpus::Testcase,
+    events::EventFirer,
+    executors::ExitKind,
+    feedbacks::Feedback,
+    inputs::Input,
+    observers::ObserversTuple,
+    state::{HasClientPerfMonitor, HasMetadata},
+    Error,
+};
+
+/// A [`CustomExitFeedback`] is interesting if the run exited with an [`ExitKind::Custom`] with a payload of type `T`,
+/// and the payload passes the filter, if any.
+/// The payload is added to the metadata of the testcase.
+#[derive(Clone, Debug)]
+pub struct CustomExitFeedback<T>
+where
+    T: SerdeAny + Clone,
+{
+    name: String,
+    filter: Option<fn(&T) -> bool>,
+    payload: Option<T>,
+}
+
+impl<I, S, T> Feedback<I, S> for CustomExitFeedback<T>
+where
+    I: Input,
+    S: HasClientPerfMonitor,
+    T: SerdeAny + Clone,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        _observers: &OT,
+        exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<I>,
+        OT: ObserversTuple<I, S>,
+    {
+        self.payload = exit_kind
+            .custom_payload::<T>()
+            .filter(|payload| self.filter.map_or(true, |filter| filter(payload)))
+            .cloned();
+        Ok(self.payload.is_some())
+    }
+
+    fn append_metadata(&mut self, _state: &mut S, testcase: &mut Testcase<I>) -> Result<(), Error> {
+        if let Some(payload) = self.payload.take() {
+            testcase.add_metadata(payload);
+        }
+        Ok(())
+    }
+
+    fn discard_metadata(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.payload = None;
+        Ok(())
+    }
+}
+
+impl<T> Named for CustomExitFeedback<T>
+where
+    T: SerdeAny + Clone,
+{
+    #[inline]
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl<T> CustomExitFeedback<T>
+where
+    T: SerdeAny + Clone,
+{
+    /// Creates a new [`CustomExitFeedback`], interesting for all payloads of type `T`
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            name: format!("CustomExitFeedback<{}>", type_name::<T>()),
+            filter: None,
+            payload: None,
+        }
+    }
+
+    /// Only payloads passing the filter are interesting
+    #[must_use]
+    pub fn with_filter(mut self, filter: fn(&T) -> bool) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Sets the name, to tell apart several feedbacks for the same payload type
+    #[must_use]
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+}
+
+impl<T> Default for CustomExitFeedback<T>
+where
+    T: SerdeAny + Clone,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+    use serde::{Deserialize, Serialize};
+
+    use crate::{
+        bolts::rands::StdRand,
+        corpus::{InMemoryCorpus, Testcase},
+        events::NopEventManager,
+        executors::ExitKind,
+        feedbacks::{CrashFeedback, CustomExitFeedback, Feedback},
+        inputs::BytesInput,
+        state::{HasMetadata, StdState},
+    };
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+    struct AssertionFailed {
+        message: String,
+    }
+
+    crate::impl_serdeany!(AssertionFailed);
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct Leak {
+        bytes: usize,
+    }
+
+    crate::impl_serdeany!(Leak);
+
+    #[test]
+    fn test_custom_exit_feedback() {
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+        let mut mgr = NopEventManager {};
+        let input = BytesInput::new(vec![]);
+
+        let assertion = ExitKind::custom(AssertionFailed {
+            message: "len <= 16".into(),
+        });
+        assert_eq!(assertion.clone(), assertion);
+        assert_eq!(
+            ExitKind::custom(AssertionFailed {
+                message: "len <= 16".into(),
+            }),
+            assertion
+        );
+        assert_ne!(assertion, ExitKind::custom(Leak { bytes: 16 }));
+        let serialized = postcard::to_allocvec(&assertion).unwrap();
+        assert_eq!(
+            postcard::from_bytes::<ExitKind>(&serialized).unwrap(),
+            assertion
+        );
+
+        let mut feedback = CustomExitFeedback::<AssertionFailed>::new();
+        let mut leaks = CustomExitFeedback::<Leak>::new().with_filter(|leak| leak.bytes > 1024);
+        let mut crashes = CrashFeedback::new();
+        for (exit_kind, expected) in [
+            (&assertion, (true, false, false)),
+            (&ExitKind::custom(Leak { bytes: 16 }), (false, false, false)),
+            (
+                &ExitKind::custom(Leak { bytes: 4096 }),
+                (false, true, false),
+            ),
+            (&ExitKind::Crash, (false, false, true)),
+        ] {
+            let interesting = (
+                feedback
+                    .is_interesting(&mut state, &mut mgr, &input, &(), exit_kind)
+                    .unwrap(),
+                leaks
+                    .is_interesting(&mut state, &mut mgr, &input, &(), exit_kind)
+                    .unwrap(),
+                Feedback::<BytesInput, _>::is_interesting(
+                    &mut crashes,
+                    &mut state,
+                    &mut mgr,
+                    &input,
+                    &(),
+                    exit_kind,
+                )
+                .unwrap(),
+            );
+            assert_eq!(interesting, expected);
+        }
+
+        feedback
+            .is_interesting(&mut state, &mut mgr, &input, &(), &assertion)
+            .unwrap();
+        let mut testcase: Testcase<BytesInput> = Testcase::new(input);
+        feedback.append_metadata(&mut state, &mut testcase).unwrap();
+        assert_eq!(
+            testcase
+                .metadata()
+                .get::<AssertionFailed>()
+                .unwrap()
+                .message,
+            "len <= 16"
+        );
+    }
+}
@@ -0,0 +1,507 @@
+//! A ready-made differential oracle for the [`crate::executors::DiffExecutor`].
+//!
+//! The [`DiffOracleFeedback`] compares the [`ExitKind`]s, the contents of pairs of [`StdOutObserver`]s and [`StdErrObserver`]s,
+//! and the return values in pairs of [`ValueObserver`]s of the two executions.
+//! Outputs can be normalised before the comparison, to scrub addresses, timestamps, and the like.
+//! Every divergence found is recorded as a [`Divergence`] in the [`DiffOracleMetadata`] of the testcase.
+//!
+//! The observers of both executors are found by name in the [`crate::executors::DiffObservers`] of the [`crate::executors::DiffExecutor`],
+//! so all of them need distinct names.
+
+use alloc::{
+    borrow::Cow,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt::Debug, marker::PhantomData};
+use regex::Regex;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    bolts::tuples::Named,
+    corpus::Testcase,
+    events::EventFirer,
+    executors::{DiffExitKind, ExitKind},
+    feedbacks::Feedback,
+    inputs::Input,
+    observers::{ObserversTuple, StdErrObserver, StdOutObserver, ValueObserver},
+    state::{HasClientPerfMonitor, HasMetadata},
+    Error,
+};
+
+/// A single difference between the primary and the secondary execution.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Divergence {
+    /// The executions finished differently.
+    ExitKind {
+        /// How the primary execution finished
+        primary: DiffExitKind,
+        /// How the secondary execution finished
+        secondary: DiffExitKind,
+    },
+    /// The (normalised) `stdout` differs.
+    StdOut {
+        /// The `stdout` of the primary execution
+        primary: Option<String>,
+        /// The `stdout` of the secondary execution
+        secondary: Option<String>,
+    },
+    /// The (normalised) `stderr` differs.
+    StdErr {
+        /// The `stderr` of the primary execution
+        primary: Option<String>,
+        /// The `stderr` of the secondary execution
+        secondary: Option<String>,
+    },
+    /// The return values differ.
+    ReturnValue {
+        /// The `Debug` representation of the primary return value
+        primary: Option<String>,
+        /// The `Debug` representation of the secondary return value
+        secondary: Option<String>,
+    },
+}
+
+/// The divergences found by a [`DiffOracleFeedback`], attached to the testcase.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DiffOracleMetadata {
+    /// All divergences of this testcase
+    pub divergences: Vec<Divergence>,
+}
+
+crate::impl_serdeany!(DiffOracleMetadata);
+
+impl DiffOracleMetadata {
+    /// Create a new [`DiffOracleMetadata`]
+    #[must_use]
+    pub fn new(divergences: Vec<Divergence>) -> Self {
+        Self { divergences }
+    }
+}
+
+/// Normalises the output of a target before comparing it, by replacing all matches of a list of regexes.
+#[derive(Debug, Clone, Default)]
+pub struct Normalizer {
+    rules: Vec<(Regex, String)>,
+}
+
+impl Normalizer {
+    /// Create a new [`Normalizer`] that does not change the output.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace all matches of `pattern` with `replacement`.
+    /// The replacement may refer to capture groups, see [`Regex::replace_all`].
+    pub fn scrub(mut self, pattern: &str, replacement: &str) -> Result<Self, Error> {
+        let regex = Regex::new(pattern).map_err(|e| {
+            Error::illegal_argument(format!("Normalizer: invalid regex {}: {}", pattern, e))
+        })?;
+        self.rules.push((regex, replacement.to_string()));
+        Ok(self)
+    }
+
+    /// Replace all hexadecimal addresses, such as `0x7ffd5e8c`, with `0xADDR`.
+    #[must_use]
+    pub fn scrub_addresses(self) -> Self {
+        self.scrub(r"0x[0-9a-fA-F]+", "0xADDR").unwrap()
+    }
+
+    /// Replace all timestamps, such as `2022-03-01 12:34:56.789` or `12:34:56`, with `TIMESTAMP`.
+    #[must_use]
+    pub fn scrub_timestamps(self) -> Self {
+        self.scrub(
+            r"(\d{4}-\d{2}-\d{2}[T ])?\d{2}:\d{2}:\d{2}(\.\d+)?(Z|[+-]\d{2}:?\d{2})?",
+            "TIMESTAMP",
+        )
+        .unwrap()
+    }
+
+    /// Normalise the given output.
+    #[must_use]
+    pub fn normalize<'a>(&self, output: &'a str) -> Cow<'a, str> {
+        let mut output = Cow::Borrowed(output);
+        for (regex, replacement) in &self.rules {
+            if let Cow::Owned(replaced) = regex.replace_all(&output, replacement.as_str()) {
+                output = Cow::Owned(replaced);
+            }
+        }
+        output
+    }
+}
+
+/// A differential oracle, comparing the outcome of the two executions of a [`crate::executors::DiffExecutor`].
+/// An input is interesting if any of the enabled comparisons finds a [`Divergence`].
+#[derive(Debug)]
+pub struct DiffOracleFeedback<'a, T> {
+    name: String,
+    compare_exit_kinds: bool,
+    stdout_names: Option<(String, String)>,
+    stderr_names: Option<(String, String)>,
+    value_names: Option<(String, String)>,
+    normalizer: Normalizer,
+    divergences: Vec<Divergence>,
+    phantom: PhantomData<&'a T>,
+}
+
+impl DiffOracleFeedback<'static, ()> {
+    /// Create a new [`DiffOracleFeedback`] that only compares the [`ExitKind`]s.
+    #[must_use]
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            compare_exit_kinds: true,
+            stdout_names: None,
+            stderr_names: None,
+            value_names: None,
+            normalizer: Normalizer::new(),
+            divergences: vec![],
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<'a, T> DiffOracleFeedback<'a, T> {
+    /// Compare the [`ExitKind`]s of both executions (enabled by default).
+    #[must_use]
+    pub fn compare_exit_kinds(mut self, compare_exit_kinds: bool) -> Self {
+        self.compare_exit_kinds = compare_exit_kinds;
+        self
+    }
+
+    /// Compare the `stdout` captured by the two [`StdOutObserver`]s with the given names.
+    #[must_use]
+    pub fn compare_stdout(mut self, primary: &str, secondary: &str) -> Self {
+        self.stdout_names = Some((primary.to_string(), secondary.to_string()));
+        self
+    }
+
+    /// Compare the `stderr` captured by the two [`StdErrObserver`]s with the given names.
+    #[must_use]
+    pub fn compare_stderr(mut self, primary: &str, secondary: &str) -> Self {
+        self.stderr_names = Some((primary.to_string(), secondary.to_string()));
+        self
+    }
+
+    /// Normalise `stdout` and `stderr` with the given [`Normalizer`] before comparing them.
+    #[must_use]
+    pub fn normalizer(mut self, normalizer: Normalizer) -> Self {
+        self.normalizer = normalizer;
+        self
+    }
+
+    /// Compare the return values held by the two [`ValueObserver`]s with the given names.
+    #[must_use]
+    pub fn compare_return_values<'b, V>(
+        self,
+        primary: &str,
+        secondary: &str,
+    ) -> DiffOracleFeedback<'b, V> {
+        DiffOracleFeedback {
+            name: self.name,
+            compare_exit_kinds: self.compare_exit_kinds,
+            stdout_names: self.stdout_names,
+            stderr_names: self.stderr_names,
+            value_names: Some((primary.to_string(), secondary.to_string())),
+            normalizer: self.normalizer,
+            divergences: self.divergences,
+            phantom: PhantomData,
+        }
+    }
+
+    /// The divergences found in the last run
+    #[must_use]
+    pub fn divergences(&self) -> &[Divergence] {
+        &self.divergences
+    }
+
+    fn compare_outputs(&self, primary: Option<&String>, secondary: Option<&String>) -> bool {
+        match (primary, secondary) {
+            (Some(primary), Some(secondary)) => {
+                self.normalizer.normalize(primary) == self.normalizer.normalize(secondary)
+            }
+            (None, None) => true,
+            _ => false,
+        }
+    }
+
+    fn normalized(&self, output: Option<&String>) -> Option<String> {
+        output.map(|o| self.normalizer.normalize(o).into_owned())
+    }
+}
+
+fn find_observer<'o, O, OT, I, S>(observers: &'o OT, name: &str) -> Result<&'o O, Error>
+where
+    OT: ObserversTuple<I, S>,
+{
+    observers.match_name::<O>(name).ok_or_else(|| {
+        Error::illegal_argument(format!("DiffOracleFeedback: observer {} not found", name))
+    })
+}
+
+impl<'a, T> Named for DiffOracleFeedback<'a, T> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl<'a, I, S, T> Feedback<I, S> for DiffOracleFeedback<'a, T>
+where
+    I: Input,
+    S: HasClientPerfMonitor,
+    T: Debug + PartialEq + Serialize + DeserializeOwned,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<I>,
+        OT: ObserversTuple<I, S>,
+    {
+        let mut divergences = vec![];
+
+        if self.compare_exit_kinds {
+            if let ExitKind::Diff { primary, secondary } = exit_kind {
+                divergences.push(Divergence::ExitKind {
+                    primary: *primary,
+                    secondary: *secondary,
+                });
+            }
+        }
+
+        if let Some((primary, secondary)) = &self.stdout_names {
+            let primary = &find_observer::<StdOutObserver, _, _, _>(observers, primary)?.stdout;
+            let secondary = &find_observer::<StdOutObserver, _, _, _>(observers, secondary)?.stdout;
+            if !self.compare_outputs(primary.as_ref(), secondary.as_ref()) {
+                divergences.push(Divergence::StdOut {
+                    primary: self.normalized(primary.as_ref()),
+                    secondary: self.normalized(secondary.as_ref()),
+                });
+            }
+        }
+
+        if let Some((primary, secondary)) = &self.stderr_names {
+            let primary = &find_observer::<StdErrObserver, _, _, _>(observers, primary)?.stderr;
+            let secondary = &find_observer::<StdErrObserver, _, _, _>(observers, secondary)?.stderr;
+            if !self.compare_outputs(primary.as_ref(), secondary.as_ref()) {
+                divergences.push(Divergence::StdErr {
+                    primary: self.normalized(primary.as_ref()),
+                    secondary: self.normalized(secondary.as_ref()),
+                });
+            }
+        }
+
+        if let Some((primary, secondary)) = &self.value_names {
+            let primary =
+                find_observer::<ValueObserver<'a, T>, _, _, _>(observers, primary)?.value();
+            let secondary =
+                find_observer::<ValueObserver<'a, T>, _, _, _>(observers, secondary)?.value();
+            if primary != secondary {
+                divergences.push(Divergence::ReturnValue {
+                    primary: primary.map(|v| format!("{:?}", v)),
+                    secondary: secondary.map(|v| format!("{:?}", v)),
+                });
+            }
+        }
+
+        self.divergences = divergences;
+        Ok(!self.divergences.is_empty())
+    }
+
+    fn append_metadata(&mut self, _state: &mut S, testcase: &mut Testcase<I>) -> Result<(), Error> {
+        let divergences = core::mem::take(&mut self.divergences);
+        if !divergences.is_empty() {
+            testcase.add_metadata(DiffOracleMetadata::new(divergences));
+        }
+        Ok(())
+    }
+
+    fn discard_metadata(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.divergences.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        bolts::{
+            rands::StdRand,
+            tuples::{tuple_list, tuple_list_type},
+        },
+        corpus::{InMemoryCorpus, Testcase},
+        events::NopEventManager,
+        executors::{DiffExecutor, DiffExitKind, Executor, ExitKind, HasObservers},
+        feedbacks::{
+            diff_oracle::{DiffOracleFeedback, DiffOracleMetadata, Divergence, Normalizer},
+            Feedback,
+        },
+        inputs::BytesInput,
+        observers::{ObserversTuple, StdOutObserver, ValueObserver},
+        state::{HasMetadata, StdState},
+        Error,
+    };
+
+    type TestState =
+        StdState<InMemoryCorpus<BytesInput>, BytesInput, StdRand, InMemoryCorpus<BytesInput>>;
+
+    /// Prints a fixed line for every input, and captures it in its own [`StdOutObserver`]
+    #[derive(Debug)]
+    struct EchoExecutor {
+        line: &'static str,
+        exit_kind: ExitKind,
+        observers: tuple_list_type!(StdOutObserver),
+    }
+
+    impl EchoExecutor {
+        fn new(name: &str, line: &'static str, exit_kind: ExitKind) -> Self {
+            Self {
+                line,
+                exit_kind,
+                observers: tuple_list!(StdOutObserver::new(name.into())),
+            }
+        }
+    }
+
+    impl<EM, S, Z> Executor<EM, BytesInput, S, Z> for EchoExecutor {
+        fn run_target(
+            &mut self,
+            _fuzzer: &mut Z,
+            _state: &mut S,
+            _mgr: &mut EM,
+            _input: &BytesInput,
+        ) -> Result<ExitKind, Error> {
+            self.observers.0.stdout = Some(self.line.into());
+            Ok(self.exit_kind.clone())
+        }
+    }
+
+    impl HasObservers<BytesInput, tuple_list_type!(StdOutObserver), TestState> for EchoExecutor {
+        fn observers(&self) -> &tuple_list_type!(StdOutObserver) {
+            &self.observers
+        }
+
+        fn observers_mut(&mut self) -> &mut tuple_list_type!(StdOutObserver) {
+            &mut self.observers
+        }
+    }
+
+    #[test]
+    fn test_normalizer() {
+        let normalizer = Normalizer::new().scrub_addresses().scrub_timestamps();
+        assert_eq!(
+            normalizer.normalize("[12:01:02] ptr at 0xdeadbeef"),
+            "[TIMESTAMP] ptr at 0xADDR"
+        );
+        assert!(Normalizer::new().scrub("(", "").is_err());
+    }
+
+    #[test]
+    fn test_diff_oracle() {
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+        let mut mgr = NopEventManager {};
+        let input = BytesInput::new(vec![0]);
+
+        let mut out1 = StdOutObserver::new("out1".into());
+        let mut out2 = StdOutObserver::new("out2".into());
+        out1.stdout = Some("alloc at 0x1000".into());
+        out2.stdout = Some("alloc at 0x2000".into());
+        let mut ret1 = Some(0_i32);
+        let mut ret2 = Some(1_i32);
+        let observers = tuple_list!(
+            out1,
+            out2,
+            ValueObserver::new("ret1", &mut ret1),
+            ValueObserver::new("ret2", &mut ret2)
+        );
+
+        let mut oracle = DiffOracleFeedback::new("oracle")
+            .compare_stdout("out1", "out2")
+            .normalizer(Normalizer::new().scrub_addresses())
+            .compare_return_values::<i32>("ret1", "ret2");
+
+        let exit_kind = ExitKind::Diff {
+            primary: DiffExitKind::Ok,
+            secondary: DiffExitKind::Crash,
+        };
+        assert!(oracle
+            .is_interesting(&mut state, &mut mgr, &input, &observers, &exit_kind)
+            .unwrap());
+
+        let mut testcase = Testcase::<BytesInput>::new(input);
+        oracle.append_metadata(&mut state, &mut testcase).unwrap();
+        let metadata = testcase.metadata().get::<DiffOracleMetadata>().unwrap();
+        assert_eq!(
+            metadata.divergences,
+            vec![
+                Divergence::ExitKind {
+                    primary: DiffExitKind::Ok,
+                    secondary: DiffExitKind::Crash,
+                },
+                Divergence::ReturnValue {
+                    primary: Some("0".into()),
+                    secondary: Some("1".into()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_oracle_with_diff_executor() {
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+        let mut mgr = NopEventManager {};
+        let input = BytesInput::new(vec![0]);
+
+        let mut executor = DiffExecutor::new::<NopEventManager, BytesInput, TestState, ()>(
+            EchoExecutor::new("out1", "alloc at 0x1000", ExitKind::Ok),
+            EchoExecutor::new("out2", "alloc at 0x2000, done", ExitKind::Ok),
+        );
+        let mut oracle = DiffOracleFeedback::new("oracle")
+            .compare_stdout("out1", "out2")
+            .normalizer(Normalizer::new().scrub_addresses());
+
+        // The observers of each side are run around its own run by the executor
+        let exit_kind = executor
+            .run_target(&mut (), &mut state, &mut mgr, &input)
+            .unwrap();
+        assert_eq!(exit_kind, ExitKind::Ok);
+        let observers = HasObservers::<BytesInput, _, TestState>::observers(&executor);
+
+        // The observer of the secondary executor is found as well
+        assert!(oracle
+            .is_interesting(&mut state, &mut mgr, &input, observers, &exit_kind)
+            .unwrap());
+        assert_eq!(
+            oracle.divergences(),
+            [Divergence::StdOut {
+                primary: Some("alloc at 0xADDR".into()),
+                secondary: Some("alloc at 0xADDR, done".into()),
+            }]
+        );
+        assert_eq!(
+            observers.secondary().0.stdout.as_deref(),
+            Some("alloc at 0x2000, done")
+        );
+    }
+}
@@ -1,7 +1,11 @@
 //! Diff Feedback, comparing the content of two observers of the same type.
+//! The [`MajorityVoteFeedback`] compares any number of implementations, and picks out the outliers.
 //!
 
-use alloc::string::{String, ToString};
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
 use core::{
     fmt::{self, Debug, Formatter},
     marker::PhantomData,
@@ -10,11 +14,12 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     bolts::tuples::{MatchName, Named},
+    corpus::Testcase,
     events::EventFirer,
     executors::ExitKind,
     feedbacks::Feedback,
     inputs::Input,
-    observers::{Observer, ObserversTuple},
+    observers::{ExitKindsObserver, Observer, ObserversTuple},
     state::{HasClientPerfMonitor, HasMetadata},
     Error,
 };
@@ -151,6 +156,176 @@ where
     }
 }
 
+/// The implementations that disagree with the majority, attached to the testcase by a [`MajorityVoteFeedback`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OutlierMetadata {
+    /// The names of the implementations that disagree with the majority
+    pub outliers: Vec<String>,
+    /// The names of the implementations that agree with each other.
+    /// If there is no single largest group of agreeing implementations, this is empty, and all implementations are outliers.
+    pub majority: Vec<String>,
+}
+
+crate::impl_serdeany!(OutlierMetadata);
+
+/// A [`MajorityVoteFeedback`] compares the outcome of any number of implementations, run by a [`crate::executors::TupleDiffExecutor`].
+/// The outcome of an implementation is its [`ExitKind`], taken from the [`ExitKindsObserver`], if any,
+/// and the content of its observer of type `O`, if observers to compare were set with [`MajorityVoteFeedback::compare_observers`].
+/// If the implementations disagree, the input is interesting, and the outliers are stored in the [`OutlierMetadata`] of the testcase.
+#[derive(Debug)]
+pub struct MajorityVoteFeedback<O> {
+    name: String,
+    implementations: Vec<String>,
+    observer_names: Vec<String>,
+    outliers: Option<OutlierMetadata>,
+    phantom: PhantomData<O>,
+}
+
+impl MajorityVoteFeedback<()> {
+    /// Create a new [`MajorityVoteFeedback`] for the given implementations, in the order of the executors.
+    #[must_use]
+    pub fn new(name: &str, implementations: &[&str]) -> Self {
+        Self {
+            name: name.to_string(),
+            implementations: implementations.iter().map(ToString::to_string).collect(),
+            observer_names: vec![],
+            outliers: None,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<O> MajorityVoteFeedback<O> {
+    /// Also compare the observers with the given names, one per implementation, in the order of the executors.
+    pub fn compare_observers<P>(self, names: &[&str]) -> Result<MajorityVoteFeedback<P>, Error> {
+        if names.len() != self.implementations.len() {
+            return Err(Error::illegal_argument(format!(
+                "MajorityVoteFeedback: got {} observers for {} implementations",
+                names.len(),
+                self.implementations.len()
+            )));
+        }
+        Ok(MajorityVoteFeedback {
+            name: self.name,
+            implementations: self.implementations,
+            observer_names: names.iter().map(ToString::to_string).collect(),
+            outliers: None,
+            phantom: PhantomData,
+        })
+    }
+
+    /// Groups the implementations by their outcome, and returns the outliers, if they disagree.
+    fn vote<F>(&self, agree: F) -> Option<OutlierMetadata>
+    where
+        F: Fn(usize, usize) -> bool,
+    {
+        let count = self.implementations.len();
+        let mut groups: Vec<Vec<usize>> = vec![];
+        for idx in 0..count {
+            match groups.iter_mut().find(|group| agree(group[0], idx)) {
+                Some(group) => group.push(idx),
+                None => groups.push(vec![idx]),
+            }
+        }
+        if groups.len() <= 1 {
+            return None;
+        }
+
+        let largest = groups.iter().map(Vec::len).max().unwrap_or(0);
+        let mut largest_groups = groups.iter().filter(|group| group.len() == largest);
+        let majority = match (largest_groups.next(), largest_groups.next()) {
+            (Some(group), None) => group.clone(),
+            _ => vec![],
+        };
+        let names = |indices: &mut dyn Iterator<Item = usize>| -> Vec<String> {
+            indices
+                .map(|idx| self.implementations[idx].clone())
+                .collect()
+        };
+        Some(OutlierMetadata {
+            outliers: names(&mut (0..count).filter(|idx| !majority.contains(idx))),
+            majority: names(&mut majority.iter().copied()),
+        })
+    }
+
+    /// The outliers found in the last run, if the implementations disagreed
+    #[must_use]
+    pub fn outliers(&self) -> Option<&OutlierMetadata> {
+        self.outliers.as_ref()
+    }
+}
+
+impl<O> Named for MajorityVoteFeedback<O> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl<I, O, S> Feedback<I, S> for MajorityVoteFeedback<O>
+where
+    I: Input,
+    S: HasMetadata + HasClientPerfMonitor,
+    O: PartialEq + Debug,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<I>,
+        OT: ObserversTuple<I, S> + MatchName,
+    {
+        let exit_kinds = observers
+            .match_name::<ExitKindsObserver>(ExitKindsObserver::NAME)
+            .map(ExitKindsObserver::exit_kinds);
+        if let Some(exit_kinds) = exit_kinds {
+            if exit_kinds.len() != self.implementations.len() {
+                return Err(Error::illegal_state(format!(
+                    "MajorityVoteFeedback: got {} exit kinds for {} implementations",
+                    exit_kinds.len(),
+                    self.implementations.len()
+                )));
+            }
+        }
+
+        let compared = self
+            .observer_names
+            .iter()
+            .map(|name| {
+                observers.match_name::<O>(name).ok_or_else(|| {
+                    Error::illegal_argument(format!(
+                        "MajorityVoteFeedback: observer {} not found",
+                        name
+                    ))
+                })
+            })
+            .collect::<Result<Vec<&O>, Error>>()?;
+
+        self.outliers = self.vote(|a, b| {
+            exit_kinds.map_or(true, |kinds| kinds[a] == kinds[b])
+                && (compared.is_empty() || compared[a] == compared[b])
+        });
+        Ok(self.outliers.is_some())
+    }
+
+    fn append_metadata(&mut self, _state: &mut S, testcase: &mut Testcase<I>) -> Result<(), Error> {
+        if let Some(outliers) = self.outliers.take() {
+            testcase.add_metadata(outliers);
+        }
+        Ok(())
+    }
+
+    fn discard_metadata(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.outliers = None;
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
@@ -160,10 +335,13 @@ mod tests {
         },
         events::EventFirer,
         executors::ExitKind,
-        feedbacks::{differential::DiffResult, DiffFeedback, Feedback},
+        feedbacks::{
+            differential::{DiffResult, MajorityVoteFeedback, OutlierMetadata},
+            DiffFeedback, Feedback,
+        },
         inputs::{BytesInput, Input},
         monitors::ClientPerfMonitor,
-        observers::Observer,
+        observers::{ExitKindsObserver, Observer},
         state::{HasClientPerfMonitor, HasMetadata},
     };
     use alloc::string::{String, ToString};
@@ -270,4 +448,49 @@ mod tests {
     fn test_diff_neq() {
         test_diff(false);
     }
+
+    #[test]
+    fn test_majority_vote() {
+        let mut nop_state = NopState;
+
+        let mut exit_kinds = ExitKindsObserver::new();
+        exit_kinds.set_exit_kinds(&[ExitKind::Ok, ExitKind::Ok, ExitKind::Ok]);
+        let observers = tuple_list![
+            exit_kinds,
+            NopObserver::new("a", true),
+            NopObserver::new("b", false),
+            NopObserver::new("c", true)
+        ];
+
+        let mut feedback = MajorityVoteFeedback::new("vote", &["A", "B", "C"])
+            .compare_observers::<NopObserver>(&["a", "b", "c"])
+            .unwrap();
+        assert!(feedback
+            .is_interesting(
+                &mut nop_state,
+                &mut NopEventFirer {},
+                &BytesInput::new(vec![0]),
+                &observers,
+                &ExitKind::Ok
+            )
+            .unwrap());
+        assert_eq!(
+            feedback.outliers(),
+            Some(&OutlierMetadata {
+                outliers: vec!["B".to_string()],
+                majority: vec!["A".to_string(), "C".to_string()],
+            })
+        );
+
+        let mut feedback = MajorityVoteFeedback::new("vote", &["A", "B", "C"]);
+        assert!(!feedback
+            .is_interesting(
+                &mut nop_state,
+                &mut NopEventFirer {},
+                &BytesInput::new(vec![0]),
+                &observers,
+                &ExitKind::Ok
+            )
+            .unwrap());
+    }
 }
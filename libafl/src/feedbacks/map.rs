@@ -16,7 +16,7 @@ use crate::{
     executors::ExitKind,
     feedbacks::{Feedback, HasObserverName},
     inputs::Input,
-    monitors::UserStats,
+    monitors::{AggregatorOps, UserStats, UserStatsValue},
     observers::{MapObserver, ObserversTuple},
     state::{HasClientPerfMonitor, HasMetadata, HasNamedMetadata},
     Error,
@@ -523,7 +523,12 @@ where
                 state,
                 Event::UpdateUserStats {
                     name: self.stats_name.to_string(),
-                    value: UserStats::Ratio(filled, len as u64),
+                    // All clients fill the same map, and share their testcases, so the coverage of the best client
+                    // is the global figure. The ratios of overlapping maps cannot be unioned.
+                    value: UserStats::new(
+                        UserStatsValue::Ratio(filled, len as u64),
+                        AggregatorOps::Max,
+                    ),
                     phantom: PhantomData,
                 },
             )?;
@@ -695,7 +700,12 @@ where
                 state,
                 Event::UpdateUserStats {
                     name: self.stats_name.to_string(),
-                    value: UserStats::Ratio(filled, len as u64),
+                    // All clients fill the same map, and share their testcases, so the coverage of the best client
+                    // is the global figure. The ratios of overlapping maps cannot be unioned.
+                    value: UserStats::new(
+                        UserStatsValue::Ratio(filled, len as u64),
+                        AggregatorOps::Max,
+                    ),
                     phantom: PhantomData,
                 },
             )?;
@@ -5,7 +5,12 @@ pub mod map;
 pub use map::*;
 
 pub mod differential;
-pub use differential::DiffFeedback;
+pub use differential::{DiffFeedback, MajorityVoteFeedback, OutlierMetadata};
+
+#[cfg(feature = "std")]
+pub mod diff_oracle;
+#[cfg(feature = "std")]
+pub use diff_oracle::{DiffOracleFeedback, DiffOracleMetadata, Divergence, Normalizer};
 #[cfg(feature = "std")]
 pub mod concolic;
 #[cfg(feature = "std")]
@@ -18,6 +23,27 @@ pub use new_hash_feedback::NewHashFeedback;
 #[cfg(feature = "std")]
 pub use new_hash_feedback::NewHashFeedbackMetadata;
 
+#[cfg(feature = "std")]
+pub mod crash_buckets;
+#[cfg(feature = "std")]
+pub use crash_buckets::{CrashBucket, CrashBucketFeedback, CrashBucketsMetadata};
+
+pub mod target_sites;
+pub use target_sites::{
+    TargetHitFeedback, TargetSiteReached, TargetSitesMetadata, TargetsReachedMetadata,
+};
+
+pub mod patch;
+pub use patch::PatchFeedback;
+
+pub mod custom_exit;
+pub use custom_exit::CustomExitFeedback;
+
+#[cfg(feature = "std")]
+pub mod stateful;
+#[cfg(feature = "std")]
+pub use stateful::StateFeedback;
+
 #[cfg(feature = "nautilus")]
 pub mod nautilus;
 #[cfg(feature = "nautilus")]
@@ -1141,7 +1167,7 @@ pub mod pybind {
                             dont_look_at_this2.clone(),
                             input.bytes(),
                             dont_look_at_this.clone(),
-                            PythonExitKind::from(*exit_kind),
+                            PythonExitKind::from(exit_kind.clone()),
                         ),
                     )?
                     .extract(py)?;
@@ -0,0 +1,247 @@
+//! The [`PatchFeedback`] keeps executions reaching lines changed by a patch, see [`crate::schedulers::patch`].
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::marker::PhantomData;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bolts::tuples::Named,
+    corpus::Testcase,
+    events::{Event, EventFirer},
+    executors::ExitKind,
+    feedbacks::{Feedback, HasObserverName},
+    inputs::Input,
+    monitors::{AggregatorOps, UserStats, UserStatsValue},
+    observers::{MapObserver, ObserversTuple},
+    schedulers::{PatchMetadata, PatchTestcaseMetadata},
+    state::{HasClientPerfMonitor, HasMetadata},
+    Error,
+};
+
+/// A [`PatchFeedback`] is interesting if an execution reaches a changed line of the [`struct@PatchMetadata`]
+/// no execution reached before, the bonus for changed code on top of the coverage feedback.
+/// It reports the ratio of reached changed lines in the user stats `changed lines`,
+/// and records the number of changed edges of each testcase for the [`crate::schedulers::PatchTestcaseScore`].
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PatchFeedback<O> {
+    name: String,
+    observer_name: String,
+    /// The changed edges of the last execution, `None` until [`Feedback::is_interesting`] ran for it
+    changed_edges: Option<Vec<usize>>,
+    phantom: PhantomData<O>,
+}
+
+impl<I, O, S> Feedback<I, S> for PatchFeedback<O>
+where
+    I: Input,
+    O: MapObserver,
+    S: HasClientPerfMonitor + HasMetadata,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting<EM, OT>(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<I>,
+        OT: ObserversTuple<I, S>,
+    {
+        let observer = observers
+            .match_name::<O>(&self.observer_name)
+            .ok_or_else(|| {
+                Error::key_not_found(format!(
+                    "PatchFeedback: observer {} not found",
+                    self.observer_name
+                ))
+            })?;
+        let meta = state
+            .metadata_mut()
+            .get_mut::<PatchMetadata>()
+            .ok_or_else(|| Error::key_not_found("PatchMetadata not found".to_string()))?;
+
+        let initial = observer.initial();
+        let size = observer.usable_count();
+        let changed_edges: Vec<usize> = meta
+            .changed_edges()
+            .filter(|edge| *edge < size && *observer.get(*edge) != initial)
+            .collect();
+        let reached = meta.reach(&changed_edges);
+        self.changed_edges = Some(changed_edges);
+        if !reached {
+            return Ok(false);
+        }
+
+        let value = UserStatsValue::Ratio(meta.reached_lines() as u64, meta.lines() as u64);
+        manager.fire(
+            state,
+            Event::UpdateUserStats {
+                name: self.name.clone(),
+                value: UserStats::new(value, AggregatorOps::Max),
+                phantom: PhantomData,
+            },
+        )?;
+        Ok(true)
+    }
+
+    fn append_metadata(&mut self, _state: &mut S, testcase: &mut Testcase<I>) -> Result<(), Error> {
+        if let Some(changed_edges) = self.changed_edges.take() {
+            testcase.add_metadata(PatchTestcaseMetadata {
+                changed_edges: changed_edges.len(),
+            });
+        }
+        Ok(())
+    }
+
+    fn discard_metadata(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.changed_edges = None;
+        Ok(())
+    }
+}
+
+impl<O> Named for PatchFeedback<O> {
+    #[inline]
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl<O> HasObserverName for PatchFeedback<O> {
+    #[inline]
+    fn observer_name(&self) -> &str {
+        &self.observer_name
+    }
+}
+
+impl<O> PatchFeedback<O>
+where
+    O: MapObserver,
+{
+    /// Creates a new [`PatchFeedback`] for the edges map observer
+    #[must_use]
+    pub fn new(observer: &O) -> Self {
+        Self {
+            name: "changed lines".to_string(),
+            observer_name: observer.name().to_string(),
+            changed_edges: None,
+            phantom: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        bolts::{rands::StdRand, tuples::tuple_list},
+        corpus::{InMemoryCorpus, Testcase},
+        events::NopEventManager,
+        executors::ExitKind,
+        feedbacks::{patch::PatchFeedback, Feedback},
+        fuzzer::{ChangedLines, EdgeLocations, SourceLocation},
+        inputs::BytesInput,
+        observers::StdMapObserver,
+        schedulers::{
+            testcase_score::TestcaseScore, PatchMetadata, PatchTestcaseMetadata, PatchTestcaseScore,
+        },
+        state::{HasMetadata, StdState},
+        Error,
+    };
+
+    type TestState =
+        StdState<InMemoryCorpus<BytesInput>, BytesInput, StdRand, InMemoryCorpus<BytesInput>>;
+
+    /// A base score of `1.0` for all testcases
+    struct OneScore;
+
+    impl TestcaseScore<BytesInput, TestState> for OneScore {
+        fn compute(_entry: &mut Testcase<BytesInput>, _state: &TestState) -> Result<f64, Error> {
+            Ok(1.0)
+        }
+    }
+
+    #[test]
+    fn test_patch_feedback() {
+        let diff = "diff --git a/src/parser.c b/src/parser.c\n\
+                    --- a/src/parser.c\n\
+                    +++ b/src/parser.c\n\
+                    @@ -10,4 +10,5 @@ int parse_header(char *buf)\n\
+                    \x20 int len = buf[0];\n\
+                    -  if (len > 16) {\n\
+                    +  if (len > 32) {\n\
+                    +    log_len(len);\n\
+                    \x20   return -1;\n\
+                    -  }\n\
+                    \x20 }\n";
+        let changed = ChangedLines::from_diff(diff);
+        assert_eq!(changed.lines("parser.c"), [11, 12, 14]);
+        assert_eq!(changed.functions(), ["parse_header"]);
+
+        let mut locations = EdgeLocations::new();
+        for (edge, line) in [(1, 10), (2, 11), (3, 12), (4, 12), (5, 30)] {
+            locations.insert(
+                edge,
+                SourceLocation {
+                    function: "parse_header".into(),
+                    file: Some("/home/ci/src/parser.c".into()),
+                    line,
+                },
+            );
+        }
+        let changed_edges = locations.changed_edges(&changed);
+        assert_eq!(changed_edges.len(), 3);
+        assert_eq!(changed_edges[1].1, changed_edges[2].1);
+
+        let mut map = [0_u8; 8];
+        let map_ptr = map.as_mut_ptr();
+        let observer = StdMapObserver::new("edges", &mut map);
+        let mut feedback = PatchFeedback::new(&observer);
+        let observers = tuple_list!(observer);
+
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+        state.add_metadata(PatchMetadata::with_lines(changed_edges));
+        let mut mgr = NopEventManager {};
+        let input = BytesInput::new(vec![]);
+
+        for (edges, expected) in [(&[1, 5][..], false), (&[1, 3], true), (&[4], false)] {
+            unsafe {
+                for i in 0..8 {
+                    *map_ptr.add(i) = 0;
+                }
+                for edge in edges {
+                    *map_ptr.add(*edge) = 1;
+                }
+            }
+            let interesting = feedback
+                .is_interesting(&mut state, &mut mgr, &input, &observers, &ExitKind::Ok)
+                .unwrap();
+            assert_eq!(interesting, expected);
+            let mut testcase: Testcase<BytesInput> = Testcase::new(input.clone());
+            feedback.append_metadata(&mut state, &mut testcase).unwrap();
+            let score =
+                PatchTestcaseScore::<OneScore, _, _>::compute(&mut testcase, &state).unwrap();
+            let boosted = edges.iter().any(|edge| (2..=4).contains(edge));
+            assert_eq!(score > 1.0, boosted);
+        }
+
+        let meta = state.metadata().get::<PatchMetadata>().unwrap();
+        assert_eq!((meta.reached_lines(), meta.lines()), (1, 2));
+
+        // Without an execution, e.g. for an imported testcase, there are no changed edges to record
+        let mut testcase: Testcase<BytesInput> = Testcase::new(input);
+        feedback.append_metadata(&mut state, &mut testcase).unwrap();
+        assert!(testcase.metadata().get::<PatchTestcaseMetadata>().is_none());
+    }
+}
@@ -0,0 +1,194 @@
+//! The [`StateFeedback`] grows the inferred state machine of a network service, see [`crate::schedulers::stateful`].
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::marker::PhantomData;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bolts::tuples::Named,
+    corpus::Testcase,
+    events::{Event, EventFirer},
+    executors::ExitKind,
+    feedbacks::{Feedback, HasObserverName},
+    inputs::Input,
+    monitors::{AggregatorOps, UserStats, UserStatsValue},
+    observers::{ObserversTuple, ResponseCodeObserver},
+    schedulers::{StateMachineMetadata, StateSequenceMetadata},
+    state::{HasClientPerfMonitor, HasMetadata},
+    Error,
+};
+
+/// A [`StateFeedback`] adds the protocol states of every session to the [`struct@StateMachineMetadata`],
+/// and is interesting if a session went through a new state or transition.
+/// It reports the number of states in the user stats `states`,
+/// and records the states of each testcase for the [`crate::schedulers::StateScheduler`].
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StateFeedback {
+    name: String,
+    observer_name: String,
+    /// The states of the last session, `None` until [`Feedback::is_interesting`] ran for it
+    states: Option<Vec<u32>>,
+}
+
+impl<I, S> Feedback<I, S> for StateFeedback
+where
+    I: Input,
+    S: HasClientPerfMonitor + HasMetadata,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        if !state.has_metadata::<StateMachineMetadata>() {
+            state.add_metadata(StateMachineMetadata::new());
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting<EM, OT>(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<I>,
+        OT: ObserversTuple<I, S>,
+    {
+        let observer = observers
+            .match_name::<ResponseCodeObserver>(&self.observer_name)
+            .ok_or_else(|| {
+                Error::key_not_found(format!(
+                    "StateFeedback: observer {} not found",
+                    self.observer_name
+                ))
+            })?;
+        let states = observer.states();
+
+        let machine = state
+            .metadata_mut()
+            .get_mut::<StateMachineMetadata>()
+            .ok_or_else(|| Error::key_not_found("StateMachineMetadata not found".to_string()))?;
+        let new_states = machine.add_sequence(&states);
+        self.states = Some(states);
+        if !new_states {
+            return Ok(false);
+        }
+
+        let value = UserStatsValue::Number(machine.state_count() as u64);
+        manager.fire(
+            state,
+            Event::UpdateUserStats {
+                name: self.name.clone(),
+                value: UserStats::new(value, AggregatorOps::Max),
+                phantom: PhantomData,
+            },
+        )?;
+        Ok(true)
+    }
+
+    fn append_metadata(&mut self, _state: &mut S, testcase: &mut Testcase<I>) -> Result<(), Error> {
+        if let Some(states) = self.states.take() {
+            testcase.add_metadata(StateSequenceMetadata { states });
+        }
+        Ok(())
+    }
+
+    fn discard_metadata(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.states = None;
+        Ok(())
+    }
+}
+
+impl Named for StateFeedback {
+    #[inline]
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl HasObserverName for StateFeedback {
+    #[inline]
+    fn observer_name(&self) -> &str {
+        &self.observer_name
+    }
+}
+
+impl StateFeedback {
+    /// Creates a new [`StateFeedback`] for the response code observer
+    #[must_use]
+    pub fn new(observer: &ResponseCodeObserver) -> Self {
+        Self {
+            name: "states".to_string(),
+            observer_name: observer.name().to_string(),
+            states: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        bolts::{rands::StdRand, tuples::tuple_list},
+        corpus::{InMemoryCorpus, Testcase},
+        events::NopEventManager,
+        executors::ExitKind,
+        feedbacks::{stateful::StateFeedback, Feedback},
+        inputs::MessageSequenceInput,
+        observers::{ResponseCodeExtractor, ResponseCodeObserver},
+        schedulers::{StateMachineMetadata, StateSequenceMetadata},
+        state::{HasMetadata, StdState},
+    };
+
+    #[test]
+    fn test_state_feedback() {
+        let observer = ResponseCodeObserver::new("codes", ResponseCodeExtractor::AsciiStatus);
+        let mut feedback = StateFeedback::new(&observer);
+        let mut observers = tuple_list!(observer);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<MessageSequenceInput>::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+        Feedback::<MessageSequenceInput, _>::init_state(&mut feedback, &mut state).unwrap();
+        let mut mgr = NopEventManager {};
+        let input = MessageSequenceInput::default();
+
+        let sessions: [(&[&[u8]], bool); 4] = [
+            (&[b"220 ready", b"331 password?", b"230 logged in"], true),
+            (&[b"220 ready", b"331 password?"], false),
+            (&[b"220 ready", b"", b"530 denied"], true),
+            (&[b"220 ready", b"", b"530 denied"], false),
+        ];
+        for (responses, expected) in sessions {
+            observers.0.observe(responses);
+            let interesting = feedback
+                .is_interesting(&mut state, &mut mgr, &input, &observers, &ExitKind::Ok)
+                .unwrap();
+            assert_eq!(interesting, expected);
+        }
+
+        let mut testcase: Testcase<MessageSequenceInput> = Testcase::new(input.clone());
+        feedback.append_metadata(&mut state, &mut testcase).unwrap();
+        let meta = testcase.metadata().get::<StateSequenceMetadata>().unwrap();
+        assert_eq!(meta.states, [220, 220, 530]);
+
+        // Without a session, e.g. for an imported testcase, there are no states to record
+        let mut testcase: Testcase<MessageSequenceInput> = Testcase::new(input);
+        feedback.append_metadata(&mut state, &mut testcase).unwrap();
+        assert!(testcase.metadata().get::<StateSequenceMetadata>().is_none());
+
+        let machine = state.metadata().get::<StateMachineMetadata>().unwrap();
+        assert_eq!(machine.state_count(), 5);
+        // The message without a response code stays in its state
+        assert!(machine.has_transition(220, 220));
+        assert!(machine.has_transition(220, 530));
+        assert!(!machine.has_transition(331, 530));
+    }
+}
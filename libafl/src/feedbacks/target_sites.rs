@@ -0,0 +1,299 @@
+//! The [`TargetHitFeedback`] reports executions reaching the target sites of a [`TargetSitesObserver`].
+//!
+//! Used as objective, each solution records the reached targets in its [`TargetsReachedMetadata`].
+//! The [`TargetSitesMetadata`] of the state counts the hits of each target, to list the targets still unreached,
+//! and the ratio of reached targets is reported as user stats.
+//! To stop the campaign once all targets are reached, the fuzzing loop checks [`TargetSitesMetadata::all_reached`].
+//! Executions stopped early at a target site exit with [`ExitKind::custom`] and a [`TargetSiteReached`] payload.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::marker::PhantomData;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bolts::tuples::Named,
+    corpus::Testcase,
+    events::{Event, EventFirer},
+    executors::ExitKind,
+    feedbacks::{Feedback, HasObserverName},
+    inputs::Input,
+    monitors::{AggregatorOps, UserStats, UserStatsValue},
+    observers::{ObserversTuple, TargetSitesObserver},
+    state::{HasClientPerfMonitor, HasMetadata},
+    Error,
+};
+
+/// The target sites reached by a testcase
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TargetsReachedMetadata {
+    /// The program counters or basic block ids of the reached targets
+    pub targets: Vec<u64>,
+}
+
+crate::impl_serdeany!(TargetsReachedMetadata);
+
+/// The payload of the [`ExitKind::Custom`] of an execution stopped at the first target site it reached,
+/// e.g. by `libafl_targets::run_until_target_site`
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TargetSiteReached {
+    /// The index of the reached target site
+    pub idx: usize,
+}
+
+crate::impl_serdeany!(TargetSiteReached);
+
+/// The hits of all target sites during the campaign
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct TargetSitesMetadata {
+    /// The program counters or basic block ids of the targets
+    pub targets: Vec<u64>,
+    /// How many executions reached each target
+    pub hits: Vec<u64>,
+}
+
+crate::impl_serdeany!(TargetSitesMetadata);
+
+impl TargetSitesMetadata {
+    /// The targets reached at least once
+    #[must_use]
+    pub fn reached(&self) -> Vec<u64> {
+        self.targets_where(|hits| hits > 0)
+    }
+
+    /// The targets no execution reached yet
+    #[must_use]
+    pub fn unreached(&self) -> Vec<u64> {
+        self.targets_where(|hits| hits == 0)
+    }
+
+    /// If every target was reached at least once
+    #[must_use]
+    pub fn all_reached(&self) -> bool {
+        self.hits.iter().all(|hits| *hits > 0)
+    }
+
+    fn targets_where<F>(&self, f: F) -> Vec<u64>
+    where
+        F: Fn(u64) -> bool,
+    {
+        self.targets
+            .iter()
+            .zip(&self.hits)
+            .filter(|(_, hits)| f(**hits))
+            .map(|(target, _)| *target)
+            .collect()
+    }
+}
+
+/// A [`TargetHitFeedback`] is interesting if an execution reaches a target site.
+/// By default, only reaching a target for the first time is interesting, to not flood the solutions.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TargetHitFeedback<'a> {
+    name: String,
+    observer_name: String,
+    targets: Vec<u64>,
+    every_hit: bool,
+    reached: Vec<u64>,
+    phantom: PhantomData<TargetSitesObserver<'a>>,
+}
+
+impl<'a, I, S> Feedback<I, S> for TargetHitFeedback<'a>
+where
+    I: Input,
+    S: HasClientPerfMonitor + HasMetadata,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        // Keep the hits of a restored state
+        if !state.has_metadata::<TargetSitesMetadata>() {
+            state.add_metadata(TargetSitesMetadata {
+                targets: self.targets.clone(),
+                hits: vec![0; self.targets.len()],
+            });
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting<EM, OT>(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<I>,
+        OT: ObserversTuple<I, S>,
+    {
+        self.reached.clear();
+        let observer = observers
+            .match_name::<TargetSitesObserver<'a>>(&self.observer_name)
+            .ok_or_else(|| {
+                Error::key_not_found(format!(
+                    "TargetHitFeedback: observer {} not found",
+                    self.observer_name
+                ))
+            })?;
+        let reached = observer.reached();
+        if reached.is_empty() {
+            return Ok(false);
+        }
+
+        let meta = state
+            .metadata_mut()
+            .get_mut::<TargetSitesMetadata>()
+            .unwrap();
+        let mut new_target = false;
+        for idx in &reached {
+            new_target |= meta.hits[*idx] == 0;
+            meta.hits[*idx] += 1;
+            self.reached.push(meta.targets[*idx]);
+        }
+
+        if new_target {
+            let reached_count = meta.hits.iter().filter(|hits| **hits > 0).count();
+            let len = meta.targets.len();
+            manager.fire(
+                state,
+                Event::UpdateUserStats {
+                    name: self.name.clone(),
+                    value: UserStats::new(
+                        UserStatsValue::Ratio(reached_count as u64, len as u64),
+                        AggregatorOps::Max,
+                    ),
+                    phantom: PhantomData,
+                },
+            )?;
+        }
+        Ok(new_target || self.every_hit)
+    }
+
+    fn append_metadata(&mut self, _state: &mut S, testcase: &mut Testcase<I>) -> Result<(), Error> {
+        if !self.reached.is_empty() {
+            testcase.add_metadata(TargetsReachedMetadata {
+                targets: core::mem::take(&mut self.reached),
+            });
+        }
+        Ok(())
+    }
+
+    fn discard_metadata(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.reached.clear();
+        Ok(())
+    }
+}
+
+impl<'a> Named for TargetHitFeedback<'a> {
+    #[inline]
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl<'a> HasObserverName for TargetHitFeedback<'a> {
+    #[inline]
+    fn observer_name(&self) -> &str {
+        &self.observer_name
+    }
+}
+
+impl<'a> TargetHitFeedback<'a> {
+    /// Creates a new [`TargetHitFeedback`] for the [`TargetSitesObserver`].
+    /// The ratio of reached targets is reported in the user stats `targets`.
+    #[must_use]
+    pub fn new(observer: &TargetSitesObserver<'a>) -> Self {
+        Self {
+            name: "targets".to_string(),
+            observer_name: observer.name().to_string(),
+            targets: observer.targets().to_vec(),
+            every_hit: false,
+            reached: vec![],
+            phantom: PhantomData,
+        }
+    }
+
+    /// Every execution reaching a target is interesting, not only the first one reaching it
+    #[must_use]
+    pub fn with_every_hit(mut self) -> Self {
+        self.every_hit = true;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        bolts::{rands::StdRand, tuples::tuple_list},
+        corpus::{InMemoryCorpus, Testcase},
+        events::NopEventManager,
+        executors::ExitKind,
+        feedbacks::{
+            target_sites::{TargetHitFeedback, TargetSitesMetadata, TargetsReachedMetadata},
+            Feedback,
+        },
+        inputs::BytesInput,
+        observers::{ObserversTuple, TargetSitesObserver},
+        state::{HasMetadata, StdState},
+    };
+
+    #[test]
+    fn test_target_hit_feedback() {
+        let mut hits = [0_u8; 4];
+        let hits_ptr = hits.as_mut_ptr();
+        let observer = unsafe {
+            TargetSitesObserver::new_from_ptr("targets", vec![0x1000, 0x2000, 0x3000], hits_ptr)
+        };
+        let mut feedback = TargetHitFeedback::new(&observer);
+        let mut observers = tuple_list!(observer);
+
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+        let mut mgr = NopEventManager {};
+        let input = BytesInput::new(vec![]);
+
+        Feedback::<BytesInput, _>::init_state(&mut feedback, &mut state).unwrap();
+        let meta = state.metadata().get::<TargetSitesMetadata>().unwrap();
+        assert_eq!(meta.unreached(), [0x1000, 0x2000, 0x3000]);
+
+        for (hit, expected) in [
+            (None, false),
+            (Some(1), true),
+            (Some(1), false),
+            (Some(0), true),
+        ] {
+            observers.pre_exec_all(&mut state, &input).unwrap();
+            if let Some(idx) = hit {
+                // Like the instrumentation, reaching the target
+                unsafe { *hits_ptr.add(idx) = 1 };
+            }
+            let interesting = feedback
+                .is_interesting(&mut state, &mut mgr, &input, &observers, &ExitKind::Ok)
+                .unwrap();
+            assert_eq!(interesting, expected);
+            if interesting {
+                let mut testcase: Testcase<BytesInput> = Testcase::new(input.clone());
+                feedback.append_metadata(&mut state, &mut testcase).unwrap();
+                let reached = testcase.metadata().get::<TargetsReachedMetadata>().unwrap();
+                assert_eq!(reached.targets.len(), 1);
+            } else {
+                feedback.discard_metadata(&mut state, &input).unwrap();
+            }
+        }
+
+        let meta = state.metadata().get::<TargetSitesMetadata>().unwrap();
+        assert_eq!(meta.hits, [1, 2, 0]);
+        assert_eq!(meta.reached(), [0x1000, 0x2000]);
+        assert_eq!(meta.unreached(), [0x3000]);
+        assert!(!meta.all_reached());
+    }
+}
@@ -0,0 +1,734 @@
+//! Coverage reports for a finished corpus, mapping the entries of a [`MapObserver`] back to the source.
+//!
+//! [`EdgeLocations`] tells where each edge of the map comes from. It is either symbolized from the program counters
+//! of a `-fsanitize-coverage=trace-pc-guard,pc-table` target, see `libafl_targets::sancov_pc_table`, or built from
+//! the functions of the edges in a `libafl_cc` CFG dump, see `ControlFlowGraph::edge_functions`, which has no lines.
+//! Replaying the corpus with [`crate::fuzzer::ReplayRunner::replay_coverage`] fills a [`CoverageReport`],
+//! which is written as an lcov `.info` file, or summarized per function.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::Write as _;
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::{Read, Write},
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use crate::{observers::MapObserver, Error};
+
+/// The source location of an edge
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceLocation {
+    /// The function containing the edge
+    pub function: String,
+    /// The source file, if known
+    pub file: Option<String>,
+    /// The line in the source file, or `0` if unknown
+    pub line: u32,
+}
+
+/// The source locations of the edges of a coverage map, by their index in the map
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EdgeLocations {
+    locations: HashMap<usize, SourceLocation>,
+}
+
+impl EdgeLocations {
+    /// Creates an empty [`EdgeLocations`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates [`EdgeLocations`] knowing only the function of each edge, e.g. from a `libafl_cc` CFG dump
+    pub fn from_functions<'a, IT>(edges: IT) -> Self
+    where
+        IT: IntoIterator<Item = (usize, &'a str)>,
+    {
+        let mut locations = Self::new();
+        for (edge, function) in edges {
+            locations.insert(
+                edge,
+                SourceLocation {
+                    function: function.to_string(),
+                    file: None,
+                    line: 0,
+                },
+            );
+        }
+        locations
+    }
+
+    /// Creates [`EdgeLocations`] from the functions and source lines of the edges in a `libafl_cc` CFG dump,
+    /// see `ControlFlowGraph::edge_locations`. Edges without a line are known by their function only.
+    pub fn from_cfg<'a, IT>(edges: IT) -> Self
+    where
+        IT: IntoIterator<Item = (usize, &'a str, Option<(&'a str, u32)>)>,
+    {
+        let mut locations = Self::new();
+        for (edge, function, line) in edges {
+            locations.insert(
+                edge,
+                SourceLocation {
+                    function: function.to_string(),
+                    file: line.map(|(file, _)| file.to_string()),
+                    line: line.map_or(0, |(_, line)| line),
+                },
+            );
+        }
+        locations
+    }
+
+    /// Symbolizes the program counters of the edges in the current process, e.g. from `libafl_targets::sancov_pc_table`,
+    /// with `llvm-symbolizer`, or the given symbolizer with the same interface.
+    /// The targets need debug info; edges the symbolizer does not know are skipped.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn symbolize_pcs(pcs: &[(usize, usize)], symbolizer: Option<&str>) -> Result<Self, Error> {
+        let maps = fs::read_to_string("/proc/self/maps")?;
+        let modules = parse_maps(&maps);
+
+        let mut by_module: HashMap<&str, Vec<(usize, usize)>> = HashMap::new();
+        for &(edge, pc) in pcs {
+            if let Some((_, _, path)) = modules
+                .iter()
+                .find(|(start, end, _)| (*start..*end).contains(&pc))
+            {
+                by_module.entry(*path).or_default().push((edge, pc));
+            }
+        }
+
+        let mut locations = Self::new();
+        for (path, pcs) in by_module {
+            // Executables are linked at fixed addresses, everything else is relative to its first mapping
+            let base = if is_position_independent(path)? {
+                modules
+                    .iter()
+                    .filter(|(_, _, p)| *p == path)
+                    .map(|(start, _, _)| *start)
+                    .min()
+                    .unwrap_or(0)
+            } else {
+                0
+            };
+            let addresses: Vec<usize> = pcs.iter().map(|(_, pc)| pc - base).collect();
+            let found = run_symbolizer(symbolizer.unwrap_or("llvm-symbolizer"), path, &addresses)?;
+            for ((edge, _), location) in pcs.iter().zip(found) {
+                if let Some(location) = location {
+                    locations.insert(*edge, location);
+                }
+            }
+        }
+        Ok(locations)
+    }
+
+    /// Sets the location of an edge
+    pub fn insert(&mut self, edge: usize, location: SourceLocation) {
+        self.locations.insert(edge, location);
+    }
+
+    /// The location of an edge, if known
+    #[must_use]
+    pub fn get(&self, edge: usize) -> Option<&SourceLocation> {
+        self.locations.get(&edge)
+    }
+
+    /// The number of edges with a known location
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.locations.len()
+    }
+
+    /// If no edge has a known location
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+
+    /// Iterates over all edges and their locations, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &SourceLocation)> {
+        self.locations
+            .iter()
+            .map(|(edge, location)| (*edge, location))
+    }
+
+    /// The edges at the target locations, given as `file:line`, matching the last components of the path, or as function names.
+    /// Fails if a target matches no edge. Used for directed fuzzing, see [`crate::schedulers::DirectedScheduleMetadata`].
+    /// The edges are indexes of the map these locations were made for: to compute distances in a `libafl_cc` CFG,
+    /// they must come from [`EdgeLocations::from_cfg`], not from the program counters of a different instrumentation.
+    pub fn find_targets<S>(&self, targets: &[S]) -> Result<Vec<usize>, Error>
+    where
+        S: AsRef<str>,
+    {
+        let mut edges = vec![];
+        for target in targets {
+            let target = target.as_ref();
+            let file_line = target
+                .rsplit_once(':')
+                .and_then(|(file, line)| Some((file, line.parse::<u32>().ok()?)));
+            let found = edges.len();
+            edges.extend(self.iter().filter_map(|(edge, location)| {
+                let matches = match file_line {
+                    Some((file, line)) => {
+                        location.line == line
+                            && location
+                                .file
+                                .as_ref()
+                                .map_or(false, |f| path_ends_with(f, file))
+                    }
+                    None => location.function == target,
+                };
+                matches.then(|| edge)
+            }));
+            if edges.len() == found {
+                return Err(Error::key_not_found(format!(
+                    "No edge found for the target {}",
+                    target
+                )));
+            }
+        }
+        edges.sort_unstable();
+        edges.dedup();
+        Ok(edges)
+    }
+
+    /// The edges at the [`ChangedLines`] of a patch, with the changed line of each edge as an id from `0`,
+    /// for the [`crate::schedulers::PatchMetadata`].
+    /// Edges without a line, e.g. from a `libafl_cc` CFG dump, match the functions of the changed hunks instead.
+    #[must_use]
+    pub fn changed_edges(&self, changed: &ChangedLines) -> Vec<(usize, usize)> {
+        let mut ids: HashMap<(&str, u32), usize> = HashMap::new();
+        let mut edges = vec![];
+        for (edge, location) in self.iter() {
+            let key = match &location.file {
+                Some(file) if location.line > 0 => changed
+                    .files
+                    .iter()
+                    .find(|(path, lines)| {
+                        path_ends_with(file, path) && lines.contains(&location.line)
+                    })
+                    .map(|(path, _)| (path.as_str(), location.line)),
+                _ => changed
+                    .functions
+                    .iter()
+                    .find(|function| **function == location.function)
+                    .map(|function| (function.as_str(), 0)),
+            };
+            if let Some(key) = key {
+                let next_id = ids.len();
+                edges.push((edge, *ids.entry(key).or_insert(next_id)));
+            }
+        }
+        edges.sort_unstable();
+        edges
+    }
+}
+
+/// The lines changed by a patch, parsed from a unified diff, like the output of `git diff`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangedLines {
+    /// The changed lines of each file, in the new version
+    files: Vec<(String, Vec<u32>)>,
+    /// The functions of the changed hunks, as far as the diff names them
+    functions: Vec<String>,
+}
+
+impl ChangedLines {
+    /// Parses a unified diff. Added lines count as changed, and so do the lines following removed ones.
+    #[must_use]
+    pub fn from_diff(diff: &str) -> Self {
+        let mut changed = Self::default();
+        let mut line = 0;
+        let mut removed = false;
+        for text in diff.lines() {
+            if let Some(path) = text.strip_prefix("+++ ") {
+                let path = path.split('\t').next().unwrap_or(path);
+                let path = path.strip_prefix("b/").unwrap_or(path);
+                if path != "/dev/null" {
+                    changed.files.push((path.to_string(), vec![]));
+                }
+            } else if let Some(hunk) = text.strip_prefix("@@ ") {
+                // @@ -old,len +new,len @@ heading
+                line = hunk
+                    .split_whitespace()
+                    .find_map(|range| range.strip_prefix('+'))
+                    .and_then(|range| range.split(',').next()?.parse().ok())
+                    .unwrap_or(0);
+                removed = false;
+                if let Some(function) = hunk
+                    .split_once("@@")
+                    .and_then(|(_, heading)| hunk_function(heading))
+                {
+                    if !changed.functions.contains(&function) {
+                        changed.functions.push(function);
+                    }
+                }
+            } else if let Some((_, lines)) = changed.files.last_mut() {
+                match text.chars().next() {
+                    Some('+') => {
+                        lines.push(line);
+                        line += 1;
+                        removed = false;
+                    }
+                    Some('-') => removed = true,
+                    Some('\\') => (),
+                    _ => {
+                        if removed {
+                            lines.push(line);
+                            removed = false;
+                        }
+                        line += 1;
+                    }
+                }
+            }
+        }
+        changed.files.retain(|(_, lines)| !lines.is_empty());
+        changed
+    }
+
+    /// Adds changed lines of a file, e.g. from another source than a diff
+    pub fn add_lines<IT>(&mut self, file: &str, lines: IT)
+    where
+        IT: IntoIterator<Item = u32>,
+    {
+        self.files
+            .push((file.to_string(), lines.into_iter().collect()));
+    }
+
+    /// Adds a changed function, matching the edges without lines
+    pub fn add_function(&mut self, function: &str) {
+        self.functions.push(function.to_string());
+    }
+
+    /// The changed lines of a file, given by the last components of its path
+    #[must_use]
+    pub fn lines(&self, file: &str) -> Vec<u32> {
+        self.files
+            .iter()
+            .filter(|(path, _)| path_ends_with(path, file))
+            .flat_map(|(_, lines)| lines.iter().copied())
+            .collect()
+    }
+
+    /// The functions of the changed hunks
+    #[must_use]
+    pub fn functions(&self) -> &[String] {
+        &self.functions
+    }
+}
+
+/// If the last components of `path` are the ones of `suffix`, so `lib/parser.c` matches `/src/lib/parser.c`,
+/// but `parser.c` does not match `/src/myparser.c`
+fn path_ends_with(path: &str, suffix: &str) -> bool {
+    Path::new(path).ends_with(suffix.trim_start_matches("./"))
+}
+
+/// The function name in the heading of a hunk, like `int parse(char *buf)`, if there is one
+fn hunk_function(heading: &str) -> Option<String> {
+    let (signature, _) = heading.split_once('(')?;
+    let name = signature
+        .split(|c: char| c.is_whitespace() || c == '*' || c == '&')
+        .rfind(|token| !token.is_empty())?;
+    name.chars()
+        .all(|c| c.is_alphanumeric() || c == '_' || c == ':')
+        .then(|| name.to_string())
+}
+
+/// Parses `/proc/self/maps` into the address ranges of all file-backed mappings, with their path
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn parse_maps(maps: &str) -> Vec<(usize, usize, &str)> {
+    let mut modules = vec![];
+    for line in maps.lines() {
+        let mut fields = line.splitn(6, ' ');
+        let range = fields.next().unwrap_or_default();
+        let path = fields.nth(4).unwrap_or_default().trim();
+        if !path.starts_with('/') {
+            continue;
+        }
+        if let Some((start, end)) = range.split_once('-') {
+            if let (Ok(start), Ok(end)) = (
+                usize::from_str_radix(start, 16),
+                usize::from_str_radix(end, 16),
+            ) {
+                modules.push((start, end, path));
+            }
+        }
+    }
+    modules
+}
+
+/// Checks the ELF header of the module, `ET_DYN` means it is relocated when loading
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn is_position_independent(path: &str) -> Result<bool, Error> {
+    const ET_DYN: u16 = 3;
+    let mut header = [0; 18];
+    File::open(path)?.read_exact(&mut header)?;
+    Ok(u16::from_le_bytes([header[16], header[17]]) == ET_DYN)
+}
+
+/// Runs `llvm-symbolizer` on the addresses of the object at `path`
+fn run_symbolizer(
+    symbolizer: &str,
+    path: &str,
+    addresses: &[usize],
+) -> Result<Vec<Option<SourceLocation>>, Error> {
+    let mut child = Command::new(symbolizer)
+        .arg(format!("--obj={}", path))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let mut input = String::new();
+    for address in addresses {
+        writeln!(input, "{:#x}", address).unwrap();
+    }
+    // The symbolizer answers each line as we write, so write from another thread to not deadlock on big tables
+    let mut stdin = child.stdin.take().unwrap();
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child.wait_with_output()?;
+    writer.join().unwrap()?;
+    if !output.status.success() {
+        return Err(Error::unknown(format!(
+            "{} failed with {}",
+            symbolizer, output.status
+        )));
+    }
+    Ok(parse_symbolizer_output(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
+
+/// Parses the output of `llvm-symbolizer`: for each address a block of function and `file:line:column` lines,
+/// with the innermost inlined frame first, then an empty line.
+fn parse_symbolizer_output(output: &str) -> Vec<Option<SourceLocation>> {
+    output
+        .split("\n\n")
+        .filter(|block| !block.trim().is_empty())
+        .map(|block| {
+            let mut lines = block.trim_start_matches('\n').lines();
+            let function = lines.next()?.trim();
+            let mut position = lines.next()?.trim().rsplitn(3, ':');
+            let _column = position.next();
+            let line = position.next()?.parse().ok()?;
+            let file = position.next()?;
+            if function == "??" || file == "??" {
+                return None;
+            }
+            Some(SourceLocation {
+                function: function.to_string(),
+                file: Some(file.to_string()),
+                line,
+            })
+        })
+        .collect()
+}
+
+/// The coverage of a function
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FunctionCoverage {
+    /// The name of the function
+    pub function: String,
+    /// The source file of the function, if known
+    pub file: Option<String>,
+    /// The first line of the function with an edge, or `0` if unknown
+    pub line: u32,
+    /// The number of edges in the function
+    pub edges: usize,
+    /// The number of edges covered by at least one input
+    pub covered_edges: usize,
+    /// The number of inputs reaching the function
+    pub hits: u64,
+}
+
+/// The coverage of a corpus: for each edge of the map, how many inputs covered it
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoverageReport {
+    hits: HashMap<usize, u64>,
+    inputs: u64,
+}
+
+impl CoverageReport {
+    /// Creates an empty [`CoverageReport`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the coverage of one execution, all entries of the map that differ from its initial value
+    pub fn add_map<O>(&mut self, observer: &O)
+    where
+        O: MapObserver,
+    {
+        let initial = observer.initial();
+        for edge in 0..observer.usable_count() {
+            if *observer.get(edge) != initial {
+                *self.hits.entry(edge).or_default() += 1;
+            }
+        }
+        self.inputs += 1;
+    }
+
+    /// The number of executions added to the report
+    #[must_use]
+    pub fn inputs(&self) -> u64 {
+        self.inputs
+    }
+
+    /// The number of inputs covering the `edge`
+    #[must_use]
+    pub fn hits(&self, edge: usize) -> u64 {
+        self.hits.get(&edge).copied().unwrap_or(0)
+    }
+
+    /// The number of edges covered by at least one input
+    #[must_use]
+    pub fn covered_edges(&self) -> usize {
+        self.hits.len()
+    }
+
+    /// The coverage of each function with known edges, sorted by file, then function.
+    /// Functions with no covered edges were never reached.
+    #[must_use]
+    pub fn function_summary(&self, locations: &EdgeLocations) -> Vec<FunctionCoverage> {
+        let mut functions: HashMap<(&Option<String>, &str), FunctionCoverage> = HashMap::new();
+        for (edge, location) in locations.iter() {
+            let function = functions
+                .entry((&location.file, &location.function))
+                .or_insert_with(|| FunctionCoverage {
+                    function: location.function.clone(),
+                    file: location.file.clone(),
+                    line: location.line,
+                    edges: 0,
+                    covered_edges: 0,
+                    hits: 0,
+                });
+            let hits = self.hits(edge);
+            function.edges += 1;
+            if hits > 0 {
+                function.covered_edges += 1;
+            }
+            function.hits = function.hits.max(hits);
+            if location.line != 0 && (function.line == 0 || location.line < function.line) {
+                function.line = location.line;
+            }
+        }
+        let mut summary: Vec<FunctionCoverage> = functions.into_iter().map(|(_, f)| f).collect();
+        summary.sort_by(|a, b| (&a.file, &a.function).cmp(&(&b.file, &b.function)));
+        summary
+    }
+
+    /// Renders the [`CoverageReport::function_summary`] as a table, one function per line
+    #[must_use]
+    pub fn function_summary_table(&self, locations: &EdgeLocations) -> String {
+        let mut table = format!(
+            "{:>8} {:>7} {:>8}  {}\n",
+            "covered", "edges", "inputs", "function"
+        );
+        for function in self.function_summary(locations) {
+            let location = match &function.file {
+                Some(file) => format!(" ({}:{})", file, function.line),
+                None => String::new(),
+            };
+            writeln!(
+                table,
+                "{:>7}% {:>7} {:>8}  {}{}",
+                function.covered_edges * 100 / function.edges,
+                function.edges,
+                function.hits,
+                function.function,
+                location
+            )
+            .unwrap();
+        }
+        table
+    }
+
+    /// Renders the report in the lcov tracefile format, with the number of inputs reaching each line and function.
+    /// Edges without a source file are left out.
+    #[must_use]
+    pub fn to_lcov(&self, locations: &EdgeLocations) -> String {
+        let mut files: HashMap<&str, HashMap<u32, u64>> = HashMap::new();
+        for (edge, location) in locations.iter() {
+            if let Some(file) = &location.file {
+                let hits = files
+                    .entry(file)
+                    .or_default()
+                    .entry(location.line)
+                    .or_default();
+                *hits = (*hits).max(self.hits(edge));
+            }
+        }
+        let mut functions: HashMap<String, Vec<FunctionCoverage>> = HashMap::new();
+        for function in self.function_summary(locations) {
+            if let Some(file) = function.file.clone() {
+                functions.entry(file).or_default().push(function);
+            }
+        }
+
+        let mut file_names: Vec<&str> = files.keys().copied().collect();
+        file_names.sort_unstable();
+        let mut lcov = String::new();
+        for file in file_names {
+            writeln!(lcov, "TN:\nSF:{}", file).unwrap();
+            let file_functions = functions.remove(file).unwrap_or_default();
+            for function in &file_functions {
+                writeln!(lcov, "FN:{},{}", function.line, function.function).unwrap();
+            }
+            for function in &file_functions {
+                writeln!(lcov, "FNDA:{},{}", function.hits, function.function).unwrap();
+            }
+            writeln!(lcov, "FNF:{}", file_functions.len()).unwrap();
+            writeln!(
+                lcov,
+                "FNH:{}",
+                file_functions.iter().filter(|f| f.hits > 0).count()
+            )
+            .unwrap();
+
+            let mut lines: Vec<(u32, u64)> = files[file].iter().map(|(l, h)| (*l, *h)).collect();
+            lines.sort_unstable();
+            for (line, hits) in &lines {
+                writeln!(lcov, "DA:{},{}", line, hits).unwrap();
+            }
+            writeln!(lcov, "LF:{}", lines.len()).unwrap();
+            writeln!(
+                lcov,
+                "LH:{}",
+                lines.iter().filter(|(_, hits)| *hits > 0).count()
+            )
+            .unwrap();
+            lcov.push_str("end_of_record\n");
+        }
+        lcov
+    }
+
+    /// Writes the report as lcov `.info` file to `path`, see [`CoverageReport::to_lcov`]
+    pub fn write_lcov<P>(&self, path: P, locations: &EdgeLocations) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        fs::write(path, self.to_lcov(locations))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use crate::{
+        fuzzer::coverage::{
+            parse_symbolizer_output, ChangedLines, CoverageReport, EdgeLocations, SourceLocation,
+        },
+        observers::StdMapObserver,
+    };
+
+    fn locations() -> EdgeLocations {
+        let mut locations = EdgeLocations::new();
+        for (edge, function, line) in [
+            (1, "parse_header", 10),
+            (2, "parse_header", 12),
+            (3, "parse_body", 30),
+            (4, "parse_trailer", 50),
+        ] {
+            locations.insert(
+                edge,
+                SourceLocation {
+                    function: function.into(),
+                    file: Some("parser.c".into()),
+                    line,
+                },
+            );
+        }
+        locations
+    }
+
+    #[test]
+    fn test_coverage_report() {
+        let mut report = CoverageReport::new();
+        for covered in [[1, 2], [1, 3]] {
+            let mut map = vec![0_u8; 8];
+            for edge in covered {
+                map[edge] = 1;
+            }
+            report.add_map(&StdMapObserver::new("edges", &mut map));
+        }
+        assert_eq!(report.inputs(), 2);
+        assert_eq!(report.covered_edges(), 3);
+
+        let locations = locations();
+        assert_eq!(
+            locations
+                .find_targets(&["parse_header", "parser.c:30"])
+                .unwrap(),
+            [1, 2, 3]
+        );
+        assert!(locations.find_targets(&["parse_footer"]).is_err());
+        let summary = report.function_summary(&locations);
+        let functions: Vec<(&str, usize, usize, u64)> = summary
+            .iter()
+            .map(|f| (f.function.as_str(), f.edges, f.covered_edges, f.hits))
+            .collect();
+        assert_eq!(
+            functions,
+            [
+                ("parse_body", 1, 1, 1),
+                ("parse_header", 2, 2, 2),
+                ("parse_trailer", 1, 0, 0)
+            ]
+        );
+
+        assert_eq!(
+            report.to_lcov(&locations),
+            "TN:\nSF:parser.c\n\
+             FN:30,parse_body\nFN:10,parse_header\nFN:50,parse_trailer\n\
+             FNDA:1,parse_body\nFNDA:2,parse_header\nFNDA:0,parse_trailer\nFNF:3\nFNH:2\n\
+             DA:10,2\nDA:12,1\nDA:30,1\nDA:50,0\nLF:4\nLH:3\nend_of_record\n"
+        );
+    }
+
+    #[test]
+    fn test_path_components() {
+        let mut locations = EdgeLocations::new();
+        for (edge, file) in [(1, "/src/lib/parser.c"), (2, "/src/myparser.c")] {
+            locations.insert(
+                edge,
+                SourceLocation {
+                    function: "parse".into(),
+                    file: Some(file.into()),
+                    line: 10,
+                },
+            );
+        }
+        assert_eq!(locations.find_targets(&["parser.c:10"]).unwrap(), [1]);
+        assert_eq!(locations.find_targets(&["./lib/parser.c:10"]).unwrap(), [1]);
+        assert!(locations.find_targets(&["ib/parser.c:10"]).is_err());
+
+        let mut changed = ChangedLines::default();
+        changed.add_lines("lib/parser.c", [10]);
+        assert_eq!(locations.changed_edges(&changed), [(1, 0)]);
+        assert_eq!(changed.lines("parser.c"), [10]);
+        assert!(changed.lines("r.c").is_empty());
+    }
+
+    #[test]
+    fn test_parse_symbolizer_output() {
+        let output = "parse_header\n/src/parser.c:12:3\n\n??\n??:0:0\n\ninlined\n/src/a.h:3:1\nouter\n/src/b.c:7:9\n\n";
+        let locations = parse_symbolizer_output(output);
+        assert_eq!(locations.len(), 3);
+        assert_eq!(
+            locations[0],
+            Some(SourceLocation {
+                function: "parse_header".into(),
+                file: Some("/src/parser.c".into()),
+                line: 12
+            })
+        );
+        assert_eq!(locations[1], None);
+        assert_eq!(locations[2].as_ref().unwrap().function, "inlined");
+    }
+}
@@ -22,6 +22,16 @@ use crate::monitors::PerfFeature;
 use alloc::string::ToString;
 use core::{marker::PhantomData, time::Duration};
 
+#[cfg(feature = "std")]
+pub mod coverage;
+#[cfg(feature = "std")]
+pub use coverage::{ChangedLines, CoverageReport, EdgeLocations, FunctionCoverage, SourceLocation};
+
+#[cfg(feature = "std")]
+pub mod replay;
+#[cfg(feature = "std")]
+pub use replay::{ReplayReport, ReplayReportFormat, ReplayResult, ReplayRunner};
+
 /// Send a monitor update all 15 (or more) seconds
 const STATS_TIMEOUT_DEFAULT: Duration = Duration::from_secs(15);
 
@@ -379,7 +389,7 @@ where
                         Event::NewTestcase {
                             input,
                             observers_buf,
-                            exit_kind: *exit_kind,
+                            exit_kind: exit_kind.clone(),
                             corpus_size: state.corpus().count(),
                             client_config: manager.configuration(),
                             time: current_time(),
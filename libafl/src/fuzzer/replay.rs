@@ -0,0 +1,458 @@
+//! Replays a corpus or crash directory against a (new build of the) target, and reports the outcome of each input.
+//!
+//! The [`ReplayRunner`] runs every input with any [`Executor`], and records its [`ExitKind`], its execution time,
+//! and how many entries of a [`MapObserver`] it covers. The resulting [`ReplayReport`] can be written as JSON or CSV.
+//! [`ReplayRunner::replay_coverage`] collects the coverage of a corpus for a [`CoverageReport`] instead.
+//! With the `cli` feature, [`ReplayRunner::replay_options`] implements the `--replay` option of [`crate::bolts::cli`].
+//!
+//! The progress of a replay is kept in the [`ReplayProgressMetadata`] of the state. If an input brings down the process,
+//! for example an in-process crash, a restarting event manager restores the state, and the replay resumes after that input.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt::Write as _, marker::PhantomData, time::Duration};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "cli")]
+use crate::bolts::cli::FuzzerOptions;
+use crate::{
+    bolts::{current_time, fs::input_files},
+    executors::{Executor, ExitKind, HasObservers},
+    fuzzer::CoverageReport,
+    inputs::Input,
+    observers::{MapObserver, ObserversTuple},
+    state::{HasExecutions, HasMetadata},
+    Error,
+};
+
+/// The format of a [`ReplayReport`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ArgEnum))]
+pub enum ReplayReportFormat {
+    /// A JSON document holding the list of results
+    Json,
+    /// One line per result, with a header line
+    Csv,
+}
+
+/// The outcome of replaying a single input
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplayResult {
+    /// The file the input was loaded from
+    pub path: PathBuf,
+    /// How the execution finished
+    pub exit_kind: ExitKind,
+    /// How long the execution took
+    pub exec_time: Duration,
+    /// How many entries of the map the execution covered
+    pub coverage: u64,
+}
+
+/// The outcomes of a replay
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplayReport {
+    /// The outcome of each execution, in order
+    pub results: Vec<ReplayResult>,
+}
+
+impl ReplayReport {
+    /// The results of the executions that finished with the given [`ExitKind`]
+    pub fn with_exit_kind(&self, exit_kind: ExitKind) -> impl Iterator<Item = &ReplayResult> {
+        self.results
+            .iter()
+            .filter(move |result| result.exit_kind == exit_kind)
+    }
+
+    /// Renders the report as CSV, with the execution time in microseconds
+    #[must_use]
+    pub fn to_csv(&self) -> String {
+        let mut csv = "path,exit_kind,exec_time_us,coverage\n".to_string();
+        for result in &self.results {
+            writeln!(
+                csv,
+                "{},{},{},{}",
+                csv_field(&result.path.to_string_lossy()),
+                csv_field(&format!("{:?}", result.exit_kind)),
+                result.exec_time.as_micros(),
+                result.coverage
+            )
+            .unwrap();
+        }
+        csv
+    }
+
+    /// Writes the report to `path`, in the given format.
+    pub fn write<P>(&self, path: P, format: ReplayReportFormat) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        match format {
+            ReplayReportFormat::Json => fs::write(path, serde_json::to_vec_pretty(self)?)?,
+            ReplayReportFormat::Csv => fs::write(path, self.to_csv())?,
+        }
+        Ok(())
+    }
+}
+
+/// The progress of a replay, kept in the state until the replay is done
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReplayProgressMetadata {
+    /// The outcomes of the runs finished so far
+    pub report: ReplayReport,
+    /// If a run is in progress. If so when the replay resumes, the run brought down the process.
+    pub running: bool,
+}
+
+crate::impl_serdeany!(ReplayProgressMetadata);
+
+/// Quotes a CSV field, if needed
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Replays inputs from files, recording the coverage of a [`MapObserver`]
+#[derive(Debug, Clone)]
+pub struct ReplayRunner<O> {
+    observer_name: String,
+    phantom: PhantomData<O>,
+}
+
+impl<O> ReplayRunner<O>
+where
+    O: MapObserver,
+{
+    /// Creates a new [`ReplayRunner`], reporting the coverage of the given [`MapObserver`].
+    #[must_use]
+    pub fn new(observer: &O) -> Self {
+        Self {
+            observer_name: observer.name().to_string(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Runs the `input` once, and returns how the execution finished, how long it took, and the covered map entries.
+    pub fn replay_input<E, EM, I, OT, S, Z>(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        mgr: &mut EM,
+        input: &I,
+    ) -> Result<(ExitKind, Duration, u64), Error>
+    where
+        E: Executor<EM, I, S, Z> + HasObservers<I, OT, S>,
+        I: Input,
+        OT: ObserversTuple<I, S>,
+        S: HasExecutions,
+    {
+        executor.observers_mut().pre_exec_all(state, input)?;
+        let start = current_time();
+        let exit_kind = executor.run_target(fuzzer, state, mgr, input)?;
+        let exec_time = current_time() - start;
+        *state.executions_mut() += 1;
+        executor
+            .observers_mut()
+            .post_exec_all(state, input, &exit_kind)?;
+
+        let observer = executor
+            .observers()
+            .match_name::<O>(&self.observer_name)
+            .ok_or_else(|| {
+                Error::key_not_found(format!(
+                    "ReplayRunner: observer {} not found",
+                    self.observer_name
+                ))
+            })?;
+        Ok((exit_kind, exec_time, observer.count_bytes()))
+    }
+
+    /// Replays the input file at `path`, or all input files in the directory at `path`, `repeat` times each.
+    /// Hidden files, like the metadata of an [`crate::corpus::OnDiskCorpus`], are skipped.
+    ///
+    /// If the state holds the [`ReplayProgressMetadata`] of an earlier, interrupted replay, it resumes after the last finished run.
+    /// A run still in progress brought down the process, and is reported as [`ExitKind::Crash`], without execution time and coverage.
+    #[allow(clippy::too_many_arguments)]
+    pub fn replay_path<E, EM, I, OT, P, S, Z>(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        mgr: &mut EM,
+        path: P,
+        repeat: usize,
+    ) -> Result<ReplayReport, Error>
+    where
+        E: Executor<EM, I, S, Z> + HasObservers<I, OT, S>,
+        I: Input,
+        OT: ObserversTuple<I, S>,
+        P: AsRef<Path>,
+        S: HasExecutions + HasMetadata,
+    {
+        if !state.has_metadata::<ReplayProgressMetadata>() {
+            state.add_metadata(ReplayProgressMetadata::default());
+        }
+
+        let mut run = 0;
+        for file in input_files(path)? {
+            let input = I::from_file(&file)?;
+            for _ in 0..repeat {
+                run += 1;
+                let progress = state
+                    .metadata_mut()
+                    .get_mut::<ReplayProgressMetadata>()
+                    .unwrap();
+                if progress.report.results.len() >= run {
+                    continue;
+                }
+                if progress.running {
+                    progress.running = false;
+                    progress.report.results.push(ReplayResult {
+                        path: file.clone(),
+                        exit_kind: ExitKind::Crash,
+                        exec_time: Duration::ZERO,
+                        coverage: 0,
+                    });
+                    continue;
+                }
+                // Kept in the state, in case this run brings down the process
+                progress.running = true;
+
+                let (exit_kind, exec_time, coverage) =
+                    self.replay_input(fuzzer, executor, state, mgr, &input)?;
+                let progress = state
+                    .metadata_mut()
+                    .get_mut::<ReplayProgressMetadata>()
+                    .unwrap();
+                progress.running = false;
+                progress.report.results.push(ReplayResult {
+                    path: file.clone(),
+                    exit_kind,
+                    exec_time,
+                    coverage,
+                });
+            }
+        }
+        Ok(state
+            .metadata_mut()
+            .remove::<ReplayProgressMetadata>()
+            .unwrap()
+            .report)
+    }
+
+    /// Replays the input file at `path`, or all input files in the directory at `path`, once each,
+    /// and adds the coverage of the [`MapObserver`] to the `report`.
+    pub fn replay_coverage<E, EM, I, OT, P, S, Z>(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        mgr: &mut EM,
+        path: P,
+        report: &mut CoverageReport,
+    ) -> Result<(), Error>
+    where
+        E: Executor<EM, I, S, Z> + HasObservers<I, OT, S>,
+        I: Input,
+        OT: ObserversTuple<I, S>,
+        P: AsRef<Path>,
+        S: HasExecutions,
+    {
+        for file in input_files(path.as_ref())? {
+            let input = I::from_file(&file)?;
+            self.replay_input(fuzzer, executor, state, mgr, &input)?;
+            // The observer was found by `replay_input`
+            let observer = executor
+                .observers()
+                .match_name::<O>(&self.observer_name)
+                .unwrap();
+            report.add_map(observer);
+        }
+        Ok(())
+    }
+
+    /// Implements the replay options of [`FuzzerOptions`]: replays `--replay`, `--repeat` times,
+    /// and writes the report to `--replay-report`, if given.
+    /// Returns `None` if no replay was requested, so the caller should fuzz instead.
+    #[cfg(feature = "cli")]
+    pub fn replay_options<E, EM, I, OT, S, Z>(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        mgr: &mut EM,
+        options: &FuzzerOptions,
+    ) -> Result<Option<ReplayReport>, Error>
+    where
+        E: Executor<EM, I, S, Z> + HasObservers<I, OT, S>,
+        I: Input,
+        OT: ObserversTuple<I, S>,
+        S: HasExecutions + HasMetadata,
+    {
+        let path = match &options.replay {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let report = self.replay_path(
+            fuzzer,
+            executor,
+            state,
+            mgr,
+            path,
+            options.repeat.unwrap_or(1),
+        )?;
+        if let Some(report_path) = &options.replay_report {
+            report.write(report_path, options.replay_format)?;
+        }
+        Ok(Some(report))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+    use std::{fs, path::PathBuf};
+
+    use crate::{
+        bolts::{
+            rands::StdRand,
+            tuples::{tuple_list, tuple_list_type},
+            AsMutSlice,
+        },
+        corpus::InMemoryCorpus,
+        executors::{Executor, ExitKind, HasObservers},
+        fuzzer::replay::{ReplayProgressMetadata, ReplayReport, ReplayResult, ReplayRunner},
+        inputs::{BytesInput, HasBytesVec, Input},
+        observers::StdMapObserver,
+        state::{HasMetadata, StdState},
+        Error,
+    };
+
+    type TestState =
+        StdState<InMemoryCorpus<BytesInput>, BytesInput, StdRand, InMemoryCorpus<BytesInput>>;
+
+    /// Sets the map entry of each input byte, and counts its runs
+    #[derive(Debug)]
+    struct BytesExecutor {
+        runs: usize,
+        observers: tuple_list_type!(StdMapObserver<'static, u8>),
+    }
+
+    impl<EM, S, Z> Executor<EM, BytesInput, S, Z> for BytesExecutor {
+        fn run_target(
+            &mut self,
+            _fuzzer: &mut Z,
+            _state: &mut S,
+            _mgr: &mut EM,
+            input: &BytesInput,
+        ) -> Result<ExitKind, Error> {
+            self.runs += 1;
+            let map = self.observers.0.as_mut_slice();
+            for byte in input.bytes() {
+                map[*byte as usize] = 1;
+            }
+            Ok(ExitKind::Ok)
+        }
+    }
+
+    impl HasObservers<BytesInput, tuple_list_type!(StdMapObserver<'static, u8>), TestState>
+        for BytesExecutor
+    {
+        fn observers(&self) -> &tuple_list_type!(StdMapObserver<'static, u8>) {
+            &self.observers
+        }
+
+        fn observers_mut(&mut self) -> &mut tuple_list_type!(StdMapObserver<'static, u8>) {
+            &mut self.observers
+        }
+    }
+
+    #[test]
+    fn test_replay_resumes() {
+        let dir =
+            std::env::temp_dir().join(format!("libafl_test_replay_resumes_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (name, bytes) in [("a", &[1_u8][..]), ("b", &[2, 3]), ("c", &[4, 5, 6])] {
+            BytesInput::new(bytes.to_vec())
+                .to_file(dir.join(name))
+                .unwrap();
+        }
+
+        let observer = StdMapObserver::new_owned("map", vec![0_u8; 256]);
+        let replay = ReplayRunner::new(&observer);
+        let mut executor = BytesExecutor {
+            runs: 0,
+            observers: tuple_list!(observer),
+        };
+        let mut state: TestState = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+
+        // The process went down while replaying `b`
+        let a = ReplayResult {
+            path: dir.join("a"),
+            exit_kind: ExitKind::Ok,
+            exec_time: Duration::from_millis(1),
+            coverage: 1,
+        };
+        state.add_metadata(ReplayProgressMetadata {
+            report: ReplayReport {
+                results: vec![a.clone()],
+            },
+            running: true,
+        });
+
+        let report = replay
+            .replay_path(&mut (), &mut executor, &mut state, &mut (), &dir, 1)
+            .unwrap();
+        assert_eq!(executor.runs, 1);
+        assert_eq!(report.results.len(), 3);
+        assert_eq!(report.results[0], a);
+        assert_eq!(report.results[1].path, dir.join("b"));
+        assert_eq!(report.results[1].exit_kind, ExitKind::Crash);
+        assert_eq!(report.results[2].path, dir.join("c"));
+        assert_eq!(report.results[2].coverage, 3);
+        assert!(!state.has_metadata::<ReplayProgressMetadata>());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_replay_report_csv() {
+        let report = ReplayReport {
+            results: vec![
+                ReplayResult {
+                    path: PathBuf::from("crashes/a"),
+                    exit_kind: ExitKind::Crash,
+                    exec_time: Duration::from_millis(2),
+                    coverage: 42,
+                },
+                ReplayResult {
+                    path: PathBuf::from("crashes/b,c"),
+                    exit_kind: ExitKind::Timeout,
+                    exec_time: Duration::from_secs(1),
+                    coverage: 7,
+                },
+            ],
+        };
+        assert_eq!(
+            report.to_csv(),
+            "path,exit_kind,exec_time_us,coverage\ncrashes/a,Crash,2000,42\n\"crashes/b,c\",Timeout,1000000,7\n"
+        );
+        assert_eq!(report.with_exit_kind(ExitKind::Crash).count(), 1);
+    }
+}
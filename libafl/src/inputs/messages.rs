@@ -0,0 +1,165 @@
+//! The [`MessageSequenceInput`] is a session with a stateful network service, one message after the other.
+//! Each message moves the service to its next protocol state, see [`crate::schedulers::stateful`].
+
+use ahash::AHasher;
+use alloc::{string::String, vec::Vec};
+use core::hash::Hasher;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bolts::{ownedref::OwnedSlice, HasLen},
+    inputs::{AsMultiBytes, AsMultiBytesVec, AsMultiInput, BytesInput, HasBytesVec, Input},
+};
+
+/// A sequence of messages, sent to a service in order
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct MessageSequenceInput {
+    /// The messages
+    messages: Vec<BytesInput>,
+}
+
+impl Input for MessageSequenceInput {
+    /// Generate a name for this input
+    fn generate_name(&self, _idx: usize) -> String {
+        let mut hasher = AHasher::new_with_keys(0, 0);
+        for message in &self.messages {
+            hasher.write_usize(message.bytes().len());
+            hasher.write(message.bytes());
+        }
+        format!("{:016x}", hasher.finish())
+    }
+}
+
+impl MessageSequenceInput {
+    /// Creates a new [`MessageSequenceInput`] from its messages
+    #[must_use]
+    pub fn new(messages: Vec<BytesInput>) -> Self {
+        Self { messages }
+    }
+
+    /// Splits a recorded session into its messages, each ending with the `delimiter`,
+    /// such as `\r\n` for text protocols. A trailing message without a delimiter is kept as well.
+    #[must_use]
+    pub fn from_delimited(session: &[u8], delimiter: &[u8]) -> Self {
+        let mut messages = vec![];
+        let mut start = 0;
+        if !delimiter.is_empty() {
+            let mut pos = 0;
+            while pos + delimiter.len() <= session.len() {
+                if session[pos..].starts_with(delimiter) {
+                    pos += delimiter.len();
+                    messages.push(BytesInput::new(session[start..pos].to_vec()));
+                    start = pos;
+                } else {
+                    pos += 1;
+                }
+            }
+        }
+        if start < session.len() {
+            messages.push(BytesInput::new(session[start..].to_vec()));
+        }
+        Self { messages }
+    }
+
+    /// The messages, in order
+    #[must_use]
+    pub fn messages(&self) -> &[BytesInput] {
+        &self.messages
+    }
+
+    /// The messages, in order (mutable)
+    pub fn messages_mut(&mut self) -> &mut Vec<BytesInput> {
+        &mut self.messages
+    }
+
+    /// The number of messages
+    #[must_use]
+    pub fn message_count(&self) -> usize {
+        self.messages.len()
+    }
+
+    /// The first `len` messages, which bring the service to a state
+    #[must_use]
+    pub fn prefix(&self, len: usize) -> &[BytesInput] {
+        &self.messages[..len.min(self.messages.len())]
+    }
+
+    /// The messages after the first `len`, sent once the service reached a state
+    pub fn suffix_mut(&mut self, len: usize) -> &mut [BytesInput] {
+        let len = len.min(self.messages.len());
+        &mut self.messages[len..]
+    }
+}
+
+impl From<Vec<BytesInput>> for MessageSequenceInput {
+    fn from(messages: Vec<BytesInput>) -> Self {
+        Self::new(messages)
+    }
+}
+
+impl HasLen for MessageSequenceInput {
+    /// The number of bytes of all messages
+    fn len(&self) -> usize {
+        self.messages.iter().map(HasLen::len).sum()
+    }
+}
+
+impl AsMultiInput<BytesInput> for MessageSequenceInput {
+    fn as_multi_input_mut(&mut self) -> &mut Vec<BytesInput> {
+        &mut self.messages
+    }
+
+    fn as_multi_input(&self) -> &Vec<BytesInput> {
+        &self.messages
+    }
+}
+
+impl AsMultiBytes for MessageSequenceInput {
+    fn as_multi_ownd_bytes(&self) -> Vec<OwnedSlice<u8>> {
+        self.messages
+            .iter()
+            .map(|message| OwnedSlice::from(message.bytes()))
+            .collect()
+    }
+}
+
+impl AsMultiBytesVec for MessageSequenceInput {
+    fn as_multi_bytes(&self) -> Vec<&[u8]> {
+        self.messages.iter().map(HasBytesVec::bytes).collect()
+    }
+
+    fn as_multi_bytes_mut(&mut self) -> Vec<&mut Vec<u8>> {
+        self.messages
+            .iter_mut()
+            .map(HasBytesVec::bytes_mut)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use crate::{
+        bolts::HasLen,
+        inputs::{HasBytesVec, Input, MessageSequenceInput},
+    };
+
+    #[test]
+    fn test_message_sequence_from_delimited() {
+        let input = MessageSequenceInput::from_delimited(b"USER a\r\nPASS b\r\nQUIT", b"\r\n");
+        let messages: Vec<&[u8]> = input.messages().iter().map(HasBytesVec::bytes).collect();
+        assert_eq!(
+            messages,
+            [&b"USER a\r\n"[..], &b"PASS b\r\n"[..], &b"QUIT"[..]]
+        );
+        assert_eq!(input.len(), 20);
+        assert_eq!(input.prefix(2).len(), 2);
+        assert_eq!(input.prefix(5).len(), 3);
+
+        // The message boundaries are part of the name
+        let joined = MessageSequenceInput::from_delimited(b"USER a\r\nPASS b\r\nQUIT", b"\n\n");
+        assert_eq!(joined.message_count(), 1);
+        assert_ne!(input.generate_name(0), joined.generate_name(0));
+    }
+}
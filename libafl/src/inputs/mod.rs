@@ -8,6 +8,9 @@ pub use bytes::BytesInput;
 pub mod multi;
 pub use multi::*;
 
+pub mod messages;
+pub use messages::MessageSequenceInput;
+
 pub mod encoded;
 pub use encoded::*;
 
@@ -60,7 +60,8 @@ impl AsMultiInput<BytesInput> for MultiInput {
     }
 }
 
-//@TODO @FIXME
+/// Only exposes the first field, so byte-level mutators will never touch the other fields.
+/// Use the [`crate::mutators::multi`] mutators to mutate every field of a [`MultiInput`].
 impl HasBytesVec for MultiInput {
         fn bytes(&self) -> &[u8] {
             return self.fields[0].bytes()
@@ -1,14 +1,22 @@
 //! Monitors that wrap a base one and log on disk
 
-use alloc::{string::String, vec::Vec};
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
 use core::time::Duration;
 
 use crate::{
     bolts::{current_time, format_duration_hms},
-    monitors::{ClientStats, Monitor, NopMonitor},
+    events::ControlCommand,
+    monitors::{ClientStats, Monitor, NopMonitor, UserStats, UserStatsValue},
 };
 
-use std::{fs::File, io::Write, path::PathBuf};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+};
 
 /// Wrap a monitor and log the current state of the monitor into a TOML file.
 #[derive(Debug, Clone)]
@@ -40,6 +48,10 @@ where
         self.base.start_time()
     }
 
+    fn take_control_commands(&mut self) -> Vec<(Option<u32>, ControlCommand)> {
+        self.base.take_control_commands()
+    }
+
     fn display(&mut self, event_msg: String, sender_id: u32) {
         let cur_time = current_time();
 
@@ -68,6 +80,11 @@ exec_sec = {}
             )
             .expect("Failed to write to the TOML file");
 
+            for (key, val) in self.aggregated_user_stats() {
+                writeln!(&mut file, "{} = \"{}\"", toml_key(&key), val)
+                    .expect("Failed to write to the TOML file");
+            }
+
             for (i, client) in self.client_stats_mut().iter_mut().skip(1).enumerate() {
                 let exec_sec = client.execs_per_sec(cur_time);
 
@@ -89,12 +106,7 @@ exec_sec = {}
                 .expect("Failed to write to the TOML file");
 
                 for (key, val) in &client.user_monitor {
-                    let k: String = key
-                        .chars()
-                        .map(|c| if c.is_whitespace() { '_' } else { c })
-                        .filter(|c| c.is_alphanumeric() || *c == '_')
-                        .collect();
-                    writeln!(&mut file, "{} = \"{}\"", k, val)
+                    writeln!(&mut file, "{} = \"{}\"", toml_key(key), val)
                         .expect("Failed to write to the TOML file");
                 }
             }
@@ -106,6 +118,14 @@ exec_sec = {}
     }
 }
 
+/// Turns the name of a user stat into a bare TOML key
+fn toml_key(key: &str) -> String {
+    key.chars()
+        .map(|c| if c.is_whitespace() { '_' } else { c })
+        .filter(|c| c.is_alphanumeric() || *c == '_')
+        .collect()
+}
+
 impl<M> OnDiskTOMLMonitor<M>
 where
     M: Monitor,
@@ -134,3 +154,339 @@ impl OnDiskTOMLMonitor<NopMonitor> {
         Self::new(filename, NopMonitor::new())
     }
 }
+
+/// How often the AFL stats of a client are written
+const AFL_STATS_UPDATE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The header of the AFL++ `plot_data` file
+const AFL_PLOT_DATA_HEADER: &str = "# relative_time, cycles_done, cur_item, corpus_count, pending_total, pending_favs, map_size, saved_crashes, saved_hangs, max_depth, execs_per_sec, total_execs, edges_found";
+
+/// The optional `fuzzer_stats` fields, written only if the client reports a numeric user stat with their name
+const AFL_USER_STATS_FIELDS: [&str; 11] = [
+    "fuzzer_pid",
+    "cycles_done",
+    "cycles_wo_finds",
+    "corpus_favored",
+    "corpus_imported",
+    "max_depth",
+    "cur_item",
+    "pending_favs",
+    "pending_total",
+    "saved_hangs",
+    "last_hang",
+];
+
+/// What the [`OnDiskAFLMonitor`] remembers about each client
+#[derive(Debug, Clone, Default)]
+struct AFLClientTrack {
+    corpus_size: u64,
+    objective_size: u64,
+    /// The time of the last new corpus entry
+    last_find: Option<Duration>,
+    /// The time of the last new objective
+    last_crash: Option<Duration>,
+    last_update: Option<Duration>,
+}
+
+/// Wrap a monitor and write the AFL++ `fuzzer_stats` and `plot_data` files of each client,
+/// to `<out_dir>/client_<id>/`, so that tools like `afl-whatsup` and `afl-plot` can read them.
+///
+/// Stability and map density are taken from the `stability` user stat, sent for the `CalibrationStage`,
+/// and from the coverage ratio user stat sent by the `MapFeedback` of the `edges` observer (see [`Self::with_map_stat`]).
+/// The fields without a counterpart here, such as `cycles_done` or `fuzzer_pid`, are only written
+/// if the client sends a numeric user stat of the same name.
+#[derive(Debug, Clone)]
+pub struct OnDiskAFLMonitor<M>
+where
+    M: Monitor,
+{
+    base: M,
+    out_dir: PathBuf,
+    map_stat: String,
+    banner: String,
+    clients: Vec<AFLClientTrack>,
+}
+
+impl<M> Monitor for OnDiskAFLMonitor<M>
+where
+    M: Monitor,
+{
+    /// The client monitor, mutable
+    fn client_stats_mut(&mut self) -> &mut Vec<ClientStats> {
+        self.base.client_stats_mut()
+    }
+
+    /// The client monitor
+    fn client_stats(&self) -> &[ClientStats] {
+        self.base.client_stats()
+    }
+
+    /// Time this fuzzing run stated
+    fn start_time(&mut self) -> Duration {
+        self.base.start_time()
+    }
+
+    fn take_control_commands(&mut self) -> Vec<(Option<u32>, ControlCommand)> {
+        self.base.take_control_commands()
+    }
+
+    fn display(&mut self, event_msg: String, sender_id: u32) {
+        let cur_time = current_time();
+        let id = sender_id as usize;
+        // Make sure the client exists, as the base may add it only in its own `display`
+        self.client_stats_mut_for(sender_id);
+        if self.clients.len() <= id {
+            self.clients.resize(id + 1, AFLClientTrack::default());
+        }
+
+        let (corpus_size, objective_size) = {
+            let client = &self.client_stats()[id];
+            (client.corpus_size, client.objective_size)
+        };
+        let track = &mut self.clients[id];
+        if corpus_size > track.corpus_size {
+            track.corpus_size = corpus_size;
+            track.last_find = Some(cur_time);
+        }
+        if objective_size > track.objective_size {
+            track.objective_size = objective_size;
+            track.last_crash = Some(cur_time);
+        }
+
+        let due = track
+            .last_update
+            .map_or(true, |last| cur_time - last >= AFL_STATS_UPDATE_INTERVAL);
+        if sender_id != 0 && due {
+            track.last_update = Some(cur_time);
+            if let Err(e) = self.write_afl_stats(id, cur_time) {
+                eprintln!("Could not write the AFL stats of client {}: {:?}", id, e);
+            }
+        }
+
+        self.base.display(event_msg, sender_id);
+    }
+}
+
+impl<M> OnDiskAFLMonitor<M>
+where
+    M: Monitor,
+{
+    /// Create new [`OnDiskAFLMonitor`], writing to the AFL-style output directory `out_dir`
+    #[must_use]
+    pub fn new<P>(out_dir: P, base: M) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            base,
+            out_dir: out_dir.into(),
+            map_stat: "edges".to_string(),
+            banner: "libafl".to_string(),
+            clients: vec![],
+        }
+    }
+
+    /// Take the map density from the ratio user stat with the given name, `edges` by default
+    #[must_use]
+    pub fn with_map_stat(mut self, name: &str) -> Self {
+        self.map_stat = name.to_string();
+        self
+    }
+
+    /// Set the `afl_banner`, shown by `afl-whatsup`
+    #[must_use]
+    pub fn with_banner(mut self, banner: &str) -> Self {
+        self.banner = banner.to_string();
+        self
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn write_afl_stats(&mut self, id: usize, cur_time: Duration) -> Result<(), crate::Error> {
+        let start_time = self.start_time();
+        let client = &mut self.client_stats_mut()[id];
+        let execs_per_sec = client.execs_per_sec(cur_time);
+        let client = &self.client_stats()[id];
+        let track = &self.clients[id];
+
+        let stability = match client.user_monitor.get("stability").map(UserStats::value) {
+            Some(UserStatsValue::Float(stability)) => stability * 100.0,
+            _ => 100.0,
+        };
+        let map = match client
+            .user_monitor
+            .get(&self.map_stat)
+            .map(UserStats::value)
+        {
+            Some(UserStatsValue::Ratio(found, total)) => Some((*found, *total)),
+            _ => None,
+        };
+        let as_secs = |time: Option<Duration>| time.map_or(0, |t| t.as_secs());
+
+        let mut stats = vec![];
+        let mut field = |name: &str, value: String| {
+            stats.push(format!("{:<18}: {}\n", name, value));
+        };
+        field("start_time", start_time.as_secs().to_string());
+        field("last_update", cur_time.as_secs().to_string());
+        field("run_time", (cur_time - start_time).as_secs().to_string());
+        field("execs_done", client.executions.to_string());
+        field("execs_per_sec", execs_per_sec.to_string());
+        field("corpus_count", client.corpus_size.to_string());
+        field("corpus_found", client.corpus_size.to_string());
+        field("stability", format!("{:.2}%", stability));
+        if let Some((found, total)) = map {
+            field("bitmap_cvg", format!("{:.2}%", ratio_percent(found, total)));
+            field("edges_found", found.to_string());
+            field("total_edges", total.to_string());
+        }
+        field("saved_crashes", client.objective_size.to_string());
+        field("last_find", as_secs(track.last_find).to_string());
+        field("last_crash", as_secs(track.last_crash).to_string());
+        for name in AFL_USER_STATS_FIELDS {
+            if let Some(value) = user_stat_number(client, name) {
+                field(name, value.to_string());
+            }
+        }
+        field("afl_banner", self.banner.clone());
+        field(
+            "afl_version",
+            format!("libafl-{}", env!("CARGO_PKG_VERSION")),
+        );
+        field("target_mode", "default".to_string());
+        field(
+            "command_line",
+            std::env::args().collect::<Vec<_>>().join(" "),
+        );
+
+        let dir = self.out_dir.join(format!("client_{}", id));
+        fs::create_dir_all(&dir)?;
+        let tmp_path = dir.join(".fuzzer_stats.tmp");
+        fs::write(&tmp_path, stats.concat())?;
+        fs::rename(&tmp_path, dir.join("fuzzer_stats"))?;
+
+        // The columns of `plot_data` are fixed, so the values not reported by the client are 0
+        let plot_value = |name: &str| user_stat_number(client, name).unwrap_or(0);
+        let (edges_found, total_edges) = map.unwrap_or((0, 0));
+        let plot_path = dir.join("plot_data");
+        let new_plot = !plot_path.exists();
+        let mut plot = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(plot_path)?;
+        if new_plot {
+            writeln!(plot, "{}", AFL_PLOT_DATA_HEADER)?;
+        }
+        writeln!(
+            plot,
+            "{}, {}, {}, {}, {}, {}, {:.2}%, {}, {}, {}, {}, {}, {}",
+            (cur_time - start_time).as_secs(),
+            plot_value("cycles_done"),
+            plot_value("cur_item"),
+            client.corpus_size,
+            plot_value("pending_total"),
+            plot_value("pending_favs"),
+            ratio_percent(edges_found, total_edges),
+            client.objective_size,
+            plot_value("saved_hangs"),
+            plot_value("max_depth"),
+            execs_per_sec,
+            client.executions,
+            edges_found
+        )?;
+        Ok(())
+    }
+}
+
+/// The map density, in percent
+#[allow(clippy::cast_precision_loss)]
+fn ratio_percent(found: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        found as f64 * 100.0 / total as f64
+    }
+}
+
+/// The value of a numeric user stat of the client, if it reported it
+fn user_stat_number(client: &ClientStats, name: &str) -> Option<u64> {
+    match client.user_monitor.get(name).map(UserStats::value) {
+        Some(UserStatsValue::Number(value)) => Some(*value),
+        _ => None,
+    }
+}
+
+impl OnDiskAFLMonitor<NopMonitor> {
+    /// Create new [`OnDiskAFLMonitor`] without a base
+    #[must_use]
+    pub fn nop<P>(out_dir: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self::new(out_dir, NopMonitor::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use std::fs;
+
+    use crate::monitors::{
+        disk::OnDiskAFLMonitor, AggregatorOps, Monitor, UserStats, UserStatsValue,
+    };
+
+    #[test]
+    fn test_afl_stats() {
+        let dir =
+            std::env::temp_dir().join(format!("libafl_test_afl_stats_{}", std::process::id()));
+        let mut monitor = OnDiskAFLMonitor::nop(&dir)
+            .with_banner("test")
+            .with_map_stat("shared_mem");
+
+        let client = monitor.client_stats_mut_for(1);
+        client.update_executions(1000, client.last_window_time);
+        client.update_corpus_size(12);
+        client.update_objective_size(1);
+        client.update_user_stats(
+            "stability".into(),
+            UserStats::new(UserStatsValue::Float(0.5), AggregatorOps::Avg),
+        );
+        client.update_user_stats(
+            "shared_mem".into(),
+            UserStats::new(UserStatsValue::Ratio(25, 100), AggregatorOps::Max),
+        );
+        // Not the map stat
+        client.update_user_stats(
+            "other_map".into(),
+            UserStats::new(UserStatsValue::Ratio(1, 2), AggregatorOps::Max),
+        );
+        client.update_user_stats(
+            "cycles_done".into(),
+            UserStats::new(UserStatsValue::Number(3), AggregatorOps::Max),
+        );
+        monitor.display("Testcase".into(), 1);
+
+        let stats = fs::read_to_string(dir.join("client_1").join("fuzzer_stats")).unwrap();
+        assert!(stats.contains("execs_done        : 1000\n"));
+        assert!(stats.contains("corpus_count      : 12\n"));
+        assert!(stats.contains("saved_crashes     : 1\n"));
+        assert!(stats.contains("stability         : 50.00%\n"));
+        assert!(stats.contains("bitmap_cvg        : 25.00%\n"));
+        assert!(stats.contains("edges_found       : 25\n"));
+        assert!(stats.contains("afl_banner        : test\n"));
+        assert!(stats.contains("cycles_done       : 3\n"));
+        assert!(!stats.contains("last_find         : 0\n"));
+        // Not reported by the client, so left out
+        assert!(!stats.contains("fuzzer_pid"));
+        assert!(!stats.contains("pending_total"));
+        assert!(!stats.contains("saved_hangs"));
+
+        let plot = fs::read_to_string(dir.join("client_1").join("plot_data")).unwrap();
+        let lines: Vec<&str> = plot.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("# relative_time"));
+        assert!(lines[1].ends_with(", 3, 0, 12, 0, 0, 25.00%, 1, 0, 0, 0, 1000, 25"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,451 @@
+//! A monitor that wraps a base one and appends timestamped records of all client stats to a file, for the whole campaign,
+//! and an offline reader that turns such a file back into curves, e.g. coverage over time.
+//!
+//! Records are written either as JSON lines, or as length-prefixed `postcard` records, which are more compact.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::time::Duration;
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    bolts::current_time,
+    events::ControlCommand,
+    monitors::{ClientStats, Monitor, NopMonitor, UserStats, UserStatsValue},
+    Error,
+};
+
+/// The format of a stats history file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StatsHistoryFormat {
+    /// One JSON object per line
+    JsonLines,
+    /// Each record is serialized with `postcard`, and prefixed with its length as little endian `u32`
+    Postcard,
+}
+
+/// A snapshot of the stats of one client
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatsRecord {
+    /// The time of the snapshot, since the epoch
+    pub time: Duration,
+    /// The time of the snapshot, since the campaign started
+    pub run_time: Duration,
+    /// The id of the client
+    pub client_id: u32,
+    /// The size of the corpus of the client
+    pub corpus_size: u64,
+    /// The size of the objective corpus of the client
+    pub objective_size: u64,
+    /// The executions of the client
+    pub executions: u64,
+    /// The executions per second of the client
+    pub exec_sec: u64,
+    /// The user stats of the client
+    pub user_stats: HashMap<String, UserStats>,
+}
+
+/// Wrap a monitor and append a [`StatsRecord`] for a client to a file, each time it reports new stats,
+/// but at most once per interval (one second, by default).
+#[derive(Debug)]
+pub struct StatsHistoryMonitor<M>
+where
+    M: Monitor,
+{
+    base: M,
+    filename: PathBuf,
+    format: StatsHistoryFormat,
+    interval: Duration,
+    last_records: Vec<Duration>,
+    /// The history file, opened for the first record
+    file: Option<File>,
+}
+
+impl<M> Clone for StatsHistoryMonitor<M>
+where
+    M: Monitor + Clone,
+{
+    /// The clone opens the history file again, for its first record
+    fn clone(&self) -> Self {
+        Self {
+            base: self.base.clone(),
+            filename: self.filename.clone(),
+            format: self.format,
+            interval: self.interval,
+            last_records: self.last_records.clone(),
+            file: None,
+        }
+    }
+}
+
+impl<M> Monitor for StatsHistoryMonitor<M>
+where
+    M: Monitor,
+{
+    /// The client monitor, mutable
+    fn client_stats_mut(&mut self) -> &mut Vec<ClientStats> {
+        self.base.client_stats_mut()
+    }
+
+    /// The client monitor
+    fn client_stats(&self) -> &[ClientStats] {
+        self.base.client_stats()
+    }
+
+    /// Time this fuzzing run stated
+    fn start_time(&mut self) -> Duration {
+        self.base.start_time()
+    }
+
+    fn take_control_commands(&mut self) -> Vec<(Option<u32>, ControlCommand)> {
+        self.base.take_control_commands()
+    }
+
+    fn display(&mut self, event_msg: String, sender_id: u32) {
+        let cur_time = current_time();
+        let id = sender_id as usize;
+        if self.last_records.len() <= id {
+            self.last_records.resize(id + 1, Duration::ZERO);
+        }
+
+        if sender_id != 0 && cur_time - self.last_records[id] >= self.interval {
+            self.last_records[id] = cur_time;
+            let run_time = cur_time - self.start_time();
+            let client = self.client_stats_mut_for(sender_id);
+            let record = StatsRecord {
+                time: cur_time,
+                run_time,
+                client_id: sender_id,
+                corpus_size: client.corpus_size,
+                objective_size: client.objective_size,
+                executions: client.executions,
+                exec_sec: client.execs_per_sec(cur_time),
+                user_stats: client.user_monitor.clone(),
+            };
+            if let Err(e) = self.append(&record) {
+                eprintln!(
+                    "Could not append to the stats history {}: {:?}",
+                    self.filename.display(),
+                    e
+                );
+                // Reopen the file for the next record
+                self.file = None;
+            }
+        }
+
+        self.base.display(event_msg, sender_id);
+    }
+}
+
+impl<M> StatsHistoryMonitor<M>
+where
+    M: Monitor,
+{
+    /// Create new [`StatsHistoryMonitor`], appending to `filename` in the given format
+    #[must_use]
+    pub fn new<P>(filename: P, format: StatsHistoryFormat, base: M) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            base,
+            filename: filename.into(),
+            format,
+            interval: Duration::from_secs(1),
+            last_records: vec![],
+            file: None,
+        }
+    }
+
+    /// Record the stats of each client at most once per `interval`
+    #[must_use]
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    fn append(&mut self, record: &StatsRecord) -> Result<(), Error> {
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => self.file.insert(
+                OpenOptions::new()
+                    .append(true)
+                    .create(true)
+                    .open(&self.filename)?,
+            ),
+        };
+        match self.format {
+            StatsHistoryFormat::JsonLines => {
+                let mut line = serde_json::to_vec(record)?;
+                line.push(b'\n');
+                file.write_all(&line)?;
+            }
+            StatsHistoryFormat::Postcard => {
+                let bytes = postcard::to_allocvec(record)?;
+                let len = u32::try_from(bytes.len())
+                    .map_err(|_| Error::illegal_argument("Stats record too large"))?;
+                let mut buf = len.to_le_bytes().to_vec();
+                buf.extend_from_slice(&bytes);
+                file.write_all(&buf)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl StatsHistoryMonitor<NopMonitor> {
+    /// Create new [`StatsHistoryMonitor`] without a base
+    #[must_use]
+    pub fn nop<P>(filename: P, format: StatsHistoryFormat) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self::new(filename, format, NopMonitor::new())
+    }
+}
+
+/// The records of a stats history file, written by a [`StatsHistoryMonitor`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StatsHistory {
+    records: Vec<StatsRecord>,
+}
+
+impl StatsHistory {
+    /// Reads a stats history file in the given format.
+    /// A truncated last record, e.g. from a campaign that was killed while writing it, is skipped.
+    pub fn load<P>(filename: P, format: StatsHistoryFormat) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let bytes = fs::read(filename)?;
+        let mut records = vec![];
+        match format {
+            StatsHistoryFormat::JsonLines => {
+                let mut lines = bytes.split(|b| *b == b'\n').peekable();
+                while let Some(line) = lines.next() {
+                    if line.is_empty() {
+                        continue;
+                    }
+                    match serde_json::from_slice(line) {
+                        Ok(record) => records.push(record),
+                        // The last line is not terminated, so it was not written completely
+                        Err(_) if lines.peek().is_none() => break,
+                        Err(e) => return Err(e.into()),
+                    }
+                }
+            }
+            StatsHistoryFormat::Postcard => {
+                let mut rest = &bytes[..];
+                while rest.len() >= 4 {
+                    let len = u32::from_le_bytes(rest[..4].try_into().unwrap()) as usize;
+                    let record = match rest.get(4..4 + len) {
+                        Some(record) => record,
+                        // The last record was not written completely
+                        None => break,
+                    };
+                    records.push(postcard::from_bytes(record)?);
+                    rest = &rest[4 + len..];
+                }
+            }
+        }
+        Ok(Self::from_records(records))
+    }
+
+    /// Creates a [`StatsHistory`] from records, ordering them by time
+    #[must_use]
+    pub fn from_records(mut records: Vec<StatsRecord>) -> Self {
+        records.sort_by_key(|record| record.run_time);
+        Self { records }
+    }
+
+    /// All records, ordered by time
+    #[must_use]
+    pub fn records(&self) -> &[StatsRecord] {
+        &self.records
+    }
+
+    /// The ids of all clients with records, in ascending order
+    #[must_use]
+    pub fn clients(&self) -> Vec<u32> {
+        let mut clients: Vec<u32> = self.records.iter().map(|r| r.client_id).collect();
+        clients.sort_unstable();
+        clients.dedup();
+        clients
+    }
+
+    /// Replays the records, and for each one, calls `f` with the latest record of every client so far.
+    /// Returns the run times together with the values returned by `f`, skipping `None`s.
+    pub fn series<F, T>(&self, mut f: F) -> Vec<(Duration, T)>
+    where
+        F: FnMut(&[&StatsRecord]) -> Option<T>,
+    {
+        let mut latest: Vec<&StatsRecord> = vec![];
+        let mut series = vec![];
+        for record in &self.records {
+            match latest.iter_mut().find(|r| r.client_id == record.client_id) {
+                Some(r) => *r = record,
+                None => latest.push(record),
+            }
+            if let Some(value) = f(&latest) {
+                series.push((record.run_time, value));
+            }
+        }
+        series
+    }
+
+    /// The total executions of all clients over time
+    #[must_use]
+    pub fn executions_over_time(&self) -> Vec<(Duration, u64)> {
+        self.series(|latest| Some(latest.iter().map(|r| r.executions).sum()))
+    }
+
+    /// The executions per second of all clients over time
+    #[must_use]
+    pub fn execs_per_sec_over_time(&self) -> Vec<(Duration, u64)> {
+        self.series(|latest| Some(latest.iter().map(|r| r.exec_sec).sum()))
+    }
+
+    /// The total objectives of all clients over time
+    #[must_use]
+    pub fn objectives_over_time(&self) -> Vec<(Duration, u64)> {
+        self.series(|latest| Some(latest.iter().map(|r| r.objective_size).sum()))
+    }
+
+    /// The user stat `name` over time, aggregated over all clients following its [`crate::monitors::AggregatorOps`].
+    /// For the coverage reported by a `MapFeedback`, this is the coverage over time.
+    #[must_use]
+    pub fn user_stat_over_time(&self, name: &str) -> Vec<(Duration, UserStatsValue)> {
+        self.series(|latest| {
+            let op = latest
+                .iter()
+                .find_map(|r| r.user_stats.get(name))?
+                .aggregator_op();
+            UserStatsValue::aggregate(
+                op,
+                latest
+                    .iter()
+                    .filter_map(|r| r.user_stats.get(name))
+                    .map(UserStats::value),
+            )
+        })
+    }
+
+    /// The names of all user stats in the history, sorted
+    #[must_use]
+    pub fn user_stat_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .records
+            .iter()
+            .flat_map(|r| r.user_stats.keys())
+            .map(ToString::to_string)
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use core::time::Duration;
+    use std::fs;
+
+    use crate::monitors::{
+        history::{StatsHistory, StatsHistoryFormat, StatsHistoryMonitor},
+        AggregatorOps, Monitor, UserStats, UserStatsValue,
+    };
+
+    #[test]
+    fn test_stats_history() {
+        for format in [StatsHistoryFormat::JsonLines, StatsHistoryFormat::Postcard] {
+            let filename = std::env::temp_dir().join(format!(
+                "libafl_test_stats_history_{:?}_{}",
+                format,
+                std::process::id()
+            ));
+            let mut monitor =
+                StatsHistoryMonitor::nop(&filename, format).with_interval(Duration::ZERO);
+
+            for (id, executions, edges) in [(1, 100, 10), (2, 50, 30), (1, 300, 40)] {
+                let client = monitor.client_stats_mut_for(id);
+                client.update_executions(executions, client.last_window_time);
+                client.update_user_stats(
+                    "edges".into(),
+                    UserStats::new(UserStatsValue::Ratio(edges, 100), AggregatorOps::Max),
+                );
+                monitor.display("UserStats".into(), id);
+            }
+
+            let history = StatsHistory::load(&filename, format).unwrap();
+            assert_eq!(history.records().len(), 3);
+            assert_eq!(history.clients(), [1, 2]);
+            assert_eq!(history.user_stat_names(), ["edges"]);
+            let executions: Vec<u64> = history
+                .executions_over_time()
+                .into_iter()
+                .map(|(_, e)| e)
+                .collect();
+            assert_eq!(executions, [100, 150, 350]);
+            let edges: Vec<UserStatsValue> = history
+                .user_stat_over_time("edges")
+                .into_iter()
+                .map(|(_, e)| e)
+                .collect();
+            assert_eq!(
+                edges,
+                [
+                    UserStatsValue::Ratio(10, 100),
+                    UserStatsValue::Ratio(30, 100),
+                    UserStatsValue::Ratio(40, 100)
+                ]
+            );
+
+            fs::remove_file(&filename).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_stats_history_truncated() {
+        for format in [StatsHistoryFormat::JsonLines, StatsHistoryFormat::Postcard] {
+            let filename = std::env::temp_dir().join(format!(
+                "libafl_test_stats_history_truncated_{:?}_{}",
+                format,
+                std::process::id()
+            ));
+            let mut monitor =
+                StatsHistoryMonitor::nop(&filename, format).with_interval(Duration::ZERO);
+            for (id, executions) in [(1, 100), (2, 50), (1, 300)] {
+                let client = monitor.client_stats_mut_for(id);
+                client.update_executions(executions, client.last_window_time);
+                monitor.display("UserStats".into(), id);
+            }
+            drop(monitor);
+
+            // The campaign was killed while writing the last record
+            let bytes = fs::read(&filename).unwrap();
+            fs::write(&filename, &bytes[..bytes.len() - 3]).unwrap();
+
+            let history = StatsHistory::load(&filename, format).unwrap();
+            assert_eq!(history.records().len(), 2);
+            assert_eq!(
+                history
+                    .executions_over_time()
+                    .into_iter()
+                    .map(|(_, e)| e)
+                    .collect::<Vec<u64>>(),
+                [100, 150]
+            );
+
+            fs::remove_file(&filename).unwrap();
+        }
+    }
+}
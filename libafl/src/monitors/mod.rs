@@ -10,7 +10,17 @@ pub mod tui;
 #[cfg(feature = "std")]
 pub mod disk;
 #[cfg(feature = "std")]
-pub use disk::OnDiskTOMLMonitor;
+pub use disk::{OnDiskAFLMonitor, OnDiskTOMLMonitor};
+
+#[cfg(feature = "std")]
+pub mod history;
+#[cfg(feature = "std")]
+pub use history::{StatsHistory, StatsHistoryFormat, StatsHistoryMonitor, StatsRecord};
+
+#[cfg(feature = "std")]
+pub mod prometheus;
+#[cfg(feature = "std")]
+pub use prometheus::PrometheusMonitor;
 
 use alloc::{fmt::Debug, string::String, vec::Vec};
 
@@ -21,15 +31,36 @@ use core::{fmt, time::Duration};
 use hashbrown::HashMap;
 use serde::{Deserialize, Serialize};
 
-use crate::bolts::{current_time, format_duration_hms};
+use crate::{
+    bolts::{current_time, format_duration_hms},
+    events::ControlCommand,
+};
 
 #[cfg(feature = "afl_exec_sec")]
 const CLIENT_STATS_TIME_WINDOW_SECS: u64 = 5; // 5 seconds
 
-/// User-defined stat types
-/// TODO define aggregation function (avg, median, max, ...)
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub enum UserStats {
+/// How the values of a user stat, reported by several clients, are combined into one global value
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregatorOps {
+    /// Do not aggregate, the stat is only shown per client
+    None,
+    /// The sum of all values. For ratios, the same as [`AggregatorOps::RatioUnion`].
+    Sum,
+    /// The smallest value
+    Min,
+    /// The largest value. For a coverage ratio of clients sharing their corpus, this is the best estimate of the union.
+    Max,
+    /// The mean of all values. For ratios, the mean numerator over the mean denominator,
+    /// kept as the sum of the numerators over the sum of the denominators, so nothing is rounded away.
+    Avg,
+    /// The union of ratios of disjoint parts, such as the coverage of clients fuzzing separate maps:
+    /// the sum of the numerators over the sum of the denominators. Other values are summed up.
+    RatioUnion,
+}
+
+/// The value of a user-defined stat
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum UserStatsValue {
     /// A numerical value
     Number(u64),
     /// A Float value
@@ -40,13 +71,13 @@ pub enum UserStats {
     Ratio(u64, u64),
 }
 
-impl fmt::Display for UserStats {
+impl fmt::Display for UserStatsValue {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            UserStats::Number(n) => write!(f, "{}", n),
-            UserStats::Float(n) => write!(f, "{}", n),
-            UserStats::String(s) => write!(f, "{}", s),
-            UserStats::Ratio(a, b) => {
+            UserStatsValue::Number(n) => write!(f, "{}", n),
+            UserStatsValue::Float(n) => write!(f, "{}", n),
+            UserStatsValue::String(s) => write!(f, "{}", s),
+            UserStatsValue::Ratio(a, b) => {
                 if *b == 0 {
                     write!(f, "{}/{}", a, b)
                 } else {
@@ -57,6 +88,114 @@ impl fmt::Display for UserStats {
     }
 }
 
+impl UserStatsValue {
+    /// Combines the values of several clients into one, following `op`.
+    /// Values of another kind than the first one are ignored.
+    /// Returns `None` if there are no values, for [`AggregatorOps::None`], and for strings.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn aggregate<'a, T>(op: AggregatorOps, values: T) -> Option<UserStatsValue>
+    where
+        T: IntoIterator<Item = &'a UserStatsValue>,
+    {
+        let mut values = values.into_iter().peekable();
+        let first = values.peek()?;
+        match (op, first) {
+            (AggregatorOps::None, _) | (_, UserStatsValue::String(_)) => None,
+            (_, UserStatsValue::Number(_)) => {
+                let numbers: Vec<u64> = values
+                    .filter_map(|v| match v {
+                        UserStatsValue::Number(n) => Some(*n),
+                        _ => None,
+                    })
+                    .collect();
+                Some(match op {
+                    AggregatorOps::Sum | AggregatorOps::RatioUnion => {
+                        UserStatsValue::Number(numbers.iter().sum())
+                    }
+                    AggregatorOps::Min => UserStatsValue::Number(*numbers.iter().min()?),
+                    AggregatorOps::Max => UserStatsValue::Number(*numbers.iter().max()?),
+                    _ => UserStatsValue::Float(
+                        numbers.iter().sum::<u64>() as f64 / numbers.len() as f64,
+                    ),
+                })
+            }
+            (_, UserStatsValue::Float(_)) => {
+                let floats: Vec<f64> = values
+                    .filter_map(|v| match v {
+                        UserStatsValue::Float(f) => Some(*f),
+                        _ => None,
+                    })
+                    .collect();
+                Some(UserStatsValue::Float(match op {
+                    AggregatorOps::Sum | AggregatorOps::RatioUnion => floats.iter().sum(),
+                    AggregatorOps::Min => floats.iter().copied().fold(f64::INFINITY, f64::min),
+                    AggregatorOps::Max => floats.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+                    _ => floats.iter().sum::<f64>() / floats.len() as f64,
+                }))
+            }
+            (_, UserStatsValue::Ratio(_, _)) => {
+                let ratios: Vec<(u64, u64)> = values
+                    .filter_map(|v| match v {
+                        UserStatsValue::Ratio(a, b) => Some((*a, *b)),
+                        _ => None,
+                    })
+                    .collect();
+                let (a, b) = match op {
+                    // Compare a1/b1 with a2/b2 without rounding
+                    AggregatorOps::Min => *ratios.iter().min_by(|(a1, b1), (a2, b2)| {
+                        (u128::from(*a1) * u128::from(*b2))
+                            .cmp(&(u128::from(*a2) * u128::from(*b1)))
+                    })?,
+                    AggregatorOps::Max => *ratios.iter().max_by(|(a1, b1), (a2, b2)| {
+                        (u128::from(*a1) * u128::from(*b2))
+                            .cmp(&(u128::from(*a2) * u128::from(*b1)))
+                    })?,
+                    // The mean of the numerators over the mean of the denominators is the same ratio
+                    _ => ratios.iter().fold((0, 0), |(a, b), (x, y)| (a + x, b + y)),
+                };
+                Some(UserStatsValue::Ratio(a, b))
+            }
+        }
+    }
+}
+
+/// A user-defined stat, with the policy to aggregate it over all clients
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UserStats {
+    value: UserStatsValue,
+    aggregator_op: AggregatorOps,
+}
+
+impl UserStats {
+    /// Creates a new [`UserStats`]
+    #[must_use]
+    pub fn new(value: UserStatsValue, aggregator_op: AggregatorOps) -> Self {
+        Self {
+            value,
+            aggregator_op,
+        }
+    }
+
+    /// The value of this stat
+    #[must_use]
+    pub fn value(&self) -> &UserStatsValue {
+        &self.value
+    }
+
+    /// How this stat is aggregated over all clients
+    #[must_use]
+    pub fn aggregator_op(&self) -> AggregatorOps {
+        self.aggregator_op
+    }
+}
+
+impl fmt::Display for UserStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.value, f)
+    }
+}
+
 /// A simple struct to keep track of client monitor
 #[derive(Debug, Clone, Default)]
 pub struct ClientStats {
@@ -224,6 +363,30 @@ pub trait Monitor {
             .fold(0_u64, |acc, x| acc + x.execs_per_sec(cur_time))
     }
 
+    /// The user stats of all clients, aggregated following their [`AggregatorOps`], sorted by name.
+    /// Stats that are not aggregated are left out.
+    fn aggregated_user_stats(&self) -> Vec<(String, UserStatsValue)> {
+        let mut ops: Vec<(&String, AggregatorOps)> = vec![];
+        for client in self.client_stats() {
+            for (name, stat) in &client.user_monitor {
+                if !ops.iter().any(|(n, _)| *n == name) {
+                    ops.push((name, stat.aggregator_op()));
+                }
+            }
+        }
+        ops.sort_by_key(|(name, _)| *name);
+        ops.into_iter()
+            .filter_map(|(name, op)| {
+                let values = self
+                    .client_stats()
+                    .iter()
+                    .filter_map(|client| client.user_monitor.get(name))
+                    .map(UserStats::value);
+                UserStatsValue::aggregate(op, values).map(|value| (name.clone(), value))
+            })
+            .collect()
+    }
+
     /// The client monitor for a specific id, creating new if it doesn't exist
     fn client_stats_mut_for(&mut self, client_id: u32) -> &mut ClientStats {
         let client_stat_count = self.client_stats().len();
@@ -235,6 +398,13 @@ pub trait Monitor {
         }
         &mut self.client_stats_mut()[client_id as usize]
     }
+
+    /// The [`ControlCommand`]s requested through this monitor since the last call,
+    /// with the id of the client they are for, or `None` for all clients.
+    /// The event manager sends them to the clients.
+    fn take_control_commands(&mut self) -> Vec<(Option<u32>, ControlCommand)> {
+        vec![]
+    }
 }
 
 /// Monitor that print exactly nothing.
@@ -830,6 +1000,87 @@ impl Default for ClientPerfMonitor {
         Self::new()
     }
 }
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec};
+
+    use crate::monitors::{AggregatorOps, Monitor, NopMonitor, UserStats, UserStatsValue};
+
+    #[test]
+    fn test_aggregate_user_stats() {
+        let ratios = [
+            UserStatsValue::Ratio(10, 100),
+            UserStatsValue::Ratio(30, 100),
+        ];
+        assert_eq!(
+            UserStatsValue::aggregate(AggregatorOps::Max, &ratios),
+            Some(UserStatsValue::Ratio(30, 100))
+        );
+        assert_eq!(
+            UserStatsValue::aggregate(AggregatorOps::Sum, &ratios),
+            Some(UserStatsValue::Ratio(40, 200))
+        );
+        assert_eq!(
+            UserStatsValue::aggregate(AggregatorOps::RatioUnion, &ratios),
+            Some(UserStatsValue::Ratio(40, 200))
+        );
+        assert_eq!(
+            UserStatsValue::aggregate(AggregatorOps::Avg, &ratios),
+            Some(UserStatsValue::Ratio(40, 200))
+        );
+        // Nothing is rounded away
+        assert_eq!(
+            UserStatsValue::aggregate(
+                AggregatorOps::Avg,
+                &[UserStatsValue::Ratio(1, 3), UserStatsValue::Ratio(2, 3)]
+            ),
+            Some(UserStatsValue::Ratio(3, 6))
+        );
+        assert_eq!(
+            UserStatsValue::aggregate(AggregatorOps::None, &ratios),
+            None
+        );
+
+        let numbers = [UserStatsValue::Number(1), UserStatsValue::Number(4)];
+        assert_eq!(
+            UserStatsValue::aggregate(AggregatorOps::Avg, &numbers),
+            Some(UserStatsValue::Float(2.5))
+        );
+        assert_eq!(
+            UserStatsValue::aggregate(AggregatorOps::RatioUnion, &numbers),
+            Some(UserStatsValue::Number(5))
+        );
+        assert_eq!(
+            UserStatsValue::aggregate(AggregatorOps::Min, &numbers),
+            Some(UserStatsValue::Number(1))
+        );
+
+        let mut monitor = NopMonitor::new();
+        for (id, edges) in [(1, 10), (2, 30)] {
+            monitor.client_stats_mut_for(id).update_user_stats(
+                "edges".to_string(),
+                UserStats::new(UserStatsValue::Ratio(edges, 100), AggregatorOps::Max),
+            );
+            // Each client covers its own half of the map
+            monitor.client_stats_mut_for(id).update_user_stats(
+                "half_edges".to_string(),
+                UserStats::new(UserStatsValue::Ratio(edges, 50), AggregatorOps::RatioUnion),
+            );
+            monitor.client_stats_mut_for(id).update_user_stats(
+                "mode".to_string(),
+                UserStats::new(UserStatsValue::String("x".into()), AggregatorOps::None),
+            );
+        }
+        assert_eq!(
+            monitor.aggregated_user_stats(),
+            vec![
+                ("edges".to_string(), UserStatsValue::Ratio(30, 100)),
+                ("half_edges".to_string(), UserStatsValue::Ratio(40, 100))
+            ]
+        );
+    }
+}
+
 /// `Monitor` Python bindings
 #[cfg(feature = "python")]
 #[allow(missing_docs)]
@@ -49,7 +49,7 @@ where
             String::new()
         };
         let head = format!("{}{} {}", event_msg, pad, sender);
-        let global_fmt = format!(
+        let mut global_fmt = format!(
             "[{}]  (GLOBAL) run time: {}, clients: {}, corpus: {}, objectives: {}, executions: {}, exec/sec: {}",
             head,
             format_duration_hms(&(current_time() - self.start_time)),
@@ -59,6 +59,9 @@ where
             self.total_execs(),
             self.execs_per_sec()
         );
+        for (key, val) in self.aggregated_user_stats() {
+            write!(global_fmt, ", {}: {}", key, val).unwrap();
+        }
         (self.print_fn)(global_fmt);
 
         let client = self.client_stats_mut_for(sender_id);
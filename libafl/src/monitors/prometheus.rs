@@ -0,0 +1,420 @@
+//! A monitor that wraps a base one and serves the stats as `OpenMetrics` text over HTTP, to be scraped by Prometheus.
+//!
+//! Every client gets its own samples, labeled with `client="<id>"`:
+//! - `libafl_executions_total`, `libafl_corpus_size`, `libafl_objective_size` and `libafl_execs_per_sec`,
+//! - `libafl_user_stat{stat="<name>"}` for every numeric [`UserStats`], and `libafl_user_stat_string_info` for string ones,
+//! - with the `introspection` feature, `libafl_perf_cycles_total`, the clock cycles spent in each part of the fuzzer.
+//!
+//! The served metrics are refreshed on every [`Monitor::display`], and before the client stats are borrowed mutably again,
+//! so every change made through [`Monitor::client_stats_mut`] is published.
+
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::{fmt::Write as _, time::Duration};
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::RwLock,
+    thread,
+};
+
+#[cfg(feature = "introspection")]
+use crate::monitors::PerfFeature;
+use crate::{
+    bolts::current_time,
+    events::ControlCommand,
+    monitors::{ClientStats, Monitor, NopMonitor, UserStatsValue},
+    Error,
+};
+
+/// The content type of the `OpenMetrics` text format
+const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Wrap a monitor and serve its stats on an `OpenMetrics` endpoint, at `http://<addr>/metrics`.
+#[derive(Debug, Clone)]
+pub struct PrometheusMonitor<M>
+where
+    M: Monitor,
+{
+    base: M,
+    local_addr: SocketAddr,
+    metrics: Arc<RwLock<String>>,
+    /// The client stats may have changed since the metrics were last rendered
+    dirty: bool,
+}
+
+impl<M> Monitor for PrometheusMonitor<M>
+where
+    M: Monitor,
+{
+    /// The client monitor, mutable
+    fn client_stats_mut(&mut self) -> &mut Vec<ClientStats> {
+        // Publish the changes made through the last borrow, before the caller makes new ones
+        self.publish();
+        self.dirty = true;
+        self.base.client_stats_mut()
+    }
+
+    /// The client monitor
+    fn client_stats(&self) -> &[ClientStats] {
+        self.base.client_stats()
+    }
+
+    /// Time this fuzzing run stated
+    fn start_time(&mut self) -> Duration {
+        self.base.start_time()
+    }
+
+    fn take_control_commands(&mut self) -> Vec<(Option<u32>, ControlCommand)> {
+        self.base.take_control_commands()
+    }
+
+    fn display(&mut self, event_msg: String, sender_id: u32) {
+        self.publish();
+        self.base.display(event_msg, sender_id);
+    }
+}
+
+impl<M> PrometheusMonitor<M>
+where
+    M: Monitor,
+{
+    /// Create a new [`PrometheusMonitor`], serving the metrics on `addr`, e.g. `127.0.0.1:9090`.
+    /// The endpoint is served by a background thread, for the rest of the process lifetime.
+    pub fn new<A>(addr: A, base: M) -> Result<Self, Error>
+    where
+        A: ToSocketAddrs,
+    {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let metrics = Arc::new(RwLock::new("# EOF\n".to_string()));
+
+        let served = metrics.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                // A client going away mid-request is not our problem
+                drop(serve_metrics(stream, &served));
+            }
+        });
+
+        Ok(Self {
+            base,
+            local_addr,
+            metrics,
+            dirty: true,
+        })
+    }
+
+    /// The address the metrics are served on
+    #[must_use]
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Renders the current stats and serves them, if they may have changed since the last time
+    pub fn publish(&mut self) {
+        if self.dirty {
+            self.dirty = false;
+            let metrics = self.render();
+            *self.metrics.write().unwrap() = metrics;
+        }
+    }
+
+    /// Renders the current stats in the `OpenMetrics` text format
+    #[must_use]
+    pub fn render(&mut self) -> String {
+        let cur_time = current_time();
+        let run_time = (cur_time - self.start_time()).as_secs();
+        let clients = self.client_stats().len().saturating_sub(1);
+
+        let mut out = String::new();
+        metric_family(
+            &mut out,
+            "libafl_run_time_seconds",
+            "gauge",
+            "Time since the fuzzing run started",
+        );
+        writeln!(out, "libafl_run_time_seconds {}", run_time).unwrap();
+        metric_family(&mut out, "libafl_clients", "gauge", "Number of clients");
+        writeln!(out, "libafl_clients {}", clients).unwrap();
+
+        // The client with id 0 is the broker
+        let stats = self.base.client_stats_mut();
+        let execs_per_sec: Vec<u64> = stats
+            .iter_mut()
+            .map(|client| client.execs_per_sec(cur_time))
+            .collect();
+        let stats = self.client_stats();
+
+        metric_family(
+            &mut out,
+            "libafl_executions",
+            "counter",
+            "Executions of the target",
+        );
+        for (id, client) in stats.iter().enumerate().skip(1) {
+            writeln!(
+                out,
+                "libafl_executions_total{{client=\"{}\"}} {}",
+                id, client.executions
+            )
+            .unwrap();
+        }
+        metric_family(
+            &mut out,
+            "libafl_corpus_size",
+            "gauge",
+            "Entries in the corpus",
+        );
+        for (id, client) in stats.iter().enumerate().skip(1) {
+            writeln!(
+                out,
+                "libafl_corpus_size{{client=\"{}\"}} {}",
+                id, client.corpus_size
+            )
+            .unwrap();
+        }
+        metric_family(
+            &mut out,
+            "libafl_objective_size",
+            "gauge",
+            "Entries in the objective corpus",
+        );
+        for (id, client) in stats.iter().enumerate().skip(1) {
+            writeln!(
+                out,
+                "libafl_objective_size{{client=\"{}\"}} {}",
+                id, client.objective_size
+            )
+            .unwrap();
+        }
+        metric_family(
+            &mut out,
+            "libafl_execs_per_sec",
+            "gauge",
+            "Executions per second",
+        );
+        for (id, exec_sec) in execs_per_sec.iter().enumerate().skip(1) {
+            writeln!(
+                out,
+                "libafl_execs_per_sec{{client=\"{}\"}} {}",
+                id, exec_sec
+            )
+            .unwrap();
+        }
+
+        render_user_stats(&mut out, stats);
+        #[cfg(feature = "introspection")]
+        render_introspection(&mut out, stats);
+
+        out.push_str("# EOF\n");
+        out
+    }
+}
+
+impl PrometheusMonitor<NopMonitor> {
+    /// Create a new [`PrometheusMonitor`] without a base
+    pub fn nop<A>(addr: A) -> Result<Self, Error>
+    where
+        A: ToSocketAddrs,
+    {
+        Self::new(addr, NopMonitor::new())
+    }
+}
+
+/// Renders the user stats of each client
+fn render_user_stats(out: &mut String, stats: &[ClientStats]) {
+    metric_family(out, "libafl_user_stat", "gauge", "Numeric user stats");
+    for (id, client) in stats.iter().enumerate().skip(1) {
+        for (name, value) in &client.user_monitor {
+            #[allow(clippy::cast_precision_loss)]
+            let value = match value.value() {
+                UserStatsValue::Number(n) => *n as f64,
+                UserStatsValue::Float(f) => *f,
+                UserStatsValue::Ratio(a, b) => {
+                    if *b == 0 {
+                        0.0
+                    } else {
+                        *a as f64 / *b as f64
+                    }
+                }
+                UserStatsValue::String(_) => continue,
+            };
+            writeln!(
+                out,
+                "libafl_user_stat{{client=\"{}\",stat=\"{}\"}} {}",
+                id,
+                escape_label(name),
+                value
+            )
+            .unwrap();
+        }
+    }
+    metric_family(out, "libafl_user_stat_string", "info", "String user stats");
+    for (id, client) in stats.iter().enumerate().skip(1) {
+        for (name, value) in &client.user_monitor {
+            if let UserStatsValue::String(s) = value.value() {
+                writeln!(
+                    out,
+                    "libafl_user_stat_string_info{{client=\"{}\",stat=\"{}\",value=\"{}\"}} 1",
+                    id,
+                    escape_label(name),
+                    escape_label(s)
+                )
+                .unwrap();
+            }
+        }
+    }
+}
+
+/// Renders the performance monitor of each client
+#[cfg(feature = "introspection")]
+fn render_introspection(out: &mut String, stats: &[ClientStats]) {
+    metric_family(
+        out,
+        "libafl_perf_cycles",
+        "counter",
+        "Clock cycles spent in each part of the fuzzer",
+    );
+    for (id, client) in stats.iter().enumerate().skip(1) {
+        let perf = &client.introspection_monitor;
+        let mut sample = |labels: &str, cycles: u64| {
+            writeln!(
+                out,
+                "libafl_perf_cycles_total{{client=\"{}\",{}}} {}",
+                id, labels, cycles
+            )
+            .unwrap();
+        };
+        sample("part=\"elapsed\"", perf.elapsed_cycles());
+        sample("part=\"scheduler\"", perf.scheduler_cycles());
+        sample("part=\"manager\"", perf.manager_cycles());
+        for (stage, features) in perf.used_stages() {
+            for (feature, cycles) in features.iter().enumerate() {
+                let feature: PerfFeature = feature.into();
+                sample(
+                    &format!(
+                        "part=\"stage\",stage=\"{}\",feature=\"{:?}\"",
+                        stage, feature
+                    ),
+                    *cycles,
+                );
+            }
+        }
+        for (feedback, cycles) in perf.feedbacks() {
+            sample(
+                &format!("part=\"feedback\",feedback=\"{}\"", escape_label(feedback)),
+                *cycles,
+            );
+        }
+    }
+}
+
+/// Writes the `TYPE` and `HELP` lines of a metric family
+fn metric_family(out: &mut String, name: &str, kind: &str, help: &str) {
+    writeln!(out, "# TYPE {} {}\n# HELP {} {}", name, kind, name, help).unwrap();
+}
+
+/// Escapes a label value
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Answers a single HTTP request with the metrics
+fn serve_metrics(mut stream: TcpStream, metrics: &RwLock<String>) -> Result<(), Error> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    // We only need the request line, the rest of the request is ignored
+    let mut buf = [0_u8; 1024];
+    let len = stream.read(&mut buf)?;
+    let request = String::from_utf8_lossy(&buf[..len]);
+    let path = request.split_whitespace().nth(1).unwrap_or("/");
+
+    let response = if path == "/metrics" || path == "/" {
+        let body = metrics.read().unwrap().clone();
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            OPENMETRICS_CONTENT_TYPE,
+            body.len(),
+            body
+        )
+    } else {
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+    };
+    stream.write_all(response.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+    use std::{
+        io::{Read, Write},
+        net::TcpStream,
+    };
+
+    use crate::monitors::{
+        prometheus::PrometheusMonitor, AggregatorOps, Monitor, UserStats, UserStatsValue,
+    };
+
+    #[test]
+    fn test_prometheus_monitor() {
+        let mut monitor = PrometheusMonitor::nop("127.0.0.1:0").unwrap();
+        let client = monitor.client_stats_mut_for(1);
+        client.update_executions(1234, client.last_window_time);
+        client.update_corpus_size(5);
+        client.update_user_stats(
+            "edges".into(),
+            UserStats::new(UserStatsValue::Ratio(1, 4), AggregatorOps::Max),
+        );
+        client.update_user_stats(
+            "mode".into(),
+            UserStats::new(
+                UserStatsValue::String("a \"b\"".into()),
+                AggregatorOps::None,
+            ),
+        );
+        monitor.display("Testcase".into(), 1);
+
+        let response = scrape(&monitor);
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("libafl_clients 1\n"));
+        assert!(response.contains("libafl_executions_total{client=\"1\"} 1234\n"));
+        assert!(response.contains("libafl_corpus_size{client=\"1\"} 5\n"));
+        assert!(response.contains("libafl_user_stat{client=\"1\",stat=\"edges\"} 0.25\n"));
+        assert!(response.contains(
+            "libafl_user_stat_string_info{client=\"1\",stat=\"mode\",value=\"a \\\"b\\\"\"} 1\n"
+        ));
+        assert!(response.ends_with("# EOF\n"));
+
+        // Changes right after a display are published by the next one
+        monitor.client_stats_mut_for(1).update_objective_size(2);
+        monitor.display("Objective".into(), 1);
+        assert!(scrape(&monitor).contains("libafl_objective_size{client=\"1\"} 2\n"));
+
+        // Changes made without a display are published before the next change
+        monitor.client_stats_mut_for(1).update_corpus_size(6);
+        monitor.client_stats_mut_for(2);
+        let response = scrape(&monitor);
+        assert!(response.contains("libafl_corpus_size{client=\"1\"} 6\n"));
+    }
+
+    fn scrape<M>(monitor: &PrometheusMonitor<M>) -> String
+    where
+        M: Monitor,
+    {
+        let mut stream = TcpStream::connect(monitor.local_addr()).unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+}
@@ -1,4 +1,11 @@
 //! Monitor based on tui-rs
+//!
+//! Besides the overview, the TUI takes these keys:
+//! - `d` shows the full stats of the selected client instead of the charts,
+//! - `b` shows the corpus browser instead of the logs, with the newest entries of the directories added with
+//!   [`TuiMonitor::with_corpus_dir`] and [`TuiMonitor::with_solutions_dir`]. Up/down select an entry,
+//!   page up/down scroll through its hexdump,
+//! - `p`, `r` and `s` pause, resume or stop the selected client, `P`, `R` and `S` all clients.
 
 use crossterm::{
     event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
@@ -9,9 +16,13 @@ use hashbrown::HashMap;
 use tui::{backend::CrosstermBackend, Terminal};
 
 use std::{
+    cmp::Reverse,
     collections::VecDeque,
     fmt::Write,
+    fs,
     io::{self, BufRead},
+    mem,
+    path::PathBuf,
     string::String,
     sync::{Arc, RwLock},
     thread,
@@ -24,7 +35,8 @@ use super::{ClientPerfMonitor, PerfFeature};
 
 use crate::{
     bolts::{current_time, format_duration_hms},
-    monitors::{ClientStats, Monitor, UserStats},
+    events::ControlCommand,
+    monitors::{ClientStats, Monitor, UserStats, UserStatsValue},
 };
 
 mod ui;
@@ -32,6 +44,7 @@ use ui::TuiUI;
 
 const DEFAULT_TIME_WINDOW: u64 = 60 * 10; // 10 min
 const DEFAULT_LOGS_NUMBER: usize = 128;
+const DEFAULT_BROWSER_ENTRIES: usize = 256;
 
 #[derive(Debug, Copy, Clone)]
 pub struct TimedStat {
@@ -170,10 +183,16 @@ pub struct ClientTuiContext {
     pub exec_sec: u64,
 
     pub user_stats: HashMap<String, UserStats>,
+
+    /// All stats of this client, for the details view
+    pub client_stats: ClientStats,
+    /// If we requested to pause this client
+    pub paused: bool,
 }
 
 impl ClientTuiContext {
     pub fn grab_data(&mut self, client: &ClientStats, exec_sec: u64) {
+        self.client_stats = client.clone();
         self.corpus = client.corpus_size;
         self.objectives = client.objective_size;
         self.executions = client.executions;
@@ -185,6 +204,52 @@ impl ClientTuiContext {
     }
 }
 
+/// A file in one of the directories of the corpus browser
+#[derive(Debug, Clone)]
+pub struct BrowserEntry {
+    /// The label of the directory, `corpus` or `solutions`
+    pub kind: String,
+    pub path: PathBuf,
+    pub len: u64,
+    /// The modification time, since the epoch
+    pub modified: Duration,
+}
+
+/// The newest `max` non-hidden files in the given directories, newest first
+#[must_use]
+pub fn recent_entries(dirs: &[(String, PathBuf)], max: usize) -> Vec<BrowserEntry> {
+    let mut entries = vec![];
+    for (kind, dir) in dirs {
+        let read_dir = match fs::read_dir(dir) {
+            Ok(read_dir) => read_dir,
+            Err(_) => continue,
+        };
+        for entry in read_dir.flatten() {
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let metadata = match entry.metadata() {
+                Ok(metadata) if metadata.is_file() => metadata,
+                _ => continue,
+            };
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .unwrap_or_default();
+            entries.push(BrowserEntry {
+                kind: kind.clone(),
+                path: entry.path(),
+                len: metadata.len(),
+                modified,
+            });
+        }
+    }
+    entries.sort_by_key(|entry| Reverse(entry.modified));
+    entries.truncate(max);
+    entries
+}
+
 #[derive(Debug, Clone)]
 pub struct TuiContext {
     pub graphs: Vec<String>,
@@ -204,6 +269,14 @@ pub struct TuiContext {
     pub clients_num: usize,
     pub total_execs: u64,
     pub start_time: Duration,
+
+    /// The user stats of all clients, aggregated
+    pub user_stats: Vec<(String, UserStatsValue)>,
+
+    /// The commands requested with key presses, for a client id or for all clients, not yet sent
+    pub control_commands: Vec<(Option<u32>, ControlCommand)>,
+    /// The directories shown in the corpus browser, with their label
+    pub browser_dirs: Vec<(String, PathBuf)>,
 }
 
 impl TuiContext {
@@ -225,6 +298,11 @@ impl TuiContext {
             clients_num: 0,
             total_execs: 0,
             start_time,
+
+            user_stats: vec![],
+
+            control_commands: vec![],
+            browser_dirs: vec![],
         }
     }
 }
@@ -254,6 +332,10 @@ impl Monitor for TuiMonitor {
         self.start_time
     }
 
+    fn take_control_commands(&mut self) -> Vec<(Option<u32>, ControlCommand)> {
+        mem::take(&mut self.context.write().unwrap().control_commands)
+    }
+
     fn display(&mut self, event_msg: String, sender_id: u32) {
         let cur_time = current_time();
 
@@ -261,6 +343,7 @@ impl Monitor for TuiMonitor {
             let execsec = self.execs_per_sec();
             let totalexec = self.total_execs();
             let run_time = cur_time - self.start_time;
+            let user_stats = self.aggregated_user_stats();
 
             let mut ctx = self.context.write().unwrap();
             ctx.corpus_size_timed.add(run_time, self.corpus_size());
@@ -269,6 +352,7 @@ impl Monitor for TuiMonitor {
             ctx.execs_per_sec_timed.add(run_time, execsec);
             ctx.total_execs = totalexec;
             ctx.clients_num = self.client_stats.len();
+            ctx.user_stats = user_stats;
         }
 
         let client = self.client_stats_mut_for(sender_id);
@@ -341,6 +425,34 @@ impl TuiMonitor {
             client_stats: vec![],
         }
     }
+
+    /// Show the newest entries of this corpus directory in the corpus browser
+    #[must_use]
+    pub fn with_corpus_dir<P>(self, dir: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.context
+            .write()
+            .unwrap()
+            .browser_dirs
+            .push(("corpus".into(), dir.into()));
+        self
+    }
+
+    /// Show the newest entries of this solutions directory in the corpus browser
+    #[must_use]
+    pub fn with_solutions_dir<P>(self, dir: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.context
+            .write()
+            .unwrap()
+            .browser_dirs
+            .push(("solutions".into(), dir.into()));
+        self
+    }
 }
 
 fn run_tui_thread(
@@ -375,11 +487,13 @@ fn run_tui_thread(
             if crossterm::event::poll(timeout)? {
                 if let Event::Key(key) = event::read()? {
                     match key.code {
-                        KeyCode::Char(c) => ui.on_key(c),
+                        KeyCode::Char(c) => ui.on_key(c, &context),
                         KeyCode::Left => ui.on_left(),
-                        //KeyCode::Up => ui.on_up(),
+                        KeyCode::Up => ui.on_up(),
                         KeyCode::Right => ui.on_right(),
-                        //KeyCode::Down => ui.on_down(),
+                        KeyCode::Down => ui.on_down(),
+                        KeyCode::PageUp => ui.on_page_up(),
+                        KeyCode::PageDown => ui.on_page_down(),
                         _ => {}
                     }
                 }
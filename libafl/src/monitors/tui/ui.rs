@@ -1,4 +1,7 @@
-use super::{current_time, format_duration_hms, Duration, String, TimedStats, TuiContext};
+use super::{
+    current_time, format_duration_hms, recent_entries, BrowserEntry, ControlCommand, Duration,
+    String, TimedStats, TuiContext, DEFAULT_BROWSER_ENTRIES,
+};
 
 use alloc::vec::Vec;
 use tui::{
@@ -15,19 +18,71 @@ use tui::{
 
 use std::{
     cmp::{max, min},
+    fmt::Write,
+    fs::File,
+    io::Read,
+    path::PathBuf,
     sync::{Arc, RwLock},
+    time::Instant,
 };
 
+#[cfg(feature = "introspection")]
+use crate::monitors::PerfFeature;
+
+/// At most this many bytes of an entry are shown in the corpus browser
+const HEXDUMP_MAX_BYTES: u64 = 0x10000;
+/// The lines to scroll in the hexdump with page up/down
+const HEXDUMP_PAGE_LINES: usize = 16;
+/// How often to rescan the directories of the corpus browser
+const BROWSER_SCAN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Formats `bytes` as lines of 16 hex bytes, with their offset and their printable characters
+#[must_use]
+pub fn hexdump(bytes: &[u8]) -> Vec<String> {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut line = format!("{:08x} ", i * 16);
+            for j in 0..16 {
+                match chunk.get(j) {
+                    Some(b) => write!(line, " {:02x}", b).unwrap(),
+                    None => line.push_str("   "),
+                }
+            }
+            line.push_str("  |");
+            line.extend(chunk.iter().map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            }));
+            line.push('|');
+            line
+        })
+        .collect()
+}
+
 #[derive(Default)]
 pub struct TuiUI {
     title: String,
     enhanced_graphics: bool,
     show_logs: bool,
+    show_details: bool,
+    show_browser: bool,
     clients_idx: usize,
     clients: usize,
     charts_tab_idx: usize,
     graph_data: Vec<(f64, f64)>,
 
+    browser_entries: Vec<BrowserEntry>,
+    browser_idx: usize,
+    browser_scan_time: Option<Instant>,
+    hexdump_path: Option<PathBuf>,
+    hexdump: Vec<String>,
+    hexdump_offset: usize,
+
     pub should_quit: bool,
 }
 
@@ -42,7 +97,7 @@ impl TuiUI {
         }
     }
 
-    pub fn on_key(&mut self, c: char) {
+    pub fn on_key(&mut self, c: char, app: &Arc<RwLock<TuiContext>>) {
         match c {
             'q' => {
                 self.should_quit = true;
@@ -53,13 +108,66 @@ impl TuiUI {
             't' => {
                 self.show_logs = !self.show_logs;
             }
+            'd' => {
+                self.show_details = !self.show_details;
+            }
+            'b' => {
+                self.show_browser = !self.show_browser;
+                self.show_logs = true;
+            }
+            'p' => self.request(app, Some(self.clients_idx), ControlCommand::Pause),
+            'r' => self.request(app, Some(self.clients_idx), ControlCommand::Resume),
+            's' => self.request(app, Some(self.clients_idx), ControlCommand::Stop),
+            'P' => self.request(app, None, ControlCommand::Pause),
+            'R' => self.request(app, None, ControlCommand::Resume),
+            'S' => self.request(app, None, ControlCommand::Stop),
             _ => {}
         }
     }
 
-    //pub fn on_up(&mut self) {}
+    /// Queue a command for the selected client, or for all clients, to be sent by the event manager
+    #[allow(clippy::unused_self)]
+    fn request(
+        &self,
+        app: &Arc<RwLock<TuiContext>>,
+        client_idx: Option<usize>,
+        command: ControlCommand,
+    ) {
+        let mut ctx = app.write().unwrap();
+        let paused = command == ControlCommand::Pause;
+        match client_idx {
+            Some(idx) => ctx.clients.entry(idx).or_default().paused = paused,
+            None => {
+                for client in ctx.clients.values_mut() {
+                    client.paused = paused;
+                }
+            }
+        }
+        ctx.control_commands
+            .push((client_idx.map(|idx| idx as u32), command));
+    }
 
-    //pub fn on_down(&mut self) {}
+    pub fn on_up(&mut self) {
+        if self.show_browser && self.browser_idx > 0 {
+            self.browser_idx -= 1;
+        }
+    }
+
+    pub fn on_down(&mut self) {
+        if self.show_browser && self.browser_idx + 1 < self.browser_entries.len() {
+            self.browser_idx += 1;
+        }
+    }
+
+    pub fn on_page_up(&mut self) {
+        self.hexdump_offset = self.hexdump_offset.saturating_sub(HEXDUMP_PAGE_LINES);
+    }
+
+    pub fn on_page_down(&mut self) {
+        if self.hexdump_offset + HEXDUMP_PAGE_LINES < self.hexdump.len() {
+            self.hexdump_offset += HEXDUMP_PAGE_LINES;
+        }
+    }
 
     pub fn on_right(&mut self) {
         // never 0
@@ -112,9 +220,28 @@ impl TuiUI {
 
         self.draw_text(f, app, left_layout[1]);
 
+        if self.show_details {
+            self.draw_client_details(f, app, top_layout[1]);
+        } else {
+            self.draw_charts(f, app, top_layout[1]);
+        }
+
+        if self.show_logs {
+            if self.show_browser {
+                self.draw_browser(f, app, body[1]);
+            } else {
+                self.draw_logs(f, app, body[1]);
+            }
+        }
+    }
+
+    fn draw_charts<B>(&mut self, f: &mut Frame<B>, app: &Arc<RwLock<TuiContext>>, area: Rect)
+    where
+        B: Backend,
+    {
         let right_layout = Layout::default()
             .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
-            .split(top_layout[1]);
+            .split(area);
         let titles = vec![
             Spans::from(Span::styled(
                 "speed",
@@ -132,14 +259,12 @@ impl TuiUI {
         let tabs = Tabs::new(titles)
             .block(
                 Block::default()
-                    .title(
-                        Span::styled(
-                            "charts (`g` switch)",
-                            Style::default()
-                                .fg(Color::LightCyan)
-                                .add_modifier(Modifier::BOLD),
-                        ),
-                    )
+                    .title(Span::styled(
+                        "charts (`g` switch)",
+                        Style::default()
+                            .fg(Color::LightCyan)
+                            .add_modifier(Modifier::BOLD),
+                    ))
                     .borders(Borders::ALL),
             )
             .highlight_style(Style::default().fg(Color::LightYellow))
@@ -179,10 +304,6 @@ impl TuiUI {
             }
             _ => {}
         }
-
-        if self.show_logs {
-            self.draw_logs(f, app, body[1]);
-        }
     }
 
     #[allow(clippy::too_many_lines, clippy::cast_precision_loss)]
@@ -277,14 +398,12 @@ impl TuiUI {
         let chart = Chart::new(datasets)
             .block(
                 Block::default()
-                    .title(
-                        Span::styled(
-                            title,
-                            Style::default()
-                                .fg(Color::LightCyan)
-                                .add_modifier(Modifier::BOLD),
-                        ),
-                    )
+                    .title(Span::styled(
+                        title,
+                        Style::default()
+                            .fg(Color::LightCyan)
+                            .add_modifier(Modifier::BOLD),
+                    ))
                     .borders(Borders::ALL),
             )
             .x_axis(
@@ -319,7 +438,7 @@ impl TuiUI {
     where
         B: Backend,
     {
-        let items = vec![
+        let mut items = vec![
             Row::new(vec![
                 Cell::from(Span::raw("run time")),
                 Cell::from(Span::raw(format_duration_hms(
@@ -347,6 +466,12 @@ impl TuiUI {
                 ))),
             ]),
         ];
+        for (key, val) in &app.read().unwrap().user_stats {
+            items.push(Row::new(vec![
+                Cell::from(Span::raw(key.clone())),
+                Cell::from(Span::raw(format!("{}", val))),
+            ]));
+        }
 
         let chunks = Layout::default()
             .constraints(
@@ -361,22 +486,30 @@ impl TuiUI {
         let table = Table::new(items)
             .block(
                 Block::default()
-                    .title(
-                        Span::styled(
-                            "generic",
-                            Style::default()
-                                .fg(Color::LightCyan)
-                                .add_modifier(Modifier::BOLD),
-                        ),
-                    )
+                    .title(Span::styled(
+                        "generic",
+                        Style::default()
+                            .fg(Color::LightCyan)
+                            .add_modifier(Modifier::BOLD),
+                    ))
                     .borders(Borders::ALL),
             )
             .widths(&[Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)]);
         f.render_widget(table, chunks[0]);
 
+        let paused = app
+            .read()
+            .unwrap()
+            .clients
+            .get(&self.clients_idx)
+            .map_or(false, |client| client.paused);
         let client_block = Block::default()
             .title(Span::styled(
-                format!("client #{} (l/r arrows to switch)", self.clients_idx),
+                format!(
+                    "client #{}{} (l/r arrows to switch)",
+                    self.clients_idx,
+                    if paused { " [paused]" } else { "" }
+                ),
                 Style::default()
                     .fg(Color::LightCyan)
                     .add_modifier(Modifier::BOLD),
@@ -474,23 +607,228 @@ impl TuiUI {
                 };
             }
 
-            let table =
-                Table::new(items)
-                    .block(
-                        Block::default()
-                            .title(Span::styled(
-                                "introspection",
-                                Style::default()
-                                    .fg(Color::LightCyan)
-                                    .add_modifier(Modifier::BOLD),
-                            ))
-                            .borders(Borders::ALL),
-                    )
-                    .widths(&[Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)]);
+            let table = Table::new(items)
+                .block(
+                    Block::default()
+                        .title(Span::styled(
+                            "introspection",
+                            Style::default()
+                                .fg(Color::LightCyan)
+                                .add_modifier(Modifier::BOLD),
+                        ))
+                        .borders(Borders::ALL),
+                )
+                .widths(&[Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)]);
             f.render_widget(table, client_chunks[1]);
         }
     }
 
+    fn draw_client_details<B>(
+        &mut self,
+        f: &mut Frame<B>,
+        app: &Arc<RwLock<TuiContext>>,
+        area: Rect,
+    ) where
+        B: Backend,
+    {
+        let mut rows: Vec<(String, String)> = vec![];
+        {
+            let ctx = app.read().unwrap();
+            if let Some(client) = ctx.clients.get(&self.clients_idx) {
+                let stats = &client.client_stats;
+                rows.push((
+                    "state".into(),
+                    if client.paused { "paused" } else { "running" }.into(),
+                ));
+                rows.push(("corpus".into(), format!("{}", stats.corpus_size)));
+                rows.push(("objectives".into(), format!("{}", stats.objective_size)));
+                rows.push(("executions".into(), format!("{}", stats.executions)));
+                rows.push(("exec/sec".into(), format!("{}", client.exec_sec)));
+                #[cfg(feature = "afl_exec_sec")]
+                rows.push((
+                    "last window executions".into(),
+                    format!("{}", stats.last_window_executions),
+                ));
+                rows.push((
+                    "last report".into(),
+                    format!(
+                        "{} ago",
+                        format_duration_hms(
+                            &current_time()
+                                .checked_sub(stats.last_window_time)
+                                .unwrap_or_default()
+                        )
+                    ),
+                ));
+                let mut user_stats: Vec<_> = stats.user_monitor.iter().collect();
+                user_stats.sort_by_key(|(key, _)| *key);
+                for (key, val) in user_stats {
+                    rows.push((key.clone(), format!("{}", val)));
+                }
+
+                #[cfg(feature = "introspection")]
+                {
+                    let perf = &stats.introspection_monitor;
+                    rows.push((
+                        "elapsed cycles".into(),
+                        format!("{}", perf.elapsed_cycles()),
+                    ));
+                    rows.push((
+                        "scheduler cycles".into(),
+                        format!("{}", perf.scheduler_cycles()),
+                    ));
+                    rows.push((
+                        "manager cycles".into(),
+                        format!("{}", perf.manager_cycles()),
+                    ));
+                    for (stage_idx, features) in perf.used_stages() {
+                        for (feature_idx, cycles) in features.iter().enumerate() {
+                            if *cycles > 0 {
+                                let feature: PerfFeature = feature_idx.into();
+                                rows.push((
+                                    format!("stage {} {:?} cycles", stage_idx, feature),
+                                    format!("{}", cycles),
+                                ));
+                            }
+                        }
+                    }
+                    for (name, cycles) in perf.feedbacks() {
+                        rows.push((format!("{} cycles", name), format!("{}", cycles)));
+                    }
+                }
+            }
+        }
+
+        let items: Vec<Row> = rows
+            .into_iter()
+            .map(|(key, val)| Row::new(vec![Cell::from(key), Cell::from(val)]))
+            .collect();
+        let table = Table::new(items)
+            .block(
+                Block::default()
+                    .title(Span::styled(
+                        format!(
+                            "client #{} details (`d` to hide, p/r/s to pause/resume/stop)",
+                            self.clients_idx
+                        ),
+                        Style::default()
+                            .fg(Color::LightCyan)
+                            .add_modifier(Modifier::BOLD),
+                    ))
+                    .borders(Borders::ALL),
+            )
+            .widths(&[Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)]);
+        f.render_widget(table, area);
+    }
+
+    /// Reads the selected entry of the corpus browser, if it changed
+    fn load_hexdump(&mut self) {
+        let path = self
+            .browser_entries
+            .get(self.browser_idx)
+            .map(|entry| entry.path.clone());
+        if path == self.hexdump_path {
+            return;
+        }
+        self.hexdump.clear();
+        self.hexdump_offset = 0;
+        if let Some(path) = &path {
+            let mut bytes = vec![];
+            if let Ok(file) = File::open(path) {
+                if file.take(HEXDUMP_MAX_BYTES).read_to_end(&mut bytes).is_ok() {
+                    self.hexdump = hexdump(&bytes);
+                }
+            }
+        }
+        self.hexdump_path = path;
+    }
+
+    fn draw_browser<B>(&mut self, f: &mut Frame<B>, app: &Arc<RwLock<TuiContext>>, area: Rect)
+    where
+        B: Backend,
+    {
+        if self
+            .browser_scan_time
+            .map_or(true, |time| time.elapsed() >= BROWSER_SCAN_INTERVAL)
+        {
+            // Keep the selected entry, even if new ones arrived
+            let selected = self
+                .browser_entries
+                .get(self.browser_idx)
+                .map(|entry| entry.path.clone());
+            self.browser_entries =
+                recent_entries(&app.read().unwrap().browser_dirs, DEFAULT_BROWSER_ENTRIES);
+            self.browser_idx = selected
+                .and_then(|path| self.browser_entries.iter().position(|e| e.path == path))
+                .unwrap_or(0);
+            self.browser_scan_time = Some(Instant::now());
+        }
+        self.load_hexdump();
+
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(40), Constraint::Percentage(60)].as_ref())
+            .split(area);
+
+        // Scroll the list, so that the selected entry is visible
+        let visible = chunks[0].height.saturating_sub(2) as usize;
+        let first = (self.browser_idx + 1).saturating_sub(visible);
+        let now = current_time();
+        let entries: Vec<ListItem> = self
+            .browser_entries
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(visible)
+            .map(|(idx, entry)| {
+                let name = entry
+                    .path
+                    .file_name()
+                    .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+                let text = format!(
+                    "{:<9} {} ({} bytes, {} ago)",
+                    entry.kind,
+                    name,
+                    entry.len,
+                    format_duration_hms(&now.checked_sub(entry.modified).unwrap_or_default())
+                );
+                let style = if idx == self.browser_idx {
+                    Style::default()
+                        .fg(Color::LightYellow)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+                ListItem::new(Span::styled(text, style))
+            })
+            .collect();
+        let list = List::new(entries).block(
+            Block::default().borders(Borders::ALL).title(Span::styled(
+                "corpus browser (`b` to hide, up/down to select)",
+                Style::default()
+                    .fg(Color::LightCyan)
+                    .add_modifier(Modifier::BOLD),
+            )),
+        );
+        f.render_widget(list, chunks[0]);
+
+        let lines: Vec<Spans> = self
+            .hexdump
+            .iter()
+            .skip(self.hexdump_offset)
+            .map(|line| Spans::from(Span::raw(line.as_str())))
+            .collect();
+        let paragraph = Paragraph::new(lines).block(
+            Block::default().borders(Borders::ALL).title(Span::styled(
+                "hexdump (page up/down to scroll)",
+                Style::default()
+                    .fg(Color::LightCyan)
+                    .add_modifier(Modifier::BOLD),
+            )),
+        );
+        f.render_widget(paragraph, chunks[1]);
+    }
+
     #[allow(clippy::unused_self)]
     fn draw_logs<B>(&mut self, f: &mut Frame<B>, app: &Arc<RwLock<TuiContext>>, area: Rect)
     where
@@ -503,15 +841,32 @@ impl TuiUI {
             .map(|msg| ListItem::new(Span::raw(msg)))
             .collect();
         let logs = List::new(logs).block(
-            Block::default().borders(Borders::ALL).title(
-                Span::styled(
-                    "clients logs (`t` to show/hide)",
-                    Style::default()
-                        .fg(Color::LightCyan)
-                        .add_modifier(Modifier::BOLD),
-                ),
-            ),
+            Block::default().borders(Borders::ALL).title(Span::styled(
+                "clients logs (`t` to show/hide)",
+                Style::default()
+                    .fg(Color::LightCyan)
+                    .add_modifier(Modifier::BOLD),
+            )),
         );
         f.render_widget(logs, area);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::hexdump;
+
+    #[test]
+    fn test_hexdump() {
+        let lines = hexdump(b"LibAFL\x00\x01fuzzing is fun!");
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            "00000000  4c 69 62 41 46 4c 00 01 66 75 7a 7a 69 6e 67 20  |LibAFL..fuzzing |"
+        );
+        assert_eq!(
+            lines[1],
+            "00000010  69 73 20 66 75 6e 21                             |is fun!|"
+        );
+    }
+}
@@ -14,6 +14,8 @@ pub mod gramatron;
 pub use gramatron::*;
 pub mod grimoire;
 pub use grimoire::*;
+pub mod multi;
+pub use multi::*;
 
 #[cfg(feature = "nautilus")]
 pub mod nautilus;
@@ -0,0 +1,729 @@
+//! Structure-aware mutators for inputs composed of multiple fields, such as [`crate::inputs::MultiInput`],
+//! or any other input implementing [`AsMultiInput`].
+//!
+//! The [`MultiFieldMutator`] picks one field per mutation round and applies any [`MutatorsTuple`] to it,
+//! while the field-level mutators ([`FieldSwapMutator`], [`FieldDuplicateMutator`], [`FieldCrossoverInsertMutator`]
+//! and [`FieldDeleteMutator`]) change the shape of the input itself.
+
+use alloc::vec::Vec;
+use core::{
+    fmt::{self, Debug},
+    marker::PhantomData,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bolts::{
+        rands::Rand,
+        tuples::{tuple_list, tuple_list_type, Named},
+        HasLen,
+    },
+    corpus::Corpus,
+    inputs::{AsMultiInput, BytesInput, Input},
+    mutators::{ComposedByMutations, MutationResult, Mutator, MutatorsTuple, ScheduledMutator},
+    schedulers::StateTargetMetadata,
+    state::{HasCorpus, HasMetadata, HasRand},
+    Error,
+};
+
+/// The default maximum number of fields the field-level mutators will grow an input to
+pub const DEFAULT_MAX_FIELDS: usize = 64;
+
+/// How a [`MultiFieldMutator`] chooses the field to mutate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FieldSelection {
+    /// Every field has the same probability
+    Uniform,
+    /// Fields are weighted by their length, bigger fields get mutated more often
+    BySize,
+    /// Fields are weighted by how often mutating them produced a new corpus entry
+    ByFinds,
+    /// Every field after the messages that bring a service to the state targeted by the
+    /// [`crate::schedulers::StateScheduler`] has the same probability, the others are left alone
+    StateSuffix,
+}
+
+/// Per-field statistics kept in the state by the [`MultiFieldMutator`].
+/// Fields are identified by their position in the input.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct MultiFieldMetadata {
+    /// How often each field was selected for mutation
+    pub mutations: Vec<u64>,
+    /// How often mutating each field led to a new corpus entry
+    pub finds: Vec<u64>,
+}
+
+crate::impl_serdeany!(MultiFieldMetadata);
+
+impl MultiFieldMetadata {
+    /// Creates a new, empty [`struct@MultiFieldMetadata`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn ensure_field(&mut self, field: usize) {
+        if self.mutations.len() <= field {
+            self.mutations.resize(field + 1, 0);
+            self.finds.resize(field + 1, 0);
+        }
+    }
+
+    /// Records that the field at `field` was mutated
+    pub fn record_mutation(&mut self, field: usize) {
+        self.ensure_field(field);
+        self.mutations[field] += 1;
+    }
+
+    /// Records that mutating the field at `field` produced a new corpus entry
+    pub fn record_find(&mut self, field: usize) {
+        self.ensure_field(field);
+        self.finds[field] += 1;
+    }
+
+    /// The selection weight of the field at `field` for [`FieldSelection::ByFinds`].
+    /// Fields that were never mutated keep a high weight, so that every field gets explored.
+    #[must_use]
+    pub fn weight(&self, field: usize) -> u64 {
+        let mutations = self.mutations.get(field).copied().unwrap_or(0);
+        let finds = self.finds.get(field).copied().unwrap_or(0);
+        ((finds + 1) * 1024 / (mutations + 1)).max(1)
+    }
+}
+
+/// Picks an index in `0..weights.len()` with a probability proportional to its weight
+fn choose_weighted<R>(rand: &mut R, weights: &[u64]) -> usize
+where
+    R: Rand,
+{
+    debug_assert!(!weights.is_empty());
+    let total: u64 = weights.iter().sum();
+    if total == 0 {
+        return rand.below(weights.len() as u64) as usize;
+    }
+    let mut pick = rand.below(total);
+    for (idx, weight) in weights.iter().enumerate() {
+        if pick < *weight {
+            return idx;
+        }
+        pick -= weight;
+    }
+    weights.len() - 1
+}
+
+/// A [`Mutator`] that selects one field of a multi-field input and applies
+/// stacked mutations from the embedded [`MutatorsTuple`] to it.
+///
+/// In contrast to the [`crate::mutators::MultiScheduledMutator`], the mutated field is chosen
+/// on every call, and the mutator learns which fields lead to new corpus entries.
+pub struct MultiFieldMutator<I, J, MT, S>
+where
+    I: Input + AsMultiInput<J>,
+    J: Input + HasLen,
+    MT: MutatorsTuple<J, S>,
+    S: HasRand + HasMetadata,
+{
+    mutations: MT,
+    max_stack_pow: u64,
+    selection: FieldSelection,
+    last_field: Option<usize>,
+    phantom: PhantomData<(I, J, S)>,
+}
+
+impl<I, J, MT, S> Debug for MultiFieldMutator<I, J, MT, S>
+where
+    I: Input + AsMultiInput<J>,
+    J: Input + HasLen,
+    MT: MutatorsTuple<J, S>,
+    S: HasRand + HasMetadata,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "MultiFieldMutator with {} mutations and {:?} field selection for Input type {}",
+            self.mutations.len(),
+            self.selection,
+            core::any::type_name::<I>()
+        )
+    }
+}
+
+impl<I, J, MT, S> Mutator<I, S> for MultiFieldMutator<I, J, MT, S>
+where
+    I: Input + AsMultiInput<J>,
+    J: Input + HasLen,
+    MT: MutatorsTuple<J, S>,
+    S: HasRand + HasMetadata,
+{
+    #[inline]
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut I,
+        stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        self.scheduled_mutate(state, input, stage_idx)
+    }
+
+    fn post_exec(
+        &mut self,
+        state: &mut S,
+        _stage_idx: i32,
+        corpus_idx: Option<usize>,
+    ) -> Result<(), Error> {
+        if let (Some(field), Some(_)) = (self.last_field.take(), corpus_idx) {
+            state
+                .metadata_mut()
+                .get_mut::<MultiFieldMetadata>()
+                .unwrap()
+                .record_find(field);
+        }
+        Ok(())
+    }
+}
+
+impl<I, J, MT, S> ComposedByMutations<J, MT, S> for MultiFieldMutator<I, J, MT, S>
+where
+    I: Input + AsMultiInput<J>,
+    J: Input + HasLen,
+    MT: MutatorsTuple<J, S>,
+    S: HasRand + HasMetadata,
+{
+    /// Get the mutations
+    #[inline]
+    fn mutations(&self) -> &MT {
+        &self.mutations
+    }
+
+    // Get the mutations (mutable)
+    #[inline]
+    fn mutations_mut(&mut self) -> &mut MT {
+        &mut self.mutations
+    }
+}
+
+impl<I, J, MT, S> ScheduledMutator<I, J, MT, S> for MultiFieldMutator<I, J, MT, S>
+where
+    I: Input + AsMultiInput<J>,
+    J: Input + HasLen,
+    MT: MutatorsTuple<J, S>,
+    S: HasRand + HasMetadata,
+{
+    /// Compute the number of iterations used to apply stacked mutations
+    fn iterations(&self, state: &mut S, _: &I) -> u64 {
+        1 << (1 + state.rand_mut().below(self.max_stack_pow))
+    }
+
+    /// Get the next mutation to apply
+    fn schedule(&self, state: &mut S, _: &I) -> usize {
+        debug_assert!(!self.mutations().is_empty());
+        state.rand_mut().below(self.mutations().len() as u64) as usize
+    }
+
+    fn scheduled_mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut I,
+        stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        self.last_field = None;
+        if input.as_multi_input().is_empty() {
+            return Ok(MutationResult::Skipped);
+        }
+
+        let field = self.select_field(state, input);
+        state
+            .metadata_mut()
+            .get_mut::<MultiFieldMetadata>()
+            .unwrap()
+            .record_mutation(field);
+        self.last_field = Some(field);
+
+        let mut r = MutationResult::Skipped;
+        let num = self.iterations(state, input);
+        for _ in 0..num {
+            let idx = self.schedule(state, input);
+            let sub_input = &mut input.as_multi_input_mut()[field];
+            let outcome = self
+                .mutations_mut()
+                .get_and_mutate(idx, state, sub_input, stage_idx)?;
+            if outcome == MutationResult::Mutated {
+                r = MutationResult::Mutated;
+            }
+        }
+        Ok(r)
+    }
+}
+
+impl<I, J, MT, S> MultiFieldMutator<I, J, MT, S>
+where
+    I: Input + AsMultiInput<J>,
+    J: Input + HasLen,
+    MT: MutatorsTuple<J, S>,
+    S: HasRand + HasMetadata,
+{
+    /// Create a new [`MultiFieldMutator`] instance specifying mutations and the field selection strategy
+    pub fn new(state: &mut S, mutations: MT, selection: FieldSelection) -> Self {
+        Self::with_max_stack_pow(state, mutations, selection, 7)
+    }
+
+    /// Create a new [`MultiFieldMutator`] instance specifying mutations, the field selection strategy and the maximun number of iterations
+    pub fn with_max_stack_pow(
+        state: &mut S,
+        mutations: MT,
+        selection: FieldSelection,
+        max_stack_pow: u64,
+    ) -> Self {
+        if !state.has_metadata::<MultiFieldMetadata>() {
+            state.add_metadata(MultiFieldMetadata::new());
+        }
+        Self {
+            mutations,
+            max_stack_pow,
+            selection,
+            last_field: None,
+            phantom: PhantomData,
+        }
+    }
+
+    /// The field selection strategy in use
+    #[must_use]
+    pub fn selection(&self) -> FieldSelection {
+        self.selection
+    }
+
+    /// Pick the field to mutate next, according to the [`FieldSelection`] strategy
+    fn select_field(&self, state: &mut S, input: &I) -> usize {
+        let fields = input.as_multi_input();
+        match self.selection {
+            FieldSelection::Uniform => state.rand_mut().below(fields.len() as u64) as usize,
+            FieldSelection::BySize => {
+                // Empty fields still get a chance to grow
+                let weights: Vec<u64> = fields.iter().map(|f| f.len() as u64 + 1).collect();
+                choose_weighted(state.rand_mut(), &weights)
+            }
+            FieldSelection::ByFinds => {
+                let meta = state.metadata().get::<MultiFieldMetadata>().unwrap();
+                let weights: Vec<u64> = (0..fields.len()).map(|i| meta.weight(i)).collect();
+                choose_weighted(state.rand_mut(), &weights)
+            }
+            FieldSelection::StateSuffix => {
+                let prefix_len = state
+                    .metadata()
+                    .get::<StateTargetMetadata>()
+                    .map_or(0, |target| target.prefix_len);
+                // Without messages after the prefix, mutate its last one
+                let first = prefix_len.min(fields.len() - 1);
+                first + state.rand_mut().below((fields.len() - first) as u64) as usize
+            }
+        }
+    }
+}
+
+/// Swaps two fields of a multi-field input
+#[derive(Debug, Default)]
+pub struct FieldSwapMutator;
+
+impl<I, S> Mutator<I, S> for FieldSwapMutator
+where
+    I: Input + AsMultiInput<BytesInput>,
+    S: HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut I,
+        _stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        let len = input.as_multi_input().len();
+        if len < 2 {
+            return Ok(MutationResult::Skipped);
+        }
+        let first = state.rand_mut().below(len as u64) as usize;
+        let second = state.rand_mut().below(len as u64) as usize;
+        if first == second {
+            return Ok(MutationResult::Skipped);
+        }
+        input.as_multi_input_mut().swap(first, second);
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for FieldSwapMutator {
+    fn name(&self) -> &str {
+        "FieldSwapMutator"
+    }
+}
+
+impl FieldSwapMutator {
+    /// Creates a new [`FieldSwapMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Duplicates a field of a multi-field input and inserts the copy at a random position
+#[derive(Debug)]
+pub struct FieldDuplicateMutator {
+    max_fields: usize,
+}
+
+impl<I, S> Mutator<I, S> for FieldDuplicateMutator
+where
+    I: Input + AsMultiInput<BytesInput>,
+    S: HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut I,
+        _stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        let len = input.as_multi_input().len();
+        if len == 0 || len >= self.max_fields {
+            return Ok(MutationResult::Skipped);
+        }
+        let from = state.rand_mut().below(len as u64) as usize;
+        let to = state.rand_mut().below(len as u64 + 1) as usize;
+        let fields = input.as_multi_input_mut();
+        let copy = fields[from].clone();
+        fields.insert(to, copy);
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for FieldDuplicateMutator {
+    fn name(&self) -> &str {
+        "FieldDuplicateMutator"
+    }
+}
+
+impl Default for FieldDuplicateMutator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FieldDuplicateMutator {
+    /// Creates a new [`FieldDuplicateMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_max_fields(DEFAULT_MAX_FIELDS)
+    }
+
+    /// Creates a new [`FieldDuplicateMutator`] that never grows an input beyond `max_fields` fields.
+    #[must_use]
+    pub fn with_max_fields(max_fields: usize) -> Self {
+        Self { max_fields }
+    }
+}
+
+/// Inserts a field taken from another testcase of the corpus at a random position
+#[derive(Debug)]
+pub struct FieldCrossoverInsertMutator {
+    max_fields: usize,
+}
+
+impl<I, S> Mutator<I, S> for FieldCrossoverInsertMutator
+where
+    I: Input + AsMultiInput<BytesInput>,
+    S: HasRand + HasCorpus<I>,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut I,
+        _stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        let len = input.as_multi_input().len();
+        if len >= self.max_fields {
+            return Ok(MutationResult::Skipped);
+        }
+
+        // We don't want to use the testcase we're already using for crossover
+        let count = state.corpus().count();
+        if count == 0 {
+            return Ok(MutationResult::Skipped);
+        }
+        let idx = state.rand_mut().below(count as u64) as usize;
+        if let Some(cur) = state.corpus().current() {
+            if idx == *cur {
+                return Ok(MutationResult::Skipped);
+            }
+        }
+
+        let other_len = state
+            .corpus()
+            .get(idx)?
+            .borrow_mut()
+            .load_input()?
+            .as_multi_input()
+            .len();
+        if other_len == 0 {
+            return Ok(MutationResult::Skipped);
+        }
+
+        let from = state.rand_mut().below(other_len as u64) as usize;
+        let to = state.rand_mut().below(len as u64 + 1) as usize;
+
+        let mut other_testcase = state.corpus().get(idx)?.borrow_mut();
+        let field = other_testcase.load_input()?.as_multi_input()[from].clone();
+        input.as_multi_input_mut().insert(to, field);
+
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for FieldCrossoverInsertMutator {
+    fn name(&self) -> &str {
+        "FieldCrossoverInsertMutator"
+    }
+}
+
+impl Default for FieldCrossoverInsertMutator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FieldCrossoverInsertMutator {
+    /// Creates a new [`FieldCrossoverInsertMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_max_fields(DEFAULT_MAX_FIELDS)
+    }
+
+    /// Creates a new [`FieldCrossoverInsertMutator`] that never grows an input beyond `max_fields` fields.
+    #[must_use]
+    pub fn with_max_fields(max_fields: usize) -> Self {
+        Self { max_fields }
+    }
+}
+
+/// Deletes a random field of a multi-field input
+#[derive(Debug)]
+pub struct FieldDeleteMutator {
+    min_fields: usize,
+}
+
+impl<I, S> Mutator<I, S> for FieldDeleteMutator
+where
+    I: Input + AsMultiInput<BytesInput>,
+    S: HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut I,
+        _stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        let len = input.as_multi_input().len();
+        if len == 0 || len <= self.min_fields {
+            return Ok(MutationResult::Skipped);
+        }
+        let idx = state.rand_mut().below(len as u64) as usize;
+        input.as_multi_input_mut().remove(idx);
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for FieldDeleteMutator {
+    fn name(&self) -> &str {
+        "FieldDeleteMutator"
+    }
+}
+
+impl Default for FieldDeleteMutator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FieldDeleteMutator {
+    /// Creates a new [`FieldDeleteMutator`] that keeps at least one field.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_min_fields(1)
+    }
+
+    /// Creates a new [`FieldDeleteMutator`] that never shrinks an input below `min_fields` fields.
+    #[must_use]
+    pub fn with_min_fields(min_fields: usize) -> Self {
+        Self { min_fields }
+    }
+}
+
+/// Tuple type of the mutations that change the field structure of a multi-field input
+pub type FieldMutationsType = tuple_list_type!(
+    FieldSwapMutator,
+    FieldDuplicateMutator,
+    FieldCrossoverInsertMutator,
+    FieldDeleteMutator,
+);
+
+/// Get the mutations that change the field structure of a multi-field input.
+/// Use them in a [`crate::mutators::StdScheduledMutator`], next to a [`MultiFieldMutator`].
+#[must_use]
+pub fn field_mutations() -> FieldMutationsType {
+    tuple_list!(
+        FieldSwapMutator::new(),
+        FieldDuplicateMutator::new(),
+        FieldCrossoverInsertMutator::new(),
+        FieldDeleteMutator::new(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+
+    use super::*;
+    use crate::{
+        bolts::{rands::StdRand, tuples::HasConstLen},
+        corpus::{InMemoryCorpus, Testcase},
+        inputs::{BytesInput, MultiInput},
+        mutators::havoc_mutations,
+        state::StdState,
+    };
+
+    fn test_input() -> MultiInput {
+        MultiInput::new(vec![
+            BytesInput::new(vec![1, 2, 3]),
+            BytesInput::new(vec![]),
+            BytesInput::new(vec![4, 5, 6, 7, 8]),
+        ])
+    }
+
+    #[test]
+    fn test_multi_field_mutator() {
+        let mut corpus: InMemoryCorpus<MultiInput> = InMemoryCorpus::new();
+        corpus.add(Testcase::new(test_input())).unwrap();
+        let mut state = StdState::new(
+            StdRand::with_seed(1337),
+            corpus,
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+
+        let mut mutator =
+            MultiFieldMutator::new(&mut state, havoc_mutations(), FieldSelection::ByFinds);
+        let original = test_input();
+        let mut input = test_input();
+        let mut mutated = [false; 3];
+        for i in 0..64 {
+            mutator.mutate(&mut state, &mut input, i).unwrap();
+            mutator.post_exec(&mut state, i, Some(0)).unwrap();
+            for (field, flag) in mutated.iter_mut().enumerate() {
+                *flag |= input.fields[field] != original.fields[field];
+            }
+        }
+        // Every field should have been picked at some point
+        assert!(mutated.iter().all(|m| *m));
+
+        let meta = state.metadata().get::<MultiFieldMetadata>().unwrap();
+        assert_eq!(meta.mutations.iter().sum::<u64>(), 64);
+        assert_eq!(meta.finds.iter().sum::<u64>(), 64);
+    }
+
+    #[test]
+    fn test_field_mutations() {
+        let mut corpus: InMemoryCorpus<MultiInput> = InMemoryCorpus::new();
+        corpus.add(Testcase::new(test_input())).unwrap();
+        corpus
+            .add(Testcase::new(MultiInput::new(vec![BytesInput::new(vec![
+                9;
+                4
+            ])])))
+            .unwrap();
+        let mut state = StdState::new(
+            StdRand::with_seed(1337),
+            corpus,
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+
+        let mut mutations = field_mutations();
+        let mut input = test_input();
+        for i in 0..128 {
+            let idx = state.rand_mut().below(mutations.len() as u64) as usize;
+            mutations
+                .get_and_mutate(idx, &mut state, &mut input, i)
+                .unwrap();
+            let len = input.fields.len();
+            assert!((1..=DEFAULT_MAX_FIELDS).contains(&len));
+        }
+
+        let mut single = MultiInput::new(vec![BytesInput::new(vec![1])]);
+        assert_eq!(
+            FieldDeleteMutator::new()
+                .mutate(&mut state, &mut single, 0)
+                .unwrap(),
+            MutationResult::Skipped
+        );
+        assert_eq!(
+            FieldSwapMutator::new()
+                .mutate(&mut state, &mut single, 0)
+                .unwrap(),
+            MutationResult::Skipped
+        );
+    }
+    /// A custom multi-part input, with a part that is not a field
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    struct Packets {
+        header: u32,
+        packets: Vec<BytesInput>,
+    }
+
+    impl Input for Packets {
+        fn generate_name(&self, idx: usize) -> String {
+            format!("packets_{}", idx)
+        }
+    }
+
+    impl AsMultiInput<BytesInput> for Packets {
+        fn as_multi_input_mut(&mut self) -> &mut Vec<BytesInput> {
+            &mut self.packets
+        }
+
+        fn as_multi_input(&self) -> &Vec<BytesInput> {
+            &self.packets
+        }
+    }
+
+    #[test]
+    fn test_custom_multi_part_input() {
+        let original = Packets {
+            header: 7,
+            packets: test_input().fields,
+        };
+        let mut corpus: InMemoryCorpus<Packets> = InMemoryCorpus::new();
+        corpus.add(Testcase::new(original.clone())).unwrap();
+        let mut state = StdState::new(
+            StdRand::with_seed(1337),
+            corpus,
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+
+        let mut mutator =
+            MultiFieldMutator::new(&mut state, havoc_mutations(), FieldSelection::Uniform);
+        let mut mutations = field_mutations();
+        let mut input = original.clone();
+        for i in 0..64 {
+            mutator.mutate(&mut state, &mut input, i).unwrap();
+            let idx = state.rand_mut().below(mutations.len() as u64) as usize;
+            mutations
+                .get_and_mutate(idx, &mut state, &mut input, i)
+                .unwrap();
+        }
+        assert_eq!(input.header, original.header);
+        assert_ne!(input.packets, original.packets);
+    }
+}
@@ -696,6 +696,52 @@ impl BytesSetMutator {
     }
 }
 
+/// Bytes zero mutation for inputs with a bytes vector, replacing a chunk with zeros.
+/// Mostly useful to simplify inputs, see [`crate::stages::MinimizationStage`].
+#[derive(Default, Debug)]
+pub struct BytesZeroMutator;
+
+impl<I, S> Mutator<I, S> for BytesZeroMutator
+where
+    I: Input + HasBytesVec,
+    S: HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut I,
+        _stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        let size = input.bytes().len();
+        if size == 0 {
+            return Ok(MutationResult::Skipped);
+        }
+        let off = state.rand_mut().below(size as u64) as usize;
+        let len = 1 + state.rand_mut().below((size - off) as u64) as usize;
+
+        if input.bytes()[off..off + len].iter().all(|b| *b == 0) {
+            return Ok(MutationResult::Skipped);
+        }
+        buffer_set(input.bytes_mut(), off, len, 0);
+
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for BytesZeroMutator {
+    fn name(&self) -> &str {
+        "BytesZeroMutator"
+    }
+}
+
+impl BytesZeroMutator {
+    /// Creates a new [`BytesZeroMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
 /// Bytes random set mutation for inputs with a bytes vector
 #[derive(Default, Debug)]
 pub struct BytesRandSetMutator;
@@ -11,6 +11,11 @@ pub mod stdio;
 #[cfg(feature = "std")]
 pub use stdio::{StdErrObserver, StdOutObserver};
 
+#[cfg(feature = "std")]
+pub mod network;
+#[cfg(feature = "std")]
+pub use network::{ResponseCodeExtractor, ResponseCodeObserver, ResponsesObserver};
+
 #[cfg(feature = "std")]
 pub mod stacktrace;
 #[cfg(feature = "std")]
@@ -18,6 +23,9 @@ pub use stacktrace::*;
 
 pub mod concolic;
 
+pub mod target_sites;
+pub use target_sites::TargetSitesObserver;
+
 #[cfg(unstable_feature)]
 pub mod owned;
 #[cfg(unstable_feature)]
@@ -36,7 +44,7 @@ use crate::{
         ownedref::OwnedRefMut,
         tuples::{MatchName, Named},
     },
-    executors::ExitKind,
+    executors::{DiffExitKind, ExitKind},
     Error,
 };
 
@@ -182,6 +190,10 @@ pub trait ObserverWithHashField {
     fn update_hash(&mut self, hash: u64);
     /// clears the current value of the hash and sets it to None
     fn clear_hash(&mut self);
+    /// the type of the crash the hash was computed for, if known, e.g. the ASAN error type
+    fn crash_type(&self) -> Option<&str> {
+        None
+    }
 }
 /// A simple observer, just overlooking the runtime of the target.
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -290,6 +302,106 @@ where
     }
 }
 
+/// An observer holding a single value, such as the return value of a harness.
+/// The harness writes the value to the referenced location, the observer resets it to `None` before each run.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(bound = "T: serde::de::DeserializeOwned")]
+pub struct ValueObserver<'a, T>
+where
+    T: Debug + Serialize,
+{
+    name: String,
+    /// The value
+    value: OwnedRefMut<'a, Option<T>>,
+}
+
+impl<'a, T> ValueObserver<'a, T>
+where
+    T: Debug + Serialize + serde::de::DeserializeOwned,
+{
+    /// Creates a new [`ValueObserver`] with the given name.
+    #[must_use]
+    pub fn new(name: &'static str, value: &'a mut Option<T>) -> Self {
+        Self {
+            name: name.to_string(),
+            value: OwnedRefMut::Ref(value),
+        }
+    }
+
+    /// Get the value of the last run, if any
+    #[must_use]
+    pub fn value(&self) -> Option<&T> {
+        self.value.as_ref().as_ref()
+    }
+
+    /// Set the value of the current run
+    pub fn set_value(&mut self, value: T) {
+        *self.value.as_mut() = Some(value);
+    }
+}
+
+impl<'a, I, S, T> Observer<I, S> for ValueObserver<'a, T>
+where
+    T: Debug + Serialize + serde::de::DeserializeOwned,
+{
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        *self.value.as_mut() = None;
+        Ok(())
+    }
+}
+
+impl<'a, T> Named for ValueObserver<'a, T>
+where
+    T: Debug + Serialize + serde::de::DeserializeOwned,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// An observer holding the [`DiffExitKind`] of each executor of a [`crate::executors::TupleDiffExecutor`].
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ExitKindsObserver {
+    exit_kinds: Vec<DiffExitKind>,
+}
+
+impl ExitKindsObserver {
+    /// The name of every [`ExitKindsObserver`], used by the executor to find it.
+    pub const NAME: &'static str = "ExitKindsObserver";
+
+    /// Creates a new [`ExitKindsObserver`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The exit kinds of the last run, in the order of the executors
+    #[must_use]
+    pub fn exit_kinds(&self) -> &[DiffExitKind] {
+        &self.exit_kinds
+    }
+
+    /// Set the exit kinds of the current run
+    pub fn set_exit_kinds(&mut self, exit_kinds: &[ExitKind]) {
+        self.exit_kinds.clear();
+        self.exit_kinds
+            .extend(exit_kinds.iter().map(DiffExitKind::from));
+    }
+}
+
+impl<I, S> Observer<I, S> for ExitKindsObserver {
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.exit_kinds.clear();
+        Ok(())
+    }
+}
+
+impl Named for ExitKindsObserver {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+}
+
 /// `Observer` Python bindings
 #[cfg(feature = "python")]
 #[allow(missing_docs)]
@@ -393,7 +505,7 @@ pub mod pybind {
                     (
                         PythonStdStateWrapper::wrap(state),
                         input.bytes(),
-                        PythonExitKind::from(*exit_kind),
+                        PythonExitKind::from(exit_kind.clone()),
                     ),
                 )?;
                 Ok(())
@@ -430,7 +542,7 @@ pub mod pybind {
                     (
                         PythonStdStateWrapper::wrap(state),
                         input.bytes(),
-                        PythonExitKind::from(*exit_kind),
+                        PythonExitKind::from(exit_kind.clone()),
                     ),
                 )?;
                 Ok(())
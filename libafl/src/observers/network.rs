@@ -0,0 +1,195 @@
+//! The [`ResponsesObserver`] collects the responses of a network service to the messages of a session,
+//! the [`ResponseCodeObserver`] only their response codes, as the protocol states the service went through.
+//! The executor must explicitly support them, like the [`crate::executors::NetworkExecutor`].
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bolts::tuples::Named, observers::Observer, schedulers::stateful::INITIAL_STATE, Error,
+};
+
+/// An observer that captures the responses of a network service, one per message sent.
+/// Only works for supported executors.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ResponsesObserver {
+    /// The name of the observer.
+    pub name: String,
+    /// The bytes received after each message of the last session, empty if there was no response.
+    pub responses: Vec<Vec<u8>>,
+    /// If the service ended the last session, before it got all messages or right after them.
+    pub closed: bool,
+}
+
+impl ResponsesObserver {
+    /// Create a new [`ResponsesObserver`] with the given name.
+    #[must_use]
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            responses: Vec::new(),
+            closed: false,
+        }
+    }
+
+    /// The number of messages the service answered in the last session
+    #[must_use]
+    pub fn answered(&self) -> usize {
+        self.responses
+            .iter()
+            .filter(|response| !response.is_empty())
+            .count()
+    }
+}
+
+impl<I, S> Observer<I, S> for ResponsesObserver {}
+
+impl Named for ResponsesObserver {
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// How a [`ResponseCodeObserver`] finds the response code in a response
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResponseCodeExtractor {
+    /// A decimal status code, as in `220 Service ready` or `HTTP/1.1 404 Not Found`:
+    /// the first or second word of the first line, if it is a number.
+    AsciiStatus,
+    /// The first bytes of the response, at most 4, as a big-endian number, for binary protocols
+    LeadingBytes(usize),
+}
+
+impl ResponseCodeExtractor {
+    /// The response code of a response, if it has one
+    #[must_use]
+    pub fn extract(&self, response: &[u8]) -> Option<u32> {
+        match self {
+            Self::AsciiStatus => {
+                let line = response.split(|b| *b == b'\n').next()?;
+                line.split(|b| b.is_ascii_whitespace() || *b == b'-')
+                    .filter(|word| !word.is_empty())
+                    .take(2)
+                    .find(|word| word.len() <= 9 && word.iter().all(u8::is_ascii_digit))
+                    .map(|word| {
+                        word.iter()
+                            .fold(0, |code, digit| code * 10 + u32::from(digit - b'0'))
+                    })
+            }
+            Self::LeadingBytes(count) => {
+                let count = (*count).min(4);
+                (count > 0 && response.len() >= count).then(|| {
+                    response[..count]
+                        .iter()
+                        .fold(0, |code, byte| (code << 8) | u32::from(*byte))
+                })
+            }
+        }
+    }
+}
+
+/// An observer that extracts the response code of each response of a session,
+/// identifying the protocol states the service went through, like `AFLNet`.
+/// Only works for supported executors.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ResponseCodeObserver {
+    /// The name of the observer.
+    pub name: String,
+    /// How to find the response codes
+    pub extractor: ResponseCodeExtractor,
+    /// The response code after each message of the last session, `None` for no or unknown responses.
+    pub codes: Vec<Option<u32>>,
+}
+
+impl ResponseCodeObserver {
+    /// Create a new [`ResponseCodeObserver`] with the given name.
+    #[must_use]
+    pub fn new(name: &str, extractor: ResponseCodeExtractor) -> Self {
+        Self {
+            name: name.to_string(),
+            extractor,
+            codes: Vec::new(),
+        }
+    }
+
+    /// Extracts the response codes of the responses to the messages of a session
+    pub fn observe<R>(&mut self, responses: &[R])
+    where
+        R: AsRef<[u8]>,
+    {
+        self.codes.clear();
+        self.codes.extend(
+            responses
+                .iter()
+                .map(|response| self.extractor.extract(response.as_ref())),
+        );
+    }
+
+    /// The state of the service after each message of the last session.
+    /// A message without a response code leaves the service in its previous state.
+    #[must_use]
+    pub fn states(&self) -> Vec<u32> {
+        let mut state = INITIAL_STATE;
+        self.codes
+            .iter()
+            .map(|code| {
+                state = code.unwrap_or(state);
+                state
+            })
+            .collect()
+    }
+
+    /// The state of the service at the end of the last session
+    #[must_use]
+    pub fn last_state(&self) -> u32 {
+        self.codes
+            .iter()
+            .rev()
+            .find_map(|code| *code)
+            .unwrap_or(INITIAL_STATE)
+    }
+}
+
+impl<I, S> Observer<I, S> for ResponseCodeObserver {
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.codes.clear();
+        Ok(())
+    }
+}
+
+impl Named for ResponseCodeObserver {
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::observers::{ResponseCodeExtractor, ResponseCodeObserver};
+
+    #[test]
+    fn test_response_codes() {
+        let ascii = ResponseCodeExtractor::AsciiStatus;
+        assert_eq!(ascii.extract(b"220 FTP server ready\r\n"), Some(220));
+        assert_eq!(
+            ascii.extract(b"230-Welcome\r\n230 Logged in\r\n"),
+            Some(230)
+        );
+        assert_eq!(ascii.extract(b"RTSP/1.0 404 Not Found\r\n"), Some(404));
+        assert_eq!(ascii.extract(b"hello world 200"), None);
+        assert_eq!(ascii.extract(b""), None);
+
+        let binary = ResponseCodeExtractor::LeadingBytes(2);
+        assert_eq!(binary.extract(&[0x81, 0x80, 0x00]), Some(0x8180));
+        assert_eq!(binary.extract(&[0x81]), None);
+
+        let mut observer = ResponseCodeObserver::new("codes", ascii);
+        observer.observe(&[&b"220 ready"[..], b"", b"331 password?", b"garbage"]);
+        assert_eq!(observer.codes, [Some(220), None, Some(331), None]);
+        assert_eq!(observer.states(), [220, 220, 331, 331]);
+        assert_eq!(observer.last_state(), 331);
+    }
+}
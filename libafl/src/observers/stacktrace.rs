@@ -168,6 +168,12 @@ pub fn get_asan_runtime_flags() -> String {
 pub struct ASANBacktraceObserver {
     observer_name: String,
     hash: Option<u64>,
+    /// The ASAN error type of the last crash, e.g. `heap-buffer-overflow`
+    #[serde(default)]
+    error_type: Option<String>,
+    /// Only hash the topmost frames of the crashing stack, if set
+    #[serde(default)]
+    top_frames: Option<usize>,
 }
 
 impl ASANBacktraceObserver {
@@ -177,9 +183,25 @@ impl ASANBacktraceObserver {
         Self {
             observer_name: observer_name.to_string(),
             hash: None,
+            error_type: None,
+            top_frames: None,
         }
     }
 
+    /// Only hash the topmost `top_frames` frames of the crashing stack, so that crashes reached through different paths
+    /// still get the same hash. By default, all frames of all stacks in the report are hashed.
+    #[must_use]
+    pub fn with_top_frames(mut self, top_frames: usize) -> Self {
+        self.top_frames = Some(top_frames);
+        self
+    }
+
+    /// The ASAN error type of the last crash, e.g. `heap-buffer-overflow` or `SEGV`
+    #[must_use]
+    pub fn error_type(&self) -> Option<&str> {
+        self.error_type.as_deref()
+    }
+
     /// read ASAN output from the child stderr and parse it.
     pub fn parse_asan_output_from_childstderr(
         &mut self,
@@ -207,12 +229,29 @@ impl ASANBacktraceObserver {
     /// parse ASAN error output emited by the target command and compute the hash
     pub fn parse_asan_output(&mut self, output: &str) {
         let mut hash = 0;
-        let matcher = Regex::new("\\s*#[0-9]*\\s0x([0-9a-f]*)\\s.*").unwrap();
-        matcher.captures_iter(output).for_each(|m| {
-            let g = m.get(1).unwrap();
-            hash ^= u64::from_str_radix(g.as_str(), 16).unwrap();
-        });
+        let matcher = Regex::new("\\s*#([0-9]*)\\s0x([0-9a-f]*)\\s.*").unwrap();
+        match self.top_frames {
+            None => matcher.captures_iter(output).for_each(|m| {
+                let g = m.get(2).unwrap();
+                hash ^= u64::from_str_radix(g.as_str(), 16).unwrap();
+            }),
+            Some(top_frames) => {
+                // The first stack in the report is the crashing one, later ones are e.g. where the memory was freed
+                for (idx, m) in matcher.captures_iter(output).enumerate() {
+                    let frame: usize = m.get(1).unwrap().as_str().parse().unwrap_or(0);
+                    if frame >= top_frames || (idx > 0 && frame == 0) {
+                        break;
+                    }
+                    hash ^= u64::from_str_radix(m.get(2).unwrap().as_str(), 16).unwrap();
+                }
+            }
+        }
         self.update_hash(hash);
+
+        let error_matcher = Regex::new("ERROR: AddressSanitizer: ([\\w-]+)").unwrap();
+        self.error_type = error_matcher
+            .captures(output)
+            .map(|m| m.get(1).unwrap().as_str().to_string());
     }
 }
 
@@ -231,6 +270,11 @@ impl ObserverWithHashField for ASANBacktraceObserver {
     /// Clears the current hash value
     fn clear_hash(&mut self) {
         self.hash = None;
+        self.error_type = None;
+    }
+
+    fn crash_type(&self) -> Option<&str> {
+        self.error_type()
     }
 }
 
@@ -0,0 +1,105 @@
+//! Target sites are program locations of interest, like the code changed by a patch, given as program counters or basic block ids.
+//!
+//! The instrumentation sets the entry of a target in a hit map, with one entry per target, when reaching it.
+//! For in-process targets, `libafl_targets::target_sites` provides the hook,
+//! for `QEMU` targets, the `QemuTargetSitesHelper` installs hooks or breakpoints at the targets.
+//! The [`crate::feedbacks::TargetHitFeedback`] turns the hits into solutions, and tracks the targets still unreached.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bolts::{ownedref::OwnedSliceMut, tuples::Named, AsMutSlice, AsSlice},
+    inputs::Input,
+    observers::Observer,
+    Error,
+};
+
+/// An observer for the target sites reached by an execution
+#[allow(clippy::unsafe_derive_deserialize)]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TargetSitesObserver<'a> {
+    name: String,
+    /// The program counters or basic block ids of the targets, by their index in the hit map
+    targets: Vec<u64>,
+    hits: OwnedSliceMut<'a, u8>,
+}
+
+impl<'a> TargetSitesObserver<'a> {
+    /// Creates a new [`TargetSitesObserver`] for the `targets`, with the hit map the instrumentation writes to.
+    ///
+    /// # Panics
+    /// Panics if the hit map has less entries than targets.
+    #[must_use]
+    pub fn new(name: &str, targets: Vec<u64>, hits: &'a mut [u8]) -> Self {
+        assert!(
+            hits.len() >= targets.len(),
+            "The hit map is too small for {} target sites",
+            targets.len()
+        );
+        Self {
+            name: name.to_string(),
+            targets,
+            hits: OwnedSliceMut::from(hits),
+        }
+    }
+
+    /// Creates a new [`TargetSitesObserver`] with the hit map at a raw pointer, like a map in shared memory.
+    ///
+    /// # Safety
+    /// The hit map must be valid for at least as many entries as targets.
+    #[must_use]
+    pub unsafe fn new_from_ptr(name: &str, targets: Vec<u64>, hits_ptr: *mut u8) -> Self {
+        let len = targets.len();
+        Self {
+            name: name.to_string(),
+            targets,
+            hits: OwnedSliceMut::from_raw_parts_mut(hits_ptr, len),
+        }
+    }
+
+    /// The program counters or basic block ids of all targets
+    #[must_use]
+    pub fn targets(&self) -> &[u64] {
+        &self.targets
+    }
+
+    /// The indexes of the targets reached by the last execution
+    #[must_use]
+    pub fn reached(&self) -> Vec<usize> {
+        self.hits.as_slice()[..self.targets.len()]
+            .iter()
+            .enumerate()
+            .filter(|(_, hit)| **hit != 0)
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    /// If the last execution reached any target
+    #[must_use]
+    pub fn reached_any(&self) -> bool {
+        self.hits.as_slice()[..self.targets.len()]
+            .iter()
+            .any(|hit| *hit != 0)
+    }
+}
+
+impl<'a, I, S> Observer<I, S> for TargetSitesObserver<'a>
+where
+    I: Input,
+{
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        let len = self.targets.len();
+        self.hits.as_mut_slice()[..len].fill(0);
+        Ok(())
+    }
+}
+
+impl<'a> Named for TargetSitesObserver<'a> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
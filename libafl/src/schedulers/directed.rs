@@ -0,0 +1,197 @@
+//! Directed fuzzing, following `AFLGo`: testcases closer to a set of target locations get more energy.
+//!
+//! The distance of each edge to the targets comes from the `libafl_cc` CFG, with `ControlFlowGraph::calculate_distances_to_targets`,
+//! and the target edges from `file:line` locations or function names, with [`crate::fuzzer::EdgeLocations::find_targets`]
+//! on the locations of the same CFG, from [`crate::fuzzer::EdgeLocations::from_cfg`].
+//! The [`DirectedTestcaseScore`] scales a base score with a simulated annealing schedule: in the beginning, all testcases
+//! get a similar energy to explore, and over time the ones close to the targets get more to exploit them.
+//! The testcases need the [`MapIndexesMetadata`] of the edges map, so its feedback must track the indexes.
+
+use alloc::string::ToString;
+use core::{marker::PhantomData, time::Duration};
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bolts::current_time,
+    corpus::Testcase,
+    feedbacks::MapIndexesMetadata,
+    inputs::Input,
+    schedulers::testcase_score::{
+        CorpusPowerTestcaseScore, CorpusWeightTestcaseScore, TestcaseScore,
+    },
+    state::{HasCorpus, HasMetadata},
+    Error,
+};
+
+/// The base of the exponential cooling schedule, from `AFLGo`
+const COOLING_BASE: f64 = 20.0;
+
+crate::impl_serdeany!(DirectedScheduleMetadata);
+
+/// The metadata for directed fuzzing: the distance of each edge to the targets, and the annealing schedule
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DirectedScheduleMetadata {
+    /// The distance of each edge of the map to the targets
+    distances: HashMap<usize, f64>,
+    /// The largest distance, to normalize the distances
+    max_distance: f64,
+    /// The time the directed schedule started
+    start_time: Duration,
+    /// The time after which the schedule mostly exploits
+    time_to_exploit: Duration,
+}
+
+impl DirectedScheduleMetadata {
+    /// Creates a new [`struct@DirectedScheduleMetadata`] from the distances of the edges to the targets.
+    /// Edges reaching no target are left out. After `time_to_exploit`, the schedule mostly exploits.
+    #[must_use]
+    pub fn new<IT>(distances: IT, time_to_exploit: Duration) -> Self
+    where
+        IT: IntoIterator<Item = (usize, f64)>,
+    {
+        let distances: HashMap<usize, f64> = distances.into_iter().collect();
+        let max_distance = distances.values().copied().fold(0.0, f64::max);
+        Self {
+            distances,
+            max_distance,
+            start_time: current_time(),
+            time_to_exploit,
+        }
+    }
+
+    /// The distance of an edge to the targets, if it reaches any
+    #[must_use]
+    pub fn distance(&self, edge: usize) -> Option<f64> {
+        self.distances.get(&edge).copied()
+    }
+
+    /// The time after which the schedule mostly exploits
+    #[must_use]
+    pub fn time_to_exploit(&self) -> Duration {
+        self.time_to_exploit
+    }
+
+    /// The distance of a testcase to the targets, the mean distance of its covered edges reaching any target
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn testcase_distance(&self, edges: &[usize]) -> Option<f64> {
+        let (sum, count) = edges
+            .iter()
+            .filter_map(|edge| self.distance(*edge))
+            .fold((0.0, 0), |(sum, count), distance| {
+                (sum + distance, count + 1)
+            });
+        (count > 0).then(|| sum / f64::from(count))
+    }
+
+    /// The temperature of the annealing at the time `now`, cooling from `1.0` when starting to `0.05` at the time to exploit
+    #[must_use]
+    pub fn temperature(&self, now: Duration) -> f64 {
+        if self.time_to_exploit.is_zero() {
+            return 0.0;
+        }
+        let elapsed = now.saturating_sub(self.start_time).as_secs_f64();
+        libm::pow(COOLING_BASE, -elapsed / self.time_to_exploit.as_secs_f64())
+    }
+
+    /// The factor for the score of a testcase at the given distance, from `1/32` to `32`.
+    /// Testcases reaching no target count as the most distant.
+    #[must_use]
+    pub fn power_factor(&self, distance: Option<f64>, now: Duration) -> f64 {
+        let normalized = match distance {
+            Some(distance) if self.max_distance > 0.0 => distance / self.max_distance,
+            Some(_) => 0.0,
+            None => 1.0,
+        };
+        let temperature = self.temperature(now);
+        let power = (1.0 - normalized) * (1.0 - temperature) + 0.5 * temperature;
+        libm::exp2(10.0 * power - 5.0)
+    }
+}
+
+/// The distance of a testcase to the targets, cached by [`DirectedTestcaseScore`]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct DirectedTestcaseMetadata {
+    /// The mean distance of the covered edges to the targets, if any reaches them
+    pub distance: Option<f64>,
+}
+
+crate::impl_serdeany!(DirectedTestcaseMetadata);
+
+/// Scales the score of the base [`TestcaseScore`] by the distance of the testcase to the targets,
+/// following the annealing schedule of the [`struct@DirectedScheduleMetadata`], which the state must hold.
+#[derive(Debug, Clone)]
+pub struct DirectedTestcaseScore<F, I, S>
+where
+    F: TestcaseScore<I, S>,
+    I: Input,
+    S: HasMetadata + HasCorpus<I>,
+{
+    phantom: PhantomData<(F, I, S)>,
+}
+
+impl<F, I, S> TestcaseScore<I, S> for DirectedTestcaseScore<F, I, S>
+where
+    F: TestcaseScore<I, S>,
+    I: Input,
+    S: HasMetadata + HasCorpus<I>,
+{
+    fn compute(entry: &mut Testcase<I>, state: &S) -> Result<f64, Error> {
+        let score = F::compute(entry, state)?;
+        let dsmeta = state
+            .metadata()
+            .get::<DirectedScheduleMetadata>()
+            .ok_or_else(|| {
+                Error::key_not_found("DirectedScheduleMetadata not found".to_string())
+            })?;
+
+        let distance = if let Some(tcmeta) = entry.metadata().get::<DirectedTestcaseMetadata>() {
+            tcmeta.distance
+        } else {
+            let distance = entry
+                .metadata()
+                .get::<MapIndexesMetadata>()
+                .and_then(|meta| dsmeta.testcase_distance(&meta.list));
+            entry.add_metadata(DirectedTestcaseMetadata { distance });
+            distance
+        };
+
+        Ok(score * dsmeta.power_factor(distance, current_time()))
+    }
+}
+
+/// The power of each corpus entry for a `PowerMutationalStage`, directed to the targets
+pub type DirectedPowerTestcaseScore<I, S> =
+    DirectedTestcaseScore<CorpusPowerTestcaseScore<I, S>, I, S>;
+
+/// The weight of each corpus entry for a [`crate::schedulers::WeightedScheduler`], directed to the targets
+pub type DirectedWeightTestcaseScore<I, S> =
+    DirectedTestcaseScore<CorpusWeightTestcaseScore<I, S>, I, S>;
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use crate::schedulers::directed::DirectedScheduleMetadata;
+
+    #[test]
+    fn test_directed_power_factor() {
+        let meta =
+            DirectedScheduleMetadata::new([(1, 2.0), (2, 4.0), (3, 8.0)], Duration::from_secs(100));
+        assert_eq!(meta.testcase_distance(&[1, 2, 5]), Some(3.0));
+        assert_eq!(meta.testcase_distance(&[5]), None);
+
+        // When starting, all testcases get the same energy
+        let start = meta.start_time;
+        let close = meta.power_factor(Some(2.0), start);
+        let far = meta.power_factor(None, start);
+        assert!((close - 1.0).abs() < 1e-9);
+        assert!((far - 1.0).abs() < 1e-9);
+
+        // Later on, the close ones get more
+        let later = start + Duration::from_secs(1000);
+        assert!(meta.power_factor(Some(2.0), later) > 4.0);
+        assert!(meta.power_factor(None, later) < 1.0 / 16.0);
+    }
+}
@@ -23,6 +23,23 @@ pub use weighted::{StdWeightedScheduler, WeightedScheduler};
 pub mod powersched;
 pub use powersched::PowerQueueScheduler;
 
+pub mod directed;
+pub use directed::{
+    DirectedPowerTestcaseScore, DirectedScheduleMetadata, DirectedTestcaseScore,
+    DirectedWeightTestcaseScore,
+};
+
+pub mod patch;
+pub use patch::{
+    PatchMetadata, PatchPowerTestcaseScore, PatchTestcaseMetadata, PatchTestcaseScore,
+    PatchWeightTestcaseScore,
+};
+
+pub mod stateful;
+pub use stateful::{
+    StateMachineMetadata, StateScheduler, StateSequenceMetadata, StateTargetMetadata,
+};
+
 use alloc::borrow::ToOwned;
 
 use crate::{
@@ -0,0 +1,203 @@
+//! Patch-aware fuzzing: testcases exercising the code changed by a patch get more energy, e.g. to fuzz pull requests in CI.
+//!
+//! The [`struct@PatchMetadata`] holds the changed edges, with the changed line of each edge. They are either given directly,
+//! or mapped from a diff with [`crate::fuzzer::EdgeLocations::changed_edges`].
+//! The [`crate::feedbacks::PatchFeedback`] keeps testcases reaching changed lines nobody reached before,
+//! and reports the ratio of reached changed lines, the [`PatchTestcaseScore`] boosts testcases touching changed edges.
+
+use alloc::{string::ToString, vec::Vec};
+use core::marker::PhantomData;
+use hashbrown::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::Testcase,
+    feedbacks::MapIndexesMetadata,
+    inputs::Input,
+    schedulers::testcase_score::{
+        CorpusPowerTestcaseScore, CorpusWeightTestcaseScore, TestcaseScore,
+    },
+    state::{HasCorpus, HasMetadata},
+    Error,
+};
+
+/// The default factor for the score of testcases touching changed edges
+pub const DEFAULT_PATCH_BONUS: f64 = 8.0;
+
+crate::impl_serdeany!(PatchMetadata);
+
+/// The edges changed by a patch, and the changed lines reached so far
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PatchMetadata {
+    /// The changed line of each changed edge
+    changed: HashMap<usize, usize>,
+    /// The number of changed lines with edges
+    lines: usize,
+    /// The changed lines reached so far
+    reached: HashSet<usize>,
+    /// The factor for the score of testcases touching changed edges
+    bonus: f64,
+}
+
+impl PatchMetadata {
+    /// Creates a new [`struct@PatchMetadata`] for a set of changed edges, e.g. loaded from a file.
+    /// Each edge counts as its own changed line.
+    #[must_use]
+    pub fn new<IT>(edges: IT) -> Self
+    where
+        IT: IntoIterator<Item = usize>,
+    {
+        Self::with_lines(
+            edges
+                .into_iter()
+                .enumerate()
+                .map(|(line, edge)| (edge, line)),
+        )
+    }
+
+    /// Creates a new [`struct@PatchMetadata`] for the changed edges listed in a file, one edge id per line,
+    /// e.g. derived from a `libafl_cc` CFG dump. Empty lines and lines starting with `#` are skipped.
+    pub fn from_edge_list(content: &str) -> Result<Self, Error> {
+        let edges = content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                line.parse()
+                    .map_err(|_| Error::illegal_argument(format!("Invalid edge id {}", line)))
+            })
+            .collect::<Result<Vec<usize>, Error>>()?;
+        Ok(Self::new(edges))
+    }
+
+    /// Creates a new [`struct@PatchMetadata`] for the changed edges with their changed line,
+    /// as returned by [`crate::fuzzer::EdgeLocations::changed_edges`]
+    #[must_use]
+    pub fn with_lines<IT>(edges: IT) -> Self
+    where
+        IT: IntoIterator<Item = (usize, usize)>,
+    {
+        let changed: HashMap<usize, usize> = edges.into_iter().collect();
+        let lines = changed.values().collect::<HashSet<_>>().len();
+        Self {
+            changed,
+            lines,
+            reached: HashSet::new(),
+            bonus: DEFAULT_PATCH_BONUS,
+        }
+    }
+
+    /// Sets the factor for the score of testcases touching changed edges, [`DEFAULT_PATCH_BONUS`] by default
+    #[must_use]
+    pub fn with_bonus(mut self, bonus: f64) -> Self {
+        self.bonus = bonus;
+        self
+    }
+
+    /// The factor for the score of testcases touching changed edges
+    #[must_use]
+    pub fn bonus(&self) -> f64 {
+        self.bonus
+    }
+
+    /// If the edge was changed by the patch
+    #[must_use]
+    pub fn is_changed(&self, edge: usize) -> bool {
+        self.changed.contains_key(&edge)
+    }
+
+    /// All changed edges, in no particular order
+    pub fn changed_edges(&self) -> impl Iterator<Item = usize> + '_ {
+        self.changed.keys().copied()
+    }
+
+    /// How many of the edges were changed by the patch
+    #[must_use]
+    pub fn changed_count(&self, edges: &[usize]) -> usize {
+        edges.iter().filter(|edge| self.is_changed(**edge)).count()
+    }
+
+    /// The number of changed lines with edges
+    #[must_use]
+    pub fn lines(&self) -> usize {
+        self.lines
+    }
+
+    /// The number of changed lines reached so far
+    #[must_use]
+    pub fn reached_lines(&self) -> usize {
+        self.reached.len()
+    }
+
+    /// Marks the changed lines of the edges as reached. Returns if any was not reached before.
+    pub fn reach(&mut self, edges: &[usize]) -> bool {
+        let mut new_line = false;
+        for edge in edges {
+            if let Some(line) = self.changed.get(edge) {
+                new_line |= self.reached.insert(*line);
+            }
+        }
+        new_line
+    }
+}
+
+/// The number of changed edges a testcase touches, set by the [`crate::feedbacks::PatchFeedback`]
+/// or cached by the [`PatchTestcaseScore`]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct PatchTestcaseMetadata {
+    /// The number of changed edges the testcase touches
+    pub changed_edges: usize,
+}
+
+crate::impl_serdeany!(PatchTestcaseMetadata);
+
+/// Scales the score of the base [`TestcaseScore`] by the bonus of the [`struct@PatchMetadata`], which the state must hold,
+/// if the testcase touches changed edges.
+/// Without [`PatchTestcaseMetadata`], the changed edges are taken from the [`MapIndexesMetadata`] of the edges map.
+#[derive(Debug, Clone)]
+pub struct PatchTestcaseScore<F, I, S>
+where
+    F: TestcaseScore<I, S>,
+    I: Input,
+    S: HasMetadata + HasCorpus<I>,
+{
+    phantom: PhantomData<(F, I, S)>,
+}
+
+impl<F, I, S> TestcaseScore<I, S> for PatchTestcaseScore<F, I, S>
+where
+    F: TestcaseScore<I, S>,
+    I: Input,
+    S: HasMetadata + HasCorpus<I>,
+{
+    fn compute(entry: &mut Testcase<I>, state: &S) -> Result<f64, Error> {
+        let score = F::compute(entry, state)?;
+        let pmeta = state
+            .metadata()
+            .get::<PatchMetadata>()
+            .ok_or_else(|| Error::key_not_found("PatchMetadata not found".to_string()))?;
+
+        let changed_edges = if let Some(tcmeta) = entry.metadata().get::<PatchTestcaseMetadata>() {
+            tcmeta.changed_edges
+        } else {
+            let changed_edges = entry
+                .metadata()
+                .get::<MapIndexesMetadata>()
+                .map_or(0, |meta| pmeta.changed_count(&meta.list));
+            entry.add_metadata(PatchTestcaseMetadata { changed_edges });
+            changed_edges
+        };
+
+        if changed_edges > 0 {
+            Ok(score * pmeta.bonus())
+        } else {
+            Ok(score)
+        }
+    }
+}
+
+/// The power of each corpus entry for a `PowerMutationalStage`, preferring changed code
+pub type PatchPowerTestcaseScore<I, S> = PatchTestcaseScore<CorpusPowerTestcaseScore<I, S>, I, S>;
+
+/// The weight of each corpus entry for a [`crate::schedulers::WeightedScheduler`], preferring changed code
+pub type PatchWeightTestcaseScore<I, S> = PatchTestcaseScore<CorpusWeightTestcaseScore<I, S>, I, S>;
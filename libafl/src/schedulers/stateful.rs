@@ -0,0 +1,521 @@
+//! Stateful protocol fuzzing, following `AFLNet`: the fuzzer infers the state machine of a network service
+//! from its response codes, and targets the protocol states that are rarely fuzzed, or lead to new findings.
+//!
+//! The [`crate::observers::ResponseCodeObserver`] extracts the states each session went through,
+//! the [`crate::feedbacks::StateFeedback`] grows the [`struct@StateMachineMetadata`] with new states and transitions,
+//! and records the states of each testcase in its [`StateSequenceMetadata`].
+//! The [`StateScheduler`] then picks a target state, and a testcase reaching it. The messages that bring the service
+//! to the target state are in the [`struct@StateTargetMetadata`], such that mutators can leave them alone,
+//! like the [`crate::mutators::multi::FieldSelection::StateSuffix`].
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec::Vec,
+};
+use core::marker::PhantomData;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bolts::rands::Rand,
+    corpus::{Corpus, Testcase},
+    inputs::Input,
+    schedulers::Scheduler,
+    state::{HasCorpus, HasExecutions, HasMetadata, HasRand},
+    Error,
+};
+
+/// The state of a service before it got any message
+pub const INITIAL_STATE: u32 = 0;
+
+/// A testcase reaching a protocol state
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StateSeed {
+    /// The index of the testcase in the corpus
+    pub idx: usize,
+    /// The number of messages it takes to reach the state
+    pub prefix_len: usize,
+}
+
+/// A protocol state of the inferred state machine
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ProtocolState {
+    /// How often the [`StateScheduler`] targeted this state
+    pub selected: u64,
+    /// The executions spent while targeting this state
+    pub fuzzed: u64,
+    /// The corpus entries found while targeting this state
+    pub discoveries: u64,
+    /// The testcases reaching this state
+    pub seeds: Vec<StateSeed>,
+}
+
+impl ProtocolState {
+    /// The score of the state for the selection, from `AFLNet`:
+    /// states that were rarely selected and fuzzed, or led to many discoveries, score higher.
+    #[must_use]
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    pub fn score(&self) -> u64 {
+        let fuzzed = libm::log10(self.fuzzed as f64 + 1.0);
+        let rarity = libm::exp2(-libm::log10(fuzzed * self.selected as f64 + 1.0));
+        let discoveries = libm::exp2(libm::log10(self.discoveries as f64 + 1.0));
+        (libm::ceil(1000.0 * rarity * discoveries) as u64).max(1)
+    }
+}
+
+/// The state machine of a service, inferred from the state sequences of all sessions
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StateMachineMetadata {
+    states: BTreeMap<u32, ProtocolState>,
+    transitions: BTreeSet<(u32, u32)>,
+}
+
+crate::impl_serdeany!(StateMachineMetadata);
+
+impl Default for StateMachineMetadata {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StateMachineMetadata {
+    /// Creates a new [`struct@StateMachineMetadata`], knowing only the [`INITIAL_STATE`]
+    #[must_use]
+    pub fn new() -> Self {
+        let mut states = BTreeMap::new();
+        states.insert(INITIAL_STATE, ProtocolState::default());
+        Self {
+            states,
+            transitions: BTreeSet::new(),
+        }
+    }
+
+    /// The number of known states
+    #[must_use]
+    pub fn state_count(&self) -> usize {
+        self.states.len()
+    }
+
+    /// The number of known transitions
+    #[must_use]
+    pub fn transition_count(&self) -> usize {
+        self.transitions.len()
+    }
+
+    /// The known states, by their ids
+    pub fn states(&self) -> impl Iterator<Item = (u32, &ProtocolState)> {
+        self.states.iter().map(|(id, state)| (*id, state))
+    }
+
+    /// The state with this id, if it is known
+    #[must_use]
+    pub fn state(&self, id: u32) -> Option<&ProtocolState> {
+        self.states.get(&id)
+    }
+
+    /// The state with this id, if it is known (mutable)
+    pub fn state_mut(&mut self, id: u32) -> Option<&mut ProtocolState> {
+        self.states.get_mut(&id)
+    }
+
+    /// If the service went from one state to the other in any session
+    #[must_use]
+    pub fn has_transition(&self, from: u32, to: u32) -> bool {
+        self.transitions.contains(&(from, to))
+    }
+
+    /// Adds the states a session went through, starting from the [`INITIAL_STATE`].
+    /// Returns if it found a new state or transition.
+    pub fn add_sequence(&mut self, states: &[u32]) -> bool {
+        let mut new = false;
+        let mut from = INITIAL_STATE;
+        for to in states {
+            if !self.states.contains_key(to) {
+                self.states.insert(*to, ProtocolState::default());
+                new = true;
+            }
+            new |= self.transitions.insert((from, *to));
+            from = *to;
+        }
+        new
+    }
+
+    /// Adds the testcase at `idx` as seed of each state it reaches, with the shortest prefix
+    pub fn add_seed(&mut self, idx: usize, states: &[u32]) {
+        self.add_sequence(states);
+        let reached = core::iter::once(INITIAL_STATE).chain(states.iter().copied());
+        for (prefix_len, id) in reached.enumerate() {
+            let seeds = &mut self.states.get_mut(&id).unwrap().seeds;
+            if !seeds.iter().any(|seed| seed.idx == idx) {
+                seeds.push(StateSeed { idx, prefix_len });
+            }
+        }
+    }
+
+    /// Removes the testcase at `idx` from the seeds of all states.
+    /// The corpus moves the testcases after it one index down, so do their seeds.
+    pub fn remove_seed(&mut self, idx: usize) {
+        for state in self.states.values_mut() {
+            state.seeds.retain(|seed| seed.idx != idx);
+            for seed in &mut state.seeds {
+                if seed.idx > idx {
+                    seed.idx -= 1;
+                }
+            }
+        }
+    }
+
+    /// Picks a state with seeds, with a probability proportional to its [`ProtocolState::score`]
+    pub fn select<R>(&self, rand: &mut R) -> Option<u32>
+    where
+        R: Rand,
+    {
+        let candidates: Vec<(u32, u64)> = self
+            .states
+            .iter()
+            .filter(|(_, state)| !state.seeds.is_empty())
+            .map(|(id, state)| (*id, state.score()))
+            .collect();
+        let total: u64 = candidates.iter().map(|(_, score)| score).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut pick = rand.below(total);
+        for (id, score) in &candidates {
+            if pick < *score {
+                return Some(*id);
+            }
+            pick -= score;
+        }
+        candidates.last().map(|(id, _)| *id)
+    }
+}
+
+/// The states a testcase went through, one after each message, recorded by the [`crate::feedbacks::StateFeedback`]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StateSequenceMetadata {
+    /// The state after each message
+    pub states: Vec<u32>,
+}
+
+crate::impl_serdeany!(StateSequenceMetadata);
+
+/// The state the [`StateScheduler`] targets with the current testcase
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StateTargetMetadata {
+    /// The targeted state
+    pub state: u32,
+    /// The number of messages of the current testcase that bring the service to the state
+    pub prefix_len: usize,
+    /// The executions when the state got selected
+    executions: usize,
+}
+
+crate::impl_serdeany!(StateTargetMetadata);
+
+/// A [`StateScheduler`] targets a protocol state of the [`struct@StateMachineMetadata`] with each testcase,
+/// falling back to the base scheduler as long as no testcase reaches any state.
+#[derive(Debug, Clone)]
+pub struct StateScheduler<CS, I, S>
+where
+    CS: Scheduler<I, S>,
+    I: Input,
+    S: HasCorpus<I> + HasMetadata + HasRand + HasExecutions,
+{
+    base: CS,
+    phantom: PhantomData<(I, S)>,
+}
+
+impl<CS, I, S> Scheduler<I, S> for StateScheduler<CS, I, S>
+where
+    CS: Scheduler<I, S>,
+    I: Input,
+    S: HasCorpus<I> + HasMetadata + HasRand + HasExecutions,
+{
+    fn on_add(&self, state: &mut S, idx: usize) -> Result<(), Error> {
+        let states = state
+            .corpus()
+            .get(idx)?
+            .borrow()
+            .metadata()
+            .get::<StateSequenceMetadata>()
+            .map(|meta| meta.states.clone());
+        let target = state.metadata().get::<StateTargetMetadata>().copied();
+        let machine = Self::machine_mut(state);
+        if let Some(target) = target {
+            if let Some(targeted) = machine.state_mut(target.state) {
+                targeted.discoveries += 1;
+            }
+        }
+        if let Some(states) = states {
+            machine.add_seed(idx, &states);
+        }
+        self.base.on_add(state, idx)
+    }
+
+    fn on_replace(&self, state: &mut S, idx: usize, testcase: &Testcase<I>) -> Result<(), Error> {
+        let states = state
+            .corpus()
+            .get(idx)?
+            .borrow()
+            .metadata()
+            .get::<StateSequenceMetadata>()
+            .map(|meta| meta.states.clone());
+        let machine = Self::machine_mut(state);
+        machine.remove_seed(idx);
+        if let Some(states) = states {
+            machine.add_seed(idx, &states);
+        }
+        self.base.on_replace(state, idx, testcase)
+    }
+
+    fn on_remove(
+        &self,
+        state: &mut S,
+        idx: usize,
+        testcase: &Option<Testcase<I>>,
+    ) -> Result<(), Error> {
+        Self::machine_mut(state).remove_seed(idx);
+        self.base.on_remove(state, idx, testcase)
+    }
+
+    fn next(&self, state: &mut S) -> Result<usize, Error> {
+        let executions = *state.executions();
+        let previous = state.metadata_mut().remove::<StateTargetMetadata>();
+        let mut machine = match state.metadata_mut().remove::<StateMachineMetadata>() {
+            Some(machine) => machine,
+            None => return self.base.next(state),
+        };
+        if let Some(previous) = previous {
+            if let Some(targeted) = machine.state_mut(previous.state) {
+                targeted.fuzzed += executions.saturating_sub(previous.executions) as u64;
+            }
+        }
+
+        let rand = state.rand_mut();
+        let selected = machine.select(rand).map(|target| {
+            let targeted = machine.state_mut(target).unwrap();
+            targeted.selected += 1;
+            let pick = rand.below(targeted.seeds.len() as u64) as usize;
+            (target, targeted.seeds[pick])
+        });
+        state.metadata_mut().insert_boxed(machine);
+        let (target, seed) = match selected {
+            Some(selected) => selected,
+            None => return self.base.next(state),
+        };
+
+        state.add_metadata(StateTargetMetadata {
+            state: target,
+            prefix_len: seed.prefix_len,
+            executions,
+        });
+        *state.corpus_mut().current_mut() = Some(seed.idx);
+        Ok(seed.idx)
+    }
+}
+
+impl<CS, I, S> StateScheduler<CS, I, S>
+where
+    CS: Scheduler<I, S>,
+    I: Input,
+    S: HasCorpus<I> + HasMetadata + HasRand + HasExecutions,
+{
+    /// Creates a new [`StateScheduler`], with a base scheduler for testcases reaching no state yet
+    #[must_use]
+    pub fn new(base: CS) -> Self {
+        Self {
+            base,
+            phantom: PhantomData,
+        }
+    }
+
+    /// The base scheduler
+    pub fn base(&self) -> &CS {
+        &self.base
+    }
+
+    fn machine_mut(state: &mut S) -> &mut StateMachineMetadata {
+        if !state.has_metadata::<StateMachineMetadata>() {
+            state.add_metadata(StateMachineMetadata::new());
+        }
+        state
+            .metadata_mut()
+            .get_mut::<StateMachineMetadata>()
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use crate::{
+        bolts::rands::StdRand,
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        inputs::{BytesInput, MessageSequenceInput},
+        mutators::{
+            havoc_mutations,
+            multi::{FieldSelection, MultiFieldMutator},
+            Mutator,
+        },
+        schedulers::{
+            stateful::{StateMachineMetadata, INITIAL_STATE},
+            QueueScheduler, Scheduler, StateScheduler, StateSequenceMetadata, StateTargetMetadata,
+        },
+        state::{HasCorpus, HasExecutions, HasMetadata, StdState},
+    };
+
+    fn login(messages: &[&[u8]]) -> MessageSequenceInput {
+        MessageSequenceInput::new(
+            messages
+                .iter()
+                .map(|message| BytesInput::new(message.to_vec()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_state_scheduler() {
+        let mut state = StdState::new(
+            StdRand::with_seed(1337),
+            InMemoryCorpus::<MessageSequenceInput>::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+        let scheduler = StateScheduler::new(QueueScheduler::new());
+
+        // Without states, the base scheduler picks
+        let mut testcase = Testcase::new(login(&[b"USER a\r\n"]));
+        testcase.add_metadata(StateSequenceMetadata { states: vec![] });
+        let idx = state.corpus_mut().add(testcase).unwrap();
+        assert_eq!(scheduler.next(&mut state).unwrap(), idx);
+        assert!(state.metadata().get::<StateTargetMetadata>().is_none());
+
+        for (messages, states) in [
+            (
+                &[&b"USER a\r\n"[..], b"PASS b\r\n", b"LIST\r\n"][..],
+                &[220, 331, 230][..],
+            ),
+            (&[b"USER a\r\n", b"PASS c\r\n"], &[220, 530]),
+        ] {
+            let mut testcase = Testcase::new(login(messages));
+            testcase.add_metadata(StateSequenceMetadata {
+                states: states.to_vec(),
+            });
+            let idx = state.corpus_mut().add(testcase).unwrap();
+            scheduler.on_add(&mut state, idx).unwrap();
+        }
+
+        let machine = state.metadata().get::<StateMachineMetadata>().unwrap();
+        assert_eq!(machine.state_count(), 5);
+        assert_eq!(machine.transition_count(), 4);
+        assert_eq!(machine.state(220).unwrap().seeds.len(), 2);
+        assert_eq!(machine.state(230).unwrap().seeds[0].prefix_len, 3);
+
+        let mut targeted = Vec::new();
+        for _ in 0..64 {
+            let idx = scheduler.next(&mut state).unwrap();
+            let target = *state.metadata().get::<StateTargetMetadata>().unwrap();
+            // The prefix of the testcase brings the service to the target state
+            let testcase = state.corpus().get(idx).unwrap().borrow();
+            let states = &testcase
+                .metadata()
+                .get::<StateSequenceMetadata>()
+                .unwrap()
+                .states;
+            let reached = match target.prefix_len {
+                0 => INITIAL_STATE,
+                len => states[len - 1],
+            };
+            assert_eq!(reached, target.state);
+            drop(testcase);
+            targeted.push(target.state);
+            *state.executions_mut() += 10;
+        }
+        targeted.sort_unstable();
+        targeted.dedup();
+        assert_eq!(targeted, [INITIAL_STATE, 220, 230, 331, 530]);
+
+        let machine = state.metadata().get::<StateMachineMetadata>().unwrap();
+        let selected: u64 = machine.states().map(|(_, s)| s.selected).sum();
+        let fuzzed: u64 = machine.states().map(|(_, s)| s.fuzzed).sum();
+        assert_eq!(selected, 64);
+        assert_eq!(fuzzed, 63 * 10);
+
+        // A new entry is a discovery of the targeted state
+        let target = state.metadata().get::<StateTargetMetadata>().unwrap().state;
+        let idx = state
+            .corpus_mut()
+            .add(Testcase::new(login(&[b"NOOP\r\n"])))
+            .unwrap();
+        scheduler.on_add(&mut state, idx).unwrap();
+        let machine = state.metadata().get::<StateMachineMetadata>().unwrap();
+        assert_eq!(machine.state(target).unwrap().discoveries, 1);
+
+        let mut testcase = Testcase::new(login(&[b"USER b\r\n", b"QUIT\r\n"]));
+        testcase.add_metadata(StateSequenceMetadata {
+            states: vec![220, 221],
+        });
+        let idx = state.corpus_mut().add(testcase).unwrap();
+        assert_eq!(idx, 4);
+        scheduler.on_add(&mut state, idx).unwrap();
+
+        let removed = state.corpus_mut().remove(2).unwrap();
+        scheduler.on_remove(&mut state, 2, &removed).unwrap();
+        let machine = state.metadata().get::<StateMachineMetadata>().unwrap();
+        assert!(machine.state(530).unwrap().seeds.is_empty());
+        // The entries after the removed one moved down
+        let seeds: Vec<usize> = machine
+            .state(220)
+            .unwrap()
+            .seeds
+            .iter()
+            .map(|seed| seed.idx)
+            .collect();
+        assert_eq!(seeds, [1, 3]);
+        let seed = machine.state(221).unwrap().seeds[0];
+        assert_eq!((seed.idx, seed.prefix_len), (3, 2));
+        assert!(state
+            .corpus()
+            .get(seed.idx)
+            .unwrap()
+            .borrow()
+            .metadata()
+            .get::<StateSequenceMetadata>()
+            .unwrap()
+            .states
+            .ends_with(&[221]));
+    }
+
+    #[test]
+    fn test_state_suffix_mutations() {
+        let mut state = StdState::new(
+            StdRand::with_seed(1337),
+            InMemoryCorpus::<MessageSequenceInput>::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+        state.add_metadata(StateTargetMetadata {
+            state: 331,
+            prefix_len: 2,
+            executions: 0,
+        });
+        let mut mutator =
+            MultiFieldMutator::new(&mut state, havoc_mutations(), FieldSelection::StateSuffix);
+        let original = login(&[b"USER a\r\n", b"PASS b\r\n", b"LIST\r\n", b"QUIT\r\n"]);
+        let mut input = original.clone();
+        for i in 0..64 {
+            mutator.mutate(&mut state, &mut input, i).unwrap();
+        }
+        assert_eq!(input.prefix(2), original.prefix(2));
+        assert_ne!(input, original);
+    }
+}
@@ -0,0 +1,78 @@
+//! The [`CheckpointStage`] periodically writes a checkpoint of the whole state to disk.
+//! See [`crate::state::checkpoint`] for how to resume from it.
+
+use core::{marker::PhantomData, time::Duration};
+use serde::Serialize;
+
+use crate::{
+    bolts::current_time,
+    stages::Stage,
+    state::{checkpoint::Checkpointer, HasExecutions},
+    Error,
+};
+
+/// The default interval between two checkpoints
+pub const DEFAULT_CHECKPOINT_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// A stage that writes a checkpoint of the state, at most once per interval.
+#[derive(Debug)]
+pub struct CheckpointStage<E, EM, S, Z>
+where
+    S: Serialize + HasExecutions,
+{
+    checkpointer: Checkpointer,
+    interval: Duration,
+    last_checkpoint: Duration,
+    #[allow(clippy::type_complexity)]
+    phantom: PhantomData<(E, EM, S, Z)>,
+}
+
+impl<E, EM, S, Z> Stage<E, EM, S, Z> for CheckpointStage<E, EM, S, Z>
+where
+    S: Serialize + HasExecutions,
+{
+    #[inline]
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut S,
+        _manager: &mut EM,
+        _corpus_idx: usize,
+    ) -> Result<(), Error> {
+        let now = current_time();
+        if now.saturating_sub(self.last_checkpoint) >= self.interval {
+            self.checkpointer.save(state)?;
+            self.last_checkpoint = now;
+        }
+        Ok(())
+    }
+}
+
+impl<E, EM, S, Z> CheckpointStage<E, EM, S, Z>
+where
+    S: Serialize + HasExecutions,
+{
+    /// Creates a new [`CheckpointStage`], writing a checkpoint every [`DEFAULT_CHECKPOINT_INTERVAL`].
+    #[must_use]
+    pub fn new(checkpointer: Checkpointer) -> Self {
+        Self::with_interval(checkpointer, DEFAULT_CHECKPOINT_INTERVAL)
+    }
+
+    /// Creates a new [`CheckpointStage`], writing a checkpoint every `interval`.
+    #[must_use]
+    pub fn with_interval(checkpointer: Checkpointer, interval: Duration) -> Self {
+        Self {
+            checkpointer,
+            interval,
+            last_checkpoint: current_time(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// The [`Checkpointer`] used by this stage, e.g. to write a final checkpoint on shutdown
+    #[must_use]
+    pub fn checkpointer(&self) -> &Checkpointer {
+        &self.checkpointer
+    }
+}
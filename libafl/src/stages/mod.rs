@@ -25,6 +25,12 @@ pub use generalization::GeneralizationStage;
 pub mod owned;
 pub use owned::StagesOwnedList;
 
+pub mod tmin;
+pub use tmin::{
+    BacktraceHashPredicate, ExitKindPredicate, MapHashPredicate, MinimizationPredicate,
+    MinimizationStage,
+};
+
 #[cfg(feature = "std")]
 pub mod concolic;
 #[cfg(feature = "std")]
@@ -37,6 +43,11 @@ pub mod sync;
 #[cfg(feature = "std")]
 pub use sync::*;
 
+#[cfg(feature = "std")]
+pub mod checkpoint;
+#[cfg(feature = "std")]
+pub use checkpoint::CheckpointStage;
+
 use crate::{
     events::{EventFirer, EventRestarter, HasEventManagerId, ProgressReporter},
     executors::{Executor, HasObservers},
@@ -9,11 +9,7 @@ pub mod mutational;
 pub use mutational::StdMutationalPushStage;
 
 use alloc::rc::Rc;
-use core::{
-    cell::{Cell, RefCell},
-    marker::PhantomData,
-    time::Duration,
-};
+use core::{cell::RefCell, marker::PhantomData, time::Duration};
 
 use crate::{
     bolts::current_time,
@@ -104,7 +100,7 @@ where
 
     #[allow(clippy::type_complexity)]
     phantom: PhantomData<(CS, (), EM, I, OT, S, Z)>,
-    exit_kind: Rc<Cell<Option<ExitKind>>>,
+    exit_kind: Rc<RefCell<Option<ExitKind>>>,
 }
 
 impl<CS, EM, I, OT, S, Z> PushStageHelper<CS, EM, I, OT, S, Z>
@@ -121,7 +117,7 @@ where
     #[allow(clippy::type_complexity)]
     pub fn new(
         shared_state: Rc<RefCell<Option<PushStageSharedState<CS, EM, I, OT, S, Z>>>>,
-        exit_kind_ref: Rc<Cell<Option<ExitKind>>>,
+        exit_kind_ref: Rc<RefCell<Option<ExitKind>>>,
     ) -> Self {
         Self {
             shared_state,
@@ -153,13 +149,13 @@ where
     #[inline]
     #[must_use]
     pub fn exit_kind(&self) -> Option<ExitKind> {
-        self.exit_kind.get()
+        self.exit_kind.borrow().clone()
     }
 
     /// Resets the exit kind
     #[inline]
     pub fn reset_exit_kind(&mut self) {
-        self.exit_kind.set(None);
+        self.exit_kind.replace(None);
     }
 
     /// Resets this state after a full stage iter.
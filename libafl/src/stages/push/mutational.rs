@@ -2,7 +2,7 @@
 //! For the current input, it will perform a range of random mutations, and then run them in the executor.
 
 use alloc::rc::Rc;
-use core::cell::{Cell, RefCell};
+use core::cell::RefCell;
 
 use crate::{
     bolts::rands::Rand,
@@ -224,7 +224,7 @@ where
     pub fn new(
         mutator: M,
         shared_state: Rc<RefCell<Option<PushStageSharedState<CS, EM, I, OT, S, Z>>>>,
-        exit_kind: Rc<Cell<Option<ExitKind>>>,
+        exit_kind: Rc<RefCell<Option<ExitKind>>>,
         stage_idx: i32,
     ) -> Self {
         Self {
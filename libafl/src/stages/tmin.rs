@@ -0,0 +1,480 @@
+//! The [`MinimizationStage`] shrinks inputs while they keep a property, in the spirit of `afl-tmin`.
+//! It works on the entries of the main corpus, or on the solutions, for example to shrink crashes.
+
+use alloc::string::{String, ToString};
+use core::{fmt::Debug, marker::PhantomData};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bolts::tuples::{tuple_list, tuple_list_type},
+    corpus::{Corpus, Testcase},
+    executors::{Executor, ExitKind, HasObservers},
+    inputs::{HasBytesVec, Input},
+    mark_feature_time,
+    mutators::{BytesDeleteMutator, BytesZeroMutator, MutationResult, Mutator},
+    observers::{MapObserver, ObserverWithHashField, ObserversTuple},
+    stages::Stage,
+    start_timer,
+    state::{HasClientPerfMonitor, HasCorpus, HasExecutions, HasMetadata, HasSolutions},
+    Error,
+};
+
+#[cfg(feature = "introspection")]
+use crate::monitors::PerfFeature;
+
+/// The default number of candidates tried per input
+pub const DEFAULT_MINIMIZATION_ITERATIONS: usize = 1024;
+
+/// The mutations used to minimize an input: deleting chunks, and replacing chunks with zeros
+pub type MinimizationMutationsType = tuple_list_type!(BytesDeleteMutator, BytesZeroMutator);
+
+/// Get the mutations used to minimize an input, to use with a [`crate::mutators::StdScheduledMutator`]
+#[must_use]
+pub fn minimization_mutations() -> MinimizationMutationsType {
+    tuple_list!(BytesDeleteMutator::new(), BytesZeroMutator::new())
+}
+
+/// Marks a testcase as minimized by a [`MinimizationStage`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MinimizedMetadata {
+    /// The length of the input before the minimization
+    pub original_len: usize,
+}
+
+crate::impl_serdeany!(MinimizedMetadata);
+
+/// The property an input has to keep while it is minimized.
+/// A candidate is kept if its fingerprint equals the one of the original input.
+pub trait MinimizationPredicate<I, OT, S>: Debug
+where
+    I: Input,
+    OT: ObserversTuple<I, S>,
+{
+    /// The fingerprint of a run
+    type Fingerprint: PartialEq + Debug;
+
+    /// Computes the fingerprint of the last run
+    fn fingerprint(&self, observers: &OT, exit_kind: &ExitKind)
+        -> Result<Self::Fingerprint, Error>;
+}
+
+/// Keeps the coverage: the hash of a [`MapObserver`] must stay the same
+#[derive(Debug, Clone)]
+pub struct MapHashPredicate<O> {
+    observer_name: String,
+    phantom: PhantomData<O>,
+}
+
+impl<O> MapHashPredicate<O>
+where
+    O: MapObserver,
+{
+    /// Creates a new [`MapHashPredicate`] for the given [`MapObserver`]
+    #[must_use]
+    pub fn new(map_observer: &O) -> Self {
+        Self {
+            observer_name: map_observer.name().to_string(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<I, O, OT, S> MinimizationPredicate<I, OT, S> for MapHashPredicate<O>
+where
+    I: Input,
+    O: MapObserver,
+    OT: ObserversTuple<I, S>,
+{
+    type Fingerprint = u64;
+
+    fn fingerprint(&self, observers: &OT, _exit_kind: &ExitKind) -> Result<u64, Error> {
+        Ok(observers
+            .match_name::<O>(&self.observer_name)
+            .ok_or_else(|| Error::key_not_found("MapObserver not found".to_string()))?
+            .hash())
+    }
+}
+
+/// Keeps the crash: the hash of a backtrace observer, such as the [`crate::observers::BacktraceObserver`], must stay the same
+#[derive(Debug, Clone)]
+pub struct BacktraceHashPredicate<O> {
+    observer_name: String,
+    phantom: PhantomData<O>,
+}
+
+impl<O> BacktraceHashPredicate<O>
+where
+    O: ObserverWithHashField,
+{
+    /// Creates a new [`BacktraceHashPredicate`] for the observer with the given name
+    #[must_use]
+    pub fn new(observer_name: &str) -> Self {
+        Self {
+            observer_name: observer_name.to_string(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<I, O, OT, S> MinimizationPredicate<I, OT, S> for BacktraceHashPredicate<O>
+where
+    I: Input,
+    O: ObserverWithHashField + Debug,
+    OT: ObserversTuple<I, S>,
+{
+    type Fingerprint = Option<u64>;
+
+    fn fingerprint(&self, observers: &OT, _exit_kind: &ExitKind) -> Result<Option<u64>, Error> {
+        Ok(*observers
+            .match_name::<O>(&self.observer_name)
+            .ok_or_else(|| Error::key_not_found("BacktraceObserver not found".to_string()))?
+            .hash())
+    }
+}
+
+/// Keeps the [`ExitKind`], e.g. that the input still crashes or times out
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExitKindPredicate;
+
+impl ExitKindPredicate {
+    /// Creates a new [`ExitKindPredicate`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl<I, OT, S> MinimizationPredicate<I, OT, S> for ExitKindPredicate
+where
+    I: Input,
+    OT: ObserversTuple<I, S>,
+{
+    type Fingerprint = ExitKind;
+
+    fn fingerprint(&self, _observers: &OT, exit_kind: &ExitKind) -> Result<ExitKind, Error> {
+        Ok(exit_kind.clone())
+    }
+}
+
+/// How simple an input is: shorter is simpler, and for the same length, less non-zero bytes are simpler
+fn complexity<I>(input: &I) -> (usize, usize)
+where
+    I: HasBytesVec,
+{
+    let bytes = input.bytes();
+    (bytes.len(), bytes.iter().filter(|b| **b != 0).count())
+}
+
+/// A stage that minimizes testcases, keeping a [`MinimizationPredicate`].
+/// Each testcase is minimized once, and marked with [`MinimizedMetadata`].
+/// The minimized testcase replaces the original one in its corpus, so an [`crate::corpus::OnDiskCorpus`] also stores it on disk.
+#[derive(Debug)]
+pub struct MinimizationStage<E, EM, I, M, OT, P, S, Z>
+where
+    I: Input + HasBytesVec,
+    M: Mutator<I, S>,
+    OT: ObserversTuple<I, S>,
+    P: MinimizationPredicate<I, OT, S>,
+{
+    mutator: M,
+    predicate: P,
+    iterations: usize,
+    solutions: bool,
+    #[allow(clippy::type_complexity)]
+    phantom: PhantomData<(E, EM, I, OT, S, Z)>,
+}
+
+impl<E, EM, I, M, OT, P, S, Z> Stage<E, EM, S, Z> for MinimizationStage<E, EM, I, M, OT, P, S, Z>
+where
+    E: Executor<EM, I, S, Z> + HasObservers<I, OT, S>,
+    I: Input + HasBytesVec,
+    M: Mutator<I, S>,
+    OT: ObserversTuple<I, S>,
+    P: MinimizationPredicate<I, OT, S>,
+    S: HasClientPerfMonitor + HasExecutions + HasCorpus<I> + HasSolutions<I>,
+{
+    #[inline]
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+        corpus_idx: usize,
+    ) -> Result<(), Error> {
+        if self.solutions {
+            for idx in 0..state.solutions().count() {
+                self.minimize_entry(fuzzer, executor, state, manager, idx)?;
+            }
+        } else {
+            self.minimize_entry(fuzzer, executor, state, manager, corpus_idx)?;
+        }
+
+        #[cfg(feature = "introspection")]
+        state.introspection_monitor_mut().finish_stage();
+
+        Ok(())
+    }
+}
+
+impl<E, EM, I, M, OT, P, S, Z> MinimizationStage<E, EM, I, M, OT, P, S, Z>
+where
+    E: Executor<EM, I, S, Z> + HasObservers<I, OT, S>,
+    I: Input + HasBytesVec,
+    M: Mutator<I, S>,
+    OT: ObserversTuple<I, S>,
+    P: MinimizationPredicate<I, OT, S>,
+    S: HasClientPerfMonitor + HasExecutions + HasCorpus<I> + HasSolutions<I>,
+{
+    /// Creates a new [`MinimizationStage`], minimizing the entries of the main corpus
+    pub fn new(mutator: M, predicate: P) -> Self {
+        Self {
+            mutator,
+            predicate,
+            iterations: DEFAULT_MINIMIZATION_ITERATIONS,
+            solutions: false,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Creates a new [`MinimizationStage`], minimizing all solutions not minimized yet
+    pub fn for_solutions(mutator: M, predicate: P) -> Self {
+        Self {
+            solutions: true,
+            ..Self::new(mutator, predicate)
+        }
+    }
+
+    /// Sets the number of candidates tried per input
+    #[must_use]
+    pub fn with_iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    fn testcase<'a>(
+        &self,
+        state: &'a S,
+        idx: usize,
+    ) -> Result<&'a core::cell::RefCell<Testcase<I>>, Error> {
+        if self.solutions {
+            state.solutions().get(idx)
+        } else {
+            state.corpus().get(idx)
+        }
+    }
+
+    fn run(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+        input: &I,
+    ) -> Result<P::Fingerprint, Error> {
+        start_timer!(state);
+        executor.observers_mut().pre_exec_all(state, input)?;
+        mark_feature_time!(state, PerfFeature::PreExecObservers);
+
+        start_timer!(state);
+        let exit_kind = executor.run_target(fuzzer, state, manager, input)?;
+        mark_feature_time!(state, PerfFeature::TargetExecution);
+
+        *state.executions_mut() += 1;
+
+        start_timer!(state);
+        executor
+            .observers_mut()
+            .post_exec_all(state, input, &exit_kind)?;
+        mark_feature_time!(state, PerfFeature::PostExecObservers);
+
+        self.predicate.fingerprint(executor.observers(), &exit_kind)
+    }
+
+    #[allow(clippy::cast_possible_wrap)] // more than i32 iterations on 32 bit system - highly unlikely...
+    fn minimize_entry(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+        idx: usize,
+    ) -> Result<(), Error> {
+        let original = {
+            let mut testcase = self.testcase(state, idx)?.borrow_mut();
+            if testcase.has_metadata::<MinimizedMetadata>() {
+                return Ok(());
+            }
+            testcase.load_input()?.clone()
+        };
+
+        let expected = self.run(fuzzer, executor, state, manager, &original)?;
+        let mut best = original.clone();
+        let mut best_complexity = complexity(&best);
+
+        for i in 0..self.iterations {
+            let mut candidate = best.clone();
+            if self.mutator.mutate(state, &mut candidate, i as i32)? == MutationResult::Skipped {
+                continue;
+            }
+            let candidate_complexity = complexity(&candidate);
+            if candidate_complexity >= best_complexity {
+                continue;
+            }
+            if self.run(fuzzer, executor, state, manager, &candidate)? == expected {
+                best = candidate;
+                best_complexity = candidate_complexity;
+            }
+        }
+
+        let mut testcase = self.testcase(state, idx)?.borrow().clone();
+        testcase.add_metadata(MinimizedMetadata {
+            original_len: original.bytes().len(),
+        });
+        if complexity(&best) < complexity(&original) {
+            testcase.set_input(best);
+        }
+        // Through the corpus, so an on-disk corpus stores the new input and metadata as well
+        if self.solutions {
+            state.solutions_mut().replace(idx, testcase)
+        } else {
+            state.corpus_mut().replace(idx, testcase)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::{
+        bolts::rands::StdRand,
+        corpus::{ondisk::OnDiskMetadataFormat, Corpus, InMemoryCorpus, OnDiskCorpus, Testcase},
+        executors::{Executor, ExitKind, HasObservers},
+        inputs::{BytesInput, HasBytesVec},
+        mutators::StdScheduledMutator,
+        stages::{
+            tmin::{minimization_mutations, ExitKindPredicate, MinimizedMetadata},
+            MinimizationStage, Stage,
+        },
+        state::{HasMetadata, HasSolutions, StdState},
+        Error,
+    };
+
+    /// Crashes if the input contains an `A`
+    #[derive(Debug)]
+    struct CrashOnA(());
+
+    impl<EM, S, Z> Executor<EM, BytesInput, S, Z> for CrashOnA {
+        fn run_target(
+            &mut self,
+            _fuzzer: &mut Z,
+            _state: &mut S,
+            _mgr: &mut EM,
+            input: &BytesInput,
+        ) -> Result<ExitKind, Error> {
+            if input.bytes().contains(&b'A') {
+                Ok(ExitKind::Crash)
+            } else {
+                Ok(ExitKind::Ok)
+            }
+        }
+    }
+
+    impl<S> HasObservers<BytesInput, (), S> for CrashOnA {
+        fn observers(&self) -> &() {
+            &self.0
+        }
+
+        fn observers_mut(&mut self) -> &mut () {
+            &mut self.0
+        }
+    }
+
+    #[test]
+    fn test_minimize_solution() {
+        let mut solutions = InMemoryCorpus::new();
+        solutions
+            .add(Testcase::new(BytesInput::new(b"xyzAxyz".to_vec())))
+            .unwrap();
+        let mut state = StdState::new(
+            StdRand::with_seed(1),
+            InMemoryCorpus::<BytesInput>::new(),
+            solutions,
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+
+        let mut stage = MinimizationStage::for_solutions(
+            StdScheduledMutator::new(minimization_mutations()),
+            ExitKindPredicate::new(),
+        );
+        stage
+            .perform(&mut (), &mut CrashOnA(()), &mut state, &mut (), 0)
+            .unwrap();
+
+        let testcase = state.solutions().get(0).unwrap().borrow();
+        // The deletion keeps at least two bytes, the rest is zeroed
+        let minimized = testcase.input().as_ref().unwrap().bytes();
+        assert!(minimized.len() <= 2);
+        assert!(minimized.contains(&b'A'));
+        assert_eq!(
+            testcase
+                .metadata()
+                .get::<MinimizedMetadata>()
+                .unwrap()
+                .original_len,
+            7
+        );
+    }
+
+    #[test]
+    fn test_minimize_on_disk() {
+        let dir = std::env::temp_dir().join(format!(
+            "libafl_test_minimize_on_disk_{}",
+            std::process::id()
+        ));
+        let mut solutions =
+            OnDiskCorpus::new_save_meta(dir.clone(), Some(OnDiskMetadataFormat::Json)).unwrap();
+        solutions
+            .add(Testcase::new(BytesInput::new(b"xyzAxyz".to_vec())))
+            .unwrap();
+        let mut state = StdState::new(
+            StdRand::with_seed(1),
+            InMemoryCorpus::<BytesInput>::new(),
+            solutions,
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+
+        let mut stage = MinimizationStage::for_solutions(
+            StdScheduledMutator::new(minimization_mutations()),
+            ExitKindPredicate::new(),
+        );
+        stage
+            .perform(&mut (), &mut CrashOnA(()), &mut state, &mut (), 0)
+            .unwrap();
+
+        // Both the minimized input and the metadata marking it are on disk
+        let filename = state
+            .solutions()
+            .get(0)
+            .unwrap()
+            .borrow()
+            .filename()
+            .clone()
+            .unwrap();
+        assert!(fs::read(&filename).unwrap().len() <= 2);
+        let file_name = std::path::Path::new(&filename)
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .into_owned();
+        let metadata = fs::read_to_string(dir.join(format!(".{}.metadata", file_name))).unwrap();
+        assert!(metadata.contains("\"original_len\":7"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
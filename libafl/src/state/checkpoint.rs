@@ -0,0 +1,313 @@
+//! Checkpoints of the full fuzzer state in an on-disk directory, to resume a campaign after the whole process tree died.
+//!
+//! A checkpoint directory holds two files:
+//! - `manifest.json`, a human readable [`CheckpointManifest`], used to reject incompatible checkpoints,
+//! - `state.<generation>.postcard`, the serialized state, including the corpora, the (named) metadata, the RNG, and the stats.
+//!
+//! Each checkpoint writes its state to a new file, then renames the manifest pointing to it over the previous one,
+//! so a crash during a checkpoint leaves the previous one intact.
+
+use alloc::string::String;
+use core::{any::type_name, time::Duration};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::{bolts::current_time, state::HasExecutions, Error};
+
+/// The version of the checkpoint format. Checkpoints written with a different version are rejected.
+pub const CHECKPOINT_FORMAT_VERSION: u32 = 1;
+
+/// The name of the manifest file in a checkpoint directory
+pub const CHECKPOINT_MANIFEST_FILE: &str = "manifest.json";
+
+/// The prefix of the state files in a checkpoint directory, followed by the generation of the checkpoint
+pub const CHECKPOINT_STATE_PREFIX: &str = "state.";
+
+/// The extension of the state files in a checkpoint directory
+pub const CHECKPOINT_STATE_EXTENSION: &str = "postcard";
+
+/// Describes a checkpoint, to check if it can be loaded by this fuzzer.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CheckpointManifest {
+    /// The version of the checkpoint format, see [`CHECKPOINT_FORMAT_VERSION`]
+    pub format_version: u32,
+    /// The version of `LibAFL` that wrote the checkpoint
+    pub libafl_version: String,
+    /// The type of the serialized state
+    pub state_type: String,
+    /// The time the checkpoint was written, since the epoch
+    pub time: Duration,
+    /// The executions of the state, at the time of the checkpoint
+    pub executions: usize,
+    /// The number of checkpoints written to this directory before this one
+    pub generation: u64,
+    /// The name of the file holding the serialized state, in the checkpoint directory
+    pub state_file: String,
+}
+
+impl CheckpointManifest {
+    /// Creates the manifest for the checkpoint of `state` with the given `generation`, written now.
+    #[must_use]
+    pub fn new<S>(state: &S, generation: u64) -> Self
+    where
+        S: HasExecutions,
+    {
+        Self {
+            format_version: CHECKPOINT_FORMAT_VERSION,
+            libafl_version: env!("CARGO_PKG_VERSION").into(),
+            state_type: type_name::<S>().into(),
+            time: current_time(),
+            executions: *state.executions(),
+            generation,
+            state_file: format!(
+                "{}{}.{}",
+                CHECKPOINT_STATE_PREFIX, generation, CHECKPOINT_STATE_EXTENSION
+            ),
+        }
+    }
+
+    /// Checks if a state of type `S` can be restored from the checkpoint described by this manifest.
+    pub fn check_compatible<S>(&self) -> Result<(), Error> {
+        if self.format_version != CHECKPOINT_FORMAT_VERSION {
+            return Err(Error::illegal_state(format!(
+                "Checkpoint has format version {}, expected {}",
+                self.format_version, CHECKPOINT_FORMAT_VERSION
+            )));
+        }
+        if self.libafl_version != env!("CARGO_PKG_VERSION") {
+            return Err(Error::illegal_state(format!(
+                "Checkpoint was written by LibAFL {}, this is LibAFL {}",
+                self.libafl_version,
+                env!("CARGO_PKG_VERSION")
+            )));
+        }
+        if self.state_type != type_name::<S>() {
+            return Err(Error::illegal_state(format!(
+                "Checkpoint holds a state of type {}, expected {}",
+                self.state_type,
+                type_name::<S>()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Writes checkpoints of the state to, and restores the state from, a directory.
+#[derive(Debug, Clone)]
+pub struct Checkpointer {
+    dir: PathBuf,
+}
+
+impl Checkpointer {
+    /// Creates a new [`Checkpointer`] for the given directory, creating it if needed.
+    pub fn new<P>(dir: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        fs::create_dir_all(dir.as_ref())?;
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+        })
+    }
+
+    /// The checkpoint directory
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns `true` if the directory holds a checkpoint.
+    #[must_use]
+    pub fn has_checkpoint(&self) -> bool {
+        self.dir.join(CHECKPOINT_MANIFEST_FILE).is_file()
+    }
+
+    /// Reads the manifest of the checkpoint, if there is one.
+    pub fn manifest(&self) -> Result<Option<CheckpointManifest>, Error> {
+        if !self.has_checkpoint() {
+            return Ok(None);
+        }
+        let manifest = fs::read(self.dir.join(CHECKPOINT_MANIFEST_FILE))?;
+        Ok(Some(serde_json::from_slice(&manifest)?))
+    }
+
+    /// Writes a checkpoint of the `state`, replacing the previous one.
+    ///
+    /// The state goes to a new file, and only then the manifest pointing to it is renamed over the previous manifest.
+    /// Until this rename, the previous checkpoint stays loadable; its state file is removed afterwards.
+    pub fn save<S>(&self, state: &S) -> Result<(), Error>
+    where
+        S: Serialize + HasExecutions,
+    {
+        let bytes = postcard::to_allocvec(state)?;
+        // An unreadable previous manifest does not hold a loadable checkpoint anyway
+        let generation = match self.manifest() {
+            Ok(Some(previous)) => previous.generation + 1,
+            _ => 0,
+        };
+        let manifest = CheckpointManifest::new(state, generation);
+        Self::write_synced(&self.dir.join(&manifest.state_file), &bytes)?;
+
+        let tmp_path = self.dir.join(format!(".{}.tmp", CHECKPOINT_MANIFEST_FILE));
+        Self::write_synced(&tmp_path, &serde_json::to_vec_pretty(&manifest)?)?;
+        fs::rename(&tmp_path, self.dir.join(CHECKPOINT_MANIFEST_FILE))?;
+        #[cfg(unix)]
+        File::open(&self.dir)?.sync_all()?;
+
+        // Remove the previous state, and the states of interrupted checkpoints
+        for entry in fs::read_dir(&self.dir)? {
+            let name = entry?.file_name();
+            let name = name.to_string_lossy();
+            if name != manifest.state_file
+                && name.starts_with(CHECKPOINT_STATE_PREFIX)
+                && name.ends_with(CHECKPOINT_STATE_EXTENSION)
+            {
+                fs::remove_file(self.dir.join(name.as_ref()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Restores the state from the checkpoint.
+    /// Returns `None` if there is no checkpoint, and an error if the checkpoint is incompatible with `S`.
+    pub fn load<S>(&self) -> Result<Option<S>, Error>
+    where
+        S: DeserializeOwned,
+    {
+        let manifest = match self.manifest()? {
+            Some(manifest) => manifest,
+            None => return Ok(None),
+        };
+        manifest.check_compatible::<S>()?;
+        let bytes = fs::read(self.dir.join(&manifest.state_file))?;
+        Ok(Some(postcard::from_bytes(&bytes)?))
+    }
+
+    /// Restores the state from the checkpoint, or creates a new one with `init`, if there is no checkpoint.
+    pub fn load_or_else<F, S>(&self, init: F) -> Result<S, Error>
+    where
+        F: FnOnce() -> Result<S, Error>,
+        S: DeserializeOwned,
+    {
+        match self.load()? {
+            Some(state) => Ok(state),
+            None => init(),
+        }
+    }
+
+    /// Writes the file and waits until it is on disk
+    fn write_synced(path: &Path, bytes: &[u8]) -> Result<(), Error> {
+        let mut file = File::create(path)?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use serde::{ser::SerializeStruct, Serialize, Serializer};
+    use std::fs;
+
+    use crate::{
+        bolts::rands::{Rand, StdRand},
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        inputs::BytesInput,
+        state::{
+            checkpoint::{Checkpointer, CHECKPOINT_MANIFEST_FILE, CHECKPOINT_STATE_PREFIX},
+            HasCorpus, HasExecutions, HasRand, StdState,
+        },
+    };
+
+    type TestState =
+        StdState<InMemoryCorpus<BytesInput>, BytesInput, StdRand, InMemoryCorpus<BytesInput>>;
+
+    /// A state that fails to serialize, after its first field
+    struct FailingState {
+        executions: usize,
+    }
+
+    impl Serialize for FailingState {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            let mut state = serializer.serialize_struct("FailingState", 2)?;
+            state.serialize_field("executions", &self.executions)?;
+            Err(serde::ser::Error::custom("interrupted"))
+        }
+    }
+
+    impl HasExecutions for FailingState {
+        fn executions(&self) -> &usize {
+            &self.executions
+        }
+
+        fn executions_mut(&mut self) -> &mut usize {
+            &mut self.executions
+        }
+    }
+
+    #[test]
+    fn test_checkpoint_roundtrip() {
+        let dir = ".test_checkpoint";
+        let _ = fs::remove_dir_all(dir);
+        let checkpointer = Checkpointer::new(dir).unwrap();
+        assert!(checkpointer.load::<TestState>().unwrap().is_none());
+
+        let mut state: TestState = StdState::new(
+            StdRand::with_seed(1337),
+            InMemoryCorpus::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+        state
+            .corpus_mut()
+            .add(Testcase::new(BytesInput::new(b"abc".to_vec())))
+            .unwrap();
+        *state.executions_mut() = 42;
+        checkpointer.save(&state).unwrap();
+
+        let mut restored = checkpointer.load::<TestState>().unwrap().unwrap();
+        assert_eq!(*restored.executions(), 42);
+        assert_eq!(restored.corpus().count(), 1);
+        assert_eq!(restored.rand_mut().next(), state.rand_mut().next());
+
+        // An interrupted checkpoint leaves the previous one intact
+        assert!(checkpointer.save(&FailingState { executions: 43 }).is_err());
+        let restored = checkpointer.load::<TestState>().unwrap().unwrap();
+        assert_eq!(*restored.executions(), 42);
+
+        // A new checkpoint replaces the state file of the previous one
+        *state.executions_mut() = 44;
+        checkpointer.save(&state).unwrap();
+        let restored = checkpointer.load::<TestState>().unwrap().unwrap();
+        assert_eq!(*restored.executions(), 44);
+        let state_files: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .filter(|name| name.to_string_lossy().starts_with(CHECKPOINT_STATE_PREFIX))
+            .collect();
+        assert_eq!(state_files, ["state.1.postcard"]);
+
+        // A checkpoint of another state type is rejected
+        assert!(checkpointer.load::<u32>().is_err());
+
+        // So is a checkpoint in another format version
+        let manifest_path = checkpointer.dir().join(CHECKPOINT_MANIFEST_FILE);
+        let manifest = fs::read_to_string(&manifest_path)
+            .unwrap()
+            .replace("\"format_version\": 1", "\"format_version\": 0");
+        fs::write(&manifest_path, manifest).unwrap();
+        assert!(checkpointer.load::<TestState>().is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,5 +1,8 @@
 //! The fuzzer, and state are the core pieces of every good fuzzer
 
+#[cfg(feature = "std")]
+pub mod checkpoint;
+
 use core::{fmt::Debug, marker::PhantomData, time::Duration};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 #[cfg(feature = "std")]
@@ -787,6 +787,16 @@ bool AFLCoverage::runOnModule(Module &M) {
         cfg += "%%__";
       auto current_cur_loc = record->getSecond();
       cfg += formatv("+{0}\n", current_cur_loc);
+      // The source line of the block, to find the edges of file:line targets
+      for (auto &IN : *current_bb) {
+        if (DILocation *loc = IN.getDebugLoc().get()) {
+          if (loc->getLine()) {
+            cfg += formatv("@@{0}:{1}\n", loc->getFilename(), loc->getLine())
+                       .str();
+            break;
+          }
+        }
+      }
       for (auto bb_successor = succ_begin(current_bb);
            bb_successor != succ_end(current_bb); bb_successor++) {
         cfg += formatv("->{0}\n", bb_to_cur_loc[*bb_successor]).str();
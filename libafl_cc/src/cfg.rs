@@ -1,6 +1,6 @@
 //! LLVM style control flow graph with information of AFL-style index of the each
 //! edges, use together with ``AFLCoverage`` pass having --dump-afl-cfg flag enabled.
-use core::borrow::Borrow;
+use core::{borrow::Borrow, cmp::Reverse};
 use serde::{Deserialize, Serialize};
 use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::marker::PhantomData;
@@ -25,6 +25,8 @@ where
     pub bottom_node_loc: usize,
     /// Name of the function that contains such edge. For anonymous function, it is "__".
     pub calling_func: String,
+    /// The source file and line of the to node, if the target was compiled with debug info.
+    pub location: Option<(String, u32)>,
     /// Indexes of successor block.
     pub successor_basic_blocks: Vec<usize>,
     /// ``prev_loc`` >> 1 ^ ``cur_loc`` of edges connecting [`CfgEdge.bottom_node_loc`]
@@ -122,6 +124,7 @@ where
 {
     current_bb: usize,
     bb_to_func: HashMap<usize, String>,
+    bb_to_location: HashMap<usize, (String, u32)>,
     bb_to_successors: HashMap<usize, Vec<usize>>,
     func_to_entry_bb: HashMap<String, usize>,
     phantom: PhantomData<T>,
@@ -135,6 +138,7 @@ where
         Self {
             current_bb: 0,
             bb_to_func: HashMap::default(),
+            bb_to_location: HashMap::default(),
             bb_to_successors: HashMap::default(),
             func_to_entry_bb: HashMap::default(),
             phantom: PhantomData,
@@ -170,6 +174,14 @@ where
                 self.current_bb = splitter.next().expect(FAILED_TO_PARSE).parse().expect("");
                 self.bb_to_func.insert(self.current_bb, func_name);
             }
+            "@@" => {
+                // "@@{file}:{line}": The source location of the current basic block.
+                let (file, line) = line_content.rsplit_once(':').expect(FAILED_TO_PARSE);
+                self.bb_to_location.insert(
+                    self.current_bb,
+                    (file.into(), line.parse().expect(FAILED_TO_PARSE)),
+                );
+            }
             "$$" => {
                 // "$${function name}+{index}": Function {function name}'s entry block is {index}.
                 let mut splitter = line_content.split('+');
@@ -223,6 +235,7 @@ where
                     top_node_loc: *bb_loc,
                     bottom_node_loc: *successor_loc,
                     calling_func: current_func.clone(),
+                    location: self.bb_to_location.get(successor_loc).cloned(),
                     successor_basic_blocks: vec![],
                     successor_edges: vec![],
                     metadata: None,
@@ -276,6 +289,30 @@ where
         self.edges[xored_loc].as_mut()
     }
 
+    /// Iterate over the indexes of all edges in the coverage map, with the name of the function containing them.
+    /// Together with a coverage map, this tells which functions were reached.
+    pub fn edge_functions(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.edges
+            .iter()
+            .flatten()
+            .map(|edge| (edge.xored_loc, edge.calling_func.as_str()))
+    }
+
+    /// Iterate over the indexes of all edges in the coverage map, with the name of the function containing them,
+    /// and their source file and line, if known. The edges of `file:line` targets found with these locations
+    /// have the indexes expected by [`Self::calculate_distances_to_targets`].
+    pub fn edge_locations(&self) -> impl Iterator<Item = (usize, &str, Option<(&str, u32)>)> {
+        self.edges.iter().flatten().map(|edge| {
+            (
+                edge.xored_loc,
+                edge.calling_func.as_str(),
+                edge.location
+                    .as_ref()
+                    .map(|(file, line)| (file.as_str(), *line)),
+            )
+        })
+    }
+
     /// Get entry basic block information of a function.
     #[must_use]
     pub fn get_entry(&self, func_name: &str) -> Option<&EntryBasicBlockInfo> {
@@ -328,6 +365,65 @@ where
         }
         distances
     }
+
+    /// Calculate the distance of each edge to the ``targets``, the indexes of edges in the coverage map, for directed fuzzing.
+    ///
+    /// Following `AFLGo`, this is the harmonic mean of the shortest distances to all reachable targets,
+    /// so edges close to one target are not penalized for being far from the others.
+    /// Edges reaching no target would not be inserted in the returned hash map.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn calculate_distances_to_targets(&self, targets: &[usize]) -> HashMap<usize, f64> {
+        let mut predecessors: HashMap<usize, Vec<usize>> = HashMap::new();
+        for edge in self.edges.iter().flatten() {
+            for successor in &edge.successor_edges {
+                predecessors
+                    .entry(*successor)
+                    .or_default()
+                    .push(edge.xored_loc);
+            }
+        }
+
+        // The sum of the inverse distances to the reachable targets, and their number
+        let mut inverse_sums: HashMap<usize, (f64, u32)> = HashMap::new();
+        for target in targets {
+            let target_info = match self.get_edge(*target) {
+                Some(target_info) => target_info,
+                None => continue,
+            };
+            // Dijkstra backwards from the target, the closest edges first
+            let mut distances: HashMap<usize, u32> = HashMap::new();
+            let mut to_visit = BinaryHeap::new();
+            to_visit.push(Reverse((target_info.get_weight(), *target)));
+            while let Some(Reverse((distance, edge))) = to_visit.pop() {
+                if distances.contains_key(&edge) {
+                    continue;
+                }
+                distances.insert(edge, distance);
+                for predecessor in predecessors.get(&edge).into_iter().flatten() {
+                    if !distances.contains_key(predecessor) {
+                        let predecessor_info = self
+                            .get_edge(*predecessor)
+                            .expect("unknown predecessor added");
+                        to_visit.push(Reverse((
+                            distance + predecessor_info.get_weight(),
+                            *predecessor,
+                        )));
+                    }
+                }
+            }
+            for (edge, distance) in distances {
+                let (inverse_sum, reachable) = inverse_sums.entry(edge).or_default();
+                *inverse_sum += 1.0 / f64::from(distance.max(1));
+                *reachable += 1;
+            }
+        }
+
+        inverse_sums
+            .into_iter()
+            .map(|(edge, (inverse_sum, reachable))| (edge, f64::from(reachable) / inverse_sum))
+            .collect()
+    }
 }
 
 impl<T> Default for ControlFlowGraph<T>
@@ -341,6 +437,9 @@ where
 
 #[cfg(test)]
 mod tests {
+    use core::time::Duration;
+    use libafl::{fuzzer::EdgeLocations, schedulers::DirectedScheduleMetadata};
+
     use crate::cfg::{ControlFlowGraph, HasWeight};
 
     struct TestMetaData {}
@@ -385,6 +484,11 @@ mod tests {
 
         assert!(cfg.get_edge(26911).is_none());
         assert!(cfg.get_edge(41864).is_some());
+
+        let functions: Vec<(usize, &str)> = cfg.edge_functions().collect();
+        // Including the edges from the virtual node 0 to both entries
+        assert_eq!(functions.len(), 7);
+        assert!(functions.contains(&((50306 >> 1) ^ 19123, "_ZN7MyClass1VEi")));
     }
 
     #[test]
@@ -396,4 +500,41 @@ mod tests {
         assert_eq!(*distances.get(&((26911 >> 1) ^ 41925)).unwrap(), 2);
         assert!(distances.get(&((41864 >> 1) ^ 52706)).is_none());
     }
+
+    #[test]
+    fn test_distances_to_targets() {
+        let cfg: ControlFlowGraph<TestMetaData> = ControlFlowGraph::from_content(TEST_GRAPH_STR);
+        let first = (41864 >> 1) ^ 26911;
+        let left = (26911 >> 1) ^ 52706;
+        let right = (26911 >> 1) ^ 41925;
+
+        let distances = cfg.calculate_distances_to_targets(&[left]);
+        assert!((distances[&left] - 1.0).abs() < f64::EPSILON);
+        assert!((distances[&first] - 2.0).abs() < f64::EPSILON);
+        assert!(distances.get(&right).is_none());
+
+        // The harmonic mean of the distances 2 and 2
+        let distances = cfg.calculate_distances_to_targets(&[left, right]);
+        assert!((distances[&first] - 2.0).abs() < f64::EPSILON);
+        assert!((distances[&left] - 1.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_distances_to_located_targets() {
+        let cfg: ControlFlowGraph<TestMetaData> = ControlFlowGraph::from_content(
+            "$$main+41864\n%%main+41864\n@@src/main.c:3\n->26911\n%%main+26911\n@@src/main.c:5\n->52706\n->41925\n%%main+52706\n@@src/main.c:7\n%%main+41925\n@@src/main.c:9\n",
+        );
+        let locations = EdgeLocations::from_cfg(cfg.edge_locations());
+        let targets = locations.find_targets(&["main.c:7"]).unwrap();
+        assert_eq!(targets, [(26911 >> 1) ^ 52706]);
+
+        let meta = DirectedScheduleMetadata::new(
+            cfg.calculate_distances_to_targets(&targets),
+            Duration::from_secs(100),
+        );
+        assert_eq!(meta.distance(targets[0]), Some(1.0));
+        assert_eq!(meta.distance((41864 >> 1) ^ 26911), Some(2.0));
+        assert_eq!(meta.distance(41864), Some(3.0));
+        assert_eq!(meta.distance((26911 >> 1) ^ 41925), None);
+    }
 }
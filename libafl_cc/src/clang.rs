@@ -22,6 +22,12 @@ fn dll_extension<'a>() -> &'a str {
 
 include!(concat!(env!("OUT_DIR"), "/clang_constants.rs"));
 
+/// The prefix of C symbols in assembly
+#[cfg(target_vendor = "apple")]
+const SYMBOL_PREFIX: &str = "_";
+#[cfg(not(target_vendor = "apple"))]
+const SYMBOL_PREFIX: &str = "";
+
 /// The supported LLVM passes
 #[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -73,6 +79,7 @@ pub struct ClangWrapper {
     need_libafl_arg: bool,
     has_libafl_arg: bool,
     use_new_pm: bool,
+    afl_manual_control: bool,
 
     parse_args_called: bool,
     base_args: Vec<String>,
@@ -201,6 +208,25 @@ impl CompilerWrapper for ClangWrapper {
         // Fuzzing define common among tools
         new_args.push("-DFUZZING_BUILD_MODE_UNSAFE_FOR_PRODUCTION=1".into());
 
+        // Deferred forkserver and persistent mode, like afl-clang-fast.
+        // The signatures tell the fuzzer to enable them, see `ForkserverExecutorBuilder`.
+        // The functions are declared by their symbol, to not mangle them in C++.
+        if self.afl_manual_control {
+            new_args.push("-D__AFL_HAVE_MANUAL_CONTROL=1".into());
+            new_args.push(format!(
+                "-D__AFL_LOOP(_A)=({{ static volatile const char *_B __attribute__((used, unused)); \
+                 _B = (const char *)\"##SIG_AFL_PERSISTENT##\"; \
+                 int _L(unsigned int) __asm__(\"{}__afl_persistent_loop\"); _L(_A); }})",
+                SYMBOL_PREFIX
+            ));
+            new_args.push(format!(
+                "-D__AFL_INIT()=do {{ static volatile const char *_A __attribute__((used, unused)); \
+                 _A = (const char *)\"##SIG_AFL_DEFER_FORKSRV##\"; \
+                 void _I(void) __asm__(\"{}__afl_manual_init\"); _I(); }} while (0)",
+                SYMBOL_PREFIX
+            ));
+        }
+
         // Libraries needed by libafl on Windows
         #[cfg(windows)]
         if linking {
@@ -370,6 +396,7 @@ impl ClangWrapper {
             need_libafl_arg: false,
             has_libafl_arg: false,
             use_new_pm,
+            afl_manual_control: false,
             parse_args_called: false,
             base_args: vec![],
             cc_args: vec![],
@@ -436,6 +463,13 @@ impl ClangWrapper {
         self.use_new_pm = value;
         self
     }
+
+    /// Set if the `__AFL_LOOP` and `__AFL_INIT` macros of `afl-clang-fast` are defined,
+    /// for targets using persistent mode or a deferred forkserver
+    pub fn afl_manual_control(&mut self, value: bool) -> &'_ mut Self {
+        self.afl_manual_control = value;
+        self
+    }
 }
 
 #[cfg(test)]
@@ -51,6 +51,8 @@ pub mod cmplog;
 pub use cmplog::QemuCmpLogHelper;
 pub mod snapshot;
 pub use snapshot::QemuSnapshotHelper;
+pub mod target_sites;
+pub use target_sites::QemuTargetSitesHelper;
 pub mod asan;
 pub use asan::{init_with_asan, QemuAsanHelper};
 
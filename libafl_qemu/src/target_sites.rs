@@ -0,0 +1,94 @@
+use libafl::inputs::Input;
+pub use libafl_targets::{
+    target_sites_observer, TARGET_SITES_MAP, TARGET_SITES_MAP_PTR, TARGET_SITES_MAP_SIZE,
+};
+
+use crate::{
+    emu::{Emulator, GuestAddr},
+    helper::QemuHelper,
+    Regs,
+};
+
+/// Records the target sites reached in the emulated target, for a `TargetSitesObserver`,
+/// see [`target_sites_observer`].
+///
+/// By default, a hook at each target marks it as reached, and the execution goes on.
+/// With [`QemuTargetSitesHelper::with_early_stop`], the targets are breakpoints instead, so the execution stops
+/// at the first target reached, and `Emulator::run` returns to the harness.
+#[derive(Debug)]
+pub struct QemuTargetSitesHelper {
+    targets: Vec<GuestAddr>,
+    early_stop: bool,
+    installed: bool,
+}
+
+impl QemuTargetSitesHelper {
+    /// Creates a new [`QemuTargetSitesHelper`] for the targets, given by their address
+    ///
+    /// # Panics
+    /// Panics if there are more targets than [`TARGET_SITES_MAP_SIZE`]
+    #[must_use]
+    pub fn new(targets: Vec<GuestAddr>) -> Self {
+        assert!(
+            targets.len() <= TARGET_SITES_MAP_SIZE,
+            "Too many target sites, the map holds {}",
+            TARGET_SITES_MAP_SIZE
+        );
+        Self {
+            targets,
+            early_stop: false,
+            installed: false,
+        }
+    }
+
+    /// Stop the execution at the first target reached
+    #[must_use]
+    pub fn with_early_stop(mut self) -> Self {
+        self.early_stop = true;
+        self
+    }
+
+    /// The addresses of the targets, by their index in the hit map
+    #[must_use]
+    pub fn targets(&self) -> &[GuestAddr] {
+        &self.targets
+    }
+}
+
+impl<I, S> QemuHelper<I, S> for QemuTargetSitesHelper
+where
+    I: Input,
+{
+    fn pre_exec(&mut self, emulator: &Emulator, _input: &I) {
+        // Installed on the first execution, the emulator is not ready before
+        if self.installed {
+            return;
+        }
+        for (idx, addr) in self.targets.iter().enumerate() {
+            if self.early_stop {
+                emulator.set_breakpoint(*addr);
+            } else {
+                emulator.set_hook(*addr, trace_target_site_hit, idx as u64, true);
+            }
+        }
+        self.installed = true;
+    }
+
+    fn post_exec(&mut self, emulator: &Emulator, _input: &I) {
+        if !self.early_stop {
+            return;
+        }
+        // Stopped at a breakpoint, which may be a target
+        if let Ok(pc) = emulator.read_reg::<_, GuestAddr>(Regs::Pc) {
+            if let Some(idx) = self.targets.iter().position(|addr| *addr == pc) {
+                unsafe { libafl_targets::__libafl_target_site_hit(idx) };
+            }
+        }
+    }
+}
+
+extern "C" fn trace_target_site_hit(_pc: GuestAddr, idx: u64) {
+    unsafe {
+        libafl_targets::__libafl_target_site_hit(idx as usize);
+    }
+}
@@ -118,6 +118,12 @@ fn main() {
         .file(src_dir.join("cmplog.c"))
         .compile("cmplog");
 
+    println!("cargo:rerun-if-changed=src/target_sites.c");
+
+    cc::Build::new()
+        .file(src_dir.join("target_sites.c"))
+        .compile("target_sites");
+
     #[cfg(any(target_os = "linux"))]
     {
         println!("cargo:rerun-if-changed=src/forkserver.c");
@@ -10,6 +10,9 @@
 #endif
 #include <sys/wait.h>
 #include <sys/types.h>
+#ifdef __linux__
+  #include <sys/syscall.h>
+#endif
 
 #define write_error(s) fprintf(stderr, "Error at %s:%d: %s\n", __FILE__, __LINE__, s)
 
@@ -18,6 +21,7 @@
 #define MAX_FILE (1024 * 1024)
 #define SHMEM_FUZZ_HDR_SIZE 4
 #define SHM_FUZZ_ENV_VAR "__AFL_SHM_FUZZ_ID"
+#define PERSIST_ENV_VAR "__AFL_PERSISTENT"
 
 /* Reporting errors */
 #define FS_OPT_ERROR 0xf800008f
@@ -38,6 +42,8 @@
 #define FS_OPT_AUTODICT 0x10000000
 #define FS_OPT_SHDMEM_FUZZ 0x01000000
 #define FS_OPT_NEWCMPLOG 0x02000000
+// LibAFL extension: all fields of a multi-input are passed over the shared memory
+#define FS_OPT_SHDMEM_MULTI 0x04000000
 #define FS_OPT_OLD_AFLPP_WORKAROUND 0x0f000000
 // FS_OPT_MAX_MAPSIZE is 8388608 = 0x800000 = 2^23 = 1 << 22
 #define FS_OPT_MAX_MAPSIZE ((0x00fffffeU >> 1) + 1)
@@ -50,6 +56,9 @@
 int __afl_sharedmem_fuzzing __attribute__((weak));
 
 extern size_t __afl_map_size;
+extern uint8_t* __afl_area_ptr;
+// NGRAM_SIZE_MAX of coverage.c
+extern MAYBE_THREAD_LOCAL uint32_t __afl_prev_loc[16];
 extern uint8_t* __token_start;
 extern uint8_t* __token_stop;
 
@@ -70,6 +79,108 @@ void __afl_set_persistent_mode(uint8_t mode) {
 
 }
 
+/* Where the coverage goes after the last persistent iteration, as large as
+   the map, which may be larger than EDGES_MAP_SIZE */
+static uint8_t *__afl_area_ptr_dummy;
+
+static uint8_t is_multi_input_shmem;
+static uint8_t multi_input_shmem_accepted;
+
+void __libafl_set_multi_input_shmem(uint8_t mode) {
+
+  is_multi_input_shmem = mode;
+
+}
+
+/* Multi-input shared memory layout, all values are native-endian uint32_t:
+   total_len | field_count | field_len[field_count] | field data, back to back.
+   __afl_fuzz_len points to total_len, __afl_fuzz_ptr right after it. */
+
+uint32_t __libafl_fuzz_field_count(void) {
+
+  uint32_t count;
+
+  if (!multi_input_shmem_accepted || !__afl_fuzz_ptr || *__afl_fuzz_len < 4) {
+
+    return 0;
+
+  }
+
+  memcpy(&count, __afl_fuzz_ptr, 4);
+  if (((uint64_t)count + 1) * 4 > *__afl_fuzz_len) return 0;
+  return count;
+
+}
+
+const uint8_t *__libafl_fuzz_field(uint32_t idx, uint32_t *len) {
+
+  uint32_t count = __libafl_fuzz_field_count();
+  uint32_t field_len, i;
+  uint64_t offset;
+
+  if (idx >= count) return NULL;
+
+  offset = ((uint64_t)count + 1) * 4;
+  for (i = 0; i < idx; i++) {
+
+    memcpy(&field_len, __afl_fuzz_ptr + 4 + i * 4, 4);
+    offset += field_len;
+
+  }
+
+  memcpy(&field_len, __afl_fuzz_ptr + 4 + idx * 4, 4);
+  if (offset + field_len > *__afl_fuzz_len) return NULL;
+
+  if (len) *len = field_len;
+  return __afl_fuzz_ptr + offset;
+
+}
+
+/* Copies a field into an anonymous memfd, for targets that want to read() their input.
+   Returns -1 on error, or if memfds are not supported on this platform. */
+
+int __libafl_fuzz_field_memfd(uint32_t idx) {
+
+#if defined(__linux__) && defined(SYS_memfd_create)
+  uint32_t       len = 0;
+  const uint8_t *field = __libafl_fuzz_field(idx, &len);
+  int            fd;
+
+  if (!field) return -1;
+
+  fd = syscall(SYS_memfd_create, "libafl_field", 0);
+  if (fd < 0) return -1;
+
+  while (len != 0) {
+
+    ssize_t ret = write(fd, field, len);
+    if (ret < 1) {
+
+      close(fd);
+      return -1;
+
+    }
+
+    len -= ret;
+    field += ret;
+
+  }
+
+  if (lseek(fd, 0, SEEK_SET) != 0) {
+
+    close(fd);
+    return -1;
+
+  }
+
+  return fd;
+#else
+  (void)idx;
+  return -1;
+#endif
+
+}
+
 /* Error reporting to forkserver controller */
 
 static void send_forkserver_error(int error) {
@@ -160,6 +271,8 @@ void __afl_start_forkserver(void) {
   if (already_initialized_forkserver) return;
   already_initialized_forkserver = 1;
 
+  if (getenv(PERSIST_ENV_VAR)) is_persistent = 1;
+
   struct sigaction orig_action;
   sigaction(SIGTERM, NULL, &orig_action);
   old_sigterm_handler = orig_action.sa_handler;
@@ -187,7 +300,13 @@ void __afl_start_forkserver(void) {
 
   }
 
-  if (__afl_sharedmem_fuzzing != 0) { status_for_fsrv |= FS_OPT_SHDMEM_FUZZ; }
+  if (__afl_sharedmem_fuzzing != 0 || is_multi_input_shmem) {
+
+    status_for_fsrv |= FS_OPT_SHDMEM_FUZZ;
+
+  }
+
+  if (is_multi_input_shmem) { status_for_fsrv |= FS_OPT_SHDMEM_MULTI; }
   if (status_for_fsrv) {
 
     status_for_fsrv |= FS_OPT_ENABLED;
@@ -201,7 +320,9 @@ void __afl_start_forkserver(void) {
 
   if (write(FORKSRV_FD + 1, tmp, 4) != 4) { return; }
 
-  if (__afl_sharedmem_fuzzing || autodict_on) {
+  /* The fuzzer replies to the options, as soon as any is set. */
+
+  if (status_for_fsrv) {
 
     if (read(FORKSRV_FD, &was_killed, 4) != 4) _exit(1);
 
@@ -209,6 +330,12 @@ void __afl_start_forkserver(void) {
 
       map_input_shared_memory();
 
+      if (is_multi_input_shmem && (was_killed & FS_OPT_SHDMEM_MULTI) == FS_OPT_SHDMEM_MULTI) {
+
+        multi_input_shmem_accepted = 1;
+
+      }
+
     }
 
     if ((was_killed & (FS_OPT_ENABLED | FS_OPT_AUTODICT)) == (FS_OPT_ENABLED | FS_OPT_AUTODICT) && autodict_on) {
@@ -242,9 +369,9 @@ void __afl_start_forkserver(void) {
 
     } else {
 
-      // uh this forkserver does not understand extended option passing
-      // or does not want the dictionary
-      if (!__afl_fuzz_ptr) already_read_first = 1;
+      // uh this forkserver does not understand extended option passing,
+      // then the reply was already the first request
+      if ((was_killed & FS_OPT_ENABLED) != FS_OPT_ENABLED) already_read_first = 1;
 
     }
 
@@ -358,3 +485,66 @@ void __afl_start_forkserver(void) {
   }
 
 }
+
+/* Deferred initialization: starts the forkserver at this point, instead of
+   before main, to skip the initialization of the target in each run.
+   Returns in each child, like __afl_start_forkserver. */
+
+void __afl_manual_init(void) {
+
+  __afl_start_forkserver();
+
+}
+
+/* The persistent loop: runs max_cnt inputs in the same child.
+   After each run, the child stops itself with SIGSTOP, the forkserver
+   resumes it for the next input. Returns 0 once the loop is done, then the
+   child exits, and the forkserver forks a new one. Without persistent mode,
+   it returns 1 exactly once. */
+
+int __afl_persistent_loop(unsigned int max_cnt) {
+
+  static uint8_t  first_pass = 1;
+  static uint32_t cycle_cnt;
+
+  if (first_pass) {
+
+    /* The coverage of the initialization does not count */
+    if (is_persistent) {
+
+      memset(__afl_area_ptr, 0, __afl_map_size);
+      memset(__afl_prev_loc, 0, sizeof(__afl_prev_loc));
+
+    }
+
+    cycle_cnt = max_cnt;
+    first_pass = 0;
+    return 1;
+
+  }
+
+  if (is_persistent) {
+
+    if (--cycle_cnt) {
+
+      raise(SIGSTOP);
+
+      memset(__afl_prev_loc, 0, sizeof(__afl_prev_loc));
+      return 1;
+
+    }
+
+    /* The coverage of the exit code does not count */
+    if (!__afl_area_ptr_dummy) {
+
+      __afl_area_ptr_dummy = (uint8_t *)calloc(1, __afl_map_size);
+
+    }
+
+    if (__afl_area_ptr_dummy) __afl_area_ptr = __afl_area_ptr_dummy;
+
+  }
+
+  return 0;
+
+}
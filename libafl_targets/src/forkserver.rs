@@ -13,3 +13,89 @@ extern "C" {
 pub fn start_forkserver() -> ! {
     unsafe { __afl_start_forkserver() }
 }
+
+extern "C" {
+    fn __afl_manual_init();
+    fn __afl_persistent_loop(max_cnt: u32) -> i32;
+    fn __afl_set_persistent_mode(mode: u8);
+}
+
+/// Deferred initialization: starts the forkserver at this point, if it did not start yet.
+/// Unlike [`start_forkserver`], it returns in each child, and if there is no fuzzer, so the expensive
+/// initialization of the target before it only runs once. Shared memory must be created before.
+pub fn manual_init() {
+    unsafe { __afl_manual_init() }
+}
+
+/// Runs the target in persistent mode: the child runs up to `max_iterations` inputs, one per loop, without forking again.
+/// After each run, it stops itself with `SIGSTOP`, until the forkserver resumes it for the next input.
+/// The target has to reset its state, and read the input anew, in each loop.
+///
+/// ```rust,ignore
+/// manual_init();
+/// while persistent_loop(1000) {
+///     harness(fuzz_field(0).unwrap_or_default());
+/// }
+/// ```
+///
+/// The fuzzer enables persistent mode with `ForkserverExecutorBuilder::is_persistent`, or [`enable_persistent_mode`] does.
+/// Without it, the loop runs once.
+#[must_use]
+pub fn persistent_loop(max_iterations: u32) -> bool {
+    unsafe { __afl_persistent_loop(max_iterations) != 0 }
+}
+
+/// Enables persistent mode, even if the fuzzer did not ask for it. Has to be called before [`start_forkserver`].
+pub fn enable_persistent_mode() {
+    unsafe { __afl_set_persistent_mode(1) }
+}
+
+extern "C" {
+    fn __libafl_set_multi_input_shmem(mode: u8);
+    fn __libafl_fuzz_field_count() -> u32;
+    fn __libafl_fuzz_field(idx: u32, len: *mut u32) -> *const u8;
+    fn __libafl_fuzz_field_memfd(idx: u32) -> i32;
+}
+
+/// Announce to the fuzzer that this target reads all input fields from the shared memory testcase.
+/// Has to be called before [`start_forkserver`].
+pub fn enable_multi_input_shmem() {
+    unsafe { __libafl_set_multi_input_shmem(1) }
+}
+
+/// The number of input fields in the shared memory testcase.
+/// Returns `0` if the fuzzer did not agree to pass fields over shared memory.
+#[must_use]
+pub fn fuzz_field_count() -> usize {
+    unsafe { __libafl_fuzz_field_count() as usize }
+}
+
+/// The input field at `idx` of the current shared memory testcase, if any.
+#[must_use]
+pub fn fuzz_field(idx: usize) -> Option<&'static [u8]> {
+    let mut len = 0_u32;
+    let ptr = unsafe { __libafl_fuzz_field(idx as u32, &mut len) };
+    if ptr.is_null() {
+        None
+    } else {
+        Some(unsafe { core::slice::from_raw_parts(ptr, len as usize) })
+    }
+}
+
+/// All input fields of the current shared memory testcase.
+#[must_use]
+pub fn fuzz_fields() -> alloc::vec::Vec<&'static [u8]> {
+    (0..fuzz_field_count()).filter_map(fuzz_field).collect()
+}
+
+/// Copies the input field at `idx` into a new memfd, positioned at its start.
+/// The caller owns the returned file descriptor.
+#[must_use]
+pub fn fuzz_field_memfd(idx: usize) -> Option<i32> {
+    let fd = unsafe { __libafl_fuzz_field_memfd(idx as u32) };
+    if fd < 0 {
+        None
+    } else {
+        Some(fd)
+    }
+}
@@ -97,6 +97,9 @@ pub use value_profile::*;
 pub mod cmplog;
 pub use cmplog::*;
 
+pub mod target_sites;
+pub use target_sites::*;
+
 #[cfg(feature = "std")]
 pub mod drcov;
 
@@ -1,4 +1,8 @@
 //! [`LLVM` `PcGuard`](https://clang.llvm.org/docs/SanitizerCoverage.html#tracing-pcs-with-guards) runtime for `LibAFL`.
+//!
+//! If the target is also compiled with `-fsanitize-coverage=pc-table`, [`sancov_pc_table`] maps the edges back to their program counters.
+
+use alloc::vec::Vec;
 
 use crate::coverage::{EDGES_MAP, MAX_EDGES_NUM};
 #[cfg(feature = "pointer_maps")]
@@ -60,6 +64,7 @@ pub unsafe extern "C" fn __sanitizer_cov_trace_pc_guard_init(mut start: *mut u32
     if start == stop || *start != 0 {
         return;
     }
+    LAST_GUARDS = (MAX_EDGES_NUM, stop.offset_from(start) as usize);
 
     while start < stop {
         *start = MAX_EDGES_NUM as u32;
@@ -76,3 +81,40 @@ pub unsafe extern "C" fn __sanitizer_cov_trace_pc_guard_init(mut start: *mut u32
         }
     }
 }
+
+/// The first edge index and the number of guards of the module initialized last
+static mut LAST_GUARDS: (usize, usize) = (0, 0);
+
+/// The `pc-table`s of all modules, with the index of their first edge
+static mut PC_TABLES: Vec<(usize, *const usize, usize)> = Vec::new();
+
+/// Initialize the sancov `pc-table` - usually called by `llvm`, right after [`__sanitizer_cov_trace_pc_guard_init`] of the same module.
+///
+/// # Safety
+/// Keeps the pointers, and dereferences them in [`sancov_pc_table`].
+#[no_mangle]
+pub unsafe extern "C" fn __sanitizer_cov_pcs_init(pcs_beg: *const usize, pcs_end: *const usize) {
+    // Each entry is a program counter, followed by flags
+    let len = pcs_end.offset_from(pcs_beg) as usize / 2;
+    let (first_edge, guards) = LAST_GUARDS;
+    // Otherwise, the guards of this module were initialized before, and its edges are unknown
+    if guards == len {
+        PC_TABLES.push((first_edge, pcs_beg, len));
+        LAST_GUARDS = (0, 0);
+    }
+}
+
+/// The edge indices in the [`EDGES_MAP`] together with the program counter of their basic block,
+/// for all modules compiled with `-fsanitize-coverage=trace-pc-guard,pc-table`
+#[must_use]
+pub fn sancov_pc_table() -> Vec<(usize, usize)> {
+    let mut pcs = vec![];
+    unsafe {
+        for &(first_edge, table, len) in &PC_TABLES {
+            for i in 0..len {
+                pcs.push((first_edge + i, *table.add(2 * i)));
+            }
+        }
+    }
+    pcs
+}
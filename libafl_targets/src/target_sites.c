@@ -0,0 +1,35 @@
+#include <setjmp.h>
+#include <stddef.h>
+#include <stdint.h>
+
+// The context to jump back to, while __libafl_target_sites_run runs a harness
+static jmp_buf *stop_env;
+static size_t   stop_idx;
+
+// Runs the harness on the input, returns 1 and the target site index in idx
+// if it was stopped at a target site, else 0
+int __libafl_target_sites_run(int (*harness)(const uint8_t *, size_t),
+                              const uint8_t *data, size_t len, size_t *idx) {
+  jmp_buf  env;
+  jmp_buf *prev = stop_env;
+  int      stopped;
+
+  stop_env = &env;
+  stopped = setjmp(env);
+  if (!stopped) {
+    harness(data, len);
+  } else {
+    *idx = stop_idx;
+  }
+  stop_env = prev;
+
+  return stopped;
+}
+
+// Jumps back into __libafl_target_sites_run, if it is running a harness
+void __libafl_target_sites_stop(size_t idx) {
+  if (!stop_env) { return; }
+
+  stop_idx = idx;
+  longjmp(*stop_env, 1);
+}
@@ -0,0 +1,132 @@
+//! Target sites of in-process targets, for the [`TargetSitesObserver`].
+//!
+//! The target calls [`__libafl_target_site_hit`] with the index of a target site when reaching it,
+//! e.g. from a call inserted at the lines changed by a patch.
+//! By default, the hit is only recorded, and the execution goes on, so this works with any executor.
+//! Opting in with [`TARGET_SITES_STOP_ON_HIT`], a harness run in the fuzzer process by [`run_until_target_site`],
+//! e.g. in the harness of an `InProcessExecutor`, stops at the first target site it reaches
+//! and exits with a dedicated [`ExitKind::Custom`].
+//! The `TargetHitFeedback` turns the hits into solutions, and the fuzzing loop can stop the campaign once
+//! all targets are reached, see `TargetSitesMetadata::all_reached`.
+//! With executors running the target in a child process, like the `InProcessForkExecutor`,
+//! the hit map must be in shared memory, see [`TARGET_SITES_MAP_PTR`].
+
+use alloc::vec::Vec;
+use core::ptr;
+use libafl::{executors::ExitKind, feedbacks::TargetSiteReached, observers::TargetSitesObserver};
+
+/// The maximum number of target sites
+pub const TARGET_SITES_MAP_SIZE: usize = 4096;
+
+/// The map for target site hits.
+#[no_mangle]
+pub static mut __libafl_target_sites_map: [u8; TARGET_SITES_MAP_SIZE] = [0; TARGET_SITES_MAP_SIZE];
+pub use __libafl_target_sites_map as TARGET_SITES_MAP;
+
+/// The hit map [`__libafl_target_site_hit`] writes to, if not null, instead of the [`TARGET_SITES_MAP`].
+/// It must hold at least one entry per target site.
+pub static mut TARGET_SITES_MAP_PTR: *mut u8 = ptr::null_mut();
+
+/// If set, [`__libafl_target_site_hit`] stops a harness run by [`run_until_target_site`] at the first target site.
+/// Hits outside of [`run_until_target_site`] are only recorded.
+pub static mut TARGET_SITES_STOP_ON_HIT: bool = false;
+
+/// A libfuzzer-style harness, like `LLVMFuzzerTestOneInput`
+pub type TargetSitesHarness = unsafe extern "C" fn(*const u8, usize) -> i32;
+
+extern "C" {
+    fn __libafl_target_sites_run(
+        harness: TargetSitesHarness,
+        data: *const u8,
+        len: usize,
+        idx: *mut usize,
+    ) -> i32;
+    fn __libafl_target_sites_stop(idx: usize);
+}
+
+/// Records that the target site with the index `idx` was reached - usually called by the target.
+/// With [`TARGET_SITES_STOP_ON_HIT`], jumps back to the [`run_until_target_site`] running the target.
+///
+/// # Safety
+/// Writes to the [`TARGET_SITES_MAP_PTR`], if set, which must be valid for the index.
+#[no_mangle]
+pub unsafe extern "C" fn __libafl_target_site_hit(idx: usize) {
+    if TARGET_SITES_MAP_PTR.is_null() {
+        if let Some(hit) = TARGET_SITES_MAP.get_mut(idx) {
+            *hit = 1;
+        }
+    } else {
+        TARGET_SITES_MAP_PTR.add(idx).write(1);
+    }
+    if TARGET_SITES_STOP_ON_HIT {
+        __libafl_target_sites_stop(idx);
+    }
+}
+
+/// Runs the `harness` on `buf`, for the harness of an in-process executor.
+/// With [`TARGET_SITES_STOP_ON_HIT`], the run stops at the first target site and returns an [`ExitKind::Custom`]
+/// with the [`TargetSiteReached`], else it returns [`ExitKind::Ok`] once the harness returns.
+///
+/// # Safety
+/// Stopping jumps out of the harness with `longjmp`, skipping the rest of it without cleanup,
+/// so the harness must not hold locks or resources, or run Rust code with destructors, when reaching a target site.
+pub unsafe fn run_until_target_site(harness: TargetSitesHarness, buf: &[u8]) -> ExitKind {
+    let mut idx = 0;
+    if __libafl_target_sites_run(harness, buf.as_ptr(), buf.len(), ptr::addr_of_mut!(idx)) == 0 {
+        ExitKind::Ok
+    } else {
+        ExitKind::custom(TargetSiteReached { idx })
+    }
+}
+
+/// Creates a [`TargetSitesObserver`] for the `targets`, observing the hit map [`__libafl_target_site_hit`] writes to.
+///
+/// # Safety
+/// The [`TARGET_SITES_MAP_PTR`], if set, must be valid for all targets.
+#[must_use]
+pub unsafe fn target_sites_observer<'a>(name: &str, targets: Vec<u64>) -> TargetSitesObserver<'a> {
+    let hits = if TARGET_SITES_MAP_PTR.is_null() {
+        assert!(
+            targets.len() <= TARGET_SITES_MAP_SIZE,
+            "Too many target sites, the map holds {}",
+            TARGET_SITES_MAP_SIZE
+        );
+        TARGET_SITES_MAP.as_mut_ptr()
+    } else {
+        TARGET_SITES_MAP_PTR
+    };
+    TargetSitesObserver::new_from_ptr(name, targets, hits)
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl::{executors::ExitKind, feedbacks::TargetSiteReached};
+
+    use super::{
+        __libafl_target_site_hit, run_until_target_site, TARGET_SITES_MAP, TARGET_SITES_STOP_ON_HIT,
+    };
+
+    static mut PAST_TARGET_SITE: bool = false;
+
+    unsafe extern "C" fn harness(_data: *const u8, _len: usize) -> i32 {
+        __libafl_target_site_hit(3);
+        PAST_TARGET_SITE = true;
+        0
+    }
+
+    #[test]
+    fn test_run_until_target_site() {
+        unsafe {
+            TARGET_SITES_STOP_ON_HIT = true;
+            let exit_kind = run_until_target_site(harness, b"input");
+            assert!(!PAST_TARGET_SITE);
+            assert_eq!(TARGET_SITES_MAP[3], 1);
+            assert_eq!(exit_kind, ExitKind::custom(TargetSiteReached { idx: 3 }));
+
+            TARGET_SITES_STOP_ON_HIT = false;
+            let exit_kind = run_until_target_site(harness, b"input");
+            assert!(PAST_TARGET_SITE);
+            assert_eq!(exit_kind, ExitKind::Ok);
+        }
+    }
+}
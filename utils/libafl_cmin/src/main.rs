@@ -0,0 +1,140 @@
+//! Minimizes a corpus directory for an AFL-style forkserver target, like `afl-cmin`.
+//! For in-process targets, use [`libafl::corpus::MapCorpusMinimizer`] from your fuzzer directly.
+
+use clap::{self, StructOpt};
+use core::time::Duration;
+use std::{ffi::OsString, fs, path::PathBuf};
+
+use libafl::{
+    bolts::{
+        rands::StdRand,
+        shmem::{ShMem, ShMemProvider, StdShMemProvider},
+        tuples::tuple_list,
+        AsMutSlice,
+    },
+    corpus::{InMemoryCorpus, MapCorpusMinimizer, OnDiskCorpus, SetCoverStrategy},
+    executors::{ForkserverExecutor, TimeoutForkserverExecutor},
+    inputs::BytesInput,
+    observers::ConstMapObserver,
+    state::StdState,
+    Error,
+};
+
+/// The size of the coverage map
+const MAP_SIZE: usize = 65536;
+
+#[derive(Debug, StructOpt)]
+#[clap(
+    name = "libafl_cmin",
+    about = "Minimize a corpus to the smallest set of inputs covering all edges, for an AFL-instrumented target"
+)]
+struct Opt {
+    #[clap(
+        parse(try_from_str),
+        short,
+        long,
+        name = "INPUT",
+        help = "The directory holding the corpus to minimize"
+    )]
+    input: PathBuf,
+
+    #[clap(
+        parse(try_from_str),
+        short,
+        long,
+        name = "OUTPUT",
+        help = "The directory to write the minimized corpus to"
+    )]
+    output: PathBuf,
+
+    #[clap(
+        short,
+        long,
+        help = "Compute an optimal set cover instead of the greedy one, may be slow"
+    )]
+    exact: bool,
+
+    #[clap(
+        short,
+        long,
+        help = "Also keep inputs that crash or time out, which are dropped by default"
+    )]
+    keep_failing: bool,
+
+    #[clap(
+        parse(try_from_str),
+        short,
+        long,
+        name = "TIMEOUT",
+        help = "The timeout for each run, in milliseconds",
+        default_value = "1000"
+    )]
+    timeout: u64,
+
+    #[clap(help = "The target, followed by its arguments. `@@` is replaced by the input file.")]
+    target: String,
+
+    #[clap(help = "The arguments of the target", allow_hyphen_values = true)]
+    arguments: Vec<OsString>,
+}
+
+fn main() -> Result<(), Error> {
+    let opt = Opt::parse();
+
+    let mut shmem_provider = StdShMemProvider::new()?;
+    let mut shmem = shmem_provider.new_shmem(MAP_SIZE)?;
+    shmem.write_to_env("__AFL_SHM_ID")?;
+    // The minimizer buckets the raw hitcounts itself
+    let edges_observer = ConstMapObserver::<_, MAP_SIZE>::new("shared_mem", shmem.as_mut_slice());
+    let minimizer = MapCorpusMinimizer::new(
+        &edges_observer,
+        if opt.exact {
+            SetCoverStrategy::Optimal
+        } else {
+            SetCoverStrategy::Greedy
+        },
+    )
+    .keep_failing(opt.keep_failing);
+
+    let mut state = StdState::new(
+        StdRand::with_seed(0),
+        InMemoryCorpus::<BytesInput>::new(),
+        InMemoryCorpus::new(),
+        &mut (),
+        &mut (),
+    )?;
+
+    let forkserver = ForkserverExecutor::builder()
+        .program(&opt.target)
+        .parse_afl_cmdline(&opt.arguments)
+        .build(tuple_list!(edges_observer))?;
+    let mut executor =
+        TimeoutForkserverExecutor::new(forkserver, Duration::from_millis(opt.timeout))?;
+
+    // Empty files are skipped by the minimizer, so they do not count
+    let mut inputs = 0;
+    for entry in fs::read_dir(&opt.input)? {
+        let attr = entry?.metadata()?;
+        if attr.is_file() && attr.len() > 0 {
+            inputs += 1;
+        }
+    }
+
+    fs::create_dir_all(&opt.output)?;
+    let mut out = OnDiskCorpus::<BytesInput>::new(&opt.output)?;
+    let kept = minimizer.minimize_dir(
+        &mut (),
+        &mut executor,
+        &mut state,
+        &mut (),
+        &opt.input,
+        &mut out,
+    )?;
+    println!(
+        "Kept {} inputs of {} in {}",
+        kept,
+        inputs,
+        opt.output.display()
+    );
+    Ok(())
+}